@@ -0,0 +1,125 @@
+//! Point reads over 1M entries in a `FrozenRepo` and a `MemoryRepo`, and the memory each holds
+//! them in.
+//!
+//! Criterion doesn't measure memory, so the footprints are counted by the global allocator and
+//! printed before the reads are benched.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use kv_storage::{Read, Write};
+use kv_storage_frozen::FrozenRepo;
+use kv_storage_memory::MemoryRepo;
+
+const ENTRIES: u64 = 1_000_000;
+
+/// Counts the bytes allocated and not yet freed.
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        LIVE.fetch_add(new_size, Ordering::Relaxed);
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn key(i: u64) -> [u8; 8] {
+    i.to_be_bytes()
+}
+
+fn value(i: u64) -> [u8; 32] {
+    let mut value = [0; 32];
+    value[..8].copy_from_slice(&i.to_le_bytes());
+    value
+}
+
+/// Build a repo, printing the bytes it holds once built, per entry too.
+fn measure<R>(name: &str, build: impl FnOnce() -> R) -> R {
+    let before = LIVE.load(Ordering::Relaxed);
+    let repo = build();
+    let held = LIVE.load(Ordering::Relaxed) - before;
+
+    #[allow(clippy::cast_precision_loss)]
+    let per_entry = held as f64 / ENTRIES as f64;
+    println!("{name}: {held} bytes for {ENTRIES} entries, {per_entry:.1} per entry");
+
+    repo
+}
+
+/// Keys spread over the entries, so reads don't walk them in order.
+fn keys() -> impl FnMut() -> [u8; 8] {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+
+    move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        key(state % ENTRIES)
+    }
+}
+
+fn bench_point_reads(c: &mut Criterion) {
+    let memory = measure("memory", || {
+        let mut repo = MemoryRepo::default();
+
+        for i in 0..ENTRIES {
+            repo.write(&key(i), &value(i)).unwrap();
+        }
+
+        repo
+    });
+
+    let frozen = measure("frozen", || {
+        (0..ENTRIES)
+            .map(|i| (key(i).to_vec(), value(i).to_vec()))
+            .collect::<FrozenRepo>()
+    });
+
+    let mut group = c.benchmark_group("point_read");
+
+    group.bench_function("memory", |b| {
+        let mut next = keys();
+        b.iter(|| memory.read_with(&next(), |bytes| black_box(bytes.map(<[u8]>::len))));
+    });
+
+    group.bench_function("frozen", |b| {
+        let mut next = keys();
+        b.iter(|| frozen.read_with(&next(), |bytes| black_box(bytes.map(<[u8]>::len))));
+    });
+
+    group.bench_function("memory/miss", |b| {
+        let mut next = keys();
+        b.iter(|| memory.read_with(&next()[1..], |bytes| black_box(bytes.is_some())));
+    });
+
+    group.bench_function("frozen/miss", |b| {
+        let mut next = keys();
+        b.iter(|| frozen.read_with(&next()[1..], |bytes| black_box(bytes.is_some())));
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_point_reads);
+criterion_main!(benches);
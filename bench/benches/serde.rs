@@ -0,0 +1,163 @@
+//! Save/load through `KvStore<_, MemoryRepo>` for each serde backend and value shape.
+//!
+//! To bench a new backend add it to `backends!` at the bottom. Borsh isn't a serde backend, it is
+//! benched through its own store with the same shapes. Each benchmark id carries the serialized
+//! size of the value in bytes.
+
+use std::hint::black_box;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use kv_storage::{Deserializer, KvStore, Map, Serializer};
+use kv_storage_bincode::Bincode;
+use kv_storage_borsh::{Borsh, BorshMap, BorshStore};
+use kv_storage_cbor::Cbor;
+use kv_storage_json::Json;
+use kv_storage_memory::MemoryRepo;
+use kv_storage_msgpack::MessagePack;
+use kv_storage_postcard::Postcard;
+use kv_storage_ron::Ron;
+
+#[derive(Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+struct Record {
+    id: u64,
+    owner: String,
+    tags: Vec<String>,
+    amounts: Vec<u128>,
+    memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+enum Node {
+    Leaf(u32),
+    Named { name: String, weight: i64 },
+    Branch(Vec<Node>),
+}
+
+fn small() -> u64 {
+    42
+}
+
+fn record() -> Record {
+    Record {
+        id: 7,
+        owner: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_owned(),
+        tags: (0..16).map(|i| format!("tag-{i:04}")).collect(),
+        amounts: (0..40).map(|i| i * 1_000_000_007).collect(),
+        memo: Some("x".repeat(200)),
+    }
+}
+
+fn nested() -> Node {
+    fn build(depth: u32) -> Node {
+        match depth {
+            0 => Node::Leaf(depth),
+            d if d % 2 == 0 => Node::Branch((0..3).map(|_| build(d - 1)).collect()),
+            d => Node::Branch(vec![
+                Node::Named {
+                    name: format!("node-{d}"),
+                    weight: -i64::from(d),
+                },
+                build(d - 1),
+            ]),
+        }
+    }
+
+    build(6)
+}
+
+fn bench_shape<Serde, T>(c: &mut Criterion, backend: &str, shape: &str, value: &T)
+where
+    Serde: Serializer + Deserializer + Default,
+    T: Serialize + DeserializeOwned,
+{
+    let size = Serde::default()
+        .serialize(value)
+        .map(<[u8]>::len)
+        .unwrap_or_default();
+
+    let mut group = c.benchmark_group(shape);
+    group.throughput(Throughput::Bytes(size as u64));
+
+    let map: Map<16, u64, T> = Map::new(b"values");
+    let mut store: KvStore<Serde, MemoryRepo> = KvStore::default();
+
+    group.bench_function(BenchmarkId::new(format!("save/{backend}"), size), |b| {
+        b.iter(|| {
+            map.save(&mut store, black_box(7), black_box(value))
+                .unwrap()
+        });
+    });
+
+    group.bench_function(BenchmarkId::new(format!("may_load/{backend}"), size), |b| {
+        b.iter(|| map.may_load(&store, black_box(7)).unwrap().unwrap());
+    });
+
+    group.finish();
+}
+
+fn bench_backend<Serde>(c: &mut Criterion, backend: &str)
+where
+    Serde: Serializer + Deserializer + Default,
+{
+    bench_shape::<Serde, _>(c, backend, "small", &small());
+    bench_shape::<Serde, _>(c, backend, "record", &record());
+    bench_shape::<Serde, _>(c, backend, "nested", &nested());
+}
+
+fn bench_borsh_shape<T>(c: &mut Criterion, shape: &str, value: &T)
+where
+    T: BorshSerialize + BorshDeserialize,
+{
+    let size = Borsh::new()
+        .encode(value)
+        .map(<[u8]>::len)
+        .unwrap_or_default();
+
+    let mut group = c.benchmark_group(shape);
+    group.throughput(Throughput::Bytes(size as u64));
+
+    let map: BorshMap<16, u64, T> = BorshMap::new(b"values");
+    let mut store: BorshStore<MemoryRepo> = BorshStore::default();
+
+    group.bench_function(BenchmarkId::new("save/borsh", size), |b| {
+        b.iter(|| {
+            map.save(&mut store, black_box(7), black_box(value))
+                .unwrap()
+        });
+    });
+
+    group.bench_function(BenchmarkId::new("may_load/borsh", size), |b| {
+        b.iter(|| map.may_load(&store, black_box(7)).unwrap().unwrap());
+    });
+
+    group.finish();
+}
+
+fn bench_borsh(c: &mut Criterion) {
+    bench_borsh_shape(c, "small", &small());
+    bench_borsh_shape(c, "record", &record());
+    bench_borsh_shape(c, "nested", &nested());
+}
+
+macro_rules! backends {
+    ($($name:literal => $serde:ty),+ $(,)?) => {
+        fn bench_backends(c: &mut Criterion) {
+            $(bench_backend::<$serde>(c, $name);)+
+        }
+    };
+}
+
+backends! {
+    "bincode" => Bincode,
+    "json" => Json,
+    "msgpack" => MessagePack,
+    "cbor" => Cbor,
+    "postcard" => Postcard,
+    "ron" => Ron,
+}
+
+criterion_group!(benches, bench_backends, bench_borsh);
+criterion_main!(benches);
@@ -0,0 +1,134 @@
+//! An event-sourced account: events are appended to a log, a snapshot of the state is saved
+//! every few events, and the state is rehydrated from the snapshot plus the events after it.
+
+use kv_storage::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Event {
+    Deposited(u64),
+    Withdrew(u64),
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountState {
+    pub balance: u64,
+    /// How many events have been applied.
+    pub version: u64,
+}
+
+impl AccountState {
+    fn apply(&mut self, event: &Event) {
+        match event {
+            Event::Deposited(amount) => self.balance += amount,
+            Event::Withdrew(amount) => self.balance -= amount,
+        }
+
+        self.version += 1;
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AccountError<E> {
+    #[error("insufficient funds: balance {balance}, requested {requested}")]
+    InsufficientFunds { balance: u64, requested: u64 },
+    #[error("event {0} is missing from the log")]
+    MissingEvent(u64),
+    #[error(transparent)]
+    Store(#[from] E),
+}
+
+storage_keys! {
+    const EVENTS: Map<16, u64, Event> = map!("events");
+    const EVENT_COUNT: Item<u64> = item!("event_count");
+    const SNAPSHOT: Item<AccountState> = item!("snapshot");
+}
+
+pub struct Account {
+    snapshot_every: u64,
+}
+
+impl Account {
+    /// # Panics
+    ///
+    /// Panics if `snapshot_every` is zero.
+    #[must_use]
+    pub const fn new(snapshot_every: u64) -> Self {
+        assert!(snapshot_every > 0, "snapshot interval must be positive");
+        Self { snapshot_every }
+    }
+
+    /// Validate and append an event, returning the new state.
+    ///
+    /// The event, the event count and any snapshot are separate writes, atomicity is up to
+    /// the store.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a withdrawal exceeds the balance or the store
+    /// encounters an error.
+    pub fn append<S: MutStorage>(
+        &self,
+        store: &mut S,
+        event: Event,
+    ) -> Result<AccountState, AccountError<S::Error>> {
+        let mut state = Self::rehydrate(store)?;
+
+        if let Event::Withdrew(requested) = event {
+            if requested > state.balance {
+                return Err(AccountError::InsufficientFunds {
+                    balance: state.balance,
+                    requested,
+                });
+            }
+        }
+
+        EVENTS.save(store, state.version, &event)?;
+        state.apply(&event);
+        EVENT_COUNT.save(store, state.version)?;
+
+        if state.version % self.snapshot_every == 0 {
+            SNAPSHOT.save(store, &state)?;
+        }
+
+        Ok(state)
+    }
+
+    /// The latest snapshot with the events after it replayed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an event is missing or the store encounters an
+    /// error.
+    pub fn rehydrate<S: Storage>(store: &S) -> Result<AccountState, AccountError<S::Error>> {
+        let snapshot = SNAPSHOT.may_load(store)?.unwrap_or_default();
+        Self::replay_from(store, snapshot)
+    }
+
+    /// Every event replayed from the start, ignoring snapshots.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an event is missing or the store encounters an
+    /// error.
+    pub fn replay_all<S: Storage>(store: &S) -> Result<AccountState, AccountError<S::Error>> {
+        Self::replay_from(store, AccountState::default())
+    }
+
+    fn replay_from<S: Storage>(
+        store: &S,
+        mut state: AccountState,
+    ) -> Result<AccountState, AccountError<S::Error>> {
+        let count = EVENT_COUNT.may_load(store)?.unwrap_or_default();
+
+        for index in state.version..count {
+            let event = EVENTS
+                .may_load(store, index)?
+                .ok_or(AccountError::MissingEvent(index))?;
+
+            state.apply(&event);
+        }
+
+        Ok(state)
+    }
+}
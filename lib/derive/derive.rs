@@ -0,0 +1,276 @@
+//! `#[derive(StorageKey)]`, re-exported by `kv-storage` behind its `derive` feature.
+#![deny(clippy::all)]
+#![warn(clippy::pedantic)]
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote_spanned, spanned::Spanned, Data, DataEnum, DeriveInput, Expr,
+    ExprLit, Fields, Generics, Lit, WherePredicate,
+};
+
+/// Implement `WriteCompositeKey`, `KeyDeserialize` and `EncodeLike<Self>` for a struct or a
+/// field-less enum.
+///
+/// A struct encodes exactly like the tuple of its fields in declaration order: every field but
+/// the last is length-prefixed. `KeyDeserialize` is only implemented for structs without lifetime
+/// parameters, and needs every field to decode back to its own type.
+///
+/// An enum encodes to a single byte, the variant's discriminant. Variants without an explicit
+/// discriminant follow the previous one like in Rust, so give them explicit ones to keep the
+/// encoding stable across reorderings.
+#[proc_macro_derive(StorageKey)]
+pub fn derive_storage_key(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let expanded = match &input.data {
+        Data::Struct(data) => Ok(derive_struct(&input, &data.fields)),
+        Data::Enum(data) => derive_enum(&input, data),
+        Data::Union(data) => Err(syn::Error::new(
+            data.union_token.span,
+            "`StorageKey` can't be derived for unions",
+        )),
+    };
+
+    expanded
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Add `predicates` to the where clause of `generics`.
+fn with_predicates(generics: &Generics, predicates: &[WherePredicate]) -> Generics {
+    let mut generics = generics.clone();
+    generics
+        .make_where_clause()
+        .predicates
+        .extend(predicates.iter().cloned());
+    generics
+}
+
+fn derive_struct(input: &DeriveInput, fields: &Fields) -> TokenStream2 {
+    let name = &input.ident;
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+
+    let members: Vec<_> = fields.members().collect();
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+
+    // bounds are spanned to the field types, so a field that isn't a key part is reported there
+    let key_bounds: Vec<WherePredicate> = types
+        .iter()
+        .map(|ty| parse_quote_spanned!(ty.span()=> #ty: ::kv_storage::WriteCompositeKey))
+        .collect();
+
+    let (total_len, write_into) = match members.split_last() {
+        Some((last, init)) => (
+            quote! {
+                #(::kv_storage::__derive::length_prefixed_len(&self.#init) +)*
+                ::kv_storage::WriteCompositeKey::total_len(&self.#last)
+            },
+            quote! {
+                #(::kv_storage::__derive::write_length_prefixed(&self.#init, writer);)*
+                ::kv_storage::WriteCompositeKey::write_into(&self.#last, writer);
+            },
+        ),
+        None => (quote!(0), quote!(let _ = writer;)),
+    };
+
+    let key_generics = with_predicates(&input.generics, &key_bounds);
+    let (impl_generics, _, where_clause) = key_generics.split_for_impl();
+
+    let mut expanded = quote! {
+        impl #impl_generics ::kv_storage::WriteCompositeKey for #name #ty_generics #where_clause {
+            fn total_len(&self) -> usize {
+                #total_len
+            }
+
+            fn write_into<W: ::kv_storage::WriteKeyPart>(&self, writer: &mut W) {
+                #write_into
+            }
+        }
+    };
+
+    expanded.extend(derive_by_ref_and_encode_like(input, &key_bounds));
+
+    if input.generics.lifetimes().next().is_none() {
+        expanded.extend(derive_struct_deserialize(input, &members, &types));
+    }
+
+    expanded
+}
+
+fn derive_struct_deserialize(
+    input: &DeriveInput,
+    members: &[syn::Member],
+    types: &[&syn::Type],
+) -> TokenStream2 {
+    let name = &input.ident;
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+
+    let bounds: Vec<WherePredicate> = types
+        .iter()
+        .map(|ty| parse_quote_spanned!(ty.span()=> #ty: ::kv_storage::KeyDeserialize<Owned = #ty>))
+        .collect();
+
+    let generics = with_predicates(&input.generics, &bounds);
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+    let body = if let (Some((last, init)), Some((last_ty, init_tys))) =
+        (members.split_last(), types.split_last())
+    {
+        let parts: Vec<_> = (0..init.len())
+            .map(|i| format_ident!("__part{}", i))
+            .collect();
+
+        quote! {
+            let rest = bytes;
+            #(let (#parts, rest) = ::kv_storage::__derive::split_length_prefixed(rest)?;)*
+
+            Ok(Self {
+                #(#init: <#init_tys as ::kv_storage::KeyDeserialize>::from_key_bytes(#parts)?,)*
+                #last: <#last_ty as ::kv_storage::KeyDeserialize>::from_key_bytes(rest)?,
+            })
+        }
+    } else {
+        quote! {
+            if bytes.is_empty() {
+                Ok(Self {})
+            } else {
+                Err(::kv_storage::KeyDecodeError::InvalidLength {
+                    expected: 0,
+                    found: bytes.len(),
+                })
+            }
+        }
+    };
+
+    quote! {
+        impl #impl_generics ::kv_storage::KeyDeserialize for #name #ty_generics #where_clause {
+            type Owned = Self;
+
+            fn from_key_bytes(
+                bytes: &[u8],
+            ) -> ::core::result::Result<Self::Owned, ::kv_storage::KeyDecodeError> {
+                #body
+            }
+        }
+    }
+}
+
+/// `WriteCompositeKey` for `&Self`, and `EncodeLike<Self>` for `Self` and `&Self`.
+fn derive_by_ref_and_encode_like(input: &DeriveInput, bounds: &[WherePredicate]) -> TokenStream2 {
+    let name = &input.ident;
+    let generics = with_predicates(&input.generics, bounds);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::kv_storage::WriteCompositeKey for &#name #ty_generics #where_clause {
+            fn total_len(&self) -> usize {
+                ::kv_storage::WriteCompositeKey::total_len(*self)
+            }
+
+            fn write_into<W: ::kv_storage::WriteKeyPart>(&self, writer: &mut W) {
+                ::kv_storage::WriteCompositeKey::write_into(*self, writer);
+            }
+        }
+
+        impl #impl_generics ::kv_storage::EncodeLike<#name #ty_generics>
+            for #name #ty_generics #where_clause
+        {
+        }
+
+        impl #impl_generics ::kv_storage::EncodeLike<#name #ty_generics>
+            for &#name #ty_generics #where_clause
+        {
+        }
+    }
+}
+
+fn derive_enum(input: &DeriveInput, data: &DataEnum) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let type_name = name.to_string();
+
+    let mut variants = Vec::with_capacity(data.variants.len());
+    let mut discriminants = Vec::with_capacity(data.variants.len());
+    let mut next = Some(0u8);
+
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(syn::Error::new(
+                variant.fields.span(),
+                "`StorageKey` can only be derived for enums whose variants have no fields",
+            ));
+        }
+
+        let discriminant = match &variant.discriminant {
+            Some((_, expr)) => explicit_discriminant(expr)?,
+            None => next.ok_or_else(|| {
+                syn::Error::new(
+                    variant.ident.span(),
+                    "`StorageKey` discriminants must fit in a `u8`",
+                )
+            })?,
+        };
+
+        next = discriminant.checked_add(1);
+        variants.push(&variant.ident);
+        discriminants.push(discriminant);
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut expanded = quote! {
+        impl #impl_generics ::kv_storage::WriteCompositeKey for #name #ty_generics #where_clause {
+            fn total_len(&self) -> usize {
+                1
+            }
+
+            fn write_into<W: ::kv_storage::WriteKeyPart>(&self, writer: &mut W) {
+                let discriminant: u8 = match self {
+                    #(Self::#variants => #discriminants,)*
+                };
+
+                writer.write_key_part(&[discriminant]);
+            }
+        }
+
+        impl #impl_generics ::kv_storage::KeyDeserialize for #name #ty_generics #where_clause {
+            type Owned = Self;
+
+            #[allow(unreachable_patterns)]
+            fn from_key_bytes(
+                bytes: &[u8],
+            ) -> ::core::result::Result<Self::Owned, ::kv_storage::KeyDecodeError> {
+                match bytes {
+                    #([#discriminants] => Ok(Self::#variants),)*
+                    [_] => Err(::kv_storage::KeyDecodeError::InvalidValue(#type_name)),
+                    _ => Err(::kv_storage::KeyDecodeError::InvalidLength {
+                        expected: 1,
+                        found: bytes.len(),
+                    }),
+                }
+            }
+        }
+    };
+
+    let no_bounds: [WherePredicate; 0] = [];
+    expanded.extend(derive_by_ref_and_encode_like(input, &no_bounds));
+
+    Ok(expanded)
+}
+
+fn explicit_discriminant(expr: &Expr) -> syn::Result<u8> {
+    let error = || {
+        syn::Error::new(
+            expr.span(),
+            "`StorageKey` discriminants must be integer literals that fit in a `u8`",
+        )
+    };
+
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(int), ..
+        }) => int.base10_parse().map_err(|_| error()),
+        _ => Err(error()),
+    }
+}
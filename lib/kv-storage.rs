@@ -1,9 +1,71 @@
+//! Typed key-value storage over pluggable serializers and backends.
+//!
+//! Containers ([`Item`], [`Map`]) are declared as constants and used against any [`Storage`],
+//! typically a [`KvStore`] pairing a serializer with a repo:
+//!
+//! ```
+//! use kv_storage::prelude::*;
+//! use kv_storage_bincode::Bincode;
+//! use kv_storage_memory::MemoryRepo;
+//!
+//! const OWNER: Item<String> = item!("owner");
+//! const BALANCES: Map<64, &str, u128> = map!("balances");
+//!
+//! let mut store: KvStore<Bincode, MemoryRepo> = KvStore::default();
+//!
+//! OWNER.save(&mut store, "alice".to_owned()).unwrap();
+//! BALANCES.save(&mut store, "alice", 100).unwrap();
+//!
+//! assert_eq!(OWNER.may_load(&store).unwrap().as_deref(), Some("alice"));
+//! assert_eq!(BALANCES.may_load(&store, "alice").unwrap(), Some(100));
+//! ```
 #![deny(clippy::all)]
 #![warn(clippy::pedantic)]
 
-use std::{borrow::Borrow, error::Error as StdError, marker::PhantomData};
+use std::{
+    borrow::{Borrow, Cow},
+    collections::{BTreeMap, VecDeque},
+    error::Error as StdError,
+    io,
+    marker::PhantomData,
+};
 
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Object-safe serialization, for saving values of different types in one batch, see
+/// [`MutStorage::save_batch`]. Anything implementing `serde::Serialize` implements it.
+pub use erased_serde::Serialize as ErasedSerialize;
+
+/// Derive the key traits for a struct or a field-less enum, see the `kv-storage-derive` crate.
+///
+/// ```
+/// use kv_storage::{prelude::*, StorageKey};
+///
+/// #[derive(StorageKey)]
+/// struct OrderKey {
+///     owner: String,
+///     id: u64,
+/// }
+///
+/// const ORDERS: Map<64, OrderKey, u128> = map!("orders");
+/// const BY_TUPLE: Map<64, (String, u64), u128> = map!("orders");
+///
+/// // fields are written like the parts of a tuple
+/// let key = OrderKey { owner: "alice".to_owned(), id: 7 };
+/// assert_eq!(ORDERS.key(&key).as_ref(), BY_TUPLE.key(("alice".to_owned(), 7)).as_ref());
+/// ```
+#[cfg(feature = "derive")]
+pub use kv_storage_derive::StorageKey;
+
+pub mod prelude {
+    pub use crate::{
+        deque, item, map, set, storage_keys, Bound, DefaultMap, Deque, Durability, Error, Item,
+        IterStorage, KvStore, LoadError, Map, MutStorage, Order, Removed, Set, Storage,
+    };
+
+    #[cfg(feature = "derive")]
+    pub use crate::StorageKey;
+}
 
 pub trait Fallible {
     type Error: StdError;
@@ -16,17 +78,76 @@ pub trait Serializer: Fallible {
     ///
     /// This function will return an error depending on the implementor.
     fn serialize<T: Serialize>(&mut self, item: &T) -> Result<&[u8], Self::Error>;
+
+    /// Serialize an item into bytes the caller keeps, see [`Write::write_owned`].
+    ///
+    /// The default implementation copies the buffer. Implementors should override it to hand the
+    /// buffer over, starting a new one for the next item.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error depending on the implementor.
+    fn serialize_owned<T: Serialize>(&mut self, item: &T) -> Result<Vec<u8>, Self::Error> {
+        self.serialize(item).map(<[u8]>::to_vec)
+    }
+
+    /// Serialize an item into a writer.
+    ///
+    /// The default implementation serializes into the buffer and writes it, implementors that
+    /// can encode straight into the writer should override it along with
+    /// [`Serializer::supports_streaming`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if serializing fails, or if the writer does.
+    fn serialize_into<T, W>(
+        &mut self,
+        item: &T,
+        mut writer: W,
+    ) -> Result<(), StreamError<Self::Error>>
+    where
+        T: Serialize,
+        W: io::Write,
+    {
+        let buffer = self.serialize(item).map_err(StreamError::Serde)?;
+        writer.write_all(buffer).map_err(StreamError::Io)
+    }
+
+    /// Whether [`Serializer::serialize_into`] writes without buffering the whole item.
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+}
+
+/// Why [`Serializer::serialize_into`] failed.
+#[derive(Debug, thiserror::Error)]
+pub enum StreamError<E> {
+    #[error(transparent)]
+    Serde(E),
+    #[error(transparent)]
+    Io(io::Error),
 }
 
 pub trait Deserializer: Fallible {
-    /// Deserialize some bytes.
+    /// Deserialize some bytes, borrowed so repos can lend them rather than copy.
     ///
     /// # Errors
     ///
     /// This function will return an error depending on the implementor
-    fn deserialize<T: DeserializeOwned>(bytes: Vec<u8>) -> Result<T, Self::Error>;
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error>;
 }
 
+/// Serializers whose bytes `Other` reads back as is, so stores using them can exchange raw
+/// values, see [`KvStore::copy_all`].
+///
+/// Every serializer shares its own format. Distinct serializers writing the same bytes, e.g. a
+/// wrapper adding nothing to the encoding, opt in by implementing it.
+///
+/// Pairs of different formats only copy through deserializing, see [`Map::copy_to`].
+pub trait SameFormat<Other> {}
+
+impl<S> SameFormat<S> for S {}
+
 pub trait Write: Fallible {
     /// Write some bytes into storage at the given key.
     ///
@@ -34,522 +155,7476 @@ pub trait Write: Fallible {
     ///
     /// This function will return an error depending on the implementor
     fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error>;
-}
 
-pub trait Read: Fallible {
-    /// Read some bytes from storage at the given key if they exist.
+    /// Write some bytes into storage at the given key, persisted at least to the given level
+    /// before returning.
+    ///
+    /// The default implementation ignores the level and performs a plain write, check
+    /// [`Write::supports_durability`] where the guarantee matters.
     ///
     /// # Errors
     ///
     /// This function will return an error depending on the implementor
-    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error>;
-}
+    fn write_durable(
+        &mut self,
+        key: &[u8],
+        bytes: &[u8],
+        durability: Durability,
+    ) -> Result<(), Self::Error> {
+        let _ = durability;
+        self.write(key, bytes)
+    }
 
-pub trait HasKey: Fallible {
-    /// Check if a key exists in storage.
+    /// Whether [`Write::write_durable`] honours its durability level.
+    fn supports_durability(&self) -> bool {
+        false
+    }
+
+    /// Write some bytes into storage at the given key, taking ownership of them.
+    ///
+    /// The default implementation performs a plain write. Implementors that keep the bytes as
+    /// they are should override it along with [`Write::takes_ownership`].
     ///
     /// # Errors
     ///
     /// This function will return an error depending on the implementor
-    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error>;
-}
+    fn write_owned(&mut self, key: &[u8], bytes: Vec<u8>) -> Result<(), Self::Error> {
+        self.write(key, &bytes)
+    }
 
-pub trait Remove: Fallible {
-    /// Remove a key and any associated data from storage.
+    /// Whether [`Write::write_owned`] keeps the bytes rather than copying them.
+    fn takes_ownership(&self) -> bool {
+        false
+    }
+
+    /// Write what `fill` writes into storage at the given key, without holding it all in memory
+    /// at once.
+    ///
+    /// If `fill` fails nothing is written and its error is returned in the inner result. Failures
+    /// of the writer handed to `fill` are the repo's own and returned as its error, whatever
+    /// `fill` makes of them.
+    ///
+    /// The default implementation collects the bytes and performs a plain write. Implementors
+    /// backed by files or sockets should override it along with [`Write::supports_streaming`].
     ///
     /// # Errors
     ///
     /// This function will return an error depending on the implementor
-    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error>;
+    fn write_stream<E>(
+        &mut self,
+        key: &[u8],
+        fill: &mut StreamFill<'_, E>,
+    ) -> Result<Result<(), E>, Self::Error> {
+        let mut bytes = Vec::new();
+
+        // a `Vec` never fails, so any failure is `fill`'s
+        if let Err(error) = fill(&mut bytes) {
+            return Ok(Err(error));
+        }
+
+        self.write(key, &bytes).map(Ok)
+    }
+
+    /// Whether [`Write::write_stream`] streams rather than collecting the bytes.
+    fn supports_streaming(&self) -> bool {
+        false
+    }
 }
 
-pub trait Storage: Fallible {
-    type Serde: Deserializer;
-    type Repo: Read + HasKey;
+/// How far a write must be persisted before it is acknowledged.
+///
+/// ```
+/// use kv_storage::Write;
+/// use kv_storage_memory::prelude::*;
+///
+/// const BALANCE: Item<u128> = item!("balance");
+///
+/// let mut store = MemStore::new_in_memory();
+///
+/// // the memory repo has nothing to persist to, and says so
+/// assert!(!store.repo().supports_durability());
+///
+/// BALANCE.save_with(&mut store, 100, Durability::Sync).unwrap();
+/// assert_eq!(BALANCE.may_load(&store).unwrap(), Some(100));
+/// ```
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Durability {
+    /// Buffered, may be lost on a crash.
+    #[default]
+    Relaxed,
+    /// Handed to the OS, survives the process crashing but not the machine.
+    Flush,
+    /// Synced to stable storage.
+    Sync,
+}
 
-    /// Load an item for a given key if it exists.
+pub trait Read: Fallible {
+    /// Read some bytes from storage at the given key if they exist.
     ///
     /// # Errors
     ///
-    /// This function will return an error if:
-    /// - Read encounters an error.
-    /// - Deserializer encounters an error.
-    fn may_load<T: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<T>, Self::Error>;
+    /// This function will return an error depending on the implementor
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error>;
 
-    /// Check if a key exists in storage.
+    /// Call `f` with the bytes at the given key if they exist, returning what it returns.
+    ///
+    /// The default implementation reads a copy of the bytes. Implementors that hold them, or can
+    /// pin them, should override it to lend them instead.
     ///
     /// # Errors
     ///
-    /// This function will return an error if:
-    /// - Storage encounters an error.
-    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error>;
-}
+    /// This function will return an error depending on the implementor
+    fn read_with<R, F>(&self, key: &[u8], f: F) -> Result<R, Self::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> R,
+    {
+        Ok(f(self.read(key)?.as_deref()))
+    }
 
-pub trait MutStorage: Storage {
-    /// Save an item against the given key.
+    /// Read the bytes at each of the given keys, in the same order, with `None` for missing keys.
+    ///
+    /// The default implementation reads them one at a time. Implementors with a native multi-get
+    /// should override it.
     ///
     /// # Errors
     ///
-    /// This function will return an error if:
-    /// - Serializer encounters an error.
-    /// - Write encounters an error.
-    fn save<T>(&mut self, key: &[u8], item: &T) -> Result<(), Self::Error>
-    where
-        T: Serialize;
+    /// This function will return an error depending on the implementor
+    fn read_many(&self, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>, Self::Error> {
+        keys.iter().map(|key| self.read(key)).collect()
+    }
+}
 
-    /// Remove a key and any associated data from storage.
+pub trait HasKey: Read {
+    /// Check if a key exists in storage.
+    ///
+    /// The default implementation reads the key through [`Read::read_with`]. Implementors with a
+    /// cheaper existence check should override it.
     ///
     /// # Errors
     ///
-    /// This function will return an error if:
-    /// - Storage encounters an error.
-    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error>;
+    /// This function will return an error depending on the implementor
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        self.read_with(key, |bytes| bytes.is_some())
+    }
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum Error<S, R> {
-    #[error(transparent)]
-    Serde(S),
-    #[error(transparent)]
-    Repo(R),
-}
+/// A boxed iterator over raw entries, as returned by [`Iterate::scan`].
+pub type RawEntries<'a> = Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)> + 'a>;
 
-#[derive(Default)]
-pub struct KvStore<Serde, Repo> {
-    serde: Serde,
-    repo: Repo,
-}
+/// A boxed iterator over raw keys, as returned by [`Iterate::range_keys`].
+pub type RawKeys<'a> = Box<dyn Iterator<Item = Vec<u8>> + 'a>;
 
-impl<Serde, Repo> KvStore<Serde, Repo> {
-    pub const fn new(serde: Serde, repo: Repo) -> Self {
-        Self { serde, repo }
-    }
+/// One end of a key range.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Bound<K> {
+    Inclusive(K),
+    Exclusive(K),
+    Unbounded,
+}
 
-    pub fn from_repo(repo: impl Into<Repo>) -> Self
-    where
-        Serde: Default,
-    {
-        Self {
-            serde: Serde::default(),
-            repo: repo.into(),
+impl<K> Bound<K> {
+    pub const fn as_ref(&self) -> Bound<&K> {
+        match self {
+            Bound::Inclusive(key) => Bound::Inclusive(key),
+            Bound::Exclusive(key) => Bound::Exclusive(key),
+            Bound::Unbounded => Bound::Unbounded,
         }
     }
 
-    pub fn repo(&self) -> &Repo {
-        &self.repo
-    }
-
-    pub fn mut_repo(&mut self) -> &mut Repo {
-        &mut self.repo
+    pub fn map<T, F: FnOnce(K) -> T>(self, f: F) -> Bound<T> {
+        match self {
+            Bound::Inclusive(key) => Bound::Inclusive(f(key)),
+            Bound::Exclusive(key) => Bound::Exclusive(f(key)),
+            Bound::Unbounded => Bound::Unbounded,
+        }
     }
 
-    pub fn serde(&self) -> &Serde {
-        &self.serde
+    /// Whether no key can lie between `min` and `max`.
+    pub fn is_empty_range(min: &Self, max: &Self) -> bool
+    where
+        K: Ord,
+    {
+        match (min, max) {
+            (Bound::Inclusive(min), Bound::Inclusive(max)) => min > max,
+            (
+                Bound::Inclusive(min) | Bound::Exclusive(min),
+                Bound::Inclusive(max) | Bound::Exclusive(max),
+            ) => min >= max,
+            _ => false,
+        }
     }
+}
 
-    pub fn mut_serde(&mut self) -> &mut Serde {
-        &mut self.serde
+impl<K> From<Bound<K>> for std::ops::Bound<K> {
+    fn from(bound: Bound<K>) -> Self {
+        match bound {
+            Bound::Inclusive(key) => std::ops::Bound::Included(key),
+            Bound::Exclusive(key) => std::ops::Bound::Excluded(key),
+            Bound::Unbounded => std::ops::Bound::Unbounded,
+        }
     }
 }
 
-impl<Serde, Repo> Fallible for KvStore<Serde, Repo>
-where
-    Serde: Fallible,
-    Repo: Fallible,
-{
-    type Error = Error<Serde::Error, Repo::Error>;
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Order {
+    #[default]
+    Ascending,
+    Descending,
 }
 
-impl<Serde, Repo> Storage for KvStore<Serde, Repo>
-where
-    Serde: Deserializer,
-    Repo: Read + HasKey,
-{
-    type Serde = Serde;
-    type Repo = Repo;
-
-    fn may_load<T: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<T>, Self::Error> {
-        let Some(bytes) = self.repo.read(key).map_err(Error::Repo)? else {
-            return Ok(None);
-        };
+/// The smallest key greater than every key starting with `prefix`, if there is one.
+fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let last = prefix.iter().rposition(|byte| *byte != u8::MAX)?;
 
-        Serde::deserialize(bytes).map(Some).map_err(Error::Serde)
-    }
+    let mut end = prefix[..=last].to_vec();
+    end[last] += 1;
 
-    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
-        self.repo.has_key(key).map_err(Error::Repo)
-    }
+    Some(end)
 }
 
-impl<Serde, Repo> MutStorage for KvStore<Serde, Repo>
-where
-    Serde: Serializer + Deserializer,
-    Repo: Write + Remove + Read + HasKey,
-{
-    fn save<T>(&mut self, key: &[u8], item: &T) -> Result<(), Self::Error>
-    where
-        T: Serialize,
-    {
-        let buffer = self.serde.serialize(item).map_err(Error::Serde)?;
-        self.repo.write(key, buffer).map_err(Error::Repo)?;
-        Ok(())
-    }
+pub trait Iterate: Fallible {
+    /// Iterate the entries whose keys lie between the bounds, ordered by key bytes. Keys are
+    /// yielded in full, and an empty range yields nothing.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error depending on the implementor
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error>;
 
-    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
-        self.repo.remove(key).map_err(Error::Repo)
+    /// Iterate the keys between the bounds, as [`Iterate::range`] does for entries.
+    ///
+    /// The default implementation drops the values yielded by [`Iterate::range`], implementors
+    /// that can skip reading them should override it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error depending on the implementor
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error> {
+        let entries = self.range(min, max, order)?;
+        Ok(Box::new(entries.map(|(key, _)| key)))
     }
-}
 
-#[derive(Copy, Clone)]
-pub struct Item<T> {
-    key: &'static [u8],
-    _t: PhantomData<T>,
-}
+    /// Iterate every entry whose key starts with the given prefix, in ascending order.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error depending on the implementor
+    fn scan(&self, prefix: &[u8]) -> Result<RawEntries<'_>, Self::Error> {
+        let end = prefix_end(prefix);
+        let max = end.as_deref().map_or(Bound::Unbounded, Bound::Exclusive);
 
-impl<T> Item<T> {
-    #[must_use]
-    pub const fn new(key: &'static [u8]) -> Self {
-        Self {
-            key,
-            _t: PhantomData,
-        }
+        self.range(Bound::Inclusive(prefix), max, Order::Ascending)
     }
+}
 
-    /// Save the item to storage.
+pub trait Remove: Fallible {
+    /// Remove a key and any associated data from storage.
     ///
     /// # Errors
     ///
-    /// This function will return an error if the store encounters an error.
-    pub fn save<Store, Item>(&self, store: &mut Store, item: Item) -> Result<(), Store::Error>
-    where
-        T: Serialize,
-        Store: MutStorage,
-        Item: Borrow<T>,
-    {
-        store.save(self.key, item.borrow())
-    }
+    /// This function will return an error depending on the implementor
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error>;
 
-    /// Load the item from storage if it exists, otherwise `None`.
+    /// Remove a key and any associated data from storage, reporting whether it existed.
+    ///
+    /// The default implementation checks for the key before removing it, implementors that
+    /// learn this from the removal itself should override it.
     ///
     /// # Errors
     ///
-    /// This function will return an error if the store encounters an error.
-    pub fn may_load<Store>(&self, store: &Store) -> Result<Option<T>, Store::Error>
+    /// This function will return an error depending on the implementor
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error>
     where
-        T: DeserializeOwned,
-        Store: Storage,
+        Self: HasKey,
     {
-        store.may_load::<T>(self.key)
+        if !self.has_key(key)? {
+            return Ok(Removed::DidNotExist);
+        }
+
+        self.remove(key)?;
+
+        Ok(Removed::Existed)
     }
 
-    /// Check if the item is empty
+    /// Apply writes and removals in order.
+    ///
+    /// The default implementation applies them one at a time, so a failure leaves the earlier
+    /// ones applied. Implementors that can apply a batch atomically should override it.
     ///
     /// # Errors
     ///
-    /// This function will return an error if the store encounters an error.
-    pub fn is_empty<Store>(&self, store: &Store) -> Result<bool, Store::Error>
+    /// This function will return an error depending on the implementor
+    fn write_batch(&mut self, ops: &[BatchOp<'_>]) -> Result<(), Self::Error>
     where
-        T: DeserializeOwned,
-        Store: Storage,
+        Self: Write,
     {
-        store.has_key(self.key).map(|has_key| !has_key)
-    }
+        for (key, bytes) in ops {
+            match bytes {
+                Some(bytes) => self.write(key, bytes)?,
+                None => self.remove(key)?,
+            }
+        }
 
-    /// Clear the item from storage.
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the store encounters an error.
-    pub fn clear<Store: MutStorage>(&self, store: &mut Store) -> Result<(), Store::Error> {
-        store.remove(self.key)
+        Ok(())
     }
 }
 
-pub trait WriteKeyPart {
-    fn write_key_part(&mut self, part: &[u8]);
-}
+/// A write, or with no bytes a removal, as applied by [`Remove::write_batch`].
+pub type BatchOp<'a> = (Cow<'a, [u8]>, Option<Cow<'a, [u8]>>);
 
-pub trait WriteCompositeKey {
-    fn total_len(&self) -> usize;
+/// Writes a value into the writer handed to it by [`Write::write_stream`].
+pub type StreamFill<'a, E> = dyn FnMut(&mut dyn io::Write) -> Result<(), E> + 'a;
 
-    fn write_into<W: WriteKeyPart>(&self, writer: &mut W);
+/// Whether a removed key was present in storage.
+///
+/// ```
+/// use kv_storage_memory::prelude::*;
+///
+/// const NONCES: Map<16, u64, ()> = map!("nonces");
+///
+/// let mut store = MemStore::new_in_memory();
+/// NONCES.save(&mut store, 7, ()).unwrap();
+///
+/// assert_eq!(NONCES.remove_returning(&mut store, 7).unwrap(), Removed::Existed);
+/// assert!(!NONCES.remove_returning(&mut store, 7).unwrap().existed());
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Removed {
+    Existed,
+    DidNotExist,
 }
 
-#[derive(Copy, Clone)]
-pub struct Map<const N: usize, K, V> {
-    prefix: &'static [u8],
-    _k: PhantomData<K>,
-    _v: PhantomData<V>,
+impl Removed {
+    #[must_use]
+    pub const fn existed(self) -> bool {
+        matches!(self, Removed::Existed)
+    }
 }
 
-impl<const N: usize, K, V> Map<N, K, V>
-where
-    K: WriteCompositeKey,
-{
+/// A repo that accumulates dead data (overwritten or removed values) until compacted.
+pub trait Compactable: Fallible {
+    fn compaction_stats(&self) -> CompactionStats;
+
+    /// Rewrite the repo keeping only live data.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error depending on the implementor
+    fn compact(&mut self) -> Result<CompactionReport, Self::Error>;
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct CompactionStats {
+    pub live_keys: u64,
+    /// Bytes held by overwritten or removed entries.
+    pub dead_bytes: u64,
+    /// Total bytes held, live and dead.
+    pub total_bytes: u64,
+}
+
+impl CompactionStats {
+    /// The share of `total_bytes` that is dead, in percent.
     #[must_use]
-    pub const fn new(prefix: &'static [u8]) -> Self {
+    pub const fn dead_percent(&self) -> u64 {
+        if self.total_bytes == 0 {
+            return 0;
+        }
+
+        self.dead_bytes.saturating_mul(100) / self.total_bytes
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CompactionReport {
+    pub before: CompactionStats,
+    pub after: CompactionStats,
+}
+
+impl CompactionReport {
+    #[must_use]
+    pub const fn reclaimed_bytes(&self) -> u64 {
+        self.before
+            .total_bytes
+            .saturating_sub(self.after.total_bytes)
+    }
+}
+
+/// When a [`MaintenanceScheduler`] compacts.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompactionPolicy {
+    /// Once at least this percentage of bytes is dead.
+    DeadPercent(u64),
+    /// Once at least this long has passed since the last compaction, in the caller's time unit.
+    Interval(u64),
+}
+
+/// Decides when to compact a [`Compactable`] repo. It runs nothing by itself, the caller ticks it
+/// with [`MaintenanceScheduler::maybe_compact`], e.g. after writes, when idle, or on a timer.
+#[derive(Debug, Clone)]
+pub struct MaintenanceScheduler {
+    policy: CompactionPolicy,
+    last_compaction: Option<u64>,
+}
+
+impl MaintenanceScheduler {
+    #[must_use]
+    pub const fn new(policy: CompactionPolicy) -> Self {
         Self {
-            prefix,
-            _k: PhantomData,
-            _v: PhantomData,
+            policy,
+            last_compaction: None,
         }
     }
 
-    /// Save the item for the given key.
+    #[must_use]
+    pub const fn policy(&self) -> CompactionPolicy {
+        self.policy
+    }
+
+    /// When the last compaction through this scheduler ran, if any.
+    #[must_use]
+    pub const fn last_compaction(&self) -> Option<u64> {
+        self.last_compaction
+    }
+
+    /// Whether the policy calls for compacting at `now`.
+    pub fn is_due<R: Compactable>(&self, repo: &R, now: u64) -> bool {
+        match self.policy {
+            CompactionPolicy::DeadPercent(threshold) => {
+                let stats = repo.compaction_stats();
+                stats.dead_bytes > 0 && stats.dead_percent() >= threshold
+            }
+            CompactionPolicy::Interval(interval) => self
+                .last_compaction
+                .is_none_or(|last| now.saturating_sub(last) >= interval),
+        }
+    }
+
+    /// Compact the repo if the policy calls for it, returning the report if it did.
     ///
     /// # Errors
     ///
-    /// This function will return an error if the store encounters an error.
-    pub fn save<Store, Key, Item>(
+    /// This function will return an error if compaction fails.
+    pub fn maybe_compact<R: Compactable>(
+        &mut self,
+        repo: &mut R,
+        now: u64,
+    ) -> Result<Option<CompactionReport>, R::Error> {
+        if !self.is_due(repo, now) {
+            return Ok(None);
+        }
+
+        let report = repo.compact()?;
+        self.last_compaction = Some(now);
+
+        Ok(Some(report))
+    }
+}
+
+/// Typed reads of raw keys, what every container loads through.
+///
+/// Implemented by [`KvStore`] and the stores wrapping one, so helpers can be written against
+/// any of them:
+///
+/// ```
+/// use kv_storage_memory::prelude::*;
+///
+/// fn owner<S: Storage>(store: &S) -> Result<Option<String>, S::Error> {
+///     store.may_load(b"owner")
+/// }
+///
+/// let mut store = MemStore::new_in_memory();
+/// assert_eq!(owner(&store).unwrap(), None);
+///
+/// store.save(b"owner", &"alice").unwrap();
+/// assert_eq!(owner(&store).unwrap().as_deref(), Some("alice"));
+/// ```
+pub trait Storage: Fallible {
+    type Serde: Deserializer;
+    type Repo: Read + HasKey;
+
+    /// Load an item for a given key if it exists.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Read encounters an error.
+    /// - Deserializer encounters an error.
+    fn may_load<T: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<T>, Self::Error>;
+
+    /// Load an item for a given key if it exists and the predicate accepts its serialized length.
+    ///
+    /// The predicate is consulted before deserializing, so rejected values cost a single read.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Read encounters an error.
+    /// - Deserializer encounters an error.
+    fn may_load_if<T, P>(&self, key: &[u8], pred: P) -> Result<Option<T>, Self::Error>
+    where
+        T: DeserializeOwned,
+        P: FnOnce(usize) -> bool;
+
+    /// Load the items for the given keys, in the same order, with `None` for missing keys.
+    ///
+    /// The default implementation loads them one at a time.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Read encounters an error.
+    /// - Deserializer encounters an error.
+    fn may_load_many<T: DeserializeOwned>(
         &self,
-        store: &mut Store,
-        key: Key,
-        item: Item,
-    ) -> Result<(), Store::Error>
+        keys: &[&[u8]],
+    ) -> Result<Vec<Option<T>>, Self::Error> {
+        keys.iter().map(|key| self.may_load(key)).collect()
+    }
+
+    /// Check if a key exists in storage.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Storage encounters an error.
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error>;
+}
+
+/// A boxed iterator over deserialized entries, as returned by [`IterStorage::scan`].
+pub type Entries<'a, T, E> = Box<dyn Iterator<Item = Result<(Vec<u8>, T), E>> + 'a>;
+
+/// Typed iteration over key ranges, in key order.
+///
+/// ```
+/// use kv_storage_memory::prelude::*;
+///
+/// fn total<S: IterStorage>(store: &S, prefix: &[u8]) -> Result<u64, S::Error> {
+///     store.scan::<u64>(prefix)?.map(|entry| entry.map(|(_, n)| n)).sum()
+/// }
+///
+/// let mut store = MemStore::new_in_memory();
+/// store.save(b"stock/apples", &3u64).unwrap();
+/// store.save(b"stock/pears", &4u64).unwrap();
+/// store.save(b"total", &100u64).unwrap();
+///
+/// assert_eq!(total(&store, b"stock/").unwrap(), 7);
+/// ```
+pub trait IterStorage: Storage {
+    /// Iterate the entries whose keys lie between the bounds, deserializing each value.
+    ///
+    /// Values that fail to deserialize are yielded as errors, the iteration carries on past them.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the repo fails to start the scan.
+    fn range<'a, T>(
+        &'a self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<Entries<'a, T, Self::Error>, Self::Error>
     where
-        V: Serialize,
-        Store: MutStorage,
-        Key: Borrow<K>,
-        Item: Borrow<V>,
+        T: DeserializeOwned + 'a;
+
+    /// Iterate the keys between the bounds, without reading or deserializing values.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the repo fails to start the scan.
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error>;
+
+    /// Iterate every entry whose key starts with the given prefix, deserializing each value.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the repo fails to start the scan.
+    fn scan<'a, T>(&'a self, prefix: &[u8]) -> Result<Entries<'a, T, Self::Error>, Self::Error>
+    where
+        T: DeserializeOwned + 'a,
     {
-        let composite = compose_key::<N>(self.prefix, key.borrow());
-        store.save(composite.as_ref(), item.borrow())
+        let end = prefix_end(prefix);
+        let max = end.as_deref().map_or(Bound::Unbounded, Bound::Exclusive);
+
+        self.range(Bound::Inclusive(prefix), max, Order::Ascending)
     }
 
-    /// Load the item for the given key if it exists, otherwise `None`.
+    /// Iterate every key starting with the given prefix, in ascending order.
     ///
     /// # Errors
     ///
-    /// This function will return an error if the store encounters an error.
-    pub fn may_load<Store, Key>(&self, store: &Store, key: Key) -> Result<Option<V>, Store::Error>
+    /// This function will return an error if the repo fails to start the scan.
+    fn scan_keys(&self, prefix: &[u8]) -> Result<RawKeys<'_>, Self::Error> {
+        let end = prefix_end(prefix);
+        let max = end.as_deref().map_or(Bound::Unbounded, Bound::Exclusive);
+
+        self.range_keys(Bound::Inclusive(prefix), max, Order::Ascending)
+    }
+}
+
+/// Typed writes and removals of raw keys, what every container saves through.
+///
+/// ```
+/// use kv_storage::Removed;
+/// use kv_storage_memory::prelude::*;
+///
+/// fn rename<S: MutStorage>(store: &mut S, from: &[u8], to: &[u8]) -> Result<(), S::Error> {
+///     if let Some(value) = store.may_load::<String>(from)? {
+///         store.save(to, &value)?;
+///         store.remove(from)?;
+///     }
+///     Ok(())
+/// }
+///
+/// let mut store = MemStore::new_in_memory();
+/// store.save(b"old", &"value").unwrap();
+///
+/// rename(&mut store, b"old", b"new").unwrap();
+///
+/// assert_eq!(store.may_load::<String>(b"new").unwrap().as_deref(), Some("value"));
+/// assert_eq!(store.remove_returning(b"old").unwrap(), Removed::DidNotExist);
+/// ```
+pub trait MutStorage: Storage {
+    /// Save an item against the given key.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Serializer encounters an error.
+    /// - Write encounters an error.
+    fn save<T>(&mut self, key: &[u8], item: &T) -> Result<(), Self::Error>
     where
-        V: DeserializeOwned,
-        Store: Storage,
-        Key: Borrow<K>,
+        T: Serialize;
+
+    /// Save an item against the given key, persisted at least to the given level.
+    ///
+    /// The default implementation ignores the level and performs a plain save, as
+    /// [`Write::write_durable`] does.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Serializer encounters an error.
+    /// - Write encounters an error.
+    fn save_with<T>(
+        &mut self,
+        key: &[u8],
+        item: &T,
+        durability: Durability,
+    ) -> Result<(), Self::Error>
+    where
+        T: Serialize,
     {
-        let composite = compose_key::<N>(self.prefix, key.borrow());
-        store.may_load::<V>(composite.as_ref())
+        let _ = durability;
+        self.save(key, item)
     }
 
-    /// Check if a key exists.
+    /// Save several items, each against its key, in order.
+    ///
+    /// The default implementation saves them one at a time, so a failure leaves the earlier ones
+    /// saved.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Serializer encounters an error.
+    /// - Write encounters an error.
+    fn save_batch(&mut self, items: &[(&[u8], &dyn ErasedSerialize)]) -> Result<(), Self::Error> {
+        for (key, item) in items {
+            self.save(key, item)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove a key and any associated data from storage.
     ///
     /// # Errors
     ///
     /// This function will return an error if:
     /// - Storage encounters an error.
-    pub fn has_key<Store, Key>(&self, store: &Store, key: Key) -> Result<bool, Store::Error>
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error>;
+
+    /// Remove a key and any associated data from storage, reporting whether it existed.
+    ///
+    /// The default implementation checks for the key before removing it, as
+    /// [`Remove::remove_returning`] does.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Storage encounters an error.
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        if !self.has_key(key)? {
+            return Ok(Removed::DidNotExist);
+        }
+
+        self.remove(key)?;
+
+        Ok(Removed::Existed)
+    }
+}
+
+/// A [`KvStore`] error, from either its serializer or its repo.
+///
+/// ```
+/// use kv_storage::{Error, Write};
+/// use kv_storage_memory::prelude::*;
+///
+/// const COUNT: Item<u64> = item!("count");
+///
+/// let mut store = MemStore::new_in_memory();
+///
+/// // a single byte can't be deserialized as a u64
+/// store.mut_repo().write(COUNT.key(), &[1]).unwrap();
+///
+/// assert!(matches!(COUNT.may_load(&store), Err(Error::Serde(_))));
+/// ```
+///
+/// The `debug_hooks` feature adds a variant, so matches must have a wildcard arm.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error<S, R> {
+    #[error(transparent)]
+    Serde(S),
+    #[error(transparent)]
+    Repo(R),
+    #[cfg(feature = "debug_hooks")]
+    #[error(transparent)]
+    Injected(InjectedError),
+}
+
+/// Which side of a copy between two stores failed.
+///
+/// ```
+/// use kv_storage::{ScopedError, ScopedStore, TransferError};
+/// use kv_storage_memory::prelude::*;
+///
+/// const BALANCES: Map<64, &str, u128> = map!("balances");
+///
+/// let mut from = MemStore::new_in_memory();
+/// BALANCES.save(&mut from, "alice", 10).unwrap();
+///
+/// // nothing is writable in the destination
+/// let mut to = ScopedStore::new(MemStore::new_in_memory());
+///
+/// assert!(matches!(
+///     BALANCES.copy_to(&from, &mut to),
+///     Err(TransferError::Destination(ScopedError::AccessDenied { .. }))
+/// ));
+/// ```
+#[derive(Debug, thiserror::Error)]
+pub enum TransferError<From, To> {
+    #[error("source: {0}")]
+    Source(From),
+    #[error("destination: {0}")]
+    Destination(To),
+}
+
+/// A failure injected by a debug hook.
+#[cfg(feature = "debug_hooks")]
+#[derive(Debug, thiserror::Error)]
+#[error("injected failure")]
+pub struct InjectedError;
+
+/// Called with the serialized bytes before they are written.
+#[cfg(feature = "debug_hooks")]
+pub type PostSerializeHook = fn(bytes: &[u8]) -> Result<(), InjectedError>;
+
+/// Called with the key and serialized bytes immediately before the repo write.
+#[cfg(feature = "debug_hooks")]
+pub type PreWriteHook = fn(key: &[u8], bytes: &[u8]) -> Result<(), InjectedError>;
+
+#[cfg(feature = "debug_hooks")]
+#[derive(Default)]
+struct Hooks {
+    post_serialize: Option<PostSerializeHook>,
+    pre_write: Option<PreWriteHook>,
+}
+
+#[cfg(feature = "debug_hooks")]
+impl Hooks {
+    fn is_empty(&self) -> bool {
+        self.post_serialize.is_none() && self.pre_write.is_none()
+    }
+
+    fn run(&self, key: &[u8], bytes: &[u8]) -> Result<(), InjectedError> {
+        if let Some(hook) = self.post_serialize {
+            hook(bytes)?;
+        }
+
+        if let Some(hook) = self.pre_write {
+            hook(key, bytes)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Storage built from a serializer and a repo.
+///
+/// ```
+/// use kv_storage::KvStore;
+/// use kv_storage_bincode::Bincode;
+/// use kv_storage_memory::MemoryRepo;
+///
+/// let store = KvStore::new(Bincode::new(), MemoryRepo::default());
+/// assert!(store.repo().is_empty());
+///
+/// // or with a default serializer
+/// let store: KvStore<Bincode, MemoryRepo> = KvStore::from_repo(MemoryRepo::default());
+/// ```
+#[derive(Default)]
+pub struct KvStore<Serde, Repo> {
+    serde: Serde,
+    repo: Repo,
+    #[cfg(feature = "debug_hooks")]
+    hooks: Hooks,
+}
+
+impl<Serde, Repo> KvStore<Serde, Repo> {
+    pub const fn new(serde: Serde, repo: Repo) -> Self {
+        Self {
+            serde,
+            repo,
+            #[cfg(feature = "debug_hooks")]
+            hooks: Hooks {
+                post_serialize: None,
+                pre_write: None,
+            },
+        }
+    }
+
+    /// Build a store around a repo, e.g. one holding data written earlier, with the default
+    /// serializer.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const OWNER: Item<String> = item!("owner");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// OWNER.save(&mut store, "alice".to_owned()).unwrap();
+    ///
+    /// // reopen the same data
+    /// let store = MemStore::from_repo(store.into_repo());
+    /// assert_eq!(OWNER.load(&store).unwrap(), "alice");
+    /// ```
+    pub fn from_repo(repo: impl Into<Repo>) -> Self
     where
-        Store: Storage,
-        Key: Borrow<K>,
+        Serde: Default,
     {
-        let composite = compose_key::<N>(self.prefix, key.borrow());
-        store.has_key(composite.as_ref())
+        Self::new(Serde::default(), repo.into())
+    }
+
+    /// Fail saves between serializing and writing, for testing error handling.
+    #[cfg(feature = "debug_hooks")]
+    pub fn set_post_serialize_hook(&mut self, hook: PostSerializeHook) {
+        self.hooks.post_serialize = Some(hook);
+    }
+
+    /// Fail saves immediately before the repo write, for testing error handling.
+    #[cfg(feature = "debug_hooks")]
+    pub fn set_pre_write_hook(&mut self, hook: PreWriteHook) {
+        self.hooks.pre_write = Some(hook);
+    }
+
+    #[cfg(feature = "debug_hooks")]
+    pub fn clear_hooks(&mut self) {
+        self.hooks = Hooks::default();
+    }
+
+    pub fn repo(&self) -> &Repo {
+        &self.repo
+    }
+
+    pub fn mut_repo(&mut self) -> &mut Repo {
+        &mut self.repo
+    }
+
+    pub fn into_repo(self) -> Repo {
+        self.repo
+    }
+
+    pub fn serde(&self) -> &Serde {
+        &self.serde
+    }
+
+    pub fn mut_serde(&mut self) -> &mut Serde {
+        &mut self.serde
+    }
+}
+
+/// How many entries [`KvStore::copy_all`] and [`KvStore::move_to`] write per batch.
+pub const COPY_BATCH: usize = 256;
+
+impl<Serde, Repo> KvStore<Serde, Repo>
+where
+    Repo: Iterate,
+{
+    /// Copy the raw entries whose keys start with `prefix` into another store, returning how
+    /// many were copied.
+    ///
+    /// The bytes are copied without deserializing them, so both stores must use the same
+    /// format, see [`SameFormat`]:
+    ///
+    /// ```
+    /// use kv_storage::prelude::*;
+    /// use kv_storage_bincode::Bincode;
+    /// use kv_storage_memory::MemoryRepo;
+    ///
+    /// const BALANCES: Map<64, &str, u128> = map!("balances");
+    ///
+    /// let mut old: KvStore<Bincode, MemoryRepo> = KvStore::default();
+    /// let mut new: KvStore<Bincode, MemoryRepo> = KvStore::default();
+    /// BALANCES.save(&mut old, "alice", 100).unwrap();
+    ///
+    /// assert_eq!(old.copy_all(&mut new, BALANCES.prefix()).unwrap(), 1);
+    /// assert_eq!(BALANCES.may_load(&new, "alice").unwrap(), Some(100));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either repo encounters an error, batches written
+    /// before it stay written.
+    pub fn copy_all<ToSerde, ToRepo>(
+        &self,
+        to: &mut KvStore<ToSerde, ToRepo>,
+        prefix: &[u8],
+    ) -> Result<usize, TransferError<Repo::Error, ToRepo::Error>>
+    where
+        Serde: SameFormat<ToSerde>,
+        ToRepo: Write + Remove,
+    {
+        let mut entries = self.repo.scan(prefix).map_err(TransferError::Source)?;
+        let mut copied = 0;
+
+        loop {
+            let ops: Vec<BatchOp<'_>> = entries
+                .by_ref()
+                .take(COPY_BATCH)
+                .map(|(key, bytes)| (Cow::Owned(key), Some(Cow::Owned(bytes))))
+                .collect();
+
+            if ops.is_empty() {
+                return Ok(copied);
+            }
+
+            to.repo
+                .write_batch(&ops)
+                .map_err(TransferError::Destination)?;
+            copied += ops.len();
+        }
+    }
+
+    /// Move the raw entries whose keys start with `prefix` into another store, removing each
+    /// batch from this one once the other holds it, returning how many were moved.
+    ///
+    /// Like [`KvStore::copy_all`] the stores must use the same format.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either repo encounters an error, batches moved
+    /// before it stay moved.
+    pub fn move_to<ToSerde, ToRepo>(
+        &mut self,
+        to: &mut KvStore<ToSerde, ToRepo>,
+        prefix: &[u8],
+    ) -> Result<usize, TransferError<Repo::Error, ToRepo::Error>>
+    where
+        Serde: SameFormat<ToSerde>,
+        Repo: Read + Remove,
+        ToRepo: Write + Remove,
+    {
+        let end = prefix_end(prefix);
+        let max = end.as_deref().map_or(Bound::Unbounded, Bound::Exclusive);
+        let keys: Vec<_> = self
+            .repo
+            .range_keys(Bound::Inclusive(prefix), max, Order::Ascending)
+            .map_err(TransferError::Source)?
+            .collect();
+        let mut moved = 0;
+
+        for chunk in keys.chunks(COPY_BATCH) {
+            let refs: Vec<&[u8]> = chunk.iter().map(Vec::as_slice).collect();
+            let values = self.repo.read_many(&refs).map_err(TransferError::Source)?;
+
+            // keys removed since the scan are skipped
+            let ops: Vec<BatchOp<'_>> = refs
+                .iter()
+                .zip(values)
+                .filter_map(|(key, bytes)| Some((Cow::Borrowed(*key), Some(Cow::Owned(bytes?)))))
+                .collect();
+
+            to.repo
+                .write_batch(&ops)
+                .map_err(TransferError::Destination)?;
+
+            for (key, _) in &ops {
+                self.repo.remove(key).map_err(TransferError::Source)?;
+            }
+
+            moved += ops.len();
+        }
+
+        Ok(moved)
+    }
+}
+
+impl<Serde, Repo> Fallible for KvStore<Serde, Repo>
+where
+    Serde: Fallible,
+    Repo: Fallible,
+{
+    type Error = Error<Serde::Error, Repo::Error>;
+}
+
+impl<Serde, Repo> Storage for KvStore<Serde, Repo>
+where
+    Serde: Deserializer,
+    Repo: HasKey,
+{
+    type Serde = Serde;
+    type Repo = Repo;
+
+    fn may_load<T: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<T>, Self::Error> {
+        self.repo
+            .read_with(key, |bytes| bytes.map(Serde::deserialize).transpose())
+            .map_err(Error::Repo)?
+            .map_err(Error::Serde)
+    }
+
+    fn may_load_if<T, P>(&self, key: &[u8], pred: P) -> Result<Option<T>, Self::Error>
+    where
+        T: DeserializeOwned,
+        P: FnOnce(usize) -> bool,
+    {
+        self.repo
+            .read_with(key, |bytes| {
+                bytes
+                    .filter(|bytes| pred(bytes.len()))
+                    .map(Serde::deserialize)
+                    .transpose()
+            })
+            .map_err(Error::Repo)?
+            .map_err(Error::Serde)
+    }
+
+    fn may_load_many<T: DeserializeOwned>(
+        &self,
+        keys: &[&[u8]],
+    ) -> Result<Vec<Option<T>>, Self::Error> {
+        self.repo
+            .read_many(keys)
+            .map_err(Error::Repo)?
+            .into_iter()
+            .map(|bytes| {
+                bytes
+                    .as_deref()
+                    .map(Serde::deserialize)
+                    .transpose()
+                    .map_err(Error::Serde)
+            })
+            .collect()
+    }
+
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        self.repo.has_key(key).map_err(Error::Repo)
+    }
+}
+
+impl<Serde, Repo> IterStorage for KvStore<Serde, Repo>
+where
+    Serde: Deserializer,
+    Repo: HasKey + Iterate,
+{
+    fn range<'a, T>(
+        &'a self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<Entries<'a, T, Self::Error>, Self::Error>
+    where
+        T: DeserializeOwned + 'a,
+    {
+        let entries = self.repo.range(min, max, order).map_err(Error::Repo)?;
+
+        Ok(Box::new(entries.map(|(key, bytes)| {
+            Serde::deserialize(&bytes)
+                .map(|value| (key, value))
+                .map_err(Error::Serde)
+        })))
+    }
+
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error> {
+        self.repo.range_keys(min, max, order).map_err(Error::Repo)
+    }
+}
+
+impl<Serde, Repo> KvStore<Serde, Repo>
+where
+    Serde: Serializer + Deserializer,
+    Repo: Write + Remove + HasKey,
+{
+    /// Whether saves can serialize straight into the repo.
+    fn streams(&self) -> bool {
+        // hooks are handed the whole serialized value
+        #[cfg(feature = "debug_hooks")]
+        if !self.hooks.is_empty() {
+            return false;
+        }
+
+        self.serde.supports_streaming() && self.repo.supports_streaming()
+    }
+
+    fn serialize_streaming<T: Serialize>(
+        &mut self,
+        key: &[u8],
+        item: &T,
+    ) -> Result<(), <Self as Fallible>::Error> {
+        let serde = &mut self.serde;
+
+        let filled = self
+            .repo
+            .write_stream(key, &mut |writer| serde.serialize_into(item, writer))
+            .map_err(Error::Repo)?;
+
+        match filled {
+            Ok(()) => Ok(()),
+            Err(StreamError::Serde(error)) => Err(Error::Serde(error)),
+            // the repo owns its writer's failures, so this one is the serializer's own doing and
+            // nothing was written, buffering still saves the item
+            Err(StreamError::Io(_)) => self.serialize_and_write(key, item, Repo::write),
+        }
+    }
+
+    fn serialize_owned_and_write<T: Serialize>(
+        &mut self,
+        key: &[u8],
+        item: &T,
+    ) -> Result<(), <Self as Fallible>::Error> {
+        let bytes = self.serde.serialize_owned(item).map_err(Error::Serde)?;
+
+        #[cfg(feature = "debug_hooks")]
+        self.hooks.run(key, &bytes).map_err(Error::Injected)?;
+
+        self.repo.write_owned(key, bytes).map_err(Error::Repo)
+    }
+
+    fn serialize_and_write<T, W>(
+        &mut self,
+        key: &[u8],
+        item: &T,
+        write: W,
+    ) -> Result<(), <Self as Fallible>::Error>
+    where
+        T: Serialize,
+        W: FnOnce(&mut Repo, &[u8], &[u8]) -> Result<(), Repo::Error>,
+    {
+        let buffer = self.serde.serialize(item).map_err(Error::Serde)?;
+
+        #[cfg(feature = "debug_hooks")]
+        self.hooks.run(key, buffer).map_err(Error::Injected)?;
+
+        write(&mut self.repo, key, buffer).map_err(Error::Repo)
+    }
+}
+
+impl<Serde, Repo> MutStorage for KvStore<Serde, Repo>
+where
+    Serde: Serializer + Deserializer,
+    Repo: Write + Remove + HasKey,
+{
+    /// Serializes straight into the repo when both support streaming, see
+    /// [`Serializer::serialize_into`] and [`Write::write_stream`], otherwise hands it the
+    /// serialized bytes if it keeps them, see [`Write::write_owned`].
+    fn save<T>(&mut self, key: &[u8], item: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize,
+    {
+        if self.streams() {
+            return self.serialize_streaming(key, item);
+        }
+
+        if self.repo.takes_ownership() {
+            return self.serialize_owned_and_write(key, item);
+        }
+
+        self.serialize_and_write(key, item, Repo::write)
+    }
+
+    fn save_with<T>(
+        &mut self,
+        key: &[u8],
+        item: &T,
+        durability: Durability,
+    ) -> Result<(), Self::Error>
+    where
+        T: Serialize,
+    {
+        self.serialize_and_write(key, item, |repo, key, bytes| {
+            repo.write_durable(key, bytes, durability)
+        })
+    }
+
+    fn save_batch(&mut self, items: &[(&[u8], &dyn ErasedSerialize)]) -> Result<(), Self::Error> {
+        let mut ops = Vec::with_capacity(items.len());
+
+        // serialize everything up front, so a failure writes nothing
+        for (key, item) in items {
+            let buffer = self.serde.serialize(item).map_err(Error::Serde)?;
+
+            #[cfg(feature = "debug_hooks")]
+            self.hooks.run(key, buffer).map_err(Error::Injected)?;
+
+            ops.push((Cow::Borrowed(*key), Some(Cow::Owned(buffer.to_vec()))));
+        }
+
+        self.repo.write_batch(&ops).map_err(Error::Repo)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.repo.remove(key).map_err(Error::Repo)
+    }
+
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        self.repo.remove_returning(key).map_err(Error::Repo)
+    }
+}
+
+impl<Serde, Repo> KvStore<Serde, Repo> {
+    /// Stage saves and removes in memory, applying them to the repo in one batch on
+    /// [`Transaction::commit`], dropping the transaction discards them.
+    ///
+    /// The transaction borrows the store mutably, so the store can't be used, or another
+    /// transaction started on it, until the transaction is committed or dropped.
+    ///
+    /// ```
+    /// use kv_storage::prelude::*;
+    /// use kv_storage_bincode::Bincode;
+    /// use kv_storage_memory::MemoryRepo;
+    ///
+    /// const COUNT: Item<u64> = item!("count");
+    ///
+    /// let mut store: KvStore<Bincode, MemoryRepo> = KvStore::default();
+    ///
+    /// let mut tx = store.transaction();
+    /// COUNT.save(&mut tx, 1).unwrap();
+    /// assert_eq!(COUNT.may_load(&tx).unwrap(), Some(1));
+    /// drop(tx);
+    ///
+    /// assert_eq!(COUNT.may_load(&store).unwrap(), None);
+    ///
+    /// let mut tx = store.transaction();
+    /// COUNT.save(&mut tx, 2).unwrap();
+    /// tx.commit().unwrap();
+    ///
+    /// assert_eq!(COUNT.may_load(&store).unwrap(), Some(2));
+    /// ```
+    pub fn transaction(&mut self) -> Transaction<'_, Serde, Repo> {
+        Transaction {
+            store: self,
+            staged: BTreeMap::new(),
+        }
+    }
+}
+
+/// Saves and removes staged against a [`KvStore`], see [`KvStore::transaction`].
+///
+/// Reads see the staged changes first. Durability passed to [`MutStorage::save_with`] isn't
+/// kept, the staged changes are committed with the repo's [`Remove::write_batch`], which
+/// takes no level.
+pub struct Transaction<'a, Serde, Repo> {
+    store: &'a mut KvStore<Serde, Repo>,
+    /// `None` marks a removal.
+    staged: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl<Serde, Repo> Transaction<'_, Serde, Repo> {
+    /// How many keys have staged changes.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.staged.len()
+    }
+}
+
+impl<Serde, Repo> Transaction<'_, Serde, Repo>
+where
+    Serde: Fallible,
+    Repo: Write + Remove,
+{
+    /// Apply the staged changes to the repo in one batch.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the repo fails to apply the batch, how much of it
+    /// was applied then depends on the repo's [`Remove::write_batch`] implementation.
+    pub fn commit(self) -> Result<(), Error<Serde::Error, Repo::Error>> {
+        let ops: Vec<BatchOp<'_>> = self
+            .staged
+            .iter()
+            .map(|(key, bytes)| (Cow::from(key.as_slice()), bytes.as_deref().map(Cow::from)))
+            .collect();
+
+        self.store.repo.write_batch(&ops).map_err(Error::Repo)
+    }
+}
+
+impl<Serde, Repo> Transaction<'_, Serde, Repo>
+where
+    Serde: Fallible,
+    Repo: Read,
+{
+    /// [`Read::read_with`] through the staged changes.
+    fn read_with<R, F>(&self, key: &[u8], f: F) -> Result<R, <Self as Fallible>::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> R,
+    {
+        match self.staged.get(key) {
+            Some(change) => Ok(f(change.as_deref())),
+            None => self.store.repo.read_with(key, f).map_err(Error::Repo),
+        }
+    }
+}
+
+impl<Serde, Repo> Fallible for Transaction<'_, Serde, Repo>
+where
+    Serde: Fallible,
+    Repo: Fallible,
+{
+    type Error = Error<Serde::Error, Repo::Error>;
+}
+
+impl<Serde, Repo> Storage for Transaction<'_, Serde, Repo>
+where
+    Serde: Deserializer,
+    Repo: Read + HasKey,
+{
+    type Serde = Serde;
+    type Repo = Repo;
+
+    fn may_load<T: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<T>, Self::Error> {
+        self.read_with(key, |bytes| bytes.map(Serde::deserialize).transpose())?
+            .map_err(Error::Serde)
+    }
+
+    fn may_load_if<T, P>(&self, key: &[u8], pred: P) -> Result<Option<T>, Self::Error>
+    where
+        T: DeserializeOwned,
+        P: FnOnce(usize) -> bool,
+    {
+        self.read_with(key, |bytes| {
+            bytes
+                .filter(|bytes| pred(bytes.len()))
+                .map(Serde::deserialize)
+                .transpose()
+        })?
+        .map_err(Error::Serde)
+    }
+
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        match self.staged.get(key) {
+            Some(change) => Ok(change.is_some()),
+            None => self.store.repo.has_key(key).map_err(Error::Repo),
+        }
+    }
+}
+
+impl<Serde, Repo> MutStorage for Transaction<'_, Serde, Repo>
+where
+    Serde: Serializer + Deserializer,
+    Repo: Write + Remove + HasKey,
+{
+    fn save<T>(&mut self, key: &[u8], item: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize,
+    {
+        let buffer = self.store.serde.serialize(item).map_err(Error::Serde)?;
+
+        #[cfg(feature = "debug_hooks")]
+        self.store.hooks.run(key, buffer).map_err(Error::Injected)?;
+
+        self.staged.insert(key.to_vec(), Some(buffer.to_vec()));
+
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.staged.insert(key.to_vec(), None);
+        Ok(())
+    }
+
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        if !Storage::has_key(self, key)? {
+            return Ok(Removed::DidNotExist);
+        }
+
+        self.remove(key)?;
+
+        Ok(Removed::Existed)
+    }
+}
+
+/// Returned by `load` when no value is stored, or when the store fails.
+///
+/// ```
+/// use kv_storage::LoadError;
+/// use kv_storage_memory::prelude::*;
+///
+/// const OWNER: Item<String> = item!("owner");
+///
+/// let store = MemStore::new_in_memory();
+///
+/// let Err(LoadError::NotFound { key }) = OWNER.load(&store) else {
+///     panic!("nothing was saved");
+/// };
+/// assert_eq!(key, OWNER.key());
+/// ```
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError<E> {
+    #[error("no value stored at {}", KeyDisplay::new(key))]
+    NotFound { key: Vec<u8> },
+    #[error(transparent)]
+    Store(#[from] E),
+}
+
+/// A single value stored under a fixed key.
+///
+/// ```
+/// use kv_storage_memory::prelude::*;
+///
+/// const CONFIG: Item<(String, u32)> = item!("config");
+///
+/// let mut store = MemStore::new_in_memory();
+///
+/// assert!(CONFIG.is_empty(&store).unwrap());
+///
+/// CONFIG.save(&mut store, ("admin".to_owned(), 3)).unwrap();
+/// assert_eq!(CONFIG.may_load(&store).unwrap(), Some(("admin".to_owned(), 3)));
+///
+/// CONFIG.clear(&mut store).unwrap();
+/// assert_eq!(CONFIG.may_load(&store).unwrap(), None);
+/// ```
+#[derive(Copy, Clone)]
+pub struct Item<T> {
+    key: &'static [u8],
+    _t: PhantomData<T>,
+}
+
+impl<T> Item<T> {
+    /// # Panics
+    ///
+    /// Panics (at compile time for a constant) if the key is reserved, see [`system`].
+    #[must_use]
+    pub const fn new(key: &'static [u8]) -> Self {
+        assert!(!system::is_reserved(key), "the key is reserved");
+
+        Self {
+            key,
+            _t: PhantomData,
+        }
+    }
+
+    /// Like [`Item::new`], returning an error instead of panicking.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the key is reserved, see [`system`].
+    pub const fn try_new(key: &'static [u8]) -> Result<Self, system::ReservedKey> {
+        match system::check(key) {
+            Ok(()) => Ok(Self::new(key)),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The raw storage key.
+    #[must_use]
+    pub const fn key(&self) -> &'static [u8] {
+        self.key
+    }
+
+    /// Save the item to storage.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const OWNER: Item<String> = item!("owner");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// OWNER.save(&mut store, "alice".to_owned()).unwrap();
+    ///
+    /// assert_eq!(OWNER.load(&store).unwrap(), "alice");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn save<Store, Item>(&self, store: &mut Store, item: Item) -> Result<(), Store::Error>
+    where
+        T: Serialize,
+        Store: MutStorage,
+        Item: Borrow<T>,
+    {
+        store.save(self.key, item.borrow())
+    }
+
+    /// Save the item to storage, along with what the save cost at each traceable layer of the
+    /// store's repo, see [`trace`].
+    pub fn save_traced<Store, Item>(
+        &self,
+        store: &mut Store,
+        item: Item,
+    ) -> (Result<(), Store::Error>, trace::OpTrace)
+    where
+        T: Serialize,
+        Store: MutStorage,
+        Item: Borrow<T>,
+    {
+        trace::traced(|| self.save(store, item))
+    }
+
+    /// Save the item to storage, persisted at least to the given level.
+    ///
+    /// ```
+    /// use kv_storage::Durability;
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const HEIGHT: Item<u64> = item!("height");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// HEIGHT.save_with(&mut store, 42, Durability::Sync).unwrap();
+    ///
+    /// assert_eq!(HEIGHT.load(&store).unwrap(), 42);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn save_with<Store, Item>(
+        &self,
+        store: &mut Store,
+        item: Item,
+        durability: Durability,
+    ) -> Result<(), Store::Error>
+    where
+        T: Serialize,
+        Store: MutStorage,
+        Item: Borrow<T>,
+    {
+        store.save_with(self.key, item.borrow(), durability)
+    }
+
+    /// Load the item from storage if it exists, otherwise `None`.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const OWNER: Item<String> = item!("owner");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// assert_eq!(OWNER.may_load(&store).unwrap(), None);
+    ///
+    /// OWNER.save(&mut store, "alice".to_owned()).unwrap();
+    /// assert_eq!(OWNER.may_load(&store).unwrap().as_deref(), Some("alice"));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load<Store>(&self, store: &Store) -> Result<Option<T>, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: Storage,
+    {
+        store.may_load::<T>(self.key)
+    }
+
+    /// Load the item from storage if it exists, otherwise `T::default()`.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const LIMIT: Item<u32> = item!("limit");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// assert_eq!(LIMIT.load_or_default(&store).unwrap(), 0);
+    ///
+    /// LIMIT.save(&mut store, 20).unwrap();
+    /// assert_eq!(LIMIT.load_or_default(&store).unwrap(), 20);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn load_or_default<Store>(&self, store: &Store) -> Result<T, Store::Error>
+    where
+        T: DeserializeOwned + Default,
+        Store: Storage,
+    {
+        self.load_or_else(store, T::default)
+    }
+
+    /// Load the item from storage if it exists, otherwise `fallback`.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const LIMIT: Item<u32> = item!("limit");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// assert_eq!(LIMIT.load_or(&store, 10).unwrap(), 10);
+    ///
+    /// LIMIT.save(&mut store, 20).unwrap();
+    /// assert_eq!(LIMIT.load_or(&store, 10).unwrap(), 20);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn load_or<Store>(&self, store: &Store, fallback: T) -> Result<T, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: Storage,
+    {
+        self.load_or_else(store, || fallback)
+    }
+
+    /// Load the item from storage if it exists, otherwise the result of `f`.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const NAME: Item<String> = item!("name");
+    ///
+    /// let store = MemStore::new_in_memory();
+    ///
+    /// // the fallback is only built when nothing is stored
+    /// let name = NAME.load_or_else(&store, || "anonymous".to_owned()).unwrap();
+    /// assert_eq!(name, "anonymous");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn load_or_else<Store, F>(&self, store: &Store, f: F) -> Result<T, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: Storage,
+        F: FnOnce() -> T,
+    {
+        Ok(store.may_load::<T>(self.key)?.unwrap_or_else(f))
+    }
+
+    /// Load the item from storage, failing if it doesn't exist.
+    ///
+    /// ```
+    /// use kv_storage::LoadError;
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const OWNER: Item<String> = item!("owner");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// assert!(matches!(OWNER.load(&store), Err(LoadError::NotFound { .. })));
+    ///
+    /// OWNER.save(&mut store, "alice".to_owned()).unwrap();
+    /// assert_eq!(OWNER.load(&store).unwrap(), "alice");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the item doesn't exist or the store encounters an
+    /// error.
+    pub fn load<Store>(&self, store: &Store) -> Result<T, LoadError<Store::Error>>
+    where
+        T: DeserializeOwned,
+        Store: Storage,
+    {
+        store
+            .may_load::<T>(self.key)?
+            .ok_or_else(|| LoadError::NotFound {
+                key: self.key.to_vec(),
+            })
+    }
+
+    /// Load the item from storage if it exists and the predicate accepts its serialized length.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const BLOB: Item<Vec<u8>> = item!("blob");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// BLOB.save(&mut store, vec![0; 1000]).unwrap();
+    ///
+    /// // too large, so it isn't deserialized
+    /// assert_eq!(BLOB.may_load_if(&store, |len| len <= 100).unwrap(), None);
+    /// assert!(BLOB.may_load_if(&store, |len| len <= 2000).unwrap().is_some());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load_if<Store, P>(&self, store: &Store, pred: P) -> Result<Option<T>, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: Storage,
+        P: FnOnce(usize) -> bool,
+    {
+        store.may_load_if::<T, P>(self.key, pred)
+    }
+
+    /// Load the item, initializing and saving it with `init` if it doesn't exist yet.
+    ///
+    /// `init` is called at most once, and not at all if the item exists. Nothing is written if it
+    /// fails. Concurrent initializers through separate handles on a shared repo race with
+    /// last-writer-wins semantics, `SharedKvStore::get_or_try_init` from `kv-storage-web-state`
+    /// runs only one of them.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const SEED: Item<u64> = item!("seed");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    ///
+    /// type Error = Box<dyn std::error::Error>;
+    ///
+    /// let seed = SEED.get_or_try_init(&mut store, || Ok::<_, Error>(42));
+    /// assert_eq!(seed.unwrap(), 42);
+    ///
+    /// // already initialized, so `init` isn't called
+    /// let seed = SEED.get_or_try_init(&mut store, || Err::<_, Error>("unreachable".into()));
+    /// assert_eq!(seed.unwrap(), 42);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store or `init` encounters an error.
+    pub fn get_or_try_init<Store, F, E>(&self, store: &mut Store, init: F) -> Result<T, E>
+    where
+        T: Serialize + DeserializeOwned,
+        Store: MutStorage,
+        F: FnOnce() -> Result<T, E>,
+        E: From<Store::Error>,
+    {
+        if let Some(existing) = store.may_load::<T>(self.key)? {
+            return Ok(existing);
+        }
+
+        let value = init()?;
+
+        store.save(self.key, &value)?;
+
+        Ok(value)
+    }
+
+    /// Save the item only if it doesn't exist yet, returning whether it was saved.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const OWNER: Item<String> = item!("owner");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    ///
+    /// assert!(OWNER.init(&mut store, "alice".to_owned()).unwrap());
+    /// assert!(!OWNER.init(&mut store, "mallory".to_owned()).unwrap());
+    /// assert_eq!(OWNER.load_or_default(&store).unwrap(), "alice");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn init<Store, Item>(&self, store: &mut Store, item: Item) -> Result<bool, Store::Error>
+    where
+        T: Serialize,
+        Store: MutStorage,
+        Item: Borrow<T>,
+    {
+        if store.has_key(self.key)? {
+            return Ok(false);
+        }
+
+        store.save(self.key, item.borrow())?;
+
+        Ok(true)
+    }
+
+    /// Load the item, pass it to `f` and save what it returns, which is also returned. `f` gets
+    /// `None` if the item doesn't exist, and nothing is saved if it fails.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const COUNTER: Item<u64> = item!("counter");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    ///
+    /// type Error = Box<dyn std::error::Error>;
+    ///
+    /// let next = COUNTER.update(&mut store, |n| Ok::<_, Error>(n.unwrap_or_default() + 1));
+    /// assert_eq!(next.unwrap(), 1);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store or `f` encounters an error.
+    pub fn update<Store, F, E>(&self, store: &mut Store, f: F) -> Result<T, E>
+    where
+        T: Serialize + DeserializeOwned,
+        Store: MutStorage,
+        F: FnOnce(Option<T>) -> Result<T, E>,
+        E: From<Store::Error>,
+    {
+        let existing = store.may_load::<T>(self.key)?;
+
+        let value = f(existing)?;
+
+        store.save(self.key, &value)?;
+
+        Ok(value)
+    }
+
+    /// Check if the item is empty
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const OWNER: Item<String> = item!("owner");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// assert!(OWNER.is_empty(&store).unwrap());
+    ///
+    /// OWNER.save(&mut store, "alice".to_owned()).unwrap();
+    /// assert!(!OWNER.is_empty(&store).unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn is_empty<Store>(&self, store: &Store) -> Result<bool, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: Storage,
+    {
+        store.has_key(self.key).map(|has_key| !has_key)
+    }
+
+    /// Clear the item from storage.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const OWNER: Item<String> = item!("owner");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// OWNER.save(&mut store, "alice".to_owned()).unwrap();
+    ///
+    /// OWNER.clear(&mut store).unwrap();
+    /// assert_eq!(OWNER.may_load(&store).unwrap(), None);
+    ///
+    /// // clearing a missing item is fine
+    /// OWNER.clear(&mut store).unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn clear<Store: MutStorage>(&self, store: &mut Store) -> Result<(), Store::Error> {
+        store.remove(self.key)
+    }
+
+    /// Clear the item from storage, reporting whether it was present.
+    ///
+    /// ```
+    /// use kv_storage::Removed;
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const OWNER: Item<String> = item!("owner");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// OWNER.save(&mut store, "alice".to_owned()).unwrap();
+    ///
+    /// assert_eq!(OWNER.clear_returning(&mut store).unwrap(), Removed::Existed);
+    /// assert_eq!(OWNER.clear_returning(&mut store).unwrap(), Removed::DidNotExist);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn clear_returning<Store: MutStorage>(
+        &self,
+        store: &mut Store,
+    ) -> Result<Removed, Store::Error> {
+        store.remove_returning(self.key)
+    }
+}
+
+/// Unsigned integers usable as counters, see [`Item::increment`].
+pub trait Counter: Copy + Default + Serialize + DeserializeOwned {
+    const ONE: Self;
+
+    fn checked_add(self, by: Self) -> Option<Self>;
+
+    fn checked_sub(self, by: Self) -> Option<Self>;
+}
+
+macro_rules! impl_counter {
+    ($($t:ty),+) => {
+        $(
+            impl Counter for $t {
+                const ONE: Self = 1;
+
+                fn checked_add(self, by: Self) -> Option<Self> {
+                    <$t>::checked_add(self, by)
+                }
+
+                fn checked_sub(self, by: Self) -> Option<Self> {
+                    <$t>::checked_sub(self, by)
+                }
+            }
+        )+
+    };
+}
+
+impl_counter!(u8, u16, u32, u64, u128);
+
+/// Returned by the counter helpers on [`Item`] instead of wrapping, nothing is saved then.
+///
+/// ```
+/// use kv_storage::CounterError;
+/// use kv_storage_memory::prelude::*;
+///
+/// const SUPPLY: Item<u8> = item!("supply");
+///
+/// let mut store = MemStore::new_in_memory();
+///
+/// assert!(matches!(SUPPLY.decrement(&mut store, 1), Err(CounterError::Underflow)));
+/// assert!(matches!(SUPPLY.increment(&mut store, 255), Ok(255)));
+/// assert!(matches!(SUPPLY.increment(&mut store, 1), Err(CounterError::Overflow)));
+/// ```
+#[derive(Debug, thiserror::Error)]
+pub enum CounterError<E> {
+    #[error("counter overflow")]
+    Overflow,
+    #[error("counter underflow")]
+    Underflow,
+    #[error(transparent)]
+    Store(#[from] E),
+}
+
+impl<T: Counter> Item<T> {
+    /// Add `by` to the counter, a missing counter counts as zero, returning the new value.
+    ///
+    /// ```
+    /// use kv_storage::CounterError;
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const SUPPLY: Item<u8> = item!("supply");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    ///
+    /// assert_eq!(SUPPLY.increment(&mut store, 200).unwrap(), 200);
+    /// assert!(matches!(SUPPLY.increment(&mut store, 100), Err(CounterError::Overflow)));
+    /// assert_eq!(SUPPLY.decrement(&mut store, 50).unwrap(), 150);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the addition overflows or the store encounters an
+    /// error.
+    pub fn increment<Store: MutStorage>(
+        &self,
+        store: &mut Store,
+        by: T,
+    ) -> Result<T, CounterError<Store::Error>> {
+        self.update(store, |n| {
+            n.unwrap_or_default()
+                .checked_add(by)
+                .ok_or(CounterError::Overflow)
+        })
+    }
+
+    /// Subtract `by` from the counter, a missing counter counts as zero, returning the new value.
+    ///
+    /// ```
+    /// use kv_storage::CounterError;
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const STOCK: Item<u32> = item!("stock");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// STOCK.increment(&mut store, 3).unwrap();
+    ///
+    /// assert_eq!(STOCK.decrement(&mut store, 2).unwrap(), 1);
+    /// assert!(matches!(STOCK.decrement(&mut store, 2), Err(CounterError::Underflow)));
+    /// assert_eq!(STOCK.load(&store).unwrap(), 1);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the subtraction underflows or the store encounters
+    /// an error.
+    pub fn decrement<Store: MutStorage>(
+        &self,
+        store: &mut Store,
+        by: T,
+    ) -> Result<T, CounterError<Store::Error>> {
+        self.update(store, |n| {
+            n.unwrap_or_default()
+                .checked_sub(by)
+                .ok_or(CounterError::Underflow)
+        })
+    }
+
+    /// Increment the counter by one, returning the value before, for allocating ids starting at
+    /// zero.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const NEXT_ORDER: Item<u64> = item!("next_order");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    ///
+    /// assert_eq!(NEXT_ORDER.next_id(&mut store).unwrap(), 0);
+    /// assert_eq!(NEXT_ORDER.next_id(&mut store).unwrap(), 1);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the counter is at its maximum or the store
+    /// encounters an error.
+    pub fn next_id<Store: MutStorage>(
+        &self,
+        store: &mut Store,
+    ) -> Result<T, CounterError<Store::Error>> {
+        let mut id = T::default();
+
+        self.update(store, |n| {
+            id = n.unwrap_or_default();
+            id.checked_add(T::ONE).ok_or(CounterError::Overflow)
+        })?;
+
+        Ok(id)
+    }
+}
+
+pub trait WriteKeyPart {
+    fn write_key_part(&mut self, part: &[u8]);
+}
+
+pub trait WriteCompositeKey {
+    fn total_len(&self) -> usize;
+
+    fn write_into<W: WriteKeyPart>(&self, writer: &mut W);
+}
+
+/// Values stored under a prefix and a key, composite keys are tuples of key parts.
+///
+/// `N` is how many key bytes are composed on the stack before falling back to the heap, longer
+/// keys work all the same. [`DefaultMap`] picks it when there's no reason to.
+///
+/// ```
+/// use kv_storage_memory::prelude::*;
+///
+/// const ALLOWANCES: Map<64, (&str, &str), u128> = map!("allowances");
+///
+/// let mut store = MemStore::new_in_memory();
+///
+/// ALLOWANCES.save(&mut store, ("alice", "bob"), 10).unwrap();
+///
+/// assert_eq!(ALLOWANCES.may_load(&store, ("alice", "bob")).unwrap(), Some(10));
+/// assert!(!ALLOWANCES.has_key(&store, ("bob", "alice")).unwrap());
+///
+/// ALLOWANCES.remove(&mut store, ("alice", "bob")).unwrap();
+/// assert_eq!(ALLOWANCES.may_load(&store, ("alice", "bob")).unwrap(), None);
+/// ```
+#[derive(Copy, Clone)]
+pub struct Map<const N: usize, K, V> {
+    prefix: &'static [u8],
+    _k: PhantomData<K>,
+    _v: PhantomData<V>,
+}
+
+/// Stack space for the composed keys of a [`DefaultMap`].
+pub const DEFAULT_KEY_BUFFER: usize = 128;
+
+/// A [`Map`] without a stack size to pick, keys longer than [`DEFAULT_KEY_BUFFER`] bytes
+/// (prefix included) are composed on the heap.
+///
+/// ```
+/// use kv_storage_memory::prelude::*;
+///
+/// const BALANCES: DefaultMap<&str, u128> = map!("balances");
+///
+/// let mut store = MemStore::new_in_memory();
+/// BALANCES.save(&mut store, "alice", 100).unwrap();
+///
+/// assert_eq!(BALANCES.may_load(&store, "alice").unwrap(), Some(100));
+/// ```
+pub type DefaultMap<K, V> = Map<DEFAULT_KEY_BUFFER, K, V>;
+
+impl<const N: usize, K, V> Map<N, K, V>
+where
+    K: WriteCompositeKey,
+{
+    /// # Panics
+    ///
+    /// Panics (at compile time for a constant) if the prefix is reserved, see [`system`].
+    #[must_use]
+    pub const fn new(prefix: &'static [u8]) -> Self {
+        assert!(!system::is_reserved(prefix), "the prefix is reserved");
+
+        Self {
+            prefix,
+            _k: PhantomData,
+            _v: PhantomData,
+        }
+    }
+
+    /// Like [`Map::new`], returning an error instead of panicking.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the prefix is reserved, see [`system`].
+    pub const fn try_new(prefix: &'static [u8]) -> Result<Self, system::ReservedKey> {
+        match system::check(prefix) {
+            Ok(()) => Ok(Self::new(prefix)),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Track this map's state in a header entry, see [`HeaderedMap`].
+    #[must_use]
+    pub const fn with_header(self) -> HeaderedMap<N, K, V> {
+        HeaderedMap { map: self }
+    }
+
+    /// The raw prefix every entry's storage key starts with.
+    #[must_use]
+    pub const fn prefix(&self) -> &'static [u8] {
+        self.prefix
+    }
+
+    /// Scope to the entries whose keys start with the given leading parts, e.g. `("alice",)` for a
+    /// `(&str, u64)` map, to work with the remaining parts only.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const ORDERS: Map<64, (&str, u64), u128> = map!("orders");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// ORDERS.save(&mut store, ("alice", 1), 10).unwrap();
+    /// ORDERS.save(&mut store, ("alice", 2), 20).unwrap();
+    /// ORDERS.save(&mut store, ("bob", 1), 30).unwrap();
+    ///
+    /// let alice = ORDERS.sub_prefix(("alice",));
+    ///
+    /// assert_eq!(alice.may_load(&store, 2).unwrap(), Some(20));
+    /// assert_eq!(alice.count(&store).unwrap(), 2);
+    /// ```
+    #[allow(clippy::needless_pass_by_value)] // keys are usually small and passed by value
+    pub fn sub_prefix<P: KeyPrefix<K>>(&self, partial_key: P) -> Prefix<N, P::Suffix, V> {
+        let mut prefix = CompositeKey::new(self.prefix.len() + partial_key.prefix_len());
+
+        prefix.write_key_part(self.prefix);
+        partial_key.write_prefix(&mut prefix);
+
+        Prefix {
+            bytes: prefix,
+            _s: PhantomData,
+            _v: PhantomData,
+        }
+    }
+
+    /// The full storage key for the given key, i.e. the prefix followed by the encoded key.
+    ///
+    /// ```
+    /// use kv_storage::Map;
+    ///
+    /// const SCORES: Map<16, u32, u64> = Map::new(b"scores");
+    ///
+    /// assert_eq!(SCORES.key(1).as_ref(), b"scores\0\0\0\x01");
+    /// ```
+    #[allow(clippy::needless_pass_by_value)] // keys are usually small and passed by value
+    pub fn key<Key: EncodeLike<K>>(&self, key: Key) -> CompositeKey<N> {
+        compose_key::<N>(self.prefix, &key)
+    }
+
+    /// Save the item for the given key.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const BALANCES: Map<64, &str, u128> = map!("balances");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// BALANCES.save(&mut store, "alice", 100).unwrap();
+    ///
+    /// assert_eq!(BALANCES.load(&store, "alice").unwrap(), 100);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn save<Store, Key, Item>(
+        &self,
+        store: &mut Store,
+        key: Key,
+        item: Item,
+    ) -> Result<(), Store::Error>
+    where
+        V: Serialize,
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+        Item: Borrow<V>,
+    {
+        let composite = self.key(key);
+        store.save(composite.as_ref(), item.borrow())
+    }
+
+    /// Save the item for the given key, persisted at least to the given level.
+    ///
+    /// ```
+    /// use kv_storage::Durability;
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const BALANCES: Map<64, &str, u128> = map!("balances");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// BALANCES.save_with(&mut store, "alice", 100, Durability::Flush).unwrap();
+    ///
+    /// assert_eq!(BALANCES.load(&store, "alice").unwrap(), 100);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn save_with<Store, Key, Item>(
+        &self,
+        store: &mut Store,
+        key: Key,
+        item: Item,
+        durability: Durability,
+    ) -> Result<(), Store::Error>
+    where
+        V: Serialize,
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+        Item: Borrow<V>,
+    {
+        let composite = self.key(key);
+        store.save_with(composite.as_ref(), item.borrow(), durability)
+    }
+
+    /// Load the item for the given key if it exists, otherwise `None`.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const BALANCES: Map<64, &str, u128> = map!("balances");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// BALANCES.save(&mut store, "alice", 100).unwrap();
+    ///
+    /// assert_eq!(BALANCES.may_load(&store, "alice").unwrap(), Some(100));
+    /// assert_eq!(BALANCES.may_load(&store, "bob").unwrap(), None);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load<Store, Key>(&self, store: &Store, key: Key) -> Result<Option<V>, Store::Error>
+    where
+        V: DeserializeOwned,
+        Store: Storage,
+        Key: EncodeLike<K>,
+    {
+        let composite = self.key(key);
+        store.may_load::<V>(composite.as_ref())
+    }
+
+    /// Load the items for the given keys, returned in the same order next to their key, with
+    /// `None` for missing keys.
+    ///
+    /// Repos implementing [`Read::read_many`] natively fetch all of them in one call.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const BALANCES: Map<64, &str, u128> = map!("balances");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// BALANCES.save(&mut store, "alice", 100).unwrap();
+    ///
+    /// let balances = BALANCES.load_many(&store, ["bob", "alice"]).unwrap();
+    /// assert_eq!(balances, [("bob", None), ("alice", Some(100))]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn load_many<Store, Key, I>(
+        &self,
+        store: &Store,
+        keys: I,
+    ) -> Result<Vec<(Key, Option<V>)>, Store::Error>
+    where
+        V: DeserializeOwned,
+        Store: Storage,
+        Key: EncodeLike<K> + Clone,
+        I: IntoIterator<Item = Key>,
+    {
+        let keys: Vec<Key> = keys.into_iter().collect();
+        let composites: Vec<CompositeKey<N>> =
+            keys.iter().cloned().map(|key| self.key(key)).collect();
+        let raw: Vec<&[u8]> = composites.iter().map(AsRef::as_ref).collect();
+
+        let values = store.may_load_many::<V>(&raw)?;
+
+        Ok(keys.into_iter().zip(values).collect())
+    }
+
+    /// Load the item for the given key, failing if it doesn't exist.
+    ///
+    /// ```
+    /// use kv_storage::LoadError;
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const BALANCES: Map<64, &str, u128> = map!("balances");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// BALANCES.save(&mut store, "alice", 100).unwrap();
+    ///
+    /// assert_eq!(BALANCES.load(&store, "alice").unwrap(), 100);
+    /// assert!(matches!(BALANCES.load(&store, "bob"), Err(LoadError::NotFound { .. })));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the key doesn't exist or the store encounters an
+    /// error.
+    pub fn load<Store, Key>(&self, store: &Store, key: Key) -> Result<V, LoadError<Store::Error>>
+    where
+        V: DeserializeOwned,
+        Store: Storage,
+        Key: EncodeLike<K>,
+    {
+        let composite = self.key(key);
+
+        store
+            .may_load::<V>(composite.as_ref())?
+            .ok_or_else(|| LoadError::NotFound {
+                key: composite.as_ref().to_vec(),
+            })
+    }
+
+    /// Load the item for the given key if it exists and the predicate accepts its serialized length.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const NOTES: Map<64, &str, String> = map!("notes");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// NOTES.save(&mut store, "alice", "x".repeat(1000)).unwrap();
+    ///
+    /// // too large, so it isn't deserialized
+    /// assert_eq!(NOTES.may_load_if(&store, "alice", |len| len <= 100).unwrap(), None);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load_if<Store, Key, P>(
+        &self,
+        store: &Store,
+        key: Key,
+        pred: P,
+    ) -> Result<Option<V>, Store::Error>
+    where
+        V: DeserializeOwned,
+        Store: Storage,
+        Key: EncodeLike<K>,
+        P: FnOnce(usize) -> bool,
+    {
+        let composite = self.key(key);
+        store.may_load_if::<V, P>(composite.as_ref(), pred)
+    }
+
+    /// Check if a key exists.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const BALANCES: Map<64, &str, u128> = map!("balances");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// BALANCES.save(&mut store, "alice", 0).unwrap();
+    ///
+    /// assert!(BALANCES.has_key(&store, "alice").unwrap());
+    /// assert!(!BALANCES.has_key(&store, "bob").unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Storage encounters an error.
+    pub fn has_key<Store, Key>(&self, store: &Store, key: Key) -> Result<bool, Store::Error>
+    where
+        Store: Storage,
+        Key: EncodeLike<K>,
+    {
+        let composite = self.key(key);
+        store.has_key(composite.as_ref())
+    }
+
+    /// Load the item for the given key, pass it to `f` and save what it returns, which is also
+    /// returned. `f` gets `None` if the key doesn't exist, and nothing is saved if it fails.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const BALANCES: Map<64, &str, u128> = map!("balances");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    ///
+    /// type Error = Box<dyn std::error::Error>;
+    ///
+    /// let deposit = |store: &mut MemStore, amount| {
+    ///     BALANCES.update(store, "alice", |balance| {
+    ///         Ok::<_, Error>(balance.unwrap_or_default() + amount)
+    ///     })
+    /// };
+    ///
+    /// assert_eq!(deposit(&mut store, 10).unwrap(), 10);
+    /// assert_eq!(deposit(&mut store, 5).unwrap(), 15);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store or `f` encounters an error.
+    pub fn update<Store, Key, F, E>(&self, store: &mut Store, key: Key, f: F) -> Result<V, E>
+    where
+        V: Serialize + DeserializeOwned,
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+        F: FnOnce(Option<V>) -> Result<V, E>,
+        E: From<Store::Error>,
+    {
+        let composite = self.key(key);
+
+        let existing = store.may_load::<V>(composite.as_ref())?;
+
+        let value = f(existing)?;
+
+        store.save(composite.as_ref(), &value)?;
+
+        Ok(value)
+    }
+
+    /// Like [`Map::update`], except that `f` returning `None` removes the key.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const STOCK: Map<16, &str, u32> = map!("stock");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// STOCK.save(&mut store, "apples", 1).unwrap();
+    ///
+    /// type Error = Box<dyn std::error::Error>;
+    ///
+    /// // take one, dropping the entry once none are left
+    /// let left = STOCK.update_or_remove(&mut store, "apples", |n| {
+    ///     Ok::<_, Error>(n.map(|n| n - 1).filter(|n| *n > 0))
+    /// });
+    ///
+    /// assert_eq!(left.unwrap(), None);
+    /// assert!(!STOCK.has_key(&store, "apples").unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store or `f` encounters an error.
+    pub fn update_or_remove<Store, Key, F, E>(
+        &self,
+        store: &mut Store,
+        key: Key,
+        f: F,
+    ) -> Result<Option<V>, E>
+    where
+        V: Serialize + DeserializeOwned,
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+        F: FnOnce(Option<V>) -> Result<Option<V>, E>,
+        E: From<Store::Error>,
+    {
+        let composite = self.key(key);
+
+        let existing = store.may_load::<V>(composite.as_ref())?;
+
+        let updated = f(existing)?;
+
+        if let Some(value) = &updated {
+            store.save(composite.as_ref(), value)?;
+        } else {
+            store.remove(composite.as_ref())?;
+        }
+
+        Ok(updated)
+    }
+
+    /// Load the entry for the given key for in-place manipulation, see [`Entry`].
+    ///
+    /// ```
+    /// use kv_storage::EntryState;
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const BALANCES: Map<64, &str, u128> = map!("balances");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    ///
+    /// let entry = BALANCES.entry(&mut store, "alice").unwrap();
+    /// assert_eq!(entry.state(), EntryState::Vacant);
+    ///
+    /// assert_eq!(entry.or_insert(100).save().unwrap(), Some(100));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn entry<'a, Store, Key>(
+        &self,
+        store: &'a mut Store,
+        key: Key,
+    ) -> Result<Entry<'a, N, Store, V>, Store::Error>
+    where
+        V: DeserializeOwned,
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+    {
+        let key = self.key(key);
+        let value = store.may_load::<V>(key.as_ref())?;
+
+        let state = if value.is_some() {
+            EntryState::Loaded
+        } else {
+            EntryState::Vacant
+        };
+
+        Ok(Entry {
+            store,
+            key,
+            value,
+            state,
+        })
+    }
+
+    /// Iterate the entries with keys between the bounds, decoding keys and values.
+    ///
+    /// Entries are ordered by their encoded keys: integers numerically, strings and bytes
+    /// lexicographically, and tuples by their length-prefixed leading parts, i.e. shorter strings
+    /// first. Entries that fail to decode are yielded as errors, the iteration carries on past
+    /// them. Entries of other containers whose prefix starts with this one's are included too.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const SCORES: Map<16, u32, u64> = map!("scores");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    ///
+    /// for id in 1..=5 {
+    ///     SCORES.save(&mut store, id, u64::from(id) * 10).unwrap();
+    /// }
+    ///
+    /// // the page after id 4, newest first
+    /// let page: Vec<_> = SCORES
+    ///     .range(&store, Bound::Unbounded, Bound::Exclusive(4), Order::Descending)
+    ///     .unwrap()
+    ///     .take(2)
+    ///     .map(Result::unwrap)
+    ///     .collect();
+    ///
+    /// assert_eq!(page, [(3, 30), (2, 20)]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to start the scan.
+    pub fn range<'a, Store>(
+        &self,
+        store: &'a Store,
+        min: Bound<K>,
+        max: Bound<K>,
+        order: Order,
+    ) -> Result<impl Iterator<Item = RangeItem<K, V, Store::Error>> + 'a, Store::Error>
+    where
+        K: KeyDeserialize + 'a,
+        V: DeserializeOwned + 'a,
+        Store: IterStorage,
+    {
+        range_under::<N, K, V, Store>(self.prefix, store, min, max, order)
+    }
+
+    /// Iterate every key in the map in ascending order, without reading values.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const SCORES: Map<16, &str, u64> = map!("scores");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// SCORES.save(&mut store, "bob", 5).unwrap();
+    /// SCORES.save(&mut store, "alice", 3).unwrap();
+    ///
+    /// let names: Vec<_> = SCORES.keys(&store).unwrap().map(Result::unwrap).collect();
+    /// assert_eq!(names, ["alice", "bob"]);
+    ///
+    /// let total: u64 = SCORES.values(&store).unwrap().map(Result::unwrap).sum();
+    /// assert_eq!(total, 8);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to start the scan.
+    pub fn keys<'a, Store>(
+        &self,
+        store: &'a Store,
+    ) -> Result<impl Iterator<Item = Result<K::Owned, KeyDecodeError>> + 'a, Store::Error>
+    where
+        K: KeyDeserialize + 'a,
+        Store: IterStorage,
+    {
+        let prefix_len = self.prefix.len();
+
+        let keys = store
+            .scan_keys(self.prefix)?
+            .map(move |key| K::from_key_bytes(&key[prefix_len..]));
+
+        Ok(keys)
+    }
+
+    /// Iterate every value in the map in ascending key order, without decoding keys.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const BALANCES: Map<64, &str, u128> = map!("balances");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// BALANCES.save(&mut store, "bob", 20).unwrap();
+    /// BALANCES.save(&mut store, "alice", 10).unwrap();
+    ///
+    /// let values: Vec<u128> = BALANCES.values(&store).unwrap().map(Result::unwrap).collect();
+    /// assert_eq!(values, [10, 20]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to start the scan.
+    pub fn values<'a, Store>(
+        &self,
+        store: &'a Store,
+    ) -> Result<impl Iterator<Item = Result<V, Store::Error>> + 'a, Store::Error>
+    where
+        V: DeserializeOwned + 'a,
+        Store: IterStorage,
+    {
+        let values = store
+            .scan::<V>(self.prefix)?
+            .map(|entry| entry.map(|(_, value)| value));
+
+        Ok(values)
+    }
+
+    /// Remove any item stored at the given key.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const BALANCES: Map<64, &str, u128> = map!("balances");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// BALANCES.save(&mut store, "alice", 100).unwrap();
+    ///
+    /// BALANCES.remove(&mut store, "alice").unwrap();
+    /// assert!(!BALANCES.has_key(&store, "alice").unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn remove<Store, Key>(&self, store: &mut Store, key: Key) -> Result<(), Store::Error>
+    where
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+    {
+        let composite = self.key(key);
+        store.remove(composite.as_ref())
+    }
+
+    /// Remove every entry in the map, returning how many were removed.
+    ///
+    /// Maps declared with [`map!`] can't share a byte prefix, so clearing one never touches
+    /// another. With raw prefixes given to [`Map::new`], e.g. `b"balances"` and `b"balances_v2"`,
+    /// the shorter one's clear removes the other's entries too.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const BALANCES: Map<64, &str, u128> = map!("balances");
+    /// const BALANCES_V2: Map<64, &str, u128> = map!("balances_v2");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// BALANCES.save(&mut store, "alice", 1).unwrap();
+    /// BALANCES_V2.save(&mut store, "alice", 2).unwrap();
+    ///
+    /// assert_eq!(BALANCES.clear(&mut store).unwrap(), 1);
+    /// assert_eq!(BALANCES_V2.may_load(&store, "alice").unwrap(), Some(2));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error, entries removed
+    /// before it stay removed.
+    pub fn clear<Store>(&self, store: &mut Store) -> Result<usize, Store::Error>
+    where
+        Store: MutStorage + IterStorage,
+    {
+        clear_under(store, self.prefix)
+    }
+
+    /// Copy every entry into another store, deserializing each value and serializing it again,
+    /// returning how many were copied.
+    ///
+    /// The stores may use different formats, for stores sharing one [`KvStore::copy_all`]
+    /// copies the raw bytes instead.
+    ///
+    /// ```
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const BALANCES: Map<64, &str, u128> = map!("balances");
+    ///
+    /// let mut from = MemStore::new_in_memory();
+    /// BALANCES.save(&mut from, "alice", 10).unwrap();
+    /// BALANCES.save(&mut from, "bob", 20).unwrap();
+    ///
+    /// let mut to = MemStore::new_in_memory();
+    ///
+    /// assert_eq!(BALANCES.copy_to(&from, &mut to).unwrap(), 2);
+    /// assert_eq!(BALANCES.may_load(&to, "bob").unwrap(), Some(20));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either store encounters an error, entries copied
+    /// before it stay copied.
+    pub fn copy_to<From, To>(
+        &self,
+        from: &From,
+        to: &mut To,
+    ) -> Result<usize, TransferError<From::Error, To::Error>>
+    where
+        V: Serialize + DeserializeOwned,
+        From: IterStorage,
+        To: MutStorage,
+    {
+        let mut copied = 0;
+
+        for entry in from.scan::<V>(self.prefix).map_err(TransferError::Source)? {
+            let (key, value) = entry.map_err(TransferError::Source)?;
+            to.save(&key, &value).map_err(TransferError::Destination)?;
+            copied += 1;
+        }
+
+        Ok(copied)
+    }
+
+    /// Remove any item stored at the given key, reporting whether it was present.
+    ///
+    /// ```
+    /// use kv_storage::Removed;
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const BALANCES: Map<64, &str, u128> = map!("balances");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// BALANCES.save(&mut store, "alice", 100).unwrap();
+    ///
+    /// assert_eq!(BALANCES.remove_returning(&mut store, "alice").unwrap(), Removed::Existed);
+    /// assert_eq!(BALANCES.remove_returning(&mut store, "alice").unwrap(), Removed::DidNotExist);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn remove_returning<Store, Key>(
+        &self,
+        store: &mut Store,
+        key: Key,
+    ) -> Result<Removed, Store::Error>
+    where
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+    {
+        let composite = self.key(key);
+        store.remove_returning(composite.as_ref())
+    }
+}
+
+/// How many right-side keys [`join`] loads per [`Storage::may_load_many`] call.
+pub const JOIN_BATCH: usize = 64;
+
+/// Pair each left entry with the `right` value at the key `key_fn` derives from it, `None` where
+/// there is none, in the left entries' order.
+///
+/// Right-side lookups are made [`JOIN_BATCH`] keys at a time, so repos implementing
+/// [`Read::read_many`] natively serve each batch in one call.
+///
+/// ```
+/// use kv_storage::join;
+/// use kv_storage_memory::prelude::*;
+///
+/// const ORDERS: Map<64, (&str, u64), String> = map!("orders");
+/// const PRODUCTS: Map<64, &str, u128> = map!("products");
+///
+/// let mut store = MemStore::new_in_memory();
+/// ORDERS.save(&mut store, ("alice", 1), "apple".to_owned()).unwrap();
+/// ORDERS.save(&mut store, ("alice", 2), "pear".to_owned()).unwrap();
+/// PRODUCTS.save(&mut store, "apple", 3).unwrap();
+///
+/// let orders = ORDERS
+///     .sub_prefix(("alice",))
+///     .range(&store, Bound::Unbounded, Bound::Unbounded, Order::Ascending)
+///     .unwrap()
+///     .map(Result::unwrap);
+///
+/// let priced: Vec<_> = join(&store, orders, |product: &String| product.clone(), &PRODUCTS)
+///     .map(Result::unwrap)
+///     .collect();
+///
+/// assert_eq!(
+///     priced,
+///     [
+///         (1, "apple".to_owned(), Some(3)),
+///         (2, "pear".to_owned(), None),
+///     ]
+/// );
+/// ```
+///
+/// A failed batch is yielded as a single error, ending the iteration.
+pub fn join<'a, const N: usize, Store, L, KA, A, F, Key, KB, B>(
+    store: &'a Store,
+    left: L,
+    key_fn: F,
+    right: &'a Map<N, KB, B>,
+) -> impl Iterator<Item = Result<(KA, A, Option<B>), Store::Error>> + 'a
+where
+    Store: Storage,
+    L: IntoIterator<Item = (KA, A)>,
+    L::IntoIter: 'a,
+    F: Fn(&A) -> Key + 'a,
+    Key: EncodeLike<KB>,
+    KB: WriteCompositeKey,
+    B: DeserializeOwned + 'a,
+    KA: 'a,
+    A: 'a,
+{
+    let mut left = left.into_iter();
+    let mut joined = VecDeque::new();
+    let mut failed = false;
+
+    std::iter::from_fn(move || {
+        if joined.is_empty() && !failed {
+            let batch: Vec<(KA, A)> = left.by_ref().take(JOIN_BATCH).collect();
+
+            if batch.is_empty() {
+                return None;
+            }
+
+            let composites: Vec<CompositeKey<N>> = batch
+                .iter()
+                .map(|(_, value)| right.key(key_fn(value)))
+                .collect();
+            let raw: Vec<&[u8]> = composites.iter().map(AsRef::as_ref).collect();
+
+            match store.may_load_many::<B>(&raw) {
+                Ok(values) => joined.extend(
+                    batch
+                        .into_iter()
+                        .zip(values)
+                        .map(|((key, value), other)| (key, value, other)),
+                ),
+                Err(err) => {
+                    failed = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+
+        joined.pop_front().map(Ok)
+    })
+}
+
+/// The entries of a [`Map`] whose keys start with some leading parts, keyed by the remaining
+/// parts `S`, see [`Map::sub_prefix`].
+pub struct Prefix<const N: usize, S, V> {
+    bytes: CompositeKey<N>,
+    _s: PhantomData<S>,
+    _v: PhantomData<V>,
+}
+
+impl<const N: usize, S, V> Prefix<N, S, V>
+where
+    S: WriteCompositeKey,
+{
+    /// The raw prefix every entry's storage key starts with, the map's followed by the leading
+    /// parts.
+    #[must_use]
+    pub fn prefix(&self) -> &[u8] {
+        self.bytes.as_ref()
+    }
+
+    /// The full storage key for the remaining parts of a key.
+    #[allow(clippy::needless_pass_by_value)] // keys are usually small and passed by value
+    pub fn key<Key: EncodeLike<S>>(&self, rest: Key) -> CompositeKey<N> {
+        compose_key::<N>(self.prefix(), &rest)
+    }
+
+    /// Load the item for the remaining parts of a key.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load<Store, Key>(&self, store: &Store, rest: Key) -> Result<Option<V>, Store::Error>
+    where
+        V: DeserializeOwned,
+        Store: Storage,
+        Key: EncodeLike<S>,
+    {
+        let composite = self.key(rest);
+        store.may_load::<V>(composite.as_ref())
+    }
+
+    /// Iterate the entries under the prefix with remaining parts between the bounds, see
+    /// [`Map::range`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to start the scan.
+    pub fn range<'a, Store>(
+        &self,
+        store: &'a Store,
+        min: Bound<S>,
+        max: Bound<S>,
+        order: Order,
+    ) -> Result<impl Iterator<Item = RangeItem<S, V, Store::Error>> + 'a, Store::Error>
+    where
+        S: KeyDeserialize + 'a,
+        V: DeserializeOwned + 'a,
+        Store: IterStorage,
+    {
+        range_under::<N, S, V, Store>(self.prefix(), store, min, max, order)
+    }
+
+    /// Count the entries under the prefix, without reading values.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to start the scan.
+    pub fn count<Store: IterStorage>(&self, store: &Store) -> Result<usize, Store::Error> {
+        Ok(store.scan_keys(self.prefix())?.count())
+    }
+
+    /// Remove every entry under the prefix, returning how many were removed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error, entries removed
+    /// before it stay removed.
+    pub fn clear<Store>(&self, store: &mut Store) -> Result<usize, Store::Error>
+    where
+        Store: MutStorage + IterStorage,
+    {
+        clear_under(store, self.prefix())
+    }
+}
+
+/// Iterate the entries under `prefix` between the bounds, which are encoded after it.
+fn range_under<'a, const N: usize, K, V, Store>(
+    prefix: &[u8],
+    store: &'a Store,
+    min: Bound<K>,
+    max: Bound<K>,
+    order: Order,
+) -> Result<impl Iterator<Item = RangeItem<K, V, Store::Error>> + 'a, Store::Error>
+where
+    K: WriteCompositeKey + KeyDeserialize + 'a,
+    V: DeserializeOwned + 'a,
+    Store: IterStorage,
+{
+    let min = min.map(|key| compose_key::<N>(prefix, &key));
+    let max = max.map(|key| compose_key::<N>(prefix, &key));
+    let end = prefix_end(prefix);
+
+    let min = match &min {
+        Bound::Unbounded => Bound::Inclusive(prefix),
+        min => min.as_ref().map(AsRef::as_ref),
+    };
+
+    let max = match &max {
+        Bound::Unbounded => end.as_deref().map_or(Bound::Unbounded, Bound::Exclusive),
+        max => max.as_ref().map(AsRef::as_ref),
+    };
+
+    let prefix_len = prefix.len();
+
+    let entries = store.range::<V>(min, max, order)?.map(move |entry| {
+        let (key, value) = entry.map_err(RangeError::Store)?;
+        let key = K::from_key_bytes(&key[prefix_len..])?;
+
+        Ok((key, value))
+    });
+
+    Ok(entries)
+}
+
+/// Remove every entry under `prefix`, returning how many were removed.
+fn clear_under<Store>(store: &mut Store, prefix: &[u8]) -> Result<usize, Store::Error>
+where
+    Store: MutStorage + IterStorage,
+{
+    let keys: Vec<_> = store.scan_keys(prefix)?.collect();
+
+    for key in &keys {
+        store.remove(key)?;
+    }
+
+    Ok(keys.len())
+}
+
+/// Assertions over what a store holds, for tests.
+#[cfg(feature = "testing")]
+pub mod testing {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        fmt::Debug,
+        fs, io,
+        path::{Path, PathBuf},
+    };
+
+    use serde::de::DeserializeOwned;
+
+    use crate::{
+        Deserializer, EncodeLike, IterStorage, Iterate, KeyDisplay, KvStore, Map, WriteCompositeKey,
+    };
+
+    /// How a map's entries differ from the expected ones, keys rendered with [`KeyDisplay`].
+    #[derive(Debug, Default)]
+    pub struct ContentsDiff {
+        /// Expected entries the map doesn't hold.
+        pub missing: Vec<String>,
+        /// Entries the map holds but weren't expected.
+        pub unexpected: Vec<String>,
+        /// Entries held with another value than expected.
+        pub mismatched: Vec<String>,
+    }
+
+    impl ContentsDiff {
+        #[must_use]
+        pub fn is_empty(&self) -> bool {
+            self.missing.is_empty() && self.unexpected.is_empty() && self.mismatched.is_empty()
+        }
+    }
+
+    impl std::fmt::Display for ContentsDiff {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            for (label, lines) in [
+                ("missing", &self.missing),
+                ("unexpected", &self.unexpected),
+                ("mismatched", &self.mismatched),
+            ] {
+                if !lines.is_empty() {
+                    writeln!(f, "{label}:")?;
+                }
+
+                for line in lines {
+                    writeln!(f, "  {line}")?;
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Compare the map's entries with the expected ones, values after deserializing them so
+    /// encoding details don't matter.
+    ///
+    /// An expected key given twice is reported as missing the second time, a map holds it once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the store fails to scan the map or a value fails to deserialize.
+    pub fn contents_diff<const N: usize, Store, K, V, Key, I>(
+        store: &Store,
+        map: &Map<N, K, V>,
+        expected: I,
+    ) -> ContentsDiff
+    where
+        Store: IterStorage,
+        Store::Error: Debug,
+        K: WriteCompositeKey,
+        V: DeserializeOwned + PartialEq + Debug,
+        Key: EncodeLike<K>,
+        I: IntoIterator<Item = (Key, V)>,
+    {
+        let prefix_len = map.prefix().len();
+        let show = |key: &[u8]| KeyDisplay::new(&key[prefix_len..]).to_string();
+
+        let mut found: BTreeMap<Vec<u8>, V> = store
+            .scan::<V>(map.prefix())
+            .expect("scanning the map")
+            .map(|entry| entry.expect("reading a map entry"))
+            .collect();
+
+        let mut diff = ContentsDiff::default();
+        let mut seen = BTreeSet::new();
+
+        for (key, value) in expected {
+            let key = map.key(key).as_ref().to_vec();
+
+            if !seen.insert(key.clone()) {
+                diff.missing.push(format!("{} => {value:?}", show(&key)));
+                continue;
+            }
+
+            match found.remove(&key) {
+                None => diff.missing.push(format!("{} => {value:?}", show(&key))),
+                Some(stored) if stored != value => diff.mismatched.push(format!(
+                    "{}: expected {value:?}, found {stored:?}",
+                    show(&key)
+                )),
+                Some(_) => {}
+            }
+        }
+
+        for (key, value) in found {
+            diff.unexpected.push(format!("{} => {value:?}", show(&key)));
+        }
+
+        diff
+    }
+
+    /// Assert the map holds exactly the expected entries, in any order.
+    ///
+    /// ```
+    /// use kv_storage::testing::assert_contents_eq;
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const BALANCES: Map<64, &str, u128> = map!("balances");
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    /// BALANCES.save(&mut store, "bob", 20).unwrap();
+    /// BALANCES.save(&mut store, "alice", 10).unwrap();
+    ///
+    /// assert_contents_eq(&store, &BALANCES, [("alice", 10), ("bob", 20)]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics listing the missing, unexpected and mismatched entries if there are any, or if
+    /// the map can't be read.
+    pub fn assert_contents_eq<const N: usize, Store, K, V, Key, I>(
+        store: &Store,
+        map: &Map<N, K, V>,
+        expected: I,
+    ) where
+        Store: IterStorage,
+        Store::Error: Debug,
+        K: WriteCompositeKey,
+        V: DeserializeOwned + PartialEq + Debug,
+        Key: EncodeLike<K>,
+        I: IntoIterator<Item = (Key, V)>,
+    {
+        let diff = contents_diff(store, map, expected);
+
+        assert!(
+            diff.is_empty(),
+            "map {} contents differ\n{diff}",
+            KeyDisplay::new(map.prefix())
+        );
+    }
+
+    /// Assert the map holds at least the expected entries, others are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics listing the missing and mismatched entries if there are any, or if the map can't
+    /// be read.
+    pub fn assert_contains<const N: usize, Store, K, V, Key, I>(
+        store: &Store,
+        map: &Map<N, K, V>,
+        expected: I,
+    ) where
+        Store: IterStorage,
+        Store::Error: Debug,
+        K: WriteCompositeKey,
+        V: DeserializeOwned + PartialEq + Debug,
+        Key: EncodeLike<K>,
+        I: IntoIterator<Item = (Key, V)>,
+    {
+        let mut diff = contents_diff(store, map, expected);
+        diff.unexpected.clear();
+
+        assert!(
+            diff.is_empty(),
+            "map {} lacks expected entries\n{diff}",
+            KeyDisplay::new(map.prefix())
+        );
+    }
+
+    const SAMPLE_MAGIC: &[u8; 4] = b"KVSS";
+    const SAMPLE_VERSION: u8 = 1;
+    const SAMPLE_EXTENSION: &str = "sample";
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum SampleError<E> {
+        #[error(transparent)]
+        Io(#[from] io::Error),
+        #[error(transparent)]
+        Repo(E),
+    }
+
+    /// A stored value and the storage key it was read from.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Sample {
+        pub key: Vec<u8>,
+        pub value: Vec<u8>,
+    }
+
+    impl Sample {
+        /// The magic bytes `KVSS` and a version byte, then the key and the value, each
+        /// length-prefixed with a little-endian `u64`.
+        #[must_use]
+        pub fn to_bytes(&self) -> Vec<u8> {
+            let mut out = Vec::with_capacity(21 + self.key.len() + self.value.len());
+
+            out.extend_from_slice(SAMPLE_MAGIC);
+            out.push(SAMPLE_VERSION);
+
+            for bytes in [&self.key, &self.value] {
+                out.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+                out.extend_from_slice(bytes);
+            }
+
+            out
+        }
+
+        /// Read back a sample written by [`Sample::to_bytes`].
+        ///
+        /// # Errors
+        ///
+        /// This function will return an [`io::ErrorKind::InvalidData`] error if the bytes aren't
+        /// a sample of a known version.
+        pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+            let rest = bytes
+                .strip_prefix(SAMPLE_MAGIC.as_slice())
+                .ok_or_else(|| invalid_sample("not a value sample"))?;
+
+            let (version, rest) = rest
+                .split_first()
+                .ok_or_else(|| invalid_sample("truncated sample"))?;
+
+            if *version != SAMPLE_VERSION {
+                return Err(invalid_sample(format!(
+                    "unsupported sample version {version}, expected {SAMPLE_VERSION}"
+                )));
+            }
+
+            let (key, rest) = split_length_prefixed(rest)?;
+            let (value, rest) = split_length_prefixed(rest)?;
+
+            if !rest.is_empty() {
+                return Err(invalid_sample("trailing bytes after the value"));
+            }
+
+            Ok(Self {
+                key: key.to_vec(),
+                value: value.to_vec(),
+            })
+        }
+    }
+
+    fn invalid_sample(message: impl Into<String>) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, message.into())
+    }
+
+    fn split_length_prefixed(bytes: &[u8]) -> io::Result<(&[u8], &[u8])> {
+        let (len, rest) = bytes
+            .split_first_chunk::<8>()
+            .ok_or_else(|| invalid_sample("truncated sample"))?;
+
+        let len = usize::try_from(u64::from_le_bytes(*len))
+            .ok()
+            .filter(|len| *len <= rest.len())
+            .ok_or_else(|| invalid_sample("truncated sample"))?;
+
+        Ok(rest.split_at(len))
+    }
+
+    /// The sample files in `dir`, in name order.
+    fn sample_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if path.extension().is_some_and(|ext| ext == SAMPLE_EXTENSION) {
+                files.push(path);
+            }
+        }
+
+        files.sort();
+
+        Ok(files)
+    }
+
+    /// Write every value stored in the map to `dir` as a [`Sample`] file, replacing the samples
+    /// already there, and return how many were written.
+    ///
+    /// Files are numbered in key order, so recording the same contents twice gives the same
+    /// files.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the repo fails to scan the map, or the directory
+    /// can't be created or written to.
+    pub fn record_samples<const N: usize, Serde, Repo, K, V>(
+        store: &KvStore<Serde, Repo>,
+        map: &Map<N, K, V>,
+        dir: impl AsRef<Path>,
+    ) -> Result<usize, SampleError<Repo::Error>>
+    where
+        Repo: Iterate,
+        K: WriteCompositeKey,
+    {
+        let dir = dir.as_ref();
+
+        fs::create_dir_all(dir)?;
+
+        for stale in sample_files(dir)? {
+            fs::remove_file(stale)?;
+        }
+
+        let entries = store.repo().scan(map.prefix()).map_err(SampleError::Repo)?;
+        let mut written = 0;
+
+        for (key, value) in entries {
+            let path = dir.join(format!("{written:06}.{SAMPLE_EXTENSION}"));
+
+            fs::write(path, Sample { key, value }.to_bytes())?;
+
+            written += 1;
+        }
+
+        Ok(written)
+    }
+
+    /// Assert every sample recorded in `dir` by [`record_samples`] still deserializes as `T`
+    /// with `Serde`, e.g. after changing `T`, before deploying against data stored under the
+    /// old definition.
+    ///
+    /// # Panics
+    ///
+    /// Panics listing each failing sample file, its key and the error, or if `dir` holds no
+    /// samples or one can't be read.
+    pub fn assert_can_deserialize_samples<T, Serde>(dir: impl AsRef<Path>)
+    where
+        T: DeserializeOwned,
+        Serde: Deserializer,
+    {
+        let dir = dir.as_ref();
+        let files = sample_files(dir)
+            .unwrap_or_else(|err| panic!("reading samples in {}: {err}", dir.display()));
+
+        assert!(!files.is_empty(), "no samples in {}", dir.display());
+
+        let mut failures = Vec::new();
+
+        for path in &files {
+            let sample = fs::read(path)
+                .and_then(|bytes| Sample::from_bytes(&bytes))
+                .unwrap_or_else(|err| panic!("reading sample {}: {err}", path.display()));
+
+            if let Err(err) = Serde::deserialize::<T>(&sample.value) {
+                failures.push(format!(
+                    "  {} ({}): {err}",
+                    path.display(),
+                    KeyDisplay::new(&sample.key)
+                ));
+            }
+        }
+
+        assert!(
+            failures.is_empty(),
+            "samples failing to deserialize as {}:\n{}",
+            std::any::type_name::<T>(),
+            failures.join("\n")
+        );
+    }
+}
+
+/// What a logical operation costs at each layer of a stack of decorator repos.
+///
+/// Decorators implementing [`Traceable`] report, while [`traced`] runs, the writes and
+/// removals they receive and the physical operations they issue to the repos they wrap in
+/// turn, reads included:
+///
+/// ```
+/// use kv_storage::{prelude::*, trace};
+/// use kv_storage_bincode::Bincode;
+/// use kv_storage_memory::MemoryRepo;
+///
+/// const OWNER: Item<String> = item!("owner");
+///
+/// let mut store: KvStore<Bincode, MemoryRepo> = KvStore::default();
+///
+/// let (result, trace) = OWNER.save_traced(&mut store, "alice".to_owned());
+/// result.unwrap();
+///
+/// // the memory repo isn't a decorator, so nothing reports beneath the store
+/// assert!(trace.inner.is_empty());
+/// ```
+pub mod trace {
+    use std::{cell::RefCell, fmt};
+
+    use crate::BatchOp;
+
+    /// A decorator repo that reports its operations to [`traced`].
+    ///
+    /// Implementations [`enter`] a span at the start of each write and removal, and record
+    /// on it what they received and what they issued to the repos they wrap.
+    pub trait Traceable {
+        /// The name the layer is reported under.
+        const LAYER: &'static str;
+    }
+
+    /// The kind of a traced operation.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum Op {
+        /// A read or a key check.
+        Read,
+        Write,
+        Remove,
+    }
+
+    /// Operations counted at one layer.
+    #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+    pub struct TraceCounts {
+        pub reads: u64,
+        pub writes: u64,
+        pub removes: u64,
+        pub bytes_read: u64,
+        pub bytes_written: u64,
+    }
+
+    impl TraceCounts {
+        fn count(&mut self, op: Op, bytes: usize) {
+            let bytes = bytes as u64;
+
+            match op {
+                Op::Read => {
+                    self.reads += 1;
+                    self.bytes_read += bytes;
+                }
+                Op::Write => {
+                    self.writes += 1;
+                    self.bytes_written += bytes;
+                }
+                Op::Remove => self.removes += 1,
+            }
+        }
+
+        fn count_batch(&mut self, ops: &[BatchOp<'_>]) {
+            for (_, bytes) in ops {
+                match bytes {
+                    Some(bytes) => self.count(Op::Write, bytes.len()),
+                    None => self.count(Op::Remove, 0),
+                }
+            }
+        }
+
+        fn add(&mut self, other: TraceCounts) {
+            self.reads += other.reads;
+            self.writes += other.writes;
+            self.removes += other.removes;
+            self.bytes_read += other.bytes_read;
+            self.bytes_written += other.bytes_written;
+        }
+    }
+
+    /// The operations of one layer, and of the traceable layers beneath it.
+    #[derive(Debug, Clone)]
+    pub struct OpTrace {
+        pub layer: &'static str,
+        /// What the layer was asked to do.
+        pub received: TraceCounts,
+        /// What the layer asked the repos it wraps to do.
+        pub issued: TraceCounts,
+        pub inner: Vec<OpTrace>,
+        /// Tells apart layers of the same type.
+        id: usize,
+    }
+
+    impl OpTrace {
+        fn new(layer: &'static str, id: usize) -> Self {
+            Self {
+                layer,
+                received: TraceCounts::default(),
+                issued: TraceCounts::default(),
+                inner: Vec::new(),
+                id,
+            }
+        }
+
+        /// The first layer named `layer`, searching depth-first from this one.
+        #[must_use]
+        pub fn find(&self, layer: &str) -> Option<&OpTrace> {
+            if self.layer == layer {
+                return Some(self);
+            }
+
+            self.inner.iter().find_map(|inner| inner.find(layer))
+        }
+
+        fn merge(&mut self, other: OpTrace) {
+            self.received.add(other.received);
+            self.issued.add(other.issued);
+
+            for inner in other.inner {
+                self.nest(inner);
+            }
+        }
+
+        /// Add the trace of a layer beneath this one, merging it with earlier traces of the
+        /// same layer.
+        fn nest(&mut self, trace: OpTrace) {
+            match self.inner.iter_mut().find(|inner| inner.id == trace.id) {
+                Some(inner) => inner.merge(trace),
+                None => self.inner.push(trace),
+            }
+        }
+
+        fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+            let TraceCounts {
+                reads,
+                writes,
+                removes,
+                bytes_read,
+                bytes_written,
+            } = self.issued;
+
+            writeln!(
+                f,
+                "{:indent$}{}: {reads} reads ({bytes_read} B), {writes} writes ({bytes_written} B), \
+                 {removes} removes",
+                "",
+                self.layer,
+                indent = depth * 2
+            )?;
+
+            self.inner
+                .iter()
+                .try_for_each(|inner| inner.fmt_indented(f, depth + 1))
+        }
+    }
+
+    /// One line per layer, with the operations it issued.
+    impl fmt::Display for OpTrace {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.fmt_indented(f, 0)
+        }
+    }
+
+    thread_local! {
+        /// The layers currently handling an operation, outermost first, empty unless tracing.
+        static STACK: RefCell<Vec<OpTrace>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Pops the root of a trace if the traced closure panics.
+    struct Root;
+
+    impl Drop for Root {
+        fn drop(&mut self) {
+            STACK.with_borrow_mut(Vec::pop);
+        }
+    }
+
+    /// Run `f`, returning what it returned along with a trace rooted at the store, whose issued
+    /// operations are those received by the outermost layers.
+    ///
+    /// Only [`Traceable`] layers show up, the trace stops at the first repo that isn't one.
+    pub fn traced<R>(f: impl FnOnce() -> R) -> (R, OpTrace) {
+        STACK.with_borrow_mut(|stack| stack.push(OpTrace::new("store", 0)));
+
+        let root = Root;
+        let result = f();
+        std::mem::forget(root);
+
+        let Some(mut trace) = STACK.with_borrow_mut(Vec::pop) else {
+            unreachable!("the root is popped last")
+        };
+
+        for inner in &trace.inner {
+            trace.issued.add(inner.received);
+        }
+
+        (result, trace)
+    }
+
+    /// A layer handling one operation, see [`enter`].
+    #[must_use = "the span records the layer until it is dropped"]
+    pub struct Span {
+        active: bool,
+    }
+
+    /// Start reporting the operations of `layer`, until the returned span is dropped.
+    ///
+    /// Does nothing unless called from within [`traced`].
+    pub fn enter<T: Traceable + ?Sized>(layer: &T) -> Span {
+        let id = std::ptr::from_ref(layer).cast::<()>() as usize;
+
+        let active = STACK.with_borrow_mut(|stack| {
+            if stack.is_empty() {
+                return false;
+            }
+
+            stack.push(OpTrace::new(T::LAYER, id));
+            true
+        });
+
+        Span { active }
+    }
+
+    impl Span {
+        fn record(&self, count: impl FnOnce(&mut OpTrace)) {
+            if self.active {
+                STACK.with_borrow_mut(|stack| count(stack.last_mut().expect("the span is open")));
+            }
+        }
+
+        /// Record an operation the layer was asked to do, with the bytes of its value.
+        pub fn receive(&self, op: Op, bytes: usize) {
+            self.record(|trace| trace.received.count(op, bytes));
+        }
+
+        /// Record an operation the layer asked a wrapped repo to do, with the bytes of its
+        /// value.
+        pub fn issue(&self, op: Op, bytes: usize) {
+            self.record(|trace| trace.issued.count(op, bytes));
+        }
+
+        /// Record a batch the layer was asked to apply, op by op.
+        pub fn receive_batch(&self, ops: &[BatchOp<'_>]) {
+            self.record(|trace| trace.received.count_batch(ops));
+        }
+
+        /// Record a batch the layer asked a wrapped repo to apply, op by op.
+        pub fn issue_batch(&self, ops: &[BatchOp<'_>]) {
+            self.record(|trace| trace.issued.count_batch(ops));
+        }
+    }
+
+    impl Drop for Span {
+        fn drop(&mut self) {
+            if !self.active {
+                return;
+            }
+
+            STACK.with_borrow_mut(|stack| {
+                let trace = stack.pop().expect("the span is open");
+                stack
+                    .last_mut()
+                    .expect("the root outlives every span")
+                    .nest(trace);
+            });
+        }
+    }
+}
+
+/// The keys the crate writes for its own bookkeeping, all under the reserved `kv_storage`
+/// namespace.
+///
+/// [`Item`], [`Map`], [`Deque`] and the index types refuse keys in the namespace, so user data
+/// can't overwrite or be mistaken for a system record, and [`validate_system_keys`] checks the
+/// records on startup.
+///
+/// ```
+/// use kv_storage::system::{self, ReservedKey};
+/// use kv_storage_memory::prelude::*;
+///
+/// const CONFIG: Item<u32> = item!("config");
+///
+/// assert!(system::is_reserved(system::MAP_HEADER));
+/// assert!(!system::is_reserved(CONFIG.key()));
+///
+/// let Err(ReservedKey { key }) = Item::<u32>::try_new(system::BOUNDED_COUNT) else {
+///     panic!("the key is reserved");
+/// };
+/// assert_eq!(key, system::BOUNDED_COUNT);
+///
+/// let store = MemStore::new_in_memory();
+/// system::validate_system_keys(&store).unwrap();
+/// ```
+pub mod system {
+    use std::collections::BTreeMap;
+
+    use crate::{namespaced_key, IterStorage, KeyDisplay};
+
+    /// The namespace segment every system key starts with, as [`crate::namespaced`] writes
+    /// `"kv_storage"`.
+    pub const NAMESPACE: &[u8] = b"\0\x0akv_storage";
+
+    /// [`crate::HeaderedMap`] headers, followed by the map's prefix.
+    pub const MAP_HEADER: &[u8] = namespaced_key!("kv_storage", "map_header");
+
+    /// [`crate::Deque`] head and tail indexes, followed by the length-prefixed deque prefix and
+    /// `h` or `t`.
+    pub const DEQUE_BOUNDS: &[u8] = namespaced_key!("kv_storage", "deque_bounds");
+
+    /// [`crate::BoundedMap`] counters, followed by the map's prefix.
+    pub const BOUNDED_COUNT: &[u8] = namespaced_key!("kv_storage", "bounded_count");
+
+    /// [`crate::SnapshotMap`] changelog entries, followed by the length-prefixed changelog name,
+    /// the length-prefixed map key and the big-endian height.
+    pub const CHANGELOG: &[u8] = namespaced_key!("kv_storage", "changelog");
+
+    /// Tombstones left by repos hiding a key, e.g. `kv_storage_layered::LayeredRepo`'s, followed
+    /// by the hidden key. Their values are up to the repo.
+    pub const TOMBSTONE: &[u8] = namespaced_key!("kv_storage", "tombstone");
+
+    pub(crate) const DEQUE_HEAD: &[u8] = b"h";
+    pub(crate) const DEQUE_TAIL: &[u8] = b"t";
+
+    /// Returned when a user-declared key falls in the reserved namespace.
+    #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+    #[error("{} is in the reserved system namespace", KeyDisplay::new(key))]
+    pub struct ReservedKey {
+        pub key: &'static [u8],
+    }
+
+    /// Returned by [`validate_system_keys`].
+    #[derive(Debug, thiserror::Error)]
+    pub enum SystemKeyError<E> {
+        /// A system record is malformed or disagrees with the data it describes.
+        #[error("corrupt system record {}: {reason}", KeyDisplay::new(key))]
+        Corrupt { key: Vec<u8>, reason: &'static str },
+        /// A system record couldn't be loaded, e.g. it doesn't deserialize.
+        #[error("unreadable system record {}: {error}", KeyDisplay::new(key))]
+        Unreadable { key: Vec<u8>, error: E },
+        #[error(transparent)]
+        Store(#[from] E),
+    }
+
+    /// Check if a key lies in the reserved namespace.
+    #[must_use]
+    pub const fn is_reserved(key: &[u8]) -> bool {
+        if key.len() < NAMESPACE.len() {
+            return false;
+        }
+
+        let mut i = 0;
+
+        while i < NAMESPACE.len() {
+            if key[i] != NAMESPACE[i] {
+                return false;
+            }
+            i += 1;
+        }
+
+        true
+    }
+
+    /// Reject a key in the reserved namespace.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the key is reserved.
+    pub const fn check(key: &'static [u8]) -> Result<(), ReservedKey> {
+        if is_reserved(key) {
+            Err(ReservedKey { key })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check that every system record in the store parses and agrees with the data it
+    /// describes, stopping at the first that doesn't.
+    ///
+    /// Counters are loaded as `u64`, deque bounds must span exactly the deque's values, and
+    /// changelog keys must decode. Changelog values and tombstones aren't checked, their types
+    /// aren't known here.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a system record is corrupt or the store encounters
+    /// an error.
+    pub fn validate_system_keys<Store>(store: &Store) -> Result<(), SystemKeyError<Store::Error>>
+    where
+        Store: IterStorage,
+    {
+        let mut deques = BTreeMap::<Vec<u8>, (u64, u64)>::new();
+
+        for key in store.scan_keys(NAMESPACE)? {
+            let corrupt = |reason| SystemKeyError::Corrupt {
+                key: key.clone(),
+                reason,
+            };
+
+            if key.starts_with(MAP_HEADER) || key.starts_with(BOUNDED_COUNT) {
+                load_counter(store, &key)?;
+            } else if let Some(rest) = key.strip_prefix(DEQUE_BOUNDS) {
+                let (prefix, suffix) =
+                    split_segment(rest).ok_or_else(|| corrupt("bad deque prefix"))?;
+
+                let index = load_counter(store, &key)?;
+                let bounds = deques.entry(prefix.to_vec()).or_default();
+
+                match suffix {
+                    DEQUE_HEAD => bounds.0 = index,
+                    DEQUE_TAIL => bounds.1 = index,
+                    _ => return Err(corrupt("neither a deque head nor tail")),
+                }
+            } else if let Some(rest) = key.strip_prefix(CHANGELOG) {
+                let (_, rest) = split_segment(rest).ok_or_else(|| corrupt("bad changelog name"))?;
+                let (_, height) =
+                    split_segment(rest).ok_or_else(|| corrupt("bad changelog key"))?;
+
+                if height.len() != 8 {
+                    return Err(corrupt("bad changelog height"));
+                }
+            } else if !key.starts_with(TOMBSTONE) {
+                return Err(corrupt("unknown system record"));
+            }
+        }
+
+        for (prefix, (head, tail)) in deques {
+            let values = store
+                .scan_keys(&prefix)?
+                .filter(|key| key.len() == prefix.len() + 8)
+                .count() as u64;
+
+            if values != tail.wrapping_sub(head) {
+                return Err(SystemKeyError::Corrupt {
+                    key: [DEQUE_BOUNDS, &prefix].concat(),
+                    reason: "deque bounds don't match its values",
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load_counter<Store>(store: &Store, key: &[u8]) -> Result<u64, SystemKeyError<Store::Error>>
+    where
+        Store: IterStorage,
+    {
+        match store.may_load::<u64>(key) {
+            Ok(Some(count)) => Ok(count),
+            Ok(None) => Err(SystemKeyError::Corrupt {
+                key: key.to_vec(),
+                reason: "removed while validating",
+            }),
+            Err(error) => Err(SystemKeyError::Unreadable {
+                key: key.to_vec(),
+                error,
+            }),
+        }
+    }
+
+    /// Split off a segment prefixed with its length as a big-endian `u16`.
+    fn split_segment(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+        let (len, rest) = bytes.split_first_chunk::<2>()?;
+        let len = usize::from(u16::from_be_bytes(*len));
+
+        (rest.len() >= len).then(|| rest.split_at(len))
+    }
+}
+
+/// What has happened to an [`Entry`] since it was loaded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EntryState {
+    /// Nothing is stored at the key.
+    Vacant,
+    /// The stored value, unchanged.
+    Loaded,
+    /// A value for a previously vacant key.
+    Inserted,
+    /// A stored value, changed.
+    Modified,
+}
+
+/// A map entry loaded by [`Map::entry`], written back by [`Entry::save`] only if it changed.
+///
+/// The entry holds the store mutably, so nothing else can write to it in the meantime.
+///
+/// ```
+/// use kv_storage_memory::prelude::*;
+///
+/// const BALANCES: Map<64, &str, u128> = map!("balances");
+///
+/// let mut store = MemStore::new_in_memory();
+///
+/// for _ in 0..2 {
+///     BALANCES
+///         .entry(&mut store, "alice")
+///         .unwrap()
+///         .and_modify(|balance| *balance += 10)
+///         .or_insert(100)
+///         .save()
+///         .unwrap();
+/// }
+///
+/// assert_eq!(BALANCES.may_load(&store, "alice").unwrap(), Some(110));
+/// ```
+pub struct Entry<'a, const N: usize, Store, V> {
+    store: &'a mut Store,
+    key: CompositeKey<N>,
+    value: Option<V>,
+    state: EntryState,
+}
+
+impl<const N: usize, Store, V> Entry<'_, N, Store, V>
+where
+    Store: MutStorage,
+    V: Serialize,
+{
+    /// The full storage key.
+    pub fn key(&self) -> &[u8] {
+        self.key.as_ref()
+    }
+
+    pub const fn state(&self) -> EntryState {
+        self.state
+    }
+
+    pub const fn get(&self) -> Option<&V> {
+        self.value.as_ref()
+    }
+
+    /// Set the value if the entry is vacant.
+    #[must_use]
+    pub fn or_insert(self, value: V) -> Self {
+        self.or_insert_with(|| value)
+    }
+
+    /// Set the value to the result of `f` if the entry is vacant.
+    #[must_use]
+    pub fn or_insert_with<F: FnOnce() -> V>(mut self, f: F) -> Self {
+        if self.value.is_none() {
+            self.value = Some(f());
+            self.state = EntryState::Inserted;
+        }
+
+        self
+    }
+
+    /// Modify the value in place if there is one.
+    #[must_use]
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Some(value) = &mut self.value {
+            f(value);
+
+            if self.state == EntryState::Loaded {
+                self.state = EntryState::Modified;
+            }
+        }
+
+        self
+    }
+
+    /// Write the value back if it was inserted or modified, returning it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn save(self) -> Result<Option<V>, Store::Error> {
+        if let (EntryState::Inserted | EntryState::Modified, Some(value)) =
+            (self.state, &self.value)
+        {
+            self.store.save(self.key.as_ref(), value)?;
+        }
+
+        Ok(self.value)
+    }
+}
+
+/// An entry yielded by [`Map::range`].
+pub type RangeItem<K, V, E> = Result<(<K as KeyDeserialize>::Owned, V), RangeError<E>>;
+
+/// Returned for an entry yielded by [`Map::range`] and the like, whose key doesn't decode or whose
+/// value the store fails to load.
+///
+/// ```
+/// use kv_storage::{Bound, Order, RangeError};
+/// use kv_storage_memory::prelude::*;
+///
+/// const SCORES: Map<64, u64, u32> = map!("scores");
+///
+/// let mut store = MemStore::new_in_memory();
+/// SCORES.save(&mut store, 1, 10).unwrap();
+///
+/// // a key too short to be a u64
+/// store.save(&[SCORES.prefix(), b"bad"].concat(), &20u32).unwrap();
+///
+/// let entries: Vec<_> = SCORES
+///     .range(&store, Bound::Unbounded, Bound::Unbounded, Order::Ascending)
+///     .unwrap()
+///     .collect();
+///
+/// assert!(matches!(entries[0], Ok((1, 10))));
+/// assert!(matches!(entries[1], Err(RangeError::Key(_))));
+/// ```
+#[derive(Debug, thiserror::Error)]
+pub enum RangeError<E> {
+    #[error(transparent)]
+    Key(#[from] KeyDecodeError),
+    #[error(transparent)]
+    Store(E),
+}
+
+/// A source of timestamps, in whatever unit the caller chooses (e.g. block time nanos).
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+impl<F: Fn() -> u64> Clock for F {
+    fn now(&self) -> u64 {
+        self()
+    }
+}
+
+/// A value stored alongside when it was created and last updated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timestamped<V> {
+    created_at: u64,
+    updated_at: u64,
+    value: V,
+}
+
+impl<V> Timestamped<V> {
+    pub const fn created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    pub const fn updated_at(&self) -> u64 {
+        self.updated_at
+    }
+
+    pub const fn value(&self) -> &V {
+        &self.value
+    }
+
+    pub fn into_value(self) -> V {
+        self.value
+    }
+}
+
+/// A `Map` whose values are wrapped in a [`Timestamped`] envelope maintained on save.
+///
+/// ```
+/// use kv_storage::TimestampedMap;
+/// use kv_storage_memory::prelude::*;
+///
+/// const ORDERS: TimestampedMap<16, u64, String> = TimestampedMap::new(map!("orders"));
+///
+/// let mut store = MemStore::new_in_memory();
+///
+/// ORDERS.save(&mut store, 1, "pending".to_owned(), &|| 100).unwrap();
+/// ORDERS.save(&mut store, 1, "filled".to_owned(), &|| 150).unwrap();
+///
+/// let order = ORDERS.may_load(&store, 1).unwrap().unwrap();
+/// assert_eq!((order.created_at(), order.updated_at()), (100, 150));
+/// assert_eq!(order.value(), "filled");
+/// ```
+pub struct TimestampedMap<const N: usize, K, V> {
+    map: Map<N, K, Timestamped<V>>,
+}
+
+impl<const N: usize, K, V> TimestampedMap<N, K, V>
+where
+    K: WriteCompositeKey,
+{
+    #[must_use]
+    pub const fn new(map: Map<N, K, Timestamped<V>>) -> Self {
+        Self { map }
+    }
+
+    /// Save the value for the given key, preserving `created_at` if an entry already exists.
+    ///
+    /// `updated_at` never goes backwards, even if the clock does.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn save<Store, Key>(
+        &self,
+        store: &mut Store,
+        key: Key,
+        value: V,
+        clock: &impl Clock,
+    ) -> Result<(), Store::Error>
+    where
+        V: Serialize + DeserializeOwned,
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+    {
+        let now = clock.now();
+        let key = self.map.key(key);
+
+        let (created_at, updated_at) = match store.may_load::<Timestamped<V>>(key.as_ref())? {
+            Some(previous) => (previous.created_at, now.max(previous.updated_at)),
+            None => (now, now),
+        };
+
+        store.save(
+            key.as_ref(),
+            &Timestamped {
+                created_at,
+                updated_at,
+                value,
+            },
+        )
+    }
+
+    /// Save the value for the given key with a known `created_at`, skipping the read `save` does.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn save_with_created<Store, Key>(
+        &self,
+        store: &mut Store,
+        key: Key,
+        value: V,
+        created_at: u64,
+        clock: &impl Clock,
+    ) -> Result<(), Store::Error>
+    where
+        V: Serialize,
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+    {
+        self.map.save(
+            store,
+            key,
+            Timestamped {
+                created_at,
+                updated_at: clock.now(),
+                value,
+            },
+        )
+    }
+
+    /// Load the envelope for the given key if it exists, otherwise `None`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load<Store, Key>(
+        &self,
+        store: &Store,
+        key: Key,
+    ) -> Result<Option<Timestamped<V>>, Store::Error>
+    where
+        V: DeserializeOwned,
+        Store: Storage,
+        Key: EncodeLike<K>,
+    {
+        self.map.may_load(store, key)
+    }
+
+    /// Load just the value for the given key if it exists, otherwise `None`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load_value<Store, Key>(
+        &self,
+        store: &Store,
+        key: Key,
+    ) -> Result<Option<V>, Store::Error>
+    where
+        V: DeserializeOwned,
+        Store: Storage,
+        Key: EncodeLike<K>,
+    {
+        self.map
+            .may_load(store, key)
+            .map(|envelope| envelope.map(Timestamped::into_value))
+    }
+
+    /// Remove any entry stored at the given key.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn remove<Store, Key>(&self, store: &mut Store, key: Key) -> Result<(), Store::Error>
+    where
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+    {
+        self.map.remove(store, key)
+    }
+}
+
+/// Returned by [`BoundedMap::save`] when a new key doesn't fit, or when the store fails.
+///
+/// ```
+/// use kv_storage::{BoundedError, BoundedMap};
+/// use kv_storage_memory::prelude::*;
+///
+/// const SEATS: BoundedMap<16, u32, String> = BoundedMap::new(map!("seats"), 1);
+///
+/// let mut store = MemStore::new_in_memory();
+/// SEATS.save(&mut store, 1, "alice".to_owned()).unwrap();
+///
+/// let Err(BoundedError::CapacityExceeded { max }) = SEATS.save(&mut store, 2, "bob".to_owned())
+/// else {
+///     panic!("the map is full");
+/// };
+/// assert_eq!(max, 1);
+/// ```
+#[derive(Debug, thiserror::Error)]
+pub enum BoundedError<E> {
+    #[error("map is at its capacity of {max} entries")]
+    CapacityExceeded { max: u64 },
+    #[error(transparent)]
+    Store(E),
+}
+
+/// Whether a [`HeaderedMap`] was ever written to, and if so whether it holds entries.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MapState {
+    NeverUsed,
+    Empty,
+    Populated { count_hint: u64 },
+}
+
+/// A `Map` with a header entry, written on first save and kept when the map is emptied, so
+/// "emptied" can be told apart from "never used".
+///
+/// The header lives at [`HeaderedMap::HEADER_PREFIX`] followed by the map's prefix, outside the
+/// entries' keyspace, so no key can overwrite it and scanning or clearing the map never reaches
+/// it. It holds the entry count as maintained through this wrapper.
+///
+/// ```
+/// use kv_storage::{HeaderedMap, MapState};
+/// use kv_storage_memory::prelude::*;
+///
+/// const CART: HeaderedMap<32, u32, u32> = map!("cart").with_header();
+///
+/// let mut store = MemStore::new_in_memory();
+/// assert_eq!(CART.state(&store).unwrap(), MapState::NeverUsed);
+///
+/// CART.save(&mut store, 1, 2).unwrap();
+/// CART.clear(&mut store).unwrap();
+///
+/// assert_eq!(CART.state(&store).unwrap(), MapState::Empty);
+/// ```
+pub struct HeaderedMap<const N: usize, K, V> {
+    map: Map<N, K, V>,
+}
+
+impl<const N: usize, K, V> HeaderedMap<N, K, V>
+where
+    K: WriteCompositeKey,
+{
+    /// The prefix headers are stored under, see [`system::MAP_HEADER`].
+    pub const HEADER_PREFIX: &'static [u8] = system::MAP_HEADER;
+
+    #[must_use]
+    pub const fn new(map: Map<N, K, V>) -> Self {
+        Self { map }
+    }
+
+    /// The storage key of the header entry.
+    #[must_use]
+    pub fn header_key(&self) -> CompositeKey<N> {
+        compose_key::<N>(Self::HEADER_PREFIX, &self.map.prefix())
+    }
+
+    /// The map's state according to its header.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn state<Store: Storage>(&self, store: &Store) -> Result<MapState, Store::Error> {
+        Ok(match store.may_load::<u64>(self.header_key().as_ref())? {
+            None => MapState::NeverUsed,
+            Some(0) => MapState::Empty,
+            Some(count_hint) => MapState::Populated { count_hint },
+        })
+    }
+
+    /// Save the value for the given key, creating the header on first use.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn save<Store, Key, Item>(
+        &self,
+        store: &mut Store,
+        key: Key,
+        item: Item,
+    ) -> Result<(), Store::Error>
+    where
+        V: Serialize,
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+        Item: Borrow<V>,
+    {
+        let key = self.map.key(key);
+        let header = self.header_key();
+
+        let count = store.may_load::<u64>(header.as_ref())?;
+        let existed = store.has_key(key.as_ref())?;
+
+        store.save(key.as_ref(), item.borrow())?;
+
+        match (count, existed) {
+            (Some(_), true) => Ok(()),
+            (Some(count), false) => store.save(header.as_ref(), &(count + 1)),
+            (None, _) => store.save(header.as_ref(), &1u64),
+        }
+    }
+
+    /// Load the value for the given key if it exists, otherwise `None`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load<Store, Key>(&self, store: &Store, key: Key) -> Result<Option<V>, Store::Error>
+    where
+        V: DeserializeOwned,
+        Store: Storage,
+        Key: EncodeLike<K>,
+    {
+        self.map.may_load(store, key)
+    }
+
+    /// Check if a key exists.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn has_key<Store, Key>(&self, store: &Store, key: Key) -> Result<bool, Store::Error>
+    where
+        Store: Storage,
+        Key: EncodeLike<K>,
+    {
+        self.map.has_key(store, key)
+    }
+
+    /// Remove any value stored at the given key, keeping the header. A map without a header, e.g.
+    /// one written through the plain [`Map`], is left without one.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn remove<Store, Key>(&self, store: &mut Store, key: Key) -> Result<Removed, Store::Error>
+    where
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+    {
+        let removed = store.remove_returning(self.map.key(key).as_ref())?;
+
+        if removed.existed() {
+            let header = self.header_key();
+
+            // a made-up header of 0 would report the map as emptied while entries remain
+            if let Some(count) = store.may_load::<u64>(header.as_ref())? {
+                store.save(header.as_ref(), &count.saturating_sub(1))?;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Iterate the entries with keys between the bounds, see [`Map::range`]. The header is never
+    /// yielded.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to start the scan.
+    pub fn range<'a, Store>(
+        &self,
+        store: &'a Store,
+        min: Bound<K>,
+        max: Bound<K>,
+        order: Order,
+    ) -> Result<impl Iterator<Item = RangeItem<K, V, Store::Error>> + 'a, Store::Error>
+    where
+        K: KeyDeserialize + 'a,
+        V: DeserializeOwned + 'a,
+        Store: IterStorage,
+    {
+        self.map.range(store, min, max, order)
+    }
+
+    /// Iterate every key in ascending order, see [`Map::keys`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to start the scan.
+    pub fn keys<'a, Store>(
+        &self,
+        store: &'a Store,
+    ) -> Result<impl Iterator<Item = Result<K::Owned, KeyDecodeError>> + 'a, Store::Error>
+    where
+        K: KeyDeserialize + 'a,
+        Store: IterStorage,
+    {
+        self.map.keys(store)
+    }
+
+    /// Count the entries by scanning them, unlike the header's count hint this can't drift from
+    /// writes made through the inner map.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to start the scan.
+    pub fn count<Store: IterStorage>(&self, store: &Store) -> Result<usize, Store::Error> {
+        Ok(store.scan_keys(self.map.prefix())?.count())
+    }
+
+    /// Remove every entry, returning how many were removed. A written header is kept, so the map
+    /// reads as [`MapState::Empty`] afterwards.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error, entries removed
+    /// before it stay removed.
+    pub fn clear<Store>(&self, store: &mut Store) -> Result<usize, Store::Error>
+    where
+        Store: MutStorage + IterStorage,
+    {
+        let removed = self.map.clear(store)?;
+        let header = self.header_key();
+
+        if store.has_key(header.as_ref())? {
+            store.save(header.as_ref(), &0u64)?;
+        }
+
+        Ok(removed)
+    }
+
+    /// Remove the header, so an empty map reads as never used again. Entries are left as is.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn purge<Store: MutStorage>(&self, store: &mut Store) -> Result<(), Store::Error> {
+        store.remove(self.header_key().as_ref())
+    }
+}
+
+/// A `Map` limited to `max` entries, with its cardinality kept in a counter under
+/// [`system::BOUNDED_COUNT`].
+///
+/// ```
+/// use kv_storage::{BoundedError, BoundedMap};
+/// use kv_storage_memory::prelude::*;
+///
+/// const VALIDATORS: BoundedMap<16, u32, String> =
+///     BoundedMap::new(map!("validators"), 1);
+///
+/// let mut store = MemStore::new_in_memory();
+///
+/// VALIDATORS.save(&mut store, 1, "alice".to_owned()).unwrap();
+///
+/// assert!(matches!(
+///     VALIDATORS.save(&mut store, 2, "bob".to_owned()),
+///     Err(BoundedError::CapacityExceeded { max: 1 })
+/// ));
+/// assert_eq!(VALIDATORS.count(&store).unwrap(), 1);
+/// ```
+///
+/// The counter is only maintained through this wrapper, writes through the inner map bypass it
+/// until [`BoundedMap::recount`] rebuilds it.
+/// If the second of a save's or remove's two writes fails, the first is rolled back on a best
+/// effort basis.
+pub struct BoundedMap<const N: usize, K, V> {
+    map: Map<N, K, V>,
+    max: u64,
+}
+
+impl<const N: usize, K, V> BoundedMap<N, K, V>
+where
+    K: WriteCompositeKey,
+{
+    #[must_use]
+    pub const fn new(map: Map<N, K, V>, max: u64) -> Self {
+        Self { map, max }
+    }
+
+    #[must_use]
+    pub const fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// The number of entries in the map.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn count<Store: Storage>(&self, store: &Store) -> Result<u64, Store::Error> {
+        store
+            .may_load(self.count_key().as_ref())
+            .map(Option::unwrap_or_default)
+    }
+
+    /// The storage key of the counter.
+    #[must_use]
+    pub fn count_key(&self) -> CompositeKey<N> {
+        compose_key::<N>(system::BOUNDED_COUNT, &self.map.prefix())
+    }
+
+    fn save_count<Store: MutStorage>(
+        &self,
+        store: &mut Store,
+        count: u64,
+    ) -> Result<(), Store::Error> {
+        store.save(self.count_key().as_ref(), &count)
+    }
+
+    /// Save the value for the given key, rejecting new keys once the map is full.
+    ///
+    /// Overwriting an existing key always succeeds.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The key is new and the map already holds `max` entries.
+    /// - The store encounters an error.
+    pub fn save<Store, Key, Item>(
+        &self,
+        store: &mut Store,
+        key: Key,
+        item: Item,
+    ) -> Result<(), BoundedError<Store::Error>>
+    where
+        V: Serialize,
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+        Item: Borrow<V>,
+    {
+        let key = self.map.key(key);
+        let key = key.as_ref();
+
+        if store.has_key(key).map_err(BoundedError::Store)? {
+            return store.save(key, item.borrow()).map_err(BoundedError::Store);
+        }
+
+        let count = self.count(store).map_err(BoundedError::Store)?;
+
+        if count >= self.max {
+            return Err(BoundedError::CapacityExceeded { max: self.max });
+        }
+
+        store
+            .save(key, item.borrow())
+            .map_err(BoundedError::Store)?;
+
+        if let Err(err) = self.save_count(store, count + 1) {
+            // the key was absent, so removing it restores the previous state
+            let _ = store.remove(key);
+            return Err(BoundedError::Store(err));
+        }
+
+        Ok(())
+    }
+
+    /// Load the value for the given key if it exists, otherwise `None`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load<Store, Key>(&self, store: &Store, key: Key) -> Result<Option<V>, Store::Error>
+    where
+        V: DeserializeOwned,
+        Store: Storage,
+        Key: EncodeLike<K>,
+    {
+        self.map.may_load(store, key)
+    }
+
+    /// Check if a key exists.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn has_key<Store, Key>(&self, store: &Store, key: Key) -> Result<bool, Store::Error>
+    where
+        Store: Storage,
+        Key: EncodeLike<K>,
+    {
+        self.map.has_key(store, key)
+    }
+
+    /// Remove any value stored at the given key, freeing its slot if it was present.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn remove<Store, Key>(&self, store: &mut Store, key: Key) -> Result<Removed, Store::Error>
+    where
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+    {
+        let key = self.map.key(key);
+        let key = key.as_ref();
+
+        if !store.has_key(key)? {
+            return Ok(Removed::DidNotExist);
+        }
+
+        let count = self.count(store)?;
+
+        self.save_count(store, count.saturating_sub(1))?;
+
+        if let Err(err) = store.remove(key) {
+            let _ = self.save_count(store, count);
+            return Err(err);
+        }
+
+        Ok(Removed::Existed)
+    }
+
+    /// Remove every entry and reset the counter, returning how many entries were removed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error, entries removed
+    /// before it stay removed and [`BoundedMap::recount`] repairs the counter.
+    pub fn clear<Store>(&self, store: &mut Store) -> Result<usize, Store::Error>
+    where
+        Store: MutStorage + IterStorage,
+    {
+        let removed = self.map.clear(store)?;
+
+        self.save_count(store, 0)?;
+
+        Ok(removed)
+    }
+
+    /// Rebuild the counter by scanning the map's keys, for recovering from writes that bypassed
+    /// this wrapper. Returns the new count.
+    ///
+    /// ```
+    /// use kv_storage::BoundedMap;
+    /// use kv_storage_memory::prelude::*;
+    ///
+    /// const MEMBERS: Map<16, u32, String> = map!("members");
+    /// const BOUNDED: BoundedMap<16, u32, String> =
+    ///     BoundedMap::new(MEMBERS, 10);
+    ///
+    /// let mut store = MemStore::new_in_memory();
+    ///
+    /// // bypasses the counter
+    /// MEMBERS.save(&mut store, 1, "alice".to_owned()).unwrap();
+    /// assert_eq!(BOUNDED.count(&store).unwrap(), 0);
+    ///
+    /// assert_eq!(BOUNDED.recount(&mut store).unwrap(), 1);
+    /// assert_eq!(BOUNDED.count(&store).unwrap(), 1);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn recount<Store>(&self, store: &mut Store) -> Result<u64, Store::Error>
+    where
+        Store: MutStorage + IterStorage,
+    {
+        let count = store.scan_keys(self.map.prefix())?.count() as u64;
+
+        self.save_count(store, count)?;
+
+        Ok(count)
+    }
+}
+
+/// A double-ended queue of values stored under a prefix.
+///
+/// Values are stored at the prefix followed by their big-endian `u64` index, and the head and tail
+/// indexes under [`system::DEQUE_BOUNDS`], so the counters can't collide with values.
+/// Indexes wrap around, a deque pushed to from the front starts at `u64::MAX`.
+///
+/// ```
+/// use kv_storage_memory::prelude::*;
+///
+/// const JOBS: Deque<String> = deque!("jobs");
+///
+/// let mut store = MemStore::new_in_memory();
+///
+/// JOBS.push_back(&mut store, "b".to_owned()).unwrap();
+/// JOBS.push_front(&mut store, "a".to_owned()).unwrap();
+///
+/// assert_eq!(JOBS.len(&store).unwrap(), 2);
+/// assert_eq!(JOBS.pop_front(&mut store).unwrap().as_deref(), Some("a"));
+/// assert_eq!(JOBS.pop_front(&mut store).unwrap().as_deref(), Some("b"));
+/// assert_eq!(JOBS.pop_front(&mut store).unwrap(), None);
+/// ```
+#[derive(Copy, Clone)]
+pub struct Deque<T> {
+    prefix: &'static [u8],
+    _t: PhantomData<T>,
+}
+
+/// Stack space for composed [`Deque`] keys, longer prefixes fall back to the heap.
+const DEQUE_KEY_BUFFER: usize = 64;
+
+impl<T> Deque<T> {
+    /// # Panics
+    ///
+    /// Panics (at compile time for a constant) if the prefix is reserved, see [`system`].
+    #[must_use]
+    pub const fn new(prefix: &'static [u8]) -> Self {
+        assert!(!system::is_reserved(prefix), "the prefix is reserved");
+
+        Self {
+            prefix,
+            _t: PhantomData,
+        }
+    }
+
+    #[must_use]
+    pub const fn prefix(&self) -> &'static [u8] {
+        self.prefix
+    }
+
+    fn value_key(&self, index: u64) -> CompositeKey<DEQUE_KEY_BUFFER> {
+        compose_key(self.prefix, &index)
+    }
+
+    fn head_key(&self) -> CompositeKey<DEQUE_KEY_BUFFER> {
+        compose_key(system::DEQUE_BOUNDS, &(self.prefix, system::DEQUE_HEAD))
+    }
+
+    fn tail_key(&self) -> CompositeKey<DEQUE_KEY_BUFFER> {
+        compose_key(system::DEQUE_BOUNDS, &(self.prefix, system::DEQUE_TAIL))
+    }
+
+    /// The head (first) and tail (one past the last) indexes.
+    fn bounds<Store: Storage>(&self, store: &Store) -> Result<(u64, u64), Store::Error> {
+        let head = store.may_load(self.head_key().as_ref())?.unwrap_or(0);
+        let tail = store.may_load(self.tail_key().as_ref())?.unwrap_or(0);
+
+        Ok((head, tail))
+    }
+
+    /// The number of values in the deque.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn len<Store: Storage>(&self, store: &Store) -> Result<u64, Store::Error> {
+        let (head, tail) = self.bounds(store)?;
+        Ok(tail.wrapping_sub(head))
+    }
+
+    /// Check if the deque is empty.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn is_empty<Store: Storage>(&self, store: &Store) -> Result<bool, Store::Error> {
+        self.len(store).map(|len| len == 0)
+    }
+
+    /// Add a value after the last one.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn push_back<Store, Item>(&self, store: &mut Store, item: Item) -> Result<(), Store::Error>
+    where
+        T: Serialize,
+        Store: MutStorage,
+        Item: Borrow<T>,
+    {
+        let (_, tail) = self.bounds(store)?;
+
+        store.save_batch(&[
+            (self.value_key(tail).as_ref(), item.borrow()),
+            (self.tail_key().as_ref(), &tail.wrapping_add(1)),
+        ])
+    }
+
+    /// Add a value before the first one.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn push_front<Store, Item>(&self, store: &mut Store, item: Item) -> Result<(), Store::Error>
+    where
+        T: Serialize,
+        Store: MutStorage,
+        Item: Borrow<T>,
+    {
+        let (head, _) = self.bounds(store)?;
+        let head = head.wrapping_sub(1);
+
+        store.save_batch(&[
+            (self.value_key(head).as_ref(), item.borrow()),
+            (self.head_key().as_ref(), &head),
+        ])
+    }
+
+    /// Remove and return the last value, or `None` if the deque is empty.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn pop_back<Store>(&self, store: &mut Store) -> Result<Option<T>, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: MutStorage,
+    {
+        let (head, tail) = self.bounds(store)?;
+
+        if head == tail {
+            return Ok(None);
+        }
+
+        let tail = tail.wrapping_sub(1);
+        let key = self.value_key(tail);
+
+        let value = store.may_load(key.as_ref())?;
+
+        store.save(self.tail_key().as_ref(), &tail)?;
+        store.remove(key.as_ref())?;
+
+        Ok(value)
+    }
+
+    /// Remove and return the first value, or `None` if the deque is empty.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn pop_front<Store>(&self, store: &mut Store) -> Result<Option<T>, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: MutStorage,
+    {
+        let (head, tail) = self.bounds(store)?;
+
+        if head == tail {
+            return Ok(None);
+        }
+
+        let key = self.value_key(head);
+
+        let value = store.may_load(key.as_ref())?;
+
+        store.save(self.head_key().as_ref(), &head.wrapping_add(1))?;
+        store.remove(key.as_ref())?;
+
+        Ok(value)
+    }
+
+    /// The first value, or `None` if the deque is empty.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn front<Store>(&self, store: &Store) -> Result<Option<T>, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: Storage,
+    {
+        let (head, tail) = self.bounds(store)?;
+
+        if head == tail {
+            return Ok(None);
+        }
+
+        store.may_load(self.value_key(head).as_ref())
+    }
+
+    /// The last value, or `None` if the deque is empty.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn back<Store>(&self, store: &Store) -> Result<Option<T>, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: Storage,
+    {
+        let (head, tail) = self.bounds(store)?;
+
+        if head == tail {
+            return Ok(None);
+        }
+
+        store.may_load(self.value_key(tail.wrapping_sub(1)).as_ref())
+    }
+}
+
+/// A set of keys stored under a prefix, each member is a [`Map`] entry with an empty value.
+///
+/// ```
+/// use kv_storage_memory::prelude::*;
+///
+/// const ALLOWED: Set<64, &str> = set!("allowed");
+///
+/// let mut store = MemStore::new_in_memory();
+///
+/// assert!(ALLOWED.insert(&mut store, "alice").unwrap());
+/// assert!(!ALLOWED.insert(&mut store, "alice").unwrap());
+///
+/// assert!(ALLOWED.contains(&store, "alice").unwrap());
+/// assert!(!ALLOWED.contains(&store, "bob").unwrap());
+/// ```
+#[derive(Copy, Clone)]
+pub struct Set<const N: usize, K> {
+    map: Map<N, K, ()>,
+}
+
+impl<const N: usize, K> Set<N, K>
+where
+    K: WriteCompositeKey,
+{
+    #[must_use]
+    pub const fn new(prefix: &'static [u8]) -> Self {
+        Self {
+            map: Map::new(prefix),
+        }
+    }
+
+    /// The raw prefix every member's storage key starts with.
+    #[must_use]
+    pub const fn prefix(&self) -> &'static [u8] {
+        self.map.prefix()
+    }
+
+    /// The full storage key for the given member.
+    pub fn key<Key: EncodeLike<K>>(&self, key: Key) -> CompositeKey<N> {
+        self.map.key(key)
+    }
+
+    /// Add a member, returning whether it was newly added.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn insert<Store, Key>(&self, store: &mut Store, key: Key) -> Result<bool, Store::Error>
+    where
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+    {
+        let key = self.map.key(key);
+
+        if store.has_key(key.as_ref())? {
+            return Ok(false);
+        }
+
+        store.save(key.as_ref(), &())?;
+
+        Ok(true)
+    }
+
+    /// Check if the given key is a member.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn contains<Store, Key>(&self, store: &Store, key: Key) -> Result<bool, Store::Error>
+    where
+        Store: Storage,
+        Key: EncodeLike<K>,
+    {
+        self.map.has_key(store, key)
+    }
+
+    /// Remove a member, returning whether it was present.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn remove<Store, Key>(&self, store: &mut Store, key: Key) -> Result<bool, Store::Error>
+    where
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+    {
+        self.map.remove_returning(store, key).map(Removed::existed)
+    }
+
+    /// Iterate every member in ascending storage key order.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to start the scan.
+    #[allow(clippy::iter_not_returning_iterator)] // starting the scan can fail
+    pub fn iter<'a, Store>(
+        &self,
+        store: &'a Store,
+    ) -> Result<impl Iterator<Item = Result<K::Owned, KeyDecodeError>> + 'a, Store::Error>
+    where
+        K: KeyDeserialize + 'a,
+        Store: IterStorage,
+    {
+        self.map.keys(store)
+    }
+}
+
+/// A secondary index kept up to date by an [`IndexedMap`].
+///
+/// `pk` is the primary key as stored, i.e. the map's storage key without the map's prefix.
+pub trait Index<V> {
+    /// Check that a value can be saved under `pk` before anything is written, the default accepts
+    /// every value.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the value conflicts with the index or the store
+    /// encounters an error.
+    fn check<Store: Storage>(
+        &self,
+        _store: &Store,
+        _pk: &[u8],
+        _value: &V,
+    ) -> Result<(), IndexError<Store::Error>> {
+        Ok(())
+    }
+
+    /// Add the index entries for a value saved under `pk`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    fn save<Store: MutStorage>(
+        &self,
+        store: &mut Store,
+        pk: &[u8],
+        value: &V,
+    ) -> Result<(), Store::Error>;
+
+    /// Remove the index entries for a value that was stored under `pk`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    fn remove<Store: MutStorage>(
+        &self,
+        store: &mut Store,
+        pk: &[u8],
+        old: &V,
+    ) -> Result<(), Store::Error>;
+}
+
+/// The indexes of an [`IndexedMap`], implemented for `()` and tuples of up to three [`Index`]es.
+pub trait IndexList<V> {
+    /// Check the value against every index, see [`Index::check`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the value conflicts with an index or the store
+    /// encounters an error.
+    fn check_all<Store: Storage>(
+        &self,
+        store: &Store,
+        pk: &[u8],
+        value: &V,
+    ) -> Result<(), IndexError<Store::Error>>;
+
+    /// Add the entries of every index, see [`Index::save`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    fn save_all<Store: MutStorage>(
+        &self,
+        store: &mut Store,
+        pk: &[u8],
+        value: &V,
+    ) -> Result<(), Store::Error>;
+
+    /// Remove the entries of every index, see [`Index::remove`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    fn remove_all<Store: MutStorage>(
+        &self,
+        store: &mut Store,
+        pk: &[u8],
+        old: &V,
+    ) -> Result<(), Store::Error>;
+}
+
+macro_rules! impl_index_list {
+    ($($index:ident: $n:tt),*) => {
+        impl<V, $($index: Index<V>),*> IndexList<V> for ($($index,)*) {
+            #[allow(unused_variables)]
+            fn check_all<Store: Storage>(
+                &self,
+                store: &Store,
+                pk: &[u8],
+                value: &V,
+            ) -> Result<(), IndexError<Store::Error>> {
+                $(self.$n.check(store, pk, value)?;)*
+                Ok(())
+            }
+
+            #[allow(unused_variables)]
+            fn save_all<Store: MutStorage>(
+                &self,
+                store: &mut Store,
+                pk: &[u8],
+                value: &V,
+            ) -> Result<(), Store::Error> {
+                $(self.$n.save(store, pk, value)?;)*
+                Ok(())
+            }
+
+            #[allow(unused_variables)]
+            fn remove_all<Store: MutStorage>(
+                &self,
+                store: &mut Store,
+                pk: &[u8],
+                old: &V,
+            ) -> Result<(), Store::Error> {
+                $(self.$n.remove(store, pk, old)?;)*
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_index_list!();
+impl_index_list!(I1: 0);
+impl_index_list!(I1: 0, I2: 1);
+impl_index_list!(I1: 0, I2: 1, I3: 2);
+
+/// An index from a function of the value to every primary key holding a value it maps to.
+///
+/// Entries are stored with empty values at the index prefix, followed by the length-prefixed
+/// index key and the primary key. `N` is how many bytes of the index key are composed on the
+/// stack.
+pub struct MultiIndex<const N: usize, IK, K, V> {
+    prefix: &'static [u8],
+    pk_prefix: &'static [u8],
+    index_fn: fn(&V) -> IK,
+    _k: PhantomData<K>,
+}
+
+impl<const N: usize, IK, K, V> MultiIndex<N, IK, K, V>
+where
+    IK: WriteCompositeKey,
+{
+    /// An index stored under `prefix`, over the map stored under `pk_prefix`.
+    ///
+    /// # Panics
+    ///
+    /// Panics (at compile time for a constant) if either prefix is reserved, see [`system`].
+    #[must_use]
+    pub const fn new(
+        index_fn: fn(&V) -> IK,
+        pk_prefix: &'static [u8],
+        prefix: &'static [u8],
+    ) -> Self {
+        assert!(!system::is_reserved(prefix), "the prefix is reserved");
+        assert!(!system::is_reserved(pk_prefix), "the prefix is reserved");
+
+        Self {
+            prefix,
+            pk_prefix,
+            index_fn,
+            _k: PhantomData,
+        }
+    }
+
+    /// Like [`MultiIndex::new`], returning an error instead of panicking.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either prefix is reserved, see [`system`].
+    pub const fn try_new(
+        index_fn: fn(&V) -> IK,
+        pk_prefix: &'static [u8],
+        prefix: &'static [u8],
+    ) -> Result<Self, system::ReservedKey> {
+        if let Err(err) = system::check(pk_prefix) {
+            return Err(err);
+        }
+
+        match system::check(prefix) {
+            Ok(()) => Ok(Self::new(index_fn, pk_prefix, prefix)),
+            Err(err) => Err(err),
+        }
+    }
+
+    #[must_use]
+    pub const fn prefix(&self) -> &'static [u8] {
+        self.prefix
+    }
+
+    /// The prefix shared by every entry for the given index key.
+    fn index_prefix(&self, index_key: &IK) -> Vec<u8> {
+        let encoded = compose_key::<N>(&[], index_key);
+        let encoded = encoded.as_ref();
+
+        let len = u16::try_from(encoded.len()).expect("index keys are at most 65535 bytes");
+
+        [self.prefix, &len.to_be_bytes(), encoded].concat()
+    }
+
+    fn entry_key(&self, value: &V, pk: &[u8]) -> Vec<u8> {
+        let mut key = self.index_prefix(&(self.index_fn)(value));
+        key.extend_from_slice(pk);
+        key
+    }
+
+    /// Iterate the primary keys of every value mapping to the given index key, in ascending
+    /// primary key order.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to start the scan.
+    pub fn keys<'a, Store>(
+        &self,
+        store: &'a Store,
+        index_key: &IK,
+    ) -> Result<impl Iterator<Item = Result<K::Owned, KeyDecodeError>> + 'a, Store::Error>
+    where
+        K: KeyDeserialize + 'a,
+        Store: IterStorage,
+    {
+        let prefix = self.index_prefix(index_key);
+        let prefix_len = prefix.len();
+
+        let keys = store
+            .scan_keys(&prefix)?
+            .map(move |key| K::from_key_bytes(&key[prefix_len..]));
+
+        Ok(keys)
+    }
+
+    /// Iterate every entry whose value maps to the given index key, in ascending primary key
+    /// order.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to start the scan.
+    pub fn items<'a, Store>(
+        &self,
+        store: &'a Store,
+        index_key: &IK,
+    ) -> Result<impl Iterator<Item = RangeItem<K, V, Store::Error>> + 'a, Store::Error>
+    where
+        K: KeyDeserialize + 'a,
+        V: DeserializeOwned + 'a,
+        Store: IterStorage,
+    {
+        let prefix = self.index_prefix(index_key);
+        let prefix_len = prefix.len();
+        let pk_prefix = self.pk_prefix;
+
+        let items = store.scan_keys(&prefix)?.filter_map(move |key| {
+            let pk = &key[prefix_len..];
+
+            let value = match store.may_load::<V>(&[pk_prefix, pk].concat()) {
+                Ok(value) => value?,
+                Err(err) => return Some(Err(RangeError::Store(err))),
+            };
+
+            Some(
+                K::from_key_bytes(pk)
+                    .map(|key| (key, value))
+                    .map_err(RangeError::Key),
+            )
+        });
+
+        Ok(items)
+    }
+}
+
+impl<const N: usize, IK, K, V> Index<V> for MultiIndex<N, IK, K, V>
+where
+    IK: WriteCompositeKey,
+{
+    fn save<Store: MutStorage>(
+        &self,
+        store: &mut Store,
+        pk: &[u8],
+        value: &V,
+    ) -> Result<(), Store::Error> {
+        store.save(&self.entry_key(value, pk), &())
+    }
+
+    fn remove<Store: MutStorage>(
+        &self,
+        store: &mut Store,
+        pk: &[u8],
+        old: &V,
+    ) -> Result<(), Store::Error> {
+        store.remove(&self.entry_key(old, pk))
+    }
+}
+
+/// An index from a function of the value to the single primary key holding a value it maps to.
+///
+/// Entries are stored at the index prefix followed by the index key, holding the primary key.
+/// Saving a value whose index key is held by another entry fails with
+/// [`IndexError::Duplicate`]. `N` is how many bytes of entry keys are composed on the stack.
+///
+/// ```
+/// use kv_storage::{IndexError, IndexedMap, UniqueIndex};
+/// use kv_storage_memory::prelude::*;
+///
+/// const USERS: Map<16, u64, String> = map!("users");
+///
+/// const BY_EMAIL: UniqueIndex<64, String, String> =
+///     UniqueIndex::new(Clone::clone, USERS.prefix(), b"users_by_email");
+///
+/// const INDEXED: IndexedMap<16, u64, String, (UniqueIndex<64, String, String>,)> =
+///     IndexedMap::new(USERS, (BY_EMAIL,));
+///
+/// let mut store = MemStore::new_in_memory();
+///
+/// INDEXED.save(&mut store, 1, "alice@example.com".to_owned()).unwrap();
+///
+/// let taken = INDEXED.save(&mut store, 2, "alice@example.com".to_owned());
+/// assert!(matches!(taken, Err(IndexError::Duplicate { .. })));
+///
+/// let email = "alice@example.com".to_owned();
+/// assert_eq!(BY_EMAIL.load_by_unique(&store, &email).unwrap(), Some(email));
+/// ```
+pub struct UniqueIndex<const N: usize, IK, V> {
+    prefix: &'static [u8],
+    pk_prefix: &'static [u8],
+    index_fn: fn(&V) -> IK,
+}
+
+impl<const N: usize, IK, V> UniqueIndex<N, IK, V>
+where
+    IK: WriteCompositeKey,
+{
+    /// An index stored under `prefix`, over the map stored under `pk_prefix`.
+    ///
+    /// # Panics
+    ///
+    /// Panics (at compile time for a constant) if either prefix is reserved, see [`system`].
+    #[must_use]
+    pub const fn new(
+        index_fn: fn(&V) -> IK,
+        pk_prefix: &'static [u8],
+        prefix: &'static [u8],
+    ) -> Self {
+        assert!(!system::is_reserved(prefix), "the prefix is reserved");
+        assert!(!system::is_reserved(pk_prefix), "the prefix is reserved");
+
+        Self {
+            prefix,
+            pk_prefix,
+            index_fn,
+        }
+    }
+
+    /// Like [`UniqueIndex::new`], returning an error instead of panicking.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either prefix is reserved, see [`system`].
+    pub const fn try_new(
+        index_fn: fn(&V) -> IK,
+        pk_prefix: &'static [u8],
+        prefix: &'static [u8],
+    ) -> Result<Self, system::ReservedKey> {
+        if let Err(err) = system::check(pk_prefix) {
+            return Err(err);
+        }
+
+        match system::check(prefix) {
+            Ok(()) => Ok(Self::new(index_fn, pk_prefix, prefix)),
+            Err(err) => Err(err),
+        }
+    }
+
+    #[must_use]
+    pub const fn prefix(&self) -> &'static [u8] {
+        self.prefix
+    }
+
+    fn entry_key(&self, index_key: &IK) -> CompositeKey<N> {
+        compose_key(self.prefix, index_key)
+    }
+
+    /// The primary key, as stored, of the entry holding the given index key.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn load_pk<Store: Storage>(
+        &self,
+        store: &Store,
+        index_key: &IK,
+    ) -> Result<Option<Vec<u8>>, Store::Error> {
+        store.may_load(self.entry_key(index_key).as_ref())
+    }
+
+    /// The value of the entry holding the given index key.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn load_by_unique<Store>(
+        &self,
+        store: &Store,
+        index_key: &IK,
+    ) -> Result<Option<V>, Store::Error>
+    where
+        V: DeserializeOwned,
+        Store: Storage,
+    {
+        let Some(pk) = self.load_pk(store, index_key)? else {
+            return Ok(None);
+        };
+
+        store.may_load(&[self.pk_prefix, &pk].concat())
+    }
+}
+
+impl<const N: usize, IK, V> Index<V> for UniqueIndex<N, IK, V>
+where
+    IK: WriteCompositeKey,
+{
+    fn check<Store: Storage>(
+        &self,
+        store: &Store,
+        pk: &[u8],
+        value: &V,
+    ) -> Result<(), IndexError<Store::Error>> {
+        let key = self.entry_key(&(self.index_fn)(value));
+
+        match store.may_load::<Vec<u8>>(key.as_ref())? {
+            Some(existing_pk) if existing_pk != pk => Err(IndexError::Duplicate {
+                index_key: key.as_ref()[self.prefix.len()..].to_vec(),
+                existing_pk,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn save<Store: MutStorage>(
+        &self,
+        store: &mut Store,
+        pk: &[u8],
+        value: &V,
+    ) -> Result<(), Store::Error> {
+        store.save(self.entry_key(&(self.index_fn)(value)).as_ref(), &pk)
+    }
+
+    fn remove<Store: MutStorage>(
+        &self,
+        store: &mut Store,
+        _pk: &[u8],
+        old: &V,
+    ) -> Result<(), Store::Error> {
+        store.remove(self.entry_key(&(self.index_fn)(old)).as_ref())
+    }
+}
+
+/// Returned by [`IndexedMap::save`] when a value conflicts with an index, or when the store fails.
+///
+/// ```
+/// use kv_storage::{IndexError, IndexedMap, UniqueIndex};
+/// use kv_storage_memory::prelude::*;
+///
+/// const USERS: Map<64, u64, String> = map!("users");
+///
+/// const BY_NAME: UniqueIndex<64, String, String> =
+///     UniqueIndex::new(|name| name.clone(), USERS.prefix(), b"users_by_name");
+///
+/// const INDEXED: IndexedMap<64, u64, String, (UniqueIndex<64, String, String>,)> =
+///     IndexedMap::new(USERS, (BY_NAME,));
+///
+/// let mut store = MemStore::new_in_memory();
+/// INDEXED.save(&mut store, 1, "alice".to_owned()).unwrap();
+///
+/// assert!(matches!(
+///     INDEXED.save(&mut store, 2, "alice".to_owned()),
+///     Err(IndexError::Duplicate { .. })
+/// ));
+/// ```
+#[derive(Debug, thiserror::Error)]
+pub enum IndexError<E> {
+    /// A [`UniqueIndex`] key is already held by the entry at `existing_pk`.
+    #[error("index key {} is already taken", KeyDisplay::new(index_key))]
+    Duplicate {
+        index_key: Vec<u8>,
+        existing_pk: Vec<u8>,
+    },
+    #[error(transparent)]
+    Store(#[from] E),
+}
+
+/// A [`Map`] whose saves and removes also maintain the secondary indexes in `I`, see
+/// [`IndexList`].
+///
+/// Every index checks a value before anything is written, then saving over an existing entry
+/// removes the index entries for the old value, so changing an indexed field leaves no stale
+/// entries behind.
+///
+/// Writes are ordered so that stopping after any of them, e.g. on a crash, never leaves an index
+/// entry pointing at a missing entry or at one whose value it doesn't index: a value's index
+/// entries are removed before it's overwritten or removed, and added only once it's saved. An
+/// entry can be left without some of its index entries, saving it again restores them.
+///
+/// ```
+/// use kv_storage::{IndexedMap, MultiIndex};
+/// use kv_storage_memory::prelude::*;
+///
+/// const ORDERS: Map<64, u64, (String, u64)> = map!("orders");
+///
+/// const BY_OWNER: MultiIndex<64, String, u64, (String, u64)> =
+///     MultiIndex::new(|(owner, _)| owner.clone(), ORDERS.prefix(), b"orders_by_owner");
+///
+/// const INDEXED: IndexedMap<64, u64, (String, u64), (MultiIndex<64, String, u64, (String, u64)>,)> =
+///     IndexedMap::new(ORDERS, (BY_OWNER,));
+///
+/// let mut store = MemStore::new_in_memory();
+///
+/// INDEXED.save(&mut store, 1, ("alice".to_owned(), 10)).unwrap();
+/// INDEXED.save(&mut store, 2, ("bob".to_owned(), 20)).unwrap();
+/// INDEXED.save(&mut store, 3, ("alice".to_owned(), 30)).unwrap();
+///
+/// let alice: Vec<u64> = BY_OWNER
+///     .keys(&store, &"alice".to_owned())
+///     .unwrap()
+///     .map(Result::unwrap)
+///     .collect();
+/// assert_eq!(alice, [1, 3]);
+/// ```
+pub struct IndexedMap<const N: usize, K, V, I> {
+    map: Map<N, K, V>,
+    indexes: I,
+}
+
+impl<const N: usize, K, V, I> IndexedMap<N, K, V, I>
+where
+    K: WriteCompositeKey,
+    I: IndexList<V>,
+{
+    #[must_use]
+    pub const fn new(map: Map<N, K, V>, indexes: I) -> Self {
+        Self { map, indexes }
+    }
+
+    /// The underlying map, writing through it bypasses the indexes.
+    #[must_use]
+    pub const fn map(&self) -> &Map<N, K, V> {
+        &self.map
+    }
+
+    #[must_use]
+    pub const fn indexes(&self) -> &I {
+        &self.indexes
+    }
+
+    /// Save the value for the given key, replacing the index entries of any previous value.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the value conflicts with an index, nothing is
+    /// written then, or if the store encounters an error.
+    pub fn save<Store, Key, Item>(
+        &self,
+        store: &mut Store,
+        key: Key,
+        item: Item,
+    ) -> Result<(), IndexError<Store::Error>>
+    where
+        V: Serialize + DeserializeOwned,
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+        Item: Borrow<V>,
+    {
+        let key = self.map.key(key);
+        let pk = &key.as_ref()[self.map.prefix().len()..];
+
+        self.indexes.check_all(store, pk, item.borrow())?;
+
+        if let Some(old) = store.may_load::<V>(key.as_ref())? {
+            self.indexes.remove_all(store, pk, &old)?;
+        }
+
+        store.save(key.as_ref(), item.borrow())?;
+        self.indexes.save_all(store, pk, item.borrow())?;
+
+        Ok(())
+    }
+
+    /// Load the value for the given key if it exists, otherwise `None`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load<Store, Key>(&self, store: &Store, key: Key) -> Result<Option<V>, Store::Error>
+    where
+        V: DeserializeOwned,
+        Store: Storage,
+        Key: EncodeLike<K>,
+    {
+        self.map.may_load(store, key)
+    }
+
+    /// Remove the value for the given key along with its index entries.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn remove<Store, Key>(&self, store: &mut Store, key: Key) -> Result<Removed, Store::Error>
+    where
+        V: DeserializeOwned,
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+    {
+        let key = self.map.key(key);
+        let pk = &key.as_ref()[self.map.prefix().len()..];
+
+        let Some(old) = store.may_load::<V>(key.as_ref())? else {
+            return Ok(Removed::DidNotExist);
+        };
+
+        self.indexes.remove_all(store, pk, &old)?;
+        store.remove(key.as_ref())?;
+
+        Ok(Removed::Existed)
+    }
+}
+
+/// A [`Map`] that also records every save and remove in a changelog keyed by key and height, so
+/// values can be loaded as they were at a past height.
+///
+/// Changelog entries are stored under [`system::CHANGELOG`], followed by the length-prefixed
+/// changelog name and key and the big-endian height, holding the value saved at that height or
+/// `None` for a removal. Only the last change at a height is kept.
+///
+/// ```
+/// use kv_storage::SnapshotMap;
+/// use kv_storage_memory::prelude::*;
+///
+/// const BALANCES: SnapshotMap<64, &str, u128> =
+///     SnapshotMap::new(map!("balances"), b"balances_changelog");
+///
+/// let mut store = MemStore::new_in_memory();
+///
+/// BALANCES.save(&mut store, "alice", 100, 10).unwrap();
+/// BALANCES.save(&mut store, "alice", 50, 20).unwrap();
+///
+/// assert_eq!(BALANCES.may_load_at_height(&store, "alice", 9).unwrap(), None);
+/// assert_eq!(BALANCES.may_load_at_height(&store, "alice", 15).unwrap(), Some(100));
+/// assert_eq!(BALANCES.may_load(&store, "alice").unwrap(), Some(50));
+/// ```
+pub struct SnapshotMap<const N: usize, K, V> {
+    map: Map<N, K, V>,
+    changelog: &'static [u8],
+}
+
+impl<const N: usize, K, V> SnapshotMap<N, K, V>
+where
+    K: WriteCompositeKey,
+{
+    #[must_use]
+    pub const fn new(map: Map<N, K, V>, changelog: &'static [u8]) -> Self {
+        Self { map, changelog }
+    }
+
+    /// The underlying map, writing through it bypasses the changelog.
+    #[must_use]
+    pub const fn map(&self) -> &Map<N, K, V> {
+        &self.map
+    }
+
+    /// The prefix shared by every changelog entry for the given map storage key.
+    fn changelog_prefix(&self, key: &CompositeKey<N>) -> Vec<u8> {
+        let encoded = &key.as_ref()[self.map.prefix().len()..];
+
+        let len = u16::try_from(encoded.len()).expect("snapshot keys are at most 65535 bytes");
+
+        let name_len =
+            u16::try_from(self.changelog.len()).expect("changelog names are at most 65535 bytes");
+
+        [
+            system::CHANGELOG,
+            &name_len.to_be_bytes(),
+            self.changelog,
+            &len.to_be_bytes(),
+            encoded,
+        ]
+        .concat()
+    }
+
+    fn changelog_key(prefix: &[u8], height: u64) -> Vec<u8> {
+        [prefix, &height.to_be_bytes()].concat()
+    }
+
+    /// Save the value for the given key, recording it in the changelog at `height`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn save<Store, Key, Item>(
+        &self,
+        store: &mut Store,
+        key: Key,
+        item: Item,
+        height: u64,
+    ) -> Result<(), Store::Error>
+    where
+        V: Serialize,
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+        Item: Borrow<V>,
+    {
+        let item = item.borrow();
+        let key = self.map.key(key);
+        let changelog = Self::changelog_key(&self.changelog_prefix(&key), height);
+
+        store.save_batch(&[(key.as_ref(), item), (&changelog, &Some(item))])
+    }
+
+    /// Remove the value for the given key, recording the removal in the changelog at `height`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn remove<Store, Key>(
+        &self,
+        store: &mut Store,
+        key: Key,
+        height: u64,
+    ) -> Result<Removed, Store::Error>
+    where
+        V: Serialize,
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+    {
+        let key = self.map.key(key);
+        let changelog = Self::changelog_key(&self.changelog_prefix(&key), height);
+
+        store.save(&changelog, &None::<&V>)?;
+
+        store.remove_returning(key.as_ref())
+    }
+
+    /// Load the current value for the given key if it exists, otherwise `None`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load<Store, Key>(&self, store: &Store, key: Key) -> Result<Option<V>, Store::Error>
+    where
+        V: DeserializeOwned,
+        Store: Storage,
+        Key: EncodeLike<K>,
+    {
+        self.map.may_load(store, key)
+    }
+
+    /// Load the value for the given key as of `height`, i.e. as left by the newest change at or
+    /// below it, or `None` if it had never been saved or was removed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load_at_height<Store, Key>(
+        &self,
+        store: &Store,
+        key: Key,
+        height: u64,
+    ) -> Result<Option<V>, Store::Error>
+    where
+        V: DeserializeOwned,
+        Store: IterStorage,
+        Key: EncodeLike<K>,
+    {
+        let prefix = self.changelog_prefix(&self.map.key(key));
+        let max = Self::changelog_key(&prefix, height);
+
+        let newest = store
+            .range::<Option<V>>(
+                Bound::Inclusive(&prefix),
+                Bound::Inclusive(&max),
+                Order::Descending,
+            )?
+            .next()
+            .transpose()?;
+
+        Ok(newest.and_then(|(_, value)| value))
+    }
+}
+
+/// A secret used to obfuscate the logical part of map keys, provided at runtime and never stored.
+#[cfg(feature = "obfuscation")]
+#[derive(Clone)]
+pub struct KeyObfuscation {
+    secret: [u8; 16],
+}
+
+#[cfg(feature = "obfuscation")]
+impl KeyObfuscation {
+    /// The length of an obfuscated key component.
+    pub const DIGEST_LEN: usize = 16;
+
+    #[must_use]
+    pub const fn new(secret: [u8; 16]) -> Self {
+        Self { secret }
+    }
+
+    /// The keyed SipHash-1-3 digest of the encoded key.
+    pub fn digest(&self, key: &impl WriteCompositeKey) -> [u8; Self::DIGEST_LEN] {
+        use siphasher::sip128::{Hasher128, SipHasher13};
+
+        struct HashWriter(SipHasher13);
+
+        impl WriteKeyPart for HashWriter {
+            fn write_key_part(&mut self, part: &[u8]) {
+                std::hash::Hasher::write(&mut self.0, part);
+            }
+        }
+
+        let mut writer = HashWriter(SipHasher13::new_with_key(&self.secret));
+
+        key.write_into(&mut writer);
+
+        writer.0.finish128().as_bytes()
+    }
+}
+
+#[cfg(feature = "obfuscation")]
+impl std::fmt::Debug for KeyObfuscation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyObfuscation").finish_non_exhaustive()
+    }
+}
+
+/// Whether an [`ObfuscatedMap`] stores each entry's encoded key inside its value, so scans can
+/// recover the typed keys, see [`ObfuscatedMap::entries`].
+///
+/// Embedded keys are as readable in the value bytes as any other part of the value.
+#[cfg(feature = "obfuscation")]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum EmbedOriginalKey {
+    #[default]
+    No,
+    Yes,
+}
+
+/// How values are stored with [`EmbedOriginalKey::Yes`].
+#[cfg(feature = "obfuscation")]
+#[derive(Serialize)]
+struct EmbeddedRef<'a, V> {
+    key: &'a [u8],
+    value: &'a V,
+}
+
+#[cfg(feature = "obfuscation")]
+#[derive(Deserialize)]
+struct Embedded<V> {
+    key: Vec<u8>,
+    value: V,
+}
+
+/// A `Map` whose keys are stored as the prefix followed by a keyed digest of the logical key.
+///
+/// Backend key bytes reveal nothing about the logical key, point lookups work as usual. Scans
+/// only recover keys embedded in the values, see [`EmbedOriginalKey`].
+#[cfg(feature = "obfuscation")]
+pub struct ObfuscatedMap<const N: usize, K, V> {
+    map: Map<N, K, V>,
+    obfuscation: KeyObfuscation,
+    embed: EmbedOriginalKey,
+}
+
+#[cfg(feature = "obfuscation")]
+impl<const N: usize, K, V> ObfuscatedMap<N, K, V>
+where
+    K: WriteCompositeKey,
+{
+    #[must_use]
+    pub const fn new(map: Map<N, K, V>, obfuscation: KeyObfuscation) -> Self {
+        Self {
+            map,
+            obfuscation,
+            embed: EmbedOriginalKey::No,
+        }
+    }
+
+    /// Store each entry's encoded key inside its value, or not, which is the default.
+    ///
+    /// It changes how values are stored, so entries saved with one setting don't load with the
+    /// other.
+    #[must_use]
+    pub const fn embed_original_key(mut self, embed: EmbedOriginalKey) -> Self {
+        self.embed = embed;
+        self
+    }
+
+    /// The full storage key for the given key, i.e. the prefix followed by the digest.
+    #[allow(clippy::needless_pass_by_value)] // keys are usually small and passed by value
+    pub fn key<Key: EncodeLike<K>>(&self, key: Key) -> CompositeKey<N> {
+        self.digest_key(&key)
+    }
+
+    fn digest_key(&self, key: &impl WriteCompositeKey) -> CompositeKey<N> {
+        let digest = self.obfuscation.digest(key);
+        compose_key::<N>(self.map.prefix(), &digest.as_slice())
+    }
+
+    /// Save the value for the given key.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn save<Store, Key, Item>(
+        &self,
+        store: &mut Store,
+        key: Key,
+        item: Item,
+    ) -> Result<(), Store::Error>
+    where
+        V: Serialize,
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+        Item: Borrow<V>,
+    {
+        let digest = self.digest_key(&key);
+
+        match self.embed {
+            EmbedOriginalKey::No => store.save(digest.as_ref(), item.borrow()),
+            EmbedOriginalKey::Yes => {
+                let original = self.map.key(key);
+                let embedded = EmbeddedRef {
+                    key: &original.as_ref()[self.map.prefix().len()..],
+                    value: item.borrow(),
+                };
+
+                store.save(digest.as_ref(), &embedded)
+            }
+        }
+    }
+
+    /// Load the value for the given key if it exists, otherwise `None`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load<Store, Key>(&self, store: &Store, key: Key) -> Result<Option<V>, Store::Error>
+    where
+        V: DeserializeOwned,
+        Store: Storage,
+        Key: EncodeLike<K>,
+    {
+        let key = self.key(key);
+
+        match self.embed {
+            EmbedOriginalKey::No => store.may_load(key.as_ref()),
+            EmbedOriginalKey::Yes => Ok(store
+                .may_load::<Embedded<V>>(key.as_ref())?
+                .map(|embedded| embedded.value)),
+        }
+    }
+
+    /// Iterate every entry with its original key, in the order of their digests.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless the map embeds original keys, see [`ObfuscatedMap::embed_original_key`],
+    /// the digests can't be turned back into keys.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to start the scan.
+    pub fn entries<'a, Store>(
+        &self,
+        store: &'a Store,
+    ) -> Result<impl Iterator<Item = RangeItem<K, V, Store::Error>> + 'a, Store::Error>
+    where
+        K: KeyDeserialize + 'a,
+        V: DeserializeOwned + 'a,
+        Store: IterStorage,
+    {
+        assert_eq!(
+            self.embed,
+            EmbedOriginalKey::Yes,
+            "scanning an obfuscated map needs its original keys embedded"
+        );
+
+        let entries = store.scan::<Embedded<V>>(self.map.prefix())?.map(|entry| {
+            let (_, embedded) = entry.map_err(RangeError::Store)?;
+            let key = K::from_key_bytes(&embedded.key)?;
+
+            Ok((key, embedded.value))
+        });
+
+        Ok(entries)
+    }
+
+    /// Check if a key exists.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn has_key<Store, Key>(&self, store: &Store, key: Key) -> Result<bool, Store::Error>
+    where
+        Store: Storage,
+        Key: EncodeLike<K>,
+    {
+        store.has_key(self.key(key).as_ref())
+    }
+
+    /// Remove any value stored at the given key.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn remove<Store, Key>(&self, store: &mut Store, key: Key) -> Result<(), Store::Error>
+    where
+        Store: MutStorage,
+        Key: EncodeLike<K>,
+    {
+        store.remove(self.key(key).as_ref())
+    }
+}
+
+/// The access an operation on a [`ScopedStore`] needs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    Read,
+    Write,
+}
+
+/// Returned by a [`ScopedStore`] for a key outside its permissions, or when the wrapped store
+/// fails.
+///
+/// ```
+/// use kv_storage::{Permission, ScopedError, ScopedStore};
+/// use kv_storage_memory::prelude::*;
+///
+/// const SECRET: Item<u64> = Item::new(b"admin/secret");
+///
+/// let store = ScopedStore::new(MemStore::new_in_memory()).readable(b"public/");
+///
+/// let Err(ScopedError::AccessDenied { key, needed }) = SECRET.may_load(&store) else {
+///     panic!("the key is outside the scope");
+/// };
+/// assert_eq!(key, SECRET.key());
+/// assert_eq!(needed, Permission::Read);
+/// ```
+#[derive(Debug, thiserror::Error)]
+pub enum ScopedError<E> {
+    #[error("access denied: {needed:?} on {}", KeyDisplay::new(key))]
+    AccessDenied { key: Vec<u8>, needed: Permission },
+    #[error(transparent)]
+    Store(E),
+}
+
+/// A store restricted to the keys under a set of prefixes, checked before any repo access.
+///
+/// Keys are not rewritten. Each key is governed by the longest registered prefix it starts
+/// with, so a read-only prefix can be carved out of a writable one and vice versa. Registering
+/// the same prefix again replaces its permission.
+///
+/// ```
+/// use kv_storage::{Permission, ScopedError, ScopedStore};
+/// use kv_storage_memory::prelude::*;
+///
+/// const SHARED: Item<u64> = Item::new(b"shared/height");
+/// const OWN: Item<u64> = Item::new(b"plugin_a/counter");
+///
+/// let mut store = ScopedStore::new(MemStore::new_in_memory())
+///     .readable(b"shared/")
+///     .writable(b"plugin_a/");
+///
+/// OWN.save(&mut store, 1).unwrap();
+/// assert_eq!(SHARED.may_load(&store).unwrap(), None);
+///
+/// assert!(matches!(
+///     SHARED.save(&mut store, 1),
+///     Err(ScopedError::AccessDenied { needed: Permission::Write, .. })
+/// ));
+/// ```
+pub struct ScopedStore<S> {
+    store: S,
+    rules: Vec<(Box<[u8]>, Permission)>,
+}
+
+impl<S> ScopedStore<S> {
+    /// Wrap a store, with no keys accessible until prefixes are registered.
+    pub const fn new(store: S) -> Self {
+        Self {
+            store,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Allow reading keys under `prefix`.
+    #[must_use]
+    pub fn readable(self, prefix: &[u8]) -> Self {
+        self.with_rule(prefix, Permission::Read)
+    }
+
+    /// Allow reading and writing keys under `prefix`.
+    #[must_use]
+    pub fn writable(self, prefix: &[u8]) -> Self {
+        self.with_rule(prefix, Permission::Write)
+    }
+
+    fn with_rule(mut self, prefix: &[u8], permission: Permission) -> Self {
+        match self.rules.iter_mut().find(|(p, _)| **p == *prefix) {
+            Some(rule) => rule.1 = permission,
+            None => self.rules.push((prefix.into(), permission)),
+        }
+
+        self
+    }
+
+    /// The permission granted on `key`, if any.
+    #[must_use]
+    pub fn permission(&self, key: &[u8]) -> Option<Permission> {
+        self.rules
+            .iter()
+            .filter(|(prefix, _)| key.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, permission)| *permission)
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.store
+    }
+
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+
+    fn check<E>(&self, key: &[u8], needed: Permission) -> Result<(), ScopedError<E>> {
+        match self.permission(key) {
+            Some(granted) if granted >= needed => Ok(()),
+            _ => Err(ScopedError::AccessDenied {
+                key: key.to_vec(),
+                needed,
+            }),
+        }
+    }
+}
+
+impl<S: Fallible> Fallible for ScopedStore<S> {
+    type Error = ScopedError<S::Error>;
+}
+
+impl<S: Storage> Storage for ScopedStore<S> {
+    type Serde = S::Serde;
+    type Repo = S::Repo;
+
+    fn may_load<T: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<T>, Self::Error> {
+        self.check(key, Permission::Read)?;
+        self.store.may_load(key).map_err(ScopedError::Store)
+    }
+
+    fn may_load_if<T, P>(&self, key: &[u8], pred: P) -> Result<Option<T>, Self::Error>
+    where
+        T: DeserializeOwned,
+        P: FnOnce(usize) -> bool,
+    {
+        self.check(key, Permission::Read)?;
+        self.store
+            .may_load_if(key, pred)
+            .map_err(ScopedError::Store)
+    }
+
+    fn may_load_many<T: DeserializeOwned>(
+        &self,
+        keys: &[&[u8]],
+    ) -> Result<Vec<Option<T>>, Self::Error> {
+        for key in keys {
+            self.check(key, Permission::Read)?;
+        }
+
+        self.store.may_load_many(keys).map_err(ScopedError::Store)
+    }
+
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        self.check(key, Permission::Read)?;
+        self.store.has_key(key).map_err(ScopedError::Store)
+    }
+}
+
+impl<S: MutStorage> MutStorage for ScopedStore<S> {
+    fn save<T>(&mut self, key: &[u8], item: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize,
+    {
+        self.check(key, Permission::Write)?;
+        self.store.save(key, item).map_err(ScopedError::Store)
+    }
+
+    fn save_with<T>(
+        &mut self,
+        key: &[u8],
+        item: &T,
+        durability: Durability,
+    ) -> Result<(), Self::Error>
+    where
+        T: Serialize,
+    {
+        self.check(key, Permission::Write)?;
+        self.store
+            .save_with(key, item, durability)
+            .map_err(ScopedError::Store)
+    }
+
+    fn save_batch(&mut self, items: &[(&[u8], &dyn ErasedSerialize)]) -> Result<(), Self::Error> {
+        for (key, _) in items {
+            self.check(key, Permission::Write)?;
+        }
+
+        self.store.save_batch(items).map_err(ScopedError::Store)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.check(key, Permission::Write)?;
+        self.store.remove(key).map_err(ScopedError::Store)
+    }
+
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        self.check(key, Permission::Write)?;
+        self.store.remove_returning(key).map_err(ScopedError::Store)
+    }
+}
+
+enum CompositeKeyBuffer<const N: usize> {
+    Stack { buffer: [u8; N], len: usize },
+    Heap(Box<[u8]>),
+}
+
+impl<const N: usize> AsRef<[u8]> for CompositeKeyBuffer<N> {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            CompositeKeyBuffer::Stack { buffer, len } => &buffer[..*len],
+            CompositeKeyBuffer::Heap(v) => v.as_ref(),
+        }
+    }
+}
+
+impl<const N: usize> AsMut<[u8]> for CompositeKeyBuffer<N> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        match self {
+            CompositeKeyBuffer::Stack { buffer, len } => &mut buffer[..*len],
+            CompositeKeyBuffer::Heap(v) => v.as_mut(),
+        }
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for CompositeKey<N> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+/// A composed storage key, kept on the stack when it fits in `N` bytes.
+pub struct CompositeKey<const N: usize> {
+    buffer: CompositeKeyBuffer<N>,
+    written: usize,
+}
+
+impl<const N: usize> CompositeKey<N> {
+    fn new(len: usize) -> Self {
+        let buffer = if len > N {
+            CompositeKeyBuffer::Heap(vec![0; len].into_boxed_slice())
+        } else {
+            CompositeKeyBuffer::Stack {
+                buffer: [0; N],
+                len,
+            }
+        };
+
+        Self { buffer, written: 0 }
+    }
+}
+
+impl<const N: usize> WriteKeyPart for CompositeKey<N> {
+    fn write_key_part(&mut self, part: &[u8]) {
+        let end = self.written + part.len();
+
+        let section = &mut self.buffer.as_mut()[self.written..end];
+
+        section.copy_from_slice(part);
+
+        self.written += part.len();
+    }
+}
+
+fn compose_key<const N: usize>(prefix: &[u8], keys: &impl WriteCompositeKey) -> CompositeKey<N> {
+    let total_len = prefix.len() + keys.total_len();
+
+    let mut composite_key = CompositeKey::new(total_len);
+
+    composite_key.write_key_part(prefix);
+
+    keys.write_into(&mut composite_key);
+
+    composite_key
+}
+
+trait VisitBytes {
+    fn visit_bytes<R, F: FnOnce(&[u8]) -> R>(&self, visitor: F) -> R;
+}
+
+impl<T> WriteCompositeKey for T
+where
+    T: VisitBytes,
+{
+    fn total_len(&self) -> usize {
+        self.visit_bytes(<[u8]>::len)
+    }
+
+    fn write_into<W: WriteKeyPart>(&self, writer: &mut W) {
+        self.visit_bytes(|bytes| writer.write_key_part(bytes));
+    }
+}
+
+/// The version of the storage key layout produced by [`WriteCompositeKey`].
+///
+/// - `1`: tuple parts joined with `:`, which made keys like `("a:b", "c")` and `("a", "b:c")`
+///   collide.
+/// - `2`: every tuple part but the last prefixed with its length as a big-endian `u16`.
+///
+/// Data written under an older version has to be migrated, keys aren't tagged with it.
+pub const KEY_ENCODING_VERSION: u32 = 2;
+
+/// Every tuple part but the last is prefixed with its length, so parts can contain any byte and
+/// decode back unambiguously.
+const LENGTH_PREFIX: usize = 2;
+
+fn length_prefixed_len(part: &impl WriteCompositeKey) -> usize {
+    LENGTH_PREFIX + part.total_len()
+}
+
+fn write_length_prefixed<W: WriteKeyPart>(part: &impl WriteCompositeKey, writer: &mut W) {
+    let len = u16::try_from(part.total_len()).expect("tuple key parts are at most 65535 bytes");
+
+    writer.write_key_part(&len.to_be_bytes());
+    part.write_into(writer);
+}
+
+/// Implement the key traits for a tuple, the parts before `;` are length-prefixed.
+///
+/// Parts can be any [`WriteCompositeKey`], so tuples nest: a nested tuple is one length-prefixed
+/// part of the outer key, or written as is when it's the last part.
+macro_rules! impl_tuple_key {
+    ($($part:ident $key:ident $n:tt),+; $last:ident $last_key:ident $m:tt) => {
+        impl<$($part,)+ $last> WriteCompositeKey for ($($part,)+ $last)
+        where
+            $($part: WriteCompositeKey,)+
+            $last: WriteCompositeKey,
+        {
+            fn total_len(&self) -> usize {
+                $(length_prefixed_len(&self.$n) +)+ self.$m.total_len()
+            }
+
+            fn write_into<W: WriteKeyPart>(&self, writer: &mut W) {
+                $(write_length_prefixed(&self.$n, writer);)+
+                self.$m.write_into(writer);
+            }
+        }
+
+        impl<$($part,)+ $last> WriteCompositeKey for &($($part,)+ $last)
+        where
+            ($($part,)+ $last): WriteCompositeKey,
+        {
+            fn total_len(&self) -> usize {
+                (**self).total_len()
+            }
+
+            fn write_into<W: WriteKeyPart>(&self, writer: &mut W) {
+                (**self).write_into(writer);
+            }
+        }
+
+        impl<$($part,)+ $last> KeyDeserialize for ($($part,)+ $last)
+        where
+            $($part: KeyDeserialize,)+
+            $last: KeyDeserialize,
+        {
+            type Owned = ($($part::Owned,)+ $last::Owned);
+
+            #[allow(non_snake_case)]
+            fn from_key_bytes(bytes: &[u8]) -> Result<Self::Owned, KeyDecodeError> {
+                let rest = bytes;
+                $(let ($part, rest) = split_length_prefixed(rest)?;)+
+
+                Ok((
+                    $(<$part as KeyDeserialize>::from_key_bytes($part)?,)+
+                    <$last as KeyDeserialize>::from_key_bytes(rest)?,
+                ))
+            }
+        }
+
+        impl<$($part, $key,)+ $last, $last_key> EncodeLike<($($key,)+ $last_key)>
+            for ($($part,)+ $last)
+        where
+            ($($part,)+ $last): WriteCompositeKey,
+            ($($key,)+ $last_key): WriteCompositeKey,
+            $($part: EncodeLike<$key>,)+
+            $last: EncodeLike<$last_key>,
+        {
+        }
+
+        impl<$($part, $key,)+ $last, $last_key> EncodeLike<($($key,)+ $last_key)>
+            for &($($part,)+ $last)
+        where
+            ($($part,)+ $last): EncodeLike<($($key,)+ $last_key)>,
+            ($($key,)+ $last_key): WriteCompositeKey,
+        {
+        }
+    };
+}
+
+impl_tuple_key!(T1 K1 0; T2 K2 1);
+impl_tuple_key!(T1 K1 0, T2 K2 1; T3 K3 2);
+impl_tuple_key!(T1 K1 0, T2 K2 1, T3 K3 2; T4 K4 3);
+impl_tuple_key!(T1 K1 0, T2 K2 1, T3 K3 2, T4 K4 3; T5 K5 4);
+impl_tuple_key!(T1 K1 0, T2 K2 1, T3 K3 2, T4 K4 3, T5 K5 4; T6 K6 5);
+impl_tuple_key!(T1 K1 0, T2 K2 1, T3 K3 2, T4 K4 3, T5 K5 4, T6 K6 5; T7 K7 6);
+impl_tuple_key!(T1 K1 0, T2 K2 1, T3 K3 2, T4 K4 3, T5 K5 4, T6 K6 5, T7 K7 6; T8 K8 7);
+
+/// Leading parts of the tuple key `K`, written like they are within the full key, see
+/// [`Map::sub_prefix`].
+///
+/// Implemented for 1-tuples up to 7-tuples of parts encoding like the key's leading parts.
+pub trait KeyPrefix<K> {
+    /// The key parts following the prefix, a tuple of them unless there's only one.
+    type Suffix: WriteCompositeKey;
+
+    fn prefix_len(&self) -> usize;
+
+    fn write_prefix<W: WriteKeyPart>(&self, writer: &mut W);
+}
+
+/// Implement [`KeyPrefix`] for the parts before `;` of a tuple key made of them and the keys
+/// after it.
+macro_rules! impl_key_prefix {
+    (@impl $($part:ident $key:ident $n:tt),+; $($rest:ident),+; $suffix:ty) => {
+        impl<$($part, $key,)+ $($rest),+> KeyPrefix<($($key,)+ $($rest),+)> for ($($part,)+)
+        where
+            $($part: EncodeLike<$key>,)+
+            $suffix: WriteCompositeKey,
+        {
+            type Suffix = $suffix;
+
+            fn prefix_len(&self) -> usize {
+                0 $(+ length_prefixed_len(&self.$n))+
+            }
+
+            fn write_prefix<W: WriteKeyPart>(&self, writer: &mut W) {
+                $(write_length_prefixed(&self.$n, writer);)+
+            }
+        }
+    };
+    ($($part:ident $key:ident $n:tt),+; $suffix:ident) => {
+        impl_key_prefix!(@impl $($part $key $n),+; $suffix; $suffix);
+    };
+    ($($part:ident $key:ident $n:tt),+; $($rest:ident),+) => {
+        impl_key_prefix!(@impl $($part $key $n),+; $($rest),+; ($($rest),+));
+    };
+}
+
+impl_key_prefix!(P1 K1 0; K2);
+impl_key_prefix!(P1 K1 0; K2, K3);
+impl_key_prefix!(P1 K1 0, P2 K2 1; K3);
+impl_key_prefix!(P1 K1 0; K2, K3, K4);
+impl_key_prefix!(P1 K1 0, P2 K2 1; K3, K4);
+impl_key_prefix!(P1 K1 0, P2 K2 1, P3 K3 2; K4);
+impl_key_prefix!(P1 K1 0; K2, K3, K4, K5);
+impl_key_prefix!(P1 K1 0, P2 K2 1; K3, K4, K5);
+impl_key_prefix!(P1 K1 0, P2 K2 1, P3 K3 2; K4, K5);
+impl_key_prefix!(P1 K1 0, P2 K2 1, P3 K3 2, P4 K4 3; K5);
+impl_key_prefix!(P1 K1 0; K2, K3, K4, K5, K6);
+impl_key_prefix!(P1 K1 0, P2 K2 1; K3, K4, K5, K6);
+impl_key_prefix!(P1 K1 0, P2 K2 1, P3 K3 2; K4, K5, K6);
+impl_key_prefix!(P1 K1 0, P2 K2 1, P3 K3 2, P4 K4 3; K5, K6);
+impl_key_prefix!(P1 K1 0, P2 K2 1, P3 K3 2, P4 K4 3, P5 K5 4; K6);
+impl_key_prefix!(P1 K1 0; K2, K3, K4, K5, K6, K7);
+impl_key_prefix!(P1 K1 0, P2 K2 1; K3, K4, K5, K6, K7);
+impl_key_prefix!(P1 K1 0, P2 K2 1, P3 K3 2; K4, K5, K6, K7);
+impl_key_prefix!(P1 K1 0, P2 K2 1, P3 K3 2, P4 K4 3; K5, K6, K7);
+impl_key_prefix!(P1 K1 0, P2 K2 1, P3 K3 2, P4 K4 3, P5 K5 4; K6, K7);
+impl_key_prefix!(P1 K1 0, P2 K2 1, P3 K3 2, P4 K4 3, P5 K5 4, P6 K6 5; K7);
+impl_key_prefix!(P1 K1 0; K2, K3, K4, K5, K6, K7, K8);
+impl_key_prefix!(P1 K1 0, P2 K2 1; K3, K4, K5, K6, K7, K8);
+impl_key_prefix!(P1 K1 0, P2 K2 1, P3 K3 2; K4, K5, K6, K7, K8);
+impl_key_prefix!(P1 K1 0, P2 K2 1, P3 K3 2, P4 K4 3; K5, K6, K7, K8);
+impl_key_prefix!(P1 K1 0, P2 K2 1, P3 K3 2, P4 K4 3, P5 K5 4; K6, K7, K8);
+impl_key_prefix!(P1 K1 0, P2 K2 1, P3 K3 2, P4 K4 3, P5 K5 4, P6 K6 5; K7, K8);
+impl_key_prefix!(P1 K1 0, P2 K2 1, P3 K3 2, P4 K4 3, P5 K5 4, P6 K6 5, P7 K7 6; K8);
+
+impl VisitBytes for &[u8] {
+    fn visit_bytes<R, F: FnOnce(&[u8]) -> R>(&self, visitor: F) -> R {
+        visitor(self)
+    }
+}
+
+impl VisitBytes for &str {
+    fn visit_bytes<R, F: FnOnce(&[u8]) -> R>(&self, visitor: F) -> R {
+        visitor(self.as_bytes())
+    }
+}
+
+impl VisitBytes for String {
+    fn visit_bytes<R, F: FnOnce(&[u8]) -> R>(&self, visitor: F) -> R {
+        visitor(self.as_bytes())
+    }
+}
+
+impl VisitBytes for Vec<u8> {
+    fn visit_bytes<R, F: FnOnce(&[u8]) -> R>(&self, visitor: F) -> R {
+        visitor(self.as_slice())
+    }
+}
+
+macro_rules! impl_visit_bytes_int {
+    ($($t:ty),+) => {
+        $(impl VisitBytes for $t {
+            fn visit_bytes<R, F>(&self, visitor: F) -> R
+            where
+                F: FnOnce(&[u8]) -> R,
+            {
+                visitor(&self.to_be_bytes())
+            }
+        })*
+    };
+}
+
+impl_visit_bytes_int!(u8, u16, u32, u64, u128);
+
+/// Signed integers are encoded with the sign bit flipped, so byte order matches numeric order:
+/// negative values sort before zero and positive ones.
+macro_rules! impl_visit_bytes_signed {
+    ($($t:ty),+) => {
+        $(impl VisitBytes for $t {
+            fn visit_bytes<R, F>(&self, visitor: F) -> R
+            where
+                F: FnOnce(&[u8]) -> R,
+            {
+                visitor(&(self ^ <$t>::MIN).to_be_bytes())
+            }
+        })*
+    };
+}
+
+impl_visit_bytes_signed!(i8, i16, i32, i64, i128);
+
+impl VisitBytes for bool {
+    fn visit_bytes<R, F: FnOnce(&[u8]) -> R>(&self, visitor: F) -> R {
+        visitor(&[u8::from(*self)])
+    }
+}
+
+/// UTF-8 encoded, which preserves code point order.
+impl VisitBytes for char {
+    fn visit_bytes<R, F: FnOnce(&[u8]) -> R>(&self, visitor: F) -> R {
+        let mut buffer = [0; 4];
+        visitor(self.encode_utf8(&mut buffer).as_bytes())
+    }
+}
+
+impl<const N: usize> VisitBytes for [u8; N] {
+    fn visit_bytes<R, F: FnOnce(&[u8]) -> R>(&self, visitor: F) -> R {
+        visitor(self)
+    }
+}
+
+impl<T: VisitBytes> VisitBytes for &T {
+    fn visit_bytes<R, F: FnOnce(&[u8]) -> R>(&self, visitor: F) -> R {
+        (**self).visit_bytes(visitor)
+    }
+}
+
+/// Returned when stored key bytes aren't a valid encoding of the key type, see [`KeyDeserialize`].
+///
+/// ```
+/// use kv_storage::{KeyDecodeError, KeyDeserialize};
+///
+/// assert_eq!(
+///     u64::from_key_bytes(b"short"),
+///     Err(KeyDecodeError::InvalidLength { expected: 8, found: 5 })
+/// );
+/// assert_eq!(String::from_key_bytes(b"\xff"), Err(KeyDecodeError::InvalidUtf8));
+/// ```
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum KeyDecodeError {
+    #[error("key ended before a length-prefixed part")]
+    Truncated,
+    #[error("expected a {expected} byte key part, found {found} bytes")]
+    InvalidLength { expected: usize, found: usize },
+    #[error("key part is not valid UTF-8")]
+    InvalidUtf8,
+    #[error("key part is not a valid {0}")]
+    InvalidValue(&'static str),
+}
+
+/// Decodes a key from the bytes it was encoded to, the inverse of [`WriteCompositeKey`].
+///
+/// ```
+/// use kv_storage::{KeyDeserialize, Map};
+///
+/// const ALLOWANCES: Map<64, (&str, u64), u128> = Map::new(b"allowances");
+///
+/// let key = ALLOWANCES.key(("alice", 7));
+/// let encoded = &key.as_ref()[ALLOWANCES.prefix().len()..];
+///
+/// assert_eq!(<(&str, u64)>::from_key_bytes(encoded), Ok(("alice".to_owned(), 7)));
+/// ```
+pub trait KeyDeserialize {
+    type Owned;
+
+    /// Decode a key, given exactly the bytes it was encoded to.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the bytes are not a valid encoding.
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self::Owned, KeyDecodeError>;
+}
+
+impl KeyDeserialize for String {
+    type Owned = String;
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self::Owned, KeyDecodeError> {
+        String::from_utf8(bytes.to_vec()).map_err(|_| KeyDecodeError::InvalidUtf8)
+    }
+}
+
+impl KeyDeserialize for &str {
+    type Owned = String;
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self::Owned, KeyDecodeError> {
+        String::from_key_bytes(bytes)
+    }
+}
+
+impl KeyDeserialize for bool {
+    type Owned = bool;
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self::Owned, KeyDecodeError> {
+        match bytes {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            [_] => Err(KeyDecodeError::InvalidValue("bool")),
+            _ => Err(KeyDecodeError::InvalidLength {
+                expected: 1,
+                found: bytes.len(),
+            }),
+        }
+    }
+}
+
+impl KeyDeserialize for char {
+    type Owned = char;
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self::Owned, KeyDecodeError> {
+        let text = std::str::from_utf8(bytes).map_err(|_| KeyDecodeError::InvalidUtf8)?;
+        let mut chars = text.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(KeyDecodeError::InvalidValue("char")),
+        }
+    }
+}
+
+impl<const N: usize> KeyDeserialize for [u8; N] {
+    type Owned = [u8; N];
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self::Owned, KeyDecodeError> {
+        bytes.try_into().map_err(|_| KeyDecodeError::InvalidLength {
+            expected: N,
+            found: bytes.len(),
+        })
+    }
+}
+
+impl<const N: usize> KeyDeserialize for &[u8; N] {
+    type Owned = [u8; N];
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self::Owned, KeyDecodeError> {
+        <[u8; N]>::from_key_bytes(bytes)
+    }
+}
+
+impl KeyDeserialize for Vec<u8> {
+    type Owned = Vec<u8>;
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self::Owned, KeyDecodeError> {
+        Ok(bytes.to_vec())
+    }
+}
+
+impl KeyDeserialize for &[u8] {
+    type Owned = Vec<u8>;
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self::Owned, KeyDecodeError> {
+        Ok(bytes.to_vec())
+    }
+}
+
+macro_rules! impl_key_deserialize_int {
+    ($($t:ty),+) => {
+        $(impl KeyDeserialize for $t {
+            type Owned = $t;
+
+            fn from_key_bytes(bytes: &[u8]) -> Result<Self::Owned, KeyDecodeError> {
+                let array = bytes.try_into().map_err(|_| KeyDecodeError::InvalidLength {
+                    expected: std::mem::size_of::<$t>(),
+                    found: bytes.len(),
+                })?;
+
+                Ok(<$t>::from_be_bytes(array))
+            }
+        })*
+    };
+}
+
+impl_key_deserialize_int!(u8, u16, u32, u64, u128);
+
+macro_rules! impl_key_deserialize_signed {
+    ($($t:ty),+) => {
+        $(impl KeyDeserialize for $t {
+            type Owned = $t;
+
+            fn from_key_bytes(bytes: &[u8]) -> Result<Self::Owned, KeyDecodeError> {
+                let array = bytes.try_into().map_err(|_| KeyDecodeError::InvalidLength {
+                    expected: std::mem::size_of::<$t>(),
+                    found: bytes.len(),
+                })?;
+
+                // flip the sign bit back, see `impl_visit_bytes_signed`
+                Ok(<$t>::from_be_bytes(array) ^ <$t>::MIN)
+            }
+        })*
+    };
+}
+
+impl_key_deserialize_signed!(i8, i16, i32, i64, i128);
+
+/// Helpers for the code generated by `#[derive(StorageKey)]`, not part of the public API.
+#[doc(hidden)]
+pub mod __derive {
+    use super::{KeyDecodeError, WriteCompositeKey, WriteKeyPart};
+
+    pub fn length_prefixed_len(part: &impl WriteCompositeKey) -> usize {
+        super::length_prefixed_len(part)
+    }
+
+    pub fn write_length_prefixed<W: WriteKeyPart>(part: &impl WriteCompositeKey, writer: &mut W) {
+        super::write_length_prefixed(part, writer);
+    }
+
+    /// # Errors
+    ///
+    /// This function will return an error if the part's length prefix is cut short.
+    pub fn split_length_prefixed(bytes: &[u8]) -> Result<(&[u8], &[u8]), KeyDecodeError> {
+        super::split_length_prefixed(bytes)
+    }
+}
+
+/// Split off a length-prefixed part, returning it and the rest.
+fn split_length_prefixed(bytes: &[u8]) -> Result<(&[u8], &[u8]), KeyDecodeError> {
+    let (len, rest) = bytes
+        .split_first_chunk::<LENGTH_PREFIX>()
+        .ok_or(KeyDecodeError::Truncated)?;
+
+    let len = usize::from(u16::from_be_bytes(*len));
+
+    if rest.len() < len {
+        return Err(KeyDecodeError::Truncated);
+    }
+
+    Ok(rest.split_at(len))
+}
+
+/// Renders raw key bytes for humans: printable runs as text, everything else as hex.
+///
+/// Keys longer than the maximum length (64 bytes by default, see
+/// [`KeyDisplay::with_max_len`]) are truncated with an ellipsis followed by the total length.
+///
+/// ```
+/// use kv_storage::KeyDisplay;
+///
+/// let key = [b"balance:".as_slice(), &42u64.to_be_bytes()].concat();
+///
+/// assert_eq!(KeyDisplay::new(&key).to_string(), "balance:<000000000000002a>");
+/// assert_eq!(
+///     KeyDisplay::new(&key).with_max_len(12).to_string(),
+///     "balance:<00000000>…(16 bytes)"
+/// );
+/// ```
+#[derive(Copy, Clone)]
+pub struct KeyDisplay<'a> {
+    key: &'a [u8],
+    max_len: usize,
+}
+
+impl<'a> KeyDisplay<'a> {
+    pub const DEFAULT_MAX_LEN: usize = 64;
+
+    /// Printable runs shorter than this are shown as hex, they're most likely binary data.
+    const MIN_TEXT_RUN: usize = 3;
+
+    #[must_use]
+    pub const fn new(key: &'a [u8]) -> Self {
+        Self {
+            key,
+            max_len: Self::DEFAULT_MAX_LEN,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_max_len(self, max_len: usize) -> Self {
+        Self {
+            key: self.key,
+            max_len,
+        }
     }
 
-    /// Remove any item stored at the given key.
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the store encounters an error.
-    pub fn remove<Store, Key>(&self, store: &mut Store, key: Key) -> Result<(), Store::Error>
-    where
-        Store: MutStorage,
-        Key: Borrow<K>,
-    {
-        let composite = compose_key::<N>(self.prefix, key.borrow());
-        store.remove(composite.as_ref())
+    fn text_run_len(bytes: &[u8]) -> usize {
+        bytes
+            .iter()
+            .take_while(|b| b.is_ascii_graphic() || **b == b' ')
+            .count()
     }
 }
 
-enum CompositeKeyBuffer<const N: usize> {
-    Stack { buffer: [u8; N], len: usize },
-    Heap(Box<[u8]>),
-}
+impl std::fmt::Display for KeyDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let shown = &self.key[..self.key.len().min(self.max_len)];
 
-impl<const N: usize> AsRef<[u8]> for CompositeKeyBuffer<N> {
-    fn as_ref(&self) -> &[u8] {
-        match self {
-            CompositeKeyBuffer::Stack { buffer, len } => &buffer[..*len],
-            CompositeKeyBuffer::Heap(v) => v.as_ref(),
+        let all_text = Self::text_run_len(shown) == shown.len();
+
+        let mut rest = shown;
+        let mut in_hex = false;
+
+        while let Some(&byte) = rest.first() {
+            let run = Self::text_run_len(rest);
+
+            if all_text || run >= Self::MIN_TEXT_RUN {
+                if in_hex {
+                    f.write_str(">")?;
+                    in_hex = false;
+                }
+
+                // the run is ASCII, so always valid UTF-8
+                f.write_str(std::str::from_utf8(&rest[..run]).map_err(|_| std::fmt::Error)?)?;
+                rest = &rest[run..];
+                continue;
+            }
+
+            if !in_hex {
+                f.write_str("<")?;
+                in_hex = true;
+            }
+
+            write!(f, "{byte:02x}")?;
+            rest = &rest[1..];
         }
-    }
-}
 
-impl<const N: usize> AsMut<[u8]> for CompositeKeyBuffer<N> {
-    fn as_mut(&mut self) -> &mut [u8] {
-        match self {
-            CompositeKeyBuffer::Stack { buffer, len } => &mut buffer[..*len],
-            CompositeKeyBuffer::Heap(v) => v.as_mut(),
+        if in_hex {
+            f.write_str(">")?;
+        }
+
+        if shown.len() < self.key.len() {
+            write!(f, "…({} bytes)", self.key.len())?;
         }
+
+        Ok(())
     }
 }
 
-impl<const N: usize> AsRef<[u8]> for CompositeKey<N> {
-    fn as_ref(&self) -> &[u8] {
-        self.buffer.as_ref()
+impl std::fmt::Debug for KeyDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{self}\"")
     }
 }
 
-struct CompositeKey<const N: usize> {
-    buffer: CompositeKeyBuffer<N>,
-    written: usize,
-}
+/// Returned when wrapping NaN in an [`OrderedF32`] or [`OrderedF64`].
+///
+/// ```
+/// use kv_storage::{Map, OrderedF64};
+///
+/// const PRICES: Map<16, OrderedF64, u32> = Map::new(b"prices");
+///
+/// assert!(OrderedF64::new(f64::NAN).is_err());
+///
+/// let low = PRICES.key(OrderedF64::new(-1.5).unwrap());
+/// let high = PRICES.key(OrderedF64::new(2.0).unwrap());
+/// assert!(low.as_ref() < high.as_ref());
+/// ```
+#[derive(Debug, thiserror::Error)]
+#[error("NaN cannot be used as a key")]
+pub struct NanKey;
 
-impl<const N: usize> CompositeKey<N> {
-    fn new(len: usize) -> Self {
-        let buffer = if len > N {
-            CompositeKeyBuffer::Heap(vec![0; len].into_boxed_slice())
-        } else {
-            CompositeKeyBuffer::Stack {
-                buffer: [0; N],
-                len,
+macro_rules! ordered_float {
+    ($name:ident, $f:ty, $bits:ty) => {
+        /// A non-NaN float usable as a key, encoded so that byte order matches numeric order.
+        ///
+        /// Negative zero is normalised to positive zero on construction, so `-0.0` and `0.0`
+        /// address the same key just as they compare equal.
+        #[derive(Debug, Copy, Clone, PartialEq)]
+        pub struct $name($f);
+
+        impl $name {
+            /// Wrap a float for use as a key.
+            ///
+            /// # Errors
+            ///
+            /// This function will return an error if the value is NaN.
+            pub fn new(value: $f) -> Result<Self, NanKey> {
+                if value.is_nan() {
+                    return Err(NanKey);
+                }
+
+                // `-0.0 == 0.0`, adding zero turns the former into the latter
+                Ok(Self(value + 0.0))
             }
-        };
 
-        Self { buffer, written: 0 }
-    }
-}
+            #[must_use]
+            pub const fn get(self) -> $f {
+                self.0
+            }
 
-impl<const N: usize> WriteKeyPart for CompositeKey<N> {
-    fn write_key_part(&mut self, part: &[u8]) {
-        let end = self.written + part.len();
+            fn to_ordered_bits(self) -> $bits {
+                const SIGN: $bits = 1 << (<$bits>::BITS - 1);
 
-        let section = &mut self.buffer.as_mut()[self.written..end];
+                let bits = self.0.to_bits();
 
-        section.copy_from_slice(part);
+                if bits & SIGN == 0 {
+                    bits ^ SIGN
+                } else {
+                    !bits
+                }
+            }
+        }
 
-        self.written += part.len();
-    }
+        impl Eq for $name {}
+
+        impl Ord for $name {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                self.0.total_cmp(&other.0)
+            }
+        }
+
+        impl PartialOrd for $name {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl TryFrom<$f> for $name {
+            type Error = NanKey;
+
+            fn try_from(value: $f) -> Result<Self, Self::Error> {
+                Self::new(value)
+            }
+        }
+
+        impl VisitBytes for $name {
+            fn visit_bytes<R, F: FnOnce(&[u8]) -> R>(&self, visitor: F) -> R {
+                visitor(&self.to_ordered_bits().to_be_bytes())
+            }
+        }
+    };
 }
 
-fn compose_key<const N: usize>(prefix: &[u8], keys: &impl WriteCompositeKey) -> CompositeKey<N> {
-    let total_len = prefix.len() + keys.total_len();
+ordered_float!(OrderedF32, f32, u32);
+ordered_float!(OrderedF64, f64, u64);
 
-    let mut composite_key = CompositeKey::new(total_len);
+/// A key that encodes to exactly the same bytes as `K`, so it can be used with a container
+/// declared with key type `K`, e.g. `(&str, &u64)` or `(&String, u64)` for `(String, u64)`.
+///
+/// Implement this as `impl EncodeLike<MyKey> for MyKey` (and for `&MyKey`) for custom key types.
+pub trait EncodeLike<K>: WriteCompositeKey {}
 
-    composite_key.write_key_part(prefix);
+macro_rules! impl_encode_like_self {
+    ($($t:ty),+) => {
+        $(
+            impl EncodeLike<$t> for $t {}
+            impl EncodeLike<$t> for &$t {}
+        )*
+    };
+}
 
-    keys.write_into(&mut composite_key);
+impl_encode_like_self!(
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    bool,
+    char,
+    String,
+    Vec<u8>,
+    OrderedF32,
+    OrderedF64
+);
 
-    composite_key
+impl<const N: usize> EncodeLike<[u8; N]> for [u8; N] {}
+impl<const N: usize> EncodeLike<[u8; N]> for &[u8; N] {}
+impl<const N: usize> EncodeLike<&[u8; N]> for &[u8; N] {}
+impl<const N: usize> EncodeLike<&[u8; N]> for [u8; N] {}
+
+impl EncodeLike<&str> for &str {}
+impl EncodeLike<&str> for &&str {}
+impl EncodeLike<&str> for String {}
+impl EncodeLike<&str> for &String {}
+impl EncodeLike<String> for &str {}
+impl EncodeLike<String> for &&str {}
+
+impl EncodeLike<&[u8]> for &[u8] {}
+impl EncodeLike<&[u8]> for &&[u8] {}
+impl EncodeLike<&[u8]> for Vec<u8> {}
+impl EncodeLike<&[u8]> for &Vec<u8> {}
+impl EncodeLike<Vec<u8>> for &[u8] {}
+impl EncodeLike<Vec<u8>> for &&[u8] {}
+
+/// Declare an [`Item`] keyed by the calling module's path and the given name.
+///
+/// Moving or renaming the module changes the key, and data saved under the old one is no longer
+/// found. Pass `ns = "..."` to use a fixed namespace instead, the same goes for [`map!`],
+/// [`set!`] and [`deque!`].
+///
+/// Names and namespaces can be any `&str` constant expression. `raw = ...` takes the whole key
+/// as a `&'static [u8]` constant expression instead, e.g. built with [`concat_keys!`], and uses
+/// it as is.
+///
+/// ```
+/// use kv_storage::{concat_keys, item, Item};
+///
+/// const BANK: &str = "bank";
+/// const APP: &[u8] = b"app";
+///
+/// const OWNER: Item<String> = item!("owner");
+/// const OTHER: Item<String> = item!("other");
+/// const TOTAL: Item<u64> = item!("total", ns = BANK);
+/// const CONFIG: Item<u32> = item!(raw = concat_keys!(APP, b"::config"));
+///
+/// assert_ne!(OWNER.key(), OTHER.key());
+/// assert_eq!(TOTAL.key(), b"\0\x04bank\0\x05total");
+/// assert_eq!(CONFIG.key(), b"app::config");
+/// ```
+#[macro_export]
+macro_rules! item {
+    (raw = $key:expr) => {
+        $crate::Item::new($crate::registered_key!(raw $key))
+    };
+    ($key:expr, ns = $ns:expr) => {
+        $crate::Item::new($crate::registered_key!($ns, $key))
+    };
+    ($key:expr) => {
+        $crate::Item::new($crate::registered_key!(module_path!(), $key))
+    };
 }
 
-trait VisitBytes {
-    fn visit_bytes<R, F: FnOnce(&[u8]) -> R>(&self, visitor: F) -> R;
+/// Declare a [`Map`] prefixed by the calling module's path and the given name, or the given
+/// namespace, see [`item!`].
+///
+/// ```
+/// use kv_storage::{item, map, Item, Map};
+///
+/// const BALANCE: Item<u128> = item!("balance");
+/// const BALANCES: Map<64, &str, u128> = map!("balances");
+///
+/// // length-prefixed segments keep one name from being a prefix of another
+/// assert!(!BALANCES.prefix().starts_with(BALANCE.key()));
+/// ```
+#[macro_export]
+macro_rules! map {
+    (raw = $key:expr) => {
+        $crate::Map::new($crate::registered_key!(raw $key))
+    };
+    ($key:expr, ns = $ns:expr) => {
+        $crate::Map::new($crate::registered_key!($ns, $key))
+    };
+    ($key:expr) => {
+        $crate::Map::new($crate::registered_key!(module_path!(), $key))
+    };
 }
 
-impl<T> WriteCompositeKey for T
-where
-    T: VisitBytes,
-{
-    fn total_len(&self) -> usize {
-        self.visit_bytes(<[u8]>::len)
-    }
+/// Declare a [`Set`] prefixed by the calling module's path and the given name, or the given
+/// namespace, see [`item!`].
+#[macro_export]
+macro_rules! set {
+    (raw = $key:expr) => {
+        $crate::Set::new($crate::registered_key!(raw $key))
+    };
+    ($key:expr, ns = $ns:expr) => {
+        $crate::Set::new($crate::registered_key!($ns, $key))
+    };
+    ($key:expr) => {
+        $crate::Set::new($crate::registered_key!(module_path!(), $key))
+    };
+}
 
-    fn write_into<W: WriteKeyPart>(&self, writer: &mut W) {
-        self.visit_bytes(|bytes| writer.write_key_part(bytes));
-    }
+/// Declare a [`Deque`] prefixed by the calling module's path and the given name, or the given
+/// namespace, see [`item!`].
+#[macro_export]
+macro_rules! deque {
+    (raw = $key:expr) => {
+        $crate::Deque::new($crate::registered_key!(raw $key))
+    };
+    ($key:expr, ns = $ns:expr) => {
+        $crate::Deque::new($crate::registered_key!($ns, $key))
+    };
+    ($key:expr) => {
+        $crate::Deque::new($crate::registered_key!(module_path!(), $key))
+    };
 }
 
-impl<T1, T2> WriteCompositeKey for (T1, T2)
-where
-    T1: VisitBytes,
-    T2: VisitBytes,
-{
-    fn total_len(&self) -> usize {
-        self.0.visit_bytes(<[u8]>::len) + self.1.visit_bytes(<[u8]>::len) + 1 // delimiter
-    }
+/// Build the `&'static [u8]` key for a namespace and name at compile time, see [`namespaced`].
+#[doc(hidden)]
+#[macro_export]
+macro_rules! namespaced_key {
+    ($ns:expr, $key:expr) => {{
+        const KEY: &[u8] = &$crate::namespaced::<{ $crate::namespaced_len($ns, $key) }>($ns, $key);
+        KEY
+    }};
+}
 
-    fn write_into<W: WriteKeyPart>(&self, writer: &mut W) {
-        self.0.visit_bytes(|bytes| writer.write_key_part(bytes));
-        writer.write_key_part(b":");
-        self.1.visit_bytes(|bytes| writer.write_key_part(bytes));
-    }
+/// Concatenate byte strings into a `&'static [u8]` at compile time.
+///
+/// Parts are anything coercing to `&[u8]` in a const context, e.g.
+/// `concat_keys!(PREFIX, b"::", NAME.as_bytes())`.
+///
+/// ```
+/// use kv_storage::{concat_keys, Item};
+///
+/// const APP: &[u8] = b"app";
+/// const CONFIG: Item<u32> = Item::new(concat_keys!(APP, b"::", b"config"));
+///
+/// assert_eq!(CONFIG.key(), b"app::config");
+/// ```
+#[macro_export]
+macro_rules! concat_keys {
+    ($($part:expr),+ $(,)?) => {{
+        const PARTS: &[&[u8]] = &[$($part),+];
+        const KEY: &[u8] = &$crate::concat_bytes::<{ $crate::concat_len(PARTS) }>(PARTS);
+        KEY
+    }};
 }
 
-impl<T1, T2, T3> WriteCompositeKey for (T1, T2, T3)
-where
-    T1: VisitBytes,
-    T2: VisitBytes,
-    T3: VisitBytes,
-{
-    fn total_len(&self) -> usize {
-        self.0.visit_bytes(<[u8]>::len)
-            + self.1.visit_bytes(<[u8]>::len)
-            + self.2.visit_bytes(<[u8]>::len)
-            + 2 // delimiters
-    }
+/// The total length of the parts joined by [`concat_bytes`].
+#[must_use]
+pub const fn concat_len(parts: &[&[u8]]) -> usize {
+    let mut len = 0;
+    let mut i = 0;
 
-    fn write_into<W: WriteKeyPart>(&self, writer: &mut W) {
-        self.0.visit_bytes(|bytes| writer.write_key_part(bytes));
-        writer.write_key_part(b":");
-        self.1.visit_bytes(|bytes| writer.write_key_part(bytes));
-        writer.write_key_part(b":");
-        self.2.visit_bytes(|bytes| writer.write_key_part(bytes));
+    while i < parts.len() {
+        len += parts[i].len();
+        i += 1;
     }
+
+    len
 }
 
-impl<'a> VisitBytes for &'a [u8] {
-    fn visit_bytes<R, F: FnOnce(&[u8]) -> R>(&self, visitor: F) -> R {
-        visitor(self)
+/// Join byte strings into an array at compile time.
+///
+/// # Panics
+///
+/// Panics (at compile time when used through [`concat_keys!`]) if `N` isn't [`concat_len`].
+#[must_use]
+pub const fn concat_bytes<const N: usize>(parts: &[&[u8]]) -> [u8; N] {
+    assert!(N == concat_len(parts), "wrong concatenated key length");
+
+    let mut out = [0; N];
+    let mut at = 0;
+    let mut i = 0;
+
+    while i < parts.len() {
+        let part = parts[i];
+        let mut j = 0;
+
+        while j < part.len() {
+            out[at] = part[j];
+            at += 1;
+            j += 1;
+        }
+
+        i += 1;
     }
+
+    out
 }
 
-impl<'a> VisitBytes for &'a str {
-    fn visit_bytes<R, F: FnOnce(&[u8]) -> R>(&self, visitor: F) -> R {
-        visitor(self.as_bytes())
-    }
+/// The length of the key produced by [`namespaced`].
+#[must_use]
+pub const fn namespaced_len(ns: &str, key: &str) -> usize {
+    2 + ns.len() + 2 + key.len()
 }
 
-impl VisitBytes for String {
-    fn visit_bytes<R, F: FnOnce(&[u8]) -> R>(&self, visitor: F) -> R {
-        visitor(self.as_bytes())
-    }
+/// Encode a namespace and name as two length-prefixed segments.
+///
+/// ```
+/// use kv_storage::{namespaced, namespaced_len};
+///
+/// const KEY: [u8; namespaced_len("bank", "total")] = namespaced("bank", "total");
+///
+/// assert_eq!(&KEY, b"\0\x04bank\0\x05total");
+/// ```
+///
+/// Each segment is preceded by its length as a big-endian `u16`, so no encoded pair is ever a
+/// byte prefix of another: scanning one container's prefix can't reach into another's, whatever
+/// the names contain (`"balance"` vs `"balances"`, or names containing `"::"`).
+///
+/// # Panics
+///
+/// Panics (at compile time when used through the macros) if a segment is longer than
+/// `u16::MAX` bytes or `LEN` isn't [`namespaced_len`].
+#[must_use]
+pub const fn namespaced<const LEN: usize>(ns: &str, key: &str) -> [u8; LEN] {
+    assert!(
+        LEN == namespaced_len(ns, key),
+        "wrong namespaced key length"
+    );
+
+    let mut out = [0; LEN];
+    let written = write_segment(&mut out, 0, ns.as_bytes());
+    write_segment(&mut out, written, key.as_bytes());
+
+    out
 }
 
-impl VisitBytes for Vec<u8> {
-    fn visit_bytes<R, F: FnOnce(&[u8]) -> R>(&self, visitor: F) -> R {
-        visitor(self.as_slice())
+const fn write_segment(out: &mut [u8], at: usize, segment: &[u8]) -> usize {
+    assert!(segment.len() <= u16::MAX as usize, "key segment too long");
+
+    #[allow(clippy::cast_possible_truncation)]
+    let len = (segment.len() as u16).to_be_bytes();
+
+    out[at] = len[0];
+    out[at + 1] = len[1];
+
+    let mut i = 0;
+
+    while i < segment.len() {
+        out[at + 2 + i] = segment[i];
+        i += 1;
     }
+
+    at + 2 + segment.len()
 }
 
-macro_rules! impl_visit_bytes_int {
-    ($($t:ty),+) => {
-        $(impl VisitBytes for $t {
-            fn visit_bytes<R, F>(&self, visitor: F) -> R
-            where
-                F: FnOnce(&[u8]) -> R,
-            {
-                visitor(&self.to_be_bytes())
-            }
-        })*
+/// Declare several `Item`/`Map` constants at once, rejecting duplicate key literals at compile time.
+///
+/// ```
+/// use kv_storage::{storage_keys, Item, Map};
+///
+/// storage_keys! {
+///     pub const OWNER: Item<String> = item!("owner");
+///     pub const BALANCES: Map<64, &str, u128> = map!("balances");
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use kv_storage::{storage_keys, Item};
+///
+/// storage_keys! {
+///     const OWNER: Item<String> = item!("owner");
+///     const ADMIN: Item<String> = item!("owner");
+/// }
+/// ```
+#[macro_export]
+macro_rules! storage_keys {
+    ($($vis:vis const $name:ident: $ty:ty = $kind:ident!($key:literal);)*) => {
+        $($vis const $name: $ty = $crate::$kind!($key);)*
+
+        const _: () = $crate::assert_unique_keys(&[$($key),*]);
     };
 }
 
-impl_visit_bytes_int!(u8, u16, u32, u64, u128);
+/// Panics if any two keys are equal, used by `storage_keys!` in a const context.
+#[doc(hidden)]
+pub const fn assert_unique_keys(keys: &[&str]) {
+    let mut i = 0;
+
+    while i < keys.len() {
+        let mut j = i + 1;
+
+        while j < keys.len() {
+            assert!(!const_eq(keys[i], keys[j]), "duplicate storage key");
+            j += 1;
+        }
+
+        i += 1;
+    }
+}
 
+/// Build a container key like [`namespaced_key!`], or take a raw one, declaring it to the key
+/// registry when the `key_registry` feature is enabled.
+#[doc(hidden)]
 #[macro_export]
-macro_rules! item {
-    ($key:literal) => {
-        $crate::Item::new(concat!(module_path!(), "::", $key).as_bytes())
-    };
+macro_rules! registered_key {
+    (raw $key:expr) => {{
+        const KEY: &[u8] = $key;
+        $crate::declare_key!(KEY);
+        KEY
+    }};
+    ($ns:expr, $key:expr) => {{
+        const KEY: &[u8] = $crate::namespaced_key!($ns, $key);
+        $crate::declare_key!(KEY);
+        KEY
+    }};
 }
 
+#[cfg(feature = "key_registry")]
+#[doc(hidden)]
 #[macro_export]
-macro_rules! map {
-    ($key:literal) => {
-        $crate::Map::new(concat!(module_path!(), "::", $key, "::").as_bytes())
+macro_rules! declare_key {
+    ($key:expr) => {
+        #[$crate::__registry::linkme::distributed_slice($crate::__registry::DECLARED_KEYS)]
+        #[linkme(crate = $crate::__registry::linkme)]
+        static DECLARATION: $crate::KeyDeclaration = $crate::KeyDeclaration {
+            key: $key,
+            file: file!(),
+            line: line!(),
+        };
     };
 }
 
-impl<'a, S> Fallible for &'a S
+// without the feature nothing is emitted
+#[cfg(not(feature = "key_registry"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! declare_key {
+    ($key:expr) => {};
+}
+
+#[cfg(feature = "key_registry")]
+#[doc(hidden)]
+pub mod __registry {
+    pub use linkme;
+
+    #[linkme::distributed_slice]
+    pub static DECLARED_KEYS: [super::KeyDeclaration];
+}
+
+/// Where a container key was declared with [`item!`], [`map!`], [`set!`] or [`deque!`].
+#[cfg(feature = "key_registry")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyDeclaration {
+    pub key: &'static [u8],
+    pub file: &'static str,
+    pub line: u32,
+}
+
+/// Declarations sharing the same key bytes, see [`check_unique_keys`].
+#[cfg(feature = "key_registry")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyCollision {
+    pub key: &'static [u8],
+    pub declarations: Vec<KeyDeclaration>,
+}
+
+/// Find the containers declared with the same key, among all those declared through the macros
+/// in the crates linked into the running binary, typically asserted empty in a unit test.
+///
+/// Collisions are ordered by key, and their declarations by where they appear.
+///
+/// ```
+/// use kv_storage::{check_unique_keys, item, Item};
+///
+/// const TOTAL: Item<u64> = item!("total", ns = "bank");
+///
+/// assert_eq!(check_unique_keys(), []);
+/// ```
+#[cfg(feature = "key_registry")]
+#[must_use]
+pub fn check_unique_keys() -> Vec<KeyCollision> {
+    let mut by_key: BTreeMap<&'static [u8], Vec<KeyDeclaration>> = BTreeMap::new();
+
+    for declaration in __registry::DECLARED_KEYS {
+        by_key
+            .entry(declaration.key)
+            .or_default()
+            .push(*declaration);
+    }
+
+    by_key
+        .into_iter()
+        .filter(|(_, declarations)| declarations.len() > 1)
+        .map(|(key, mut declarations)| {
+            declarations.sort_by_key(|declaration| (declaration.file, declaration.line));
+            KeyCollision { key, declarations }
+        })
+        .collect()
+}
+
+const fn const_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut i = 0;
+
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+impl<S> Fallible for &S
 where
     S: Fallible,
 {
     type Error = S::Error;
 }
 
-impl<'a, S> Storage for &'a S
+impl<S> Storage for &S
 where
     S: Storage,
 {
@@ -560,19 +7635,60 @@ where
         <S as Storage>::may_load(self, key)
     }
 
+    fn may_load_if<T, P>(&self, key: &[u8], pred: P) -> Result<Option<T>, Self::Error>
+    where
+        T: DeserializeOwned,
+        P: FnOnce(usize) -> bool,
+    {
+        <S as Storage>::may_load_if(self, key, pred)
+    }
+
+    fn may_load_many<T: DeserializeOwned>(
+        &self,
+        keys: &[&[u8]],
+    ) -> Result<Vec<Option<T>>, Self::Error> {
+        <S as Storage>::may_load_many(self, keys)
+    }
+
     fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
         <S as Storage>::has_key(self, key)
     }
 }
 
-impl<'a, S> Fallible for &'a mut S
+impl<S> IterStorage for &S
+where
+    S: IterStorage,
+{
+    fn range<'a, T>(
+        &'a self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<Entries<'a, T, Self::Error>, Self::Error>
+    where
+        T: DeserializeOwned + 'a,
+    {
+        <S as IterStorage>::range(self, min, max, order)
+    }
+
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error> {
+        <S as IterStorage>::range_keys(self, min, max, order)
+    }
+}
+
+impl<S> Fallible for &mut S
 where
     S: Fallible,
 {
     type Error = S::Error;
 }
 
-impl<'a, S> Storage for &'a mut S
+impl<S> Storage for &mut S
 where
     S: Storage,
 {
@@ -583,12 +7699,53 @@ where
         <S as Storage>::may_load(self, key)
     }
 
+    fn may_load_if<T, P>(&self, key: &[u8], pred: P) -> Result<Option<T>, Self::Error>
+    where
+        T: DeserializeOwned,
+        P: FnOnce(usize) -> bool,
+    {
+        <S as Storage>::may_load_if(self, key, pred)
+    }
+
+    fn may_load_many<T: DeserializeOwned>(
+        &self,
+        keys: &[&[u8]],
+    ) -> Result<Vec<Option<T>>, Self::Error> {
+        <S as Storage>::may_load_many(self, keys)
+    }
+
     fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
         <S as Storage>::has_key(self, key)
     }
 }
 
-impl<'a, S> MutStorage for &'a mut S
+impl<S> IterStorage for &mut S
+where
+    S: IterStorage,
+{
+    fn range<'a, T>(
+        &'a self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<Entries<'a, T, Self::Error>, Self::Error>
+    where
+        T: DeserializeOwned + 'a,
+    {
+        <S as IterStorage>::range(self, min, max, order)
+    }
+
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error> {
+        <S as IterStorage>::range_keys(self, min, max, order)
+    }
+}
+
+impl<S> MutStorage for &mut S
 where
     S: MutStorage,
 {
@@ -596,7 +7753,24 @@ where
         <S as MutStorage>::save(self, key, item)
     }
 
+    fn save_with<T: Serialize>(
+        &mut self,
+        key: &[u8],
+        item: &T,
+        durability: Durability,
+    ) -> Result<(), Self::Error> {
+        <S as MutStorage>::save_with(self, key, item, durability)
+    }
+
+    fn save_batch(&mut self, items: &[(&[u8], &dyn ErasedSerialize)]) -> Result<(), Self::Error> {
+        <S as MutStorage>::save_batch(self, items)
+    }
+
     fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
         <S as MutStorage>::remove(self, key)
     }
+
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        <S as MutStorage>::remove_returning(self, key)
+    }
 }
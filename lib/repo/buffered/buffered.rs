@@ -0,0 +1,239 @@
+use std::{borrow::Cow, collections::BTreeMap};
+
+use kv_storage::{
+    trace::{self, Op, Span, Traceable},
+    BatchOp, Fallible, HasKey, Read, Remove, Write,
+};
+
+/// Changes not yet flushed to the inner repo.
+#[derive(Default)]
+struct Dirty {
+    /// `None` marks a removal.
+    changes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    /// Total bytes of the changed keys and values.
+    bytes: usize,
+}
+
+fn change_len(key: &[u8], bytes: &Option<Vec<u8>>) -> usize {
+    key.len() + bytes.as_ref().map_or(0, Vec::len)
+}
+
+impl Dirty {
+    fn insert(&mut self, key: &[u8], bytes: Option<Vec<u8>>) {
+        self.bytes += change_len(key, &bytes);
+
+        if let Some(old) = self.changes.insert(key.to_vec(), bytes) {
+            self.bytes -= change_len(key, &old);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.changes.clear();
+        self.bytes = 0;
+    }
+}
+
+impl Drop for Dirty {
+    fn drop(&mut self) {
+        debug_assert!(
+            self.changes.is_empty() || std::thread::panicking(),
+            "a BufferedRepo was dropped with {} changes never flushed",
+            self.changes.len()
+        );
+    }
+}
+
+/// Coalesces writes and removals in memory in front of an inner repo, flushing them to it as a
+/// single batch when asked to, or once a threshold is reached.
+///
+/// Reads see the buffered changes first. Only the last change to each key is flushed, so
+/// saving the same key over and over reaches the inner repo once.
+///
+/// Pending changes must be flushed, or explicitly discarded, before the repo is dropped. Debug
+/// builds assert it.
+pub struct BufferedRepo<R> {
+    inner: R,
+    dirty: Dirty,
+    max_keys: Option<usize>,
+    max_bytes: Option<usize>,
+}
+
+impl<R> BufferedRepo<R> {
+    /// Buffer changes until [`BufferedRepo::flush`] is called.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            dirty: Dirty::default(),
+            max_keys: None,
+            max_bytes: None,
+        }
+    }
+
+    /// Flush once `max_keys` keys have pending changes.
+    pub fn flush_at_keys(mut self, max_keys: usize) -> Self {
+        self.max_keys = Some(max_keys);
+        self
+    }
+
+    /// Flush once the pending changes reach `max_bytes` bytes of keys and values.
+    pub fn flush_at_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// How many keys have pending changes.
+    pub fn pending(&self) -> usize {
+        self.dirty.changes.len()
+    }
+
+    /// Total bytes of the keys and values with pending changes.
+    pub fn pending_bytes(&self) -> usize {
+        self.dirty.bytes
+    }
+
+    /// Drop the pending changes, returning the inner repo as it was last flushed.
+    pub fn discard(mut self) -> R {
+        self.dirty.clear();
+        self.inner
+    }
+}
+
+impl<R: Write + Remove> BufferedRepo<R> {
+    /// Apply the pending changes to the inner repo in one batch.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner repo fails to apply the batch, the
+    /// changes are then kept pending so flushing can be retried.
+    pub fn flush(&mut self) -> Result<(), R::Error> {
+        let span = trace::enter(self);
+        self.flush_traced(&span)
+    }
+
+    fn flush_traced(&mut self, span: &Span) -> Result<(), R::Error> {
+        if self.dirty.changes.is_empty() {
+            return Ok(());
+        }
+
+        let ops: Vec<BatchOp<'_>> = self
+            .dirty
+            .changes
+            .iter()
+            .map(|(key, bytes)| (Cow::from(key.as_slice()), bytes.as_deref().map(Cow::from)))
+            .collect();
+
+        span.issue_batch(&ops);
+        self.inner.write_batch(&ops)?;
+        self.dirty.clear();
+
+        Ok(())
+    }
+
+    /// Flush the pending changes, returning the inner repo.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the flush fails, see [`BufferedRepo::flush`].
+    pub fn into_inner(mut self) -> Result<R, R::Error> {
+        self.flush()?;
+        Ok(self.inner)
+    }
+
+    fn buffer(&mut self, key: &[u8], bytes: Option<Vec<u8>>) -> Result<(), R::Error> {
+        let span = trace::enter(self);
+
+        match &bytes {
+            Some(bytes) => span.receive(Op::Write, bytes.len()),
+            None => span.receive(Op::Remove, 0),
+        }
+
+        self.dirty.insert(key, bytes);
+
+        self.flush_if_full(&span)
+    }
+
+    fn flush_if_full(&mut self, span: &Span) -> Result<(), R::Error> {
+        let full = self.max_keys.is_some_and(|max| self.pending() >= max)
+            || self
+                .max_bytes
+                .is_some_and(|max| self.pending_bytes() >= max);
+
+        if full {
+            self.flush_traced(span)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R> Traceable for BufferedRepo<R> {
+    const LAYER: &'static str = "buffered";
+}
+
+impl<R: Fallible> Fallible for BufferedRepo<R> {
+    type Error = R::Error;
+}
+
+impl<R: Write + Remove> Write for BufferedRepo<R> {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        self.buffer(key, Some(bytes.to_vec()))
+    }
+
+    fn write_owned(&mut self, key: &[u8], bytes: Vec<u8>) -> Result<(), Self::Error> {
+        self.buffer(key, Some(bytes))
+    }
+
+    fn takes_ownership(&self) -> bool {
+        true
+    }
+}
+
+impl<R: Read> Read for BufferedRepo<R> {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        match self.dirty.changes.get(key) {
+            Some(change) => Ok(change.clone()),
+            None => self.inner.read(key),
+        }
+    }
+
+    fn read_with<T, F>(&self, key: &[u8], f: F) -> Result<T, Self::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> T,
+    {
+        match self.dirty.changes.get(key) {
+            Some(change) => Ok(f(change.as_deref())),
+            None => self.inner.read_with(key, f),
+        }
+    }
+}
+
+impl<R: HasKey> HasKey for BufferedRepo<R> {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        match self.dirty.changes.get(key) {
+            Some(change) => Ok(change.is_some()),
+            None => self.inner.has_key(key),
+        }
+    }
+}
+
+impl<R: Write + Remove> Remove for BufferedRepo<R> {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.buffer(key, None)
+    }
+
+    // the whole batch is buffered before the thresholds are checked, so a flush never splits it
+    fn write_batch(&mut self, ops: &[BatchOp<'_>]) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive_batch(ops);
+
+        for (key, bytes) in ops {
+            self.dirty.insert(key, bytes.as_deref().map(<[u8]>::to_vec));
+        }
+
+        self.flush_if_full(&span)
+    }
+}
@@ -0,0 +1,453 @@
+use std::{
+    cell::{Cell, RefCell},
+    num::NonZeroUsize,
+    time::{Duration, Instant},
+};
+
+use kv_storage::{
+    trace::{self, Op, Traceable},
+    BatchOp, Bound, Durability, Fallible, HasKey, Iterate, Order, RawEntries, RawKeys, Read,
+    Remove, StreamFill, Write,
+};
+use lru::LruCache;
+
+/// How often reads were served from the cache.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// What to do when refreshing an expired entry fails, see [`CachedRepo::with_ttl`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum StalePolicy {
+    /// Return the inner repo's error.
+    #[default]
+    Propagate,
+    /// Serve the expired entry instead, as long as it was cached at most `max_age` ago.
+    ServeStale { max_age: Duration },
+}
+
+/// Whether bytes read through [`CachedRepo::read_with_freshness`] are known to be current.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Freshness {
+    Fresh,
+    /// Served from an expired entry because the inner repo failed, cached `age` ago.
+    Stale {
+        age: Duration,
+    },
+}
+
+struct Entry {
+    /// `None` caches a missing key.
+    bytes: Option<Vec<u8>>,
+    cached_at: Instant,
+}
+
+struct Cache {
+    entries: LruCache<Vec<u8>, Entry>,
+    max_entries: NonZeroUsize,
+    max_bytes: Option<usize>,
+    /// Total bytes of cached keys and values.
+    bytes: usize,
+}
+
+fn entry_len(key: &[u8], bytes: &Option<Vec<u8>>) -> usize {
+    key.len() + bytes.as_ref().map_or(0, Vec::len)
+}
+
+impl Cache {
+    fn insert(&mut self, key: &[u8], bytes: Option<Vec<u8>>, now: Instant) {
+        let len = entry_len(key, &bytes);
+
+        if self.max_bytes.is_some_and(|max| len > max) {
+            self.invalidate(key);
+            return;
+        }
+
+        self.bytes += len;
+
+        let entry = Entry {
+            bytes,
+            cached_at: now,
+        };
+
+        if let Some(old) = self.entries.put(key.to_vec(), entry) {
+            self.bytes -= entry_len(key, &old.bytes);
+        }
+
+        while self.entries.len() > self.max_entries.get()
+            || self.max_bytes.is_some_and(|max| self.bytes > max)
+        {
+            let Some((key, entry)) = self.entries.pop_lru() else {
+                break;
+            };
+
+            self.bytes -= entry_len(&key, &entry.bytes);
+        }
+    }
+
+    fn invalidate(&mut self, key: &[u8]) {
+        if let Some(entry) = self.entries.pop(key) {
+            self.bytes -= entry_len(key, &entry.bytes);
+        }
+    }
+}
+
+/// Serves repeated reads of the same keys from a bounded, least recently used cache in front
+/// of another repo, missing keys included.
+///
+/// Writes and removals go through to the inner repo and update the cache, so reads through the
+/// same handle never see stale data. Changes made to the inner repo by other handles aren't
+/// seen until the cached entries are evicted, expire (see [`CachedRepo::with_ttl`]) or
+/// [`CachedRepo::clear_cache`] is called. Iteration always goes to the inner repo.
+pub struct CachedRepo<R> {
+    inner: R,
+    cache: RefCell<Cache>,
+    stats: Cell<CacheStats>,
+    ttl: Option<Duration>,
+    stale_policy: StalePolicy,
+    clock: Box<dyn Fn() -> Instant + Send>,
+}
+
+impl<R> CachedRepo<R> {
+    /// Cache up to `max_entries` keys.
+    pub fn new(inner: R, max_entries: NonZeroUsize) -> Self {
+        Self {
+            inner,
+            cache: RefCell::new(Cache {
+                entries: LruCache::unbounded(),
+                max_entries,
+                max_bytes: None,
+                bytes: 0,
+            }),
+            stats: Cell::default(),
+            ttl: None,
+            stale_policy: StalePolicy::Propagate,
+            clock: Box::new(Instant::now),
+        }
+    }
+
+    /// Refresh entries from the inner repo once they were cached longer than `ttl` ago, without
+    /// it they're served until evicted.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// What to do when refreshing an expired entry fails, [`StalePolicy::Propagate`] by
+    /// default.
+    pub fn with_stale_policy(mut self, policy: StalePolicy) -> Self {
+        self.stale_policy = policy;
+        self
+    }
+
+    /// Measure entry ages with the given clock instead of [`Instant::now`], e.g. to control
+    /// time in tests.
+    pub fn with_clock(mut self, clock: impl Fn() -> Instant + Send + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Also cap the total bytes of cached keys and values, entries larger than that on their own
+    /// aren't cached.
+    pub fn with_max_bytes(self, max_bytes: usize) -> Self {
+        self.cache.borrow_mut().max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// The inner repo, changes made through it aren't seen by the cache.
+    pub fn mut_inner(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats.get()
+    }
+
+    pub fn reset_stats(&self) {
+        self.stats.take();
+    }
+
+    /// How many keys are cached.
+    pub fn cached(&self) -> usize {
+        self.cache.borrow().entries.len()
+    }
+
+    /// Total bytes of cached keys and values.
+    pub fn cached_bytes(&self) -> usize {
+        self.cache.borrow().bytes
+    }
+
+    /// Forget every cached entry, e.g. after the inner repo was changed through another handle.
+    pub fn clear_cache(&self) {
+        let mut cache = self.cache.borrow_mut();
+        cache.entries.clear();
+        cache.bytes = 0;
+    }
+
+    fn hit(&self) {
+        let mut stats = self.stats.get();
+        stats.hits += 1;
+        self.stats.set(stats);
+    }
+
+    fn miss(&self) {
+        let mut stats = self.stats.get();
+        stats.misses += 1;
+        self.stats.set(stats);
+    }
+
+    /// Keep `key`'s cache entry in line with the outcome of a change to the inner repo, whose
+    /// effect is unknown if it failed.
+    fn update<E>(&self, key: &[u8], bytes: Option<&[u8]>, result: &Result<(), E>) {
+        let now = (self.clock)();
+        let mut cache = self.cache.borrow_mut();
+
+        match result {
+            Ok(()) => cache.insert(key, bytes.map(<[u8]>::to_vec), now),
+            Err(_) => cache.invalidate(key),
+        }
+    }
+
+    /// How long ago the entry was cached, if that's longer than the TTL.
+    fn expired_age(&self, entry: &Entry) -> Option<Duration> {
+        let ttl = self.ttl?;
+        let age = (self.clock)().saturating_duration_since(entry.cached_at);
+
+        (age > ttl).then_some(age)
+    }
+
+    /// Whether an expired entry cached `age` ago may be served in place of an error.
+    fn serves_stale(&self, age: Duration) -> bool {
+        match self.stale_policy {
+            StalePolicy::Propagate => false,
+            StalePolicy::ServeStale { max_age } => age <= max_age,
+        }
+    }
+}
+
+impl<R: Read> CachedRepo<R> {
+    /// Read the bytes at `key` like [`Read::read`], telling whether they were served stale
+    /// under [`StalePolicy::ServeStale`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner repo fails and no stale entry may be
+    /// served instead.
+    pub fn read_with_freshness(
+        &self,
+        key: &[u8],
+    ) -> Result<(Option<Vec<u8>>, Freshness), R::Error> {
+        self.read_fresh(key, |bytes| bytes.map(<[u8]>::to_vec))
+    }
+
+    fn read_fresh<T, F>(&self, key: &[u8], f: F) -> Result<(T, Freshness), R::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> T,
+    {
+        // only expired entries are copied, they may have to be served after the inner read
+        let stale = match self.cache.borrow_mut().entries.get(key) {
+            Some(entry) => match self.expired_age(entry) {
+                None => {
+                    self.hit();
+                    return Ok((f(entry.bytes.as_deref()), Freshness::Fresh));
+                }
+                Some(age) => Some((entry.bytes.clone(), age)),
+            },
+            None => None,
+        };
+
+        self.miss();
+
+        match self.inner.read(key) {
+            Ok(bytes) => {
+                let output = f(bytes.as_deref());
+                let now = (self.clock)();
+                self.cache.borrow_mut().insert(key, bytes, now);
+                Ok((output, Freshness::Fresh))
+            }
+            Err(err) => match stale {
+                Some((bytes, age)) if self.serves_stale(age) => {
+                    Ok((f(bytes.as_deref()), Freshness::Stale { age }))
+                }
+                _ => Err(err),
+            },
+        }
+    }
+}
+
+impl<R> Traceable for CachedRepo<R> {
+    const LAYER: &'static str = "cached";
+}
+
+impl<R: Fallible> Fallible for CachedRepo<R> {
+    type Error = R::Error;
+}
+
+impl<R: Write> Write for CachedRepo<R> {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive(Op::Write, bytes.len());
+        span.issue(Op::Write, bytes.len());
+
+        let result = self.inner.write(key, bytes);
+        self.update(key, Some(bytes), &result);
+        result
+    }
+
+    fn write_durable(
+        &mut self,
+        key: &[u8],
+        bytes: &[u8],
+        durability: Durability,
+    ) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive(Op::Write, bytes.len());
+        span.issue(Op::Write, bytes.len());
+
+        let result = self.inner.write_durable(key, bytes, durability);
+        self.update(key, Some(bytes), &result);
+        result
+    }
+
+    fn supports_durability(&self) -> bool {
+        self.inner.supports_durability()
+    }
+
+    // the streamed bytes never pass through here, so they can't be cached
+    fn write_stream<E>(
+        &mut self,
+        key: &[u8],
+        fill: &mut StreamFill<'_, E>,
+    ) -> Result<Result<(), E>, Self::Error> {
+        let span = trace::enter(self);
+        span.receive(Op::Write, 0);
+        span.issue(Op::Write, 0);
+
+        self.cache.get_mut().invalidate(key);
+        self.inner.write_stream(key, fill)
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+}
+
+impl<R: Read> Read for CachedRepo<R> {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.read_with(key, |bytes| bytes.map(<[u8]>::to_vec))
+    }
+
+    /// Stale entries are served without telling them apart, see
+    /// [`CachedRepo::read_with_freshness`].
+    fn read_with<T, F>(&self, key: &[u8], f: F) -> Result<T, Self::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> T,
+    {
+        self.read_fresh(key, f).map(|(output, _)| output)
+    }
+}
+
+impl<R: HasKey> HasKey for CachedRepo<R> {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        let stale = match self.cache.borrow_mut().entries.get(key) {
+            Some(entry) => match self.expired_age(entry) {
+                None => {
+                    self.hit();
+                    return Ok(entry.bytes.is_some());
+                }
+                Some(age) => Some((entry.bytes.is_some(), age)),
+            },
+            None => None,
+        };
+
+        self.miss();
+
+        let exists = match self.inner.has_key(key) {
+            Ok(exists) => exists,
+            Err(err) => {
+                return match stale {
+                    Some((exists, age)) if self.serves_stale(age) => Ok(exists),
+                    _ => Err(err),
+                }
+            }
+        };
+
+        // the value of an existing key is still unknown, so an expired entry is dropped
+        let mut cache = self.cache.borrow_mut();
+
+        if exists {
+            cache.invalidate(key);
+        } else {
+            cache.insert(key, None, (self.clock)());
+        }
+
+        Ok(exists)
+    }
+}
+
+// the default `remove_returning` checks for the key through the cache
+impl<R: Write + Remove> Remove for CachedRepo<R> {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive(Op::Remove, 0);
+        span.issue(Op::Remove, 0);
+
+        let result = self.inner.remove(key);
+        self.update(key, None, &result);
+        result
+    }
+
+    fn write_batch(&mut self, ops: &[BatchOp<'_>]) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive_batch(ops);
+        span.issue_batch(ops);
+
+        let result = self.inner.write_batch(ops);
+        let now = (self.clock)();
+        let mut cache = self.cache.borrow_mut();
+
+        for (key, bytes) in ops {
+            match result {
+                Ok(()) => cache.insert(key, bytes.as_deref().map(<[u8]>::to_vec), now),
+                // how much of the batch was applied depends on the inner repo
+                Err(_) => cache.invalidate(key),
+            }
+        }
+
+        result
+    }
+}
+
+impl<R: Iterate> Iterate for CachedRepo<R> {
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error> {
+        self.inner.range(min, max, order)
+    }
+
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error> {
+        self.inner.range_keys(min, max, order)
+    }
+
+    fn scan(&self, prefix: &[u8]) -> Result<RawEntries<'_>, Self::Error> {
+        self.inner.scan(prefix)
+    }
+}
@@ -1,6 +1,26 @@
-use kv_storage::{Fallible, HasKey, Read, Remove, Write};
+//! CosmWasm contract storage as a repo, with the default `bincode` feature ready-made stores:
+//!
+//! ```
+//! use cosmwasm_std::testing::MockStorage;
+//! use kv_storage::prelude::*;
+//! use kv_storage_cosmwasm::{CwStore, CwStoreMut, FromCosmwasm, FromCosmwasmReadonly};
+//!
+//! const TOTAL: Item<u64> = item!("total");
+//!
+//! let mut storage = MockStorage::new();
+//!
+//! let mut store = CwStoreMut::cosmwasm(&mut storage);
+//! TOTAL.save(&mut store, 42).unwrap();
+//!
+//! let store = CwStore::cosmwasm_ro(&storage);
+//! assert_eq!(TOTAL.may_load(&store).unwrap(), Some(42));
+//! ```
 
-use cosmwasm_std::Storage;
+use kv_storage::{
+    Bound, Fallible, HasKey, Iterate, KvStore, Order, RawEntries, RawKeys, Read, Remove, Write,
+};
+
+use cosmwasm_std::{CustomQuery, Empty, QuerierWrapper, StdError, Storage};
 
 pub struct CosmwasmRepo<T>(T);
 
@@ -20,6 +40,34 @@ pub type Readonly<'a> = CosmwasmRepo<&'a dyn Storage>;
 
 pub type Mutable<'a> = CosmwasmRepo<&'a mut dyn Storage>;
 
+#[cfg(feature = "bincode")]
+pub type CwStore<'a> = KvStore<kv_storage_bincode::Bincode, Readonly<'a>>;
+
+#[cfg(feature = "bincode")]
+pub type CwStoreMut<'a> = KvStore<kv_storage_bincode::Bincode, Mutable<'a>>;
+
+/// Build a `KvStore` over mutable contract storage, e.g. `KvStore::<Bincode, _>::cosmwasm(deps.storage)`.
+pub trait FromCosmwasm<'a> {
+    fn cosmwasm(storage: &'a mut dyn Storage) -> Self;
+}
+
+/// Build a `KvStore` over readonly contract storage, e.g. `KvStore::<Bincode, _>::cosmwasm_ro(deps.storage)`.
+pub trait FromCosmwasmReadonly<'a> {
+    fn cosmwasm_ro(storage: &'a dyn Storage) -> Self;
+}
+
+impl<'a, Serde: Default> FromCosmwasm<'a> for KvStore<Serde, Mutable<'a>> {
+    fn cosmwasm(storage: &'a mut dyn Storage) -> Self {
+        KvStore::from_repo(storage)
+    }
+}
+
+impl<'a, Serde: Default> FromCosmwasmReadonly<'a> for KvStore<Serde, Readonly<'a>> {
+    fn cosmwasm_ro(storage: &'a dyn Storage) -> Self {
+        KvStore::from_repo(storage)
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("infallible")]
 pub struct Infallible;
@@ -30,40 +78,159 @@ impl<T> Fallible for CosmwasmRepo<T> {
     type Error = Infallible;
 }
 
-impl<'a> Write for CosmwasmRepo<&'a mut dyn Storage> {
+impl Write for CosmwasmRepo<&mut dyn Storage> {
     fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
         self.0.set(key, bytes);
         Ok(())
     }
 }
 
-impl<'a> Read for CosmwasmRepo<&'a mut dyn Storage> {
+// `Storage::get` copies the value out of the host, so there is nothing to lend and the default
+// `read_with` is as good as it gets
+impl Read for CosmwasmRepo<&mut dyn Storage> {
     fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
         Ok(self.0.get(key))
     }
 }
 
-impl<'a> Read for CosmwasmRepo<&'a dyn Storage> {
+impl Read for CosmwasmRepo<&dyn Storage> {
     fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
         Ok(self.0.get(key))
     }
 }
 
-impl<'a> HasKey for CosmwasmRepo<&'a mut dyn Storage> {
-    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
-        Ok(self.0.get(key).is_some())
+// contract storage can only tell whether a key exists by reading it
+impl HasKey for CosmwasmRepo<&mut dyn Storage> {}
+
+impl HasKey for CosmwasmRepo<&dyn Storage> {}
+
+impl Remove for CosmwasmRepo<&mut dyn Storage> {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.0.remove(key);
+        Ok(())
     }
 }
 
-impl<'a> HasKey for CosmwasmRepo<&'a dyn Storage> {
-    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
-        Ok(self.0.get(key).is_some())
+/// The inclusive start and exclusive end `Storage::range` takes for a pair of bounds.
+fn cw_range(min: Bound<&[u8]>, max: Bound<&[u8]>) -> (Option<Vec<u8>>, Option<Vec<u8>>) {
+    // the smallest key after `key` is `key` followed by a zero byte
+    let successor = |key: &[u8]| [key, &[0]].concat();
+
+    let start = match min {
+        Bound::Inclusive(key) => Some(key.to_vec()),
+        Bound::Exclusive(key) => Some(successor(key)),
+        Bound::Unbounded => None,
+    };
+
+    let end = match max {
+        Bound::Inclusive(key) => Some(successor(key)),
+        Bound::Exclusive(key) => Some(key.to_vec()),
+        Bound::Unbounded => None,
+    };
+
+    (start, end)
+}
+
+fn cw_order(order: Order) -> cosmwasm_std::Order {
+    match order {
+        Order::Ascending => cosmwasm_std::Order::Ascending,
+        Order::Descending => cosmwasm_std::Order::Descending,
     }
 }
 
-impl<'a> Remove for CosmwasmRepo<&'a mut dyn Storage> {
-    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
-        self.0.remove(key);
-        Ok(())
+fn range<'a>(
+    storage: &'a dyn Storage,
+    min: Bound<&[u8]>,
+    max: Bound<&[u8]>,
+    order: Order,
+) -> RawEntries<'a> {
+    let (start, end) = cw_range(min, max);
+    storage.range(start.as_deref(), end.as_deref(), cw_order(order))
+}
+
+fn range_keys<'a>(
+    storage: &'a dyn Storage,
+    min: Bound<&[u8]>,
+    max: Bound<&[u8]>,
+    order: Order,
+) -> RawKeys<'a> {
+    let (start, end) = cw_range(min, max);
+    storage.range_keys(start.as_deref(), end.as_deref(), cw_order(order))
+}
+
+impl Iterate for CosmwasmRepo<&mut dyn Storage> {
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error> {
+        Ok(range(&*self.0, min, max, order))
     }
+
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error> {
+        Ok(range_keys(&*self.0, min, max, order))
+    }
+}
+
+impl Iterate for CosmwasmRepo<&dyn Storage> {
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error> {
+        Ok(range(self.0, min, max, order))
+    }
+
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error> {
+        Ok(range_keys(self.0, min, max, order))
+    }
+}
+
+/// Readonly access to another contract's storage through raw wasm queries.
+///
+/// Useful in tests to inspect a deployed contract's state with the same `Item`/`Map`
+/// declarations the contract uses, e.g. over cw-multi-test's `App::wrap()`.
+/// Only `Read` and `HasKey` are implemented, so it can't be used to write.
+///
+/// Raw queries can't tell an empty value from a missing key, both read as `None`.
+pub struct ContractRepo<'a, C: CustomQuery = Empty> {
+    querier: QuerierWrapper<'a, C>,
+    contract: String,
 }
+
+impl<'a, C: CustomQuery> ContractRepo<'a, C> {
+    pub fn new(querier: QuerierWrapper<'a, C>, contract: impl Into<String>) -> Self {
+        Self {
+            querier,
+            contract: contract.into(),
+        }
+    }
+
+    pub fn contract(&self) -> &str {
+        &self.contract
+    }
+}
+
+impl<C: CustomQuery> Fallible for ContractRepo<'_, C> {
+    type Error = StdError;
+}
+
+impl<C: CustomQuery> Read for ContractRepo<'_, C> {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.querier.query_wasm_raw(self.contract.as_str(), key)
+    }
+}
+
+impl<C: CustomQuery> HasKey for ContractRepo<'_, C> {}
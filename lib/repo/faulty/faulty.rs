@@ -0,0 +1,388 @@
+//! A repo wrapper failing operations on demand, for testing error handling:
+//!
+//! ```
+//! use kv_storage::Write;
+//! use kv_storage_faulty::{FaultyRepo, Op};
+//! use kv_storage_memory::MemoryRepo;
+//!
+//! let mut repo = FaultyRepo::new(MemoryRepo::default());
+//! repo.fail_nth(Op::Write, 2);
+//!
+//! repo.write(b"first", b"1").unwrap();
+//! assert!(repo.write(b"second", b"2").is_err());
+//! assert_eq!(repo.counts().writes, 2);
+//! ```
+
+use std::cell::Cell;
+
+use kv_storage::{
+    trace::{self, Traceable},
+    Bound, Durability, Fallible, HasKey, Iterate, Order, RawEntries, RawKeys, Read, Remove,
+    Removed, Write,
+};
+
+/// A kind of operation faults can be injected into.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Op {
+    Read,
+    Write,
+    Remove,
+    HasKey,
+}
+
+/// An operation failed on purpose.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("injected {op:?} fault")]
+pub struct InjectedFault {
+    pub op: Op,
+    pub key: Vec<u8>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error<E> {
+    #[error(transparent)]
+    Fault(InjectedFault),
+    #[error(transparent)]
+    Repo(E),
+}
+
+impl<E> Error<E> {
+    /// The injected fault, if this isn't a failure of the wrapped repo.
+    pub fn fault(&self) -> Option<&InjectedFault> {
+        match self {
+            Error::Fault(fault) => Some(fault),
+            Error::Repo(_) => None,
+        }
+    }
+}
+
+/// How many operations of each kind reached the repo, failed ones included.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct OpCounts {
+    pub reads: u64,
+    pub writes: u64,
+    pub removes: u64,
+    pub has_keys: u64,
+}
+
+impl OpCounts {
+    pub fn get(&self, op: Op) -> u64 {
+        match op {
+            Op::Read => self.reads,
+            Op::Write => self.writes,
+            Op::Remove => self.removes,
+            Op::HasKey => self.has_keys,
+        }
+    }
+
+    fn get_mut(&mut self, op: Op) -> &mut u64 {
+        match op {
+            Op::Read => &mut self.reads,
+            Op::Write => &mut self.writes,
+            Op::Remove => &mut self.removes,
+            Op::HasKey => &mut self.has_keys,
+        }
+    }
+}
+
+enum Fault {
+    /// The nth operation of a kind, counting from 1.
+    Nth { op: Op, n: u64 },
+    /// Every operation on a key, or only those of a kind.
+    Key { op: Option<Op>, key: Vec<u8> },
+    /// Operations failing at random.
+    Random { op: Option<Op>, probability: f64 },
+    /// Every write and removal once `n` of them were made.
+    After { n: u64 },
+}
+
+/// SplitMix64, enough to make random faults reproducible from a seed.
+fn next_random(state: &Cell<u64>) -> f64 {
+    let seed = state.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+    state.set(seed);
+
+    let mut z = seed;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+
+    // the top 53 bits, as a float in [0, 1)
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Wraps a repo, failing its reads, writes, removals and key checks as programmed with
+/// [`InjectedFault`] errors, and counting them.
+///
+/// Writes include durable, owned and streamed writes, and each write or removal of a batch.
+/// Iteration is passed through, neither counted nor failed.
+pub struct FaultyRepo<R> {
+    inner: R,
+    faults: Vec<Fault>,
+    counts: Cell<OpCounts>,
+    rng: Cell<u64>,
+}
+
+impl<R> FaultyRepo<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            faults: Vec::new(),
+            counts: Cell::default(),
+            rng: Cell::new(0),
+        }
+    }
+
+    /// Fail the `n`th operation of the kind, counting from 1 and including those already
+    /// performed.
+    pub fn fail_nth(&mut self, op: Op, n: u64) -> &mut Self {
+        self.faults.push(Fault::Nth { op, n });
+        self
+    }
+
+    /// Fail every operation on the key.
+    pub fn fail_key(&mut self, key: impl Into<Vec<u8>>) -> &mut Self {
+        self.faults.push(Fault::Key {
+            op: None,
+            key: key.into(),
+        });
+        self
+    }
+
+    /// Fail every operation of the kind on the key.
+    pub fn fail_key_on(&mut self, op: Op, key: impl Into<Vec<u8>>) -> &mut Self {
+        self.faults.push(Fault::Key {
+            op: Some(op),
+            key: key.into(),
+        });
+        self
+    }
+
+    /// Fail operations of the kind, or of any kind, with the given probability. The same seed
+    /// fails the same operations.
+    pub fn fail_randomly(&mut self, op: Option<Op>, probability: f64, seed: u64) -> &mut Self {
+        self.faults.push(Fault::Random { op, probability });
+        self.rng.set(seed);
+        self
+    }
+
+    /// Fail every write and removal once `n` of them were made, failed ones included, as if the
+    /// process crashed there.
+    pub fn fail_after(&mut self, n: u64) -> &mut Self {
+        self.faults.push(Fault::After { n });
+        self
+    }
+
+    /// Stop injecting faults, the counts are kept.
+    pub fn clear_faults(&mut self) -> &mut Self {
+        self.faults.clear();
+        self
+    }
+
+    pub fn counts(&self) -> OpCounts {
+        self.counts.get()
+    }
+
+    pub fn reset_counts(&self) {
+        self.counts.take();
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn mut_inner(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Count an operation, failing it if a fault matches.
+    fn check<E>(&self, op: Op, key: &[u8]) -> Result<(), Error<E>> {
+        let mut counts = self.counts.get();
+        *counts.get_mut(op) += 1;
+        self.counts.set(counts);
+
+        let n = counts.get(op);
+        let applies = |kind: &Option<Op>| kind.is_none_or(|kind| kind == op);
+
+        // every random fault draws, so outcomes only depend on the seed and the operations
+        let mut fail = false;
+
+        for fault in &self.faults {
+            fail |= match fault {
+                Fault::Nth { op: kind, n: nth } => *kind == op && *nth == n,
+                Fault::Key {
+                    op: kind,
+                    key: faulty,
+                } => applies(kind) && faulty == key,
+                Fault::Random {
+                    op: kind,
+                    probability,
+                } => applies(kind) && next_random(&self.rng) < *probability,
+                Fault::After { n } => {
+                    matches!(op, Op::Write | Op::Remove) && counts.writes + counts.removes > *n
+                }
+            };
+        }
+
+        if fail {
+            return Err(Error::Fault(InjectedFault {
+                op,
+                key: key.to_vec(),
+            }));
+        }
+
+        Ok(())
+    }
+}
+
+/// Run an operation once for every point it could crash at, checking what each leaves behind.
+///
+/// The operation runs to completion on a repo from `setup` to count its writes and removals,
+/// then once more on a fresh repo for each count `n` below that total, failing everything after
+/// the first `n` with [`FaultyRepo::fail_after`]. `invariant` is called with the inner repo after
+/// every run along with `n`, the total for the complete run. Writes made by `setup` aren't
+/// counted.
+///
+/// Returns the number of writes and removals the operation makes.
+pub fn check_crash_points<R>(
+    mut setup: impl FnMut() -> R,
+    mut operation: impl FnMut(FaultyRepo<R>) -> FaultyRepo<R>,
+    mut invariant: impl FnMut(R, u64),
+) -> u64 {
+    let complete = operation(FaultyRepo::new(setup()));
+    let counts = complete.counts();
+    let total = counts.writes + counts.removes;
+
+    invariant(complete.into_inner(), total);
+
+    for n in 0..total {
+        let mut repo = FaultyRepo::new(setup());
+        repo.fail_after(n);
+
+        invariant(operation(repo).into_inner(), n);
+    }
+
+    total
+}
+
+impl<R> Traceable for FaultyRepo<R> {
+    const LAYER: &'static str = "faulty";
+}
+
+impl<R: Fallible> Fallible for FaultyRepo<R> {
+    type Error = Error<R::Error>;
+}
+
+impl<R: Write> Write for FaultyRepo<R> {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive(trace::Op::Write, bytes.len());
+
+        self.check(Op::Write, key)?;
+        span.issue(trace::Op::Write, bytes.len());
+        self.inner.write(key, bytes).map_err(Error::Repo)
+    }
+
+    fn write_durable(
+        &mut self,
+        key: &[u8],
+        bytes: &[u8],
+        durability: Durability,
+    ) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive(trace::Op::Write, bytes.len());
+
+        self.check(Op::Write, key)?;
+        span.issue(trace::Op::Write, bytes.len());
+        self.inner
+            .write_durable(key, bytes, durability)
+            .map_err(Error::Repo)
+    }
+
+    fn supports_durability(&self) -> bool {
+        self.inner.supports_durability()
+    }
+
+    fn write_owned(&mut self, key: &[u8], bytes: Vec<u8>) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive(trace::Op::Write, bytes.len());
+
+        self.check(Op::Write, key)?;
+        span.issue(trace::Op::Write, bytes.len());
+        self.inner.write_owned(key, bytes).map_err(Error::Repo)
+    }
+
+    fn takes_ownership(&self) -> bool {
+        self.inner.takes_ownership()
+    }
+}
+
+impl<R: Read> Read for FaultyRepo<R> {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.check(Op::Read, key)?;
+        self.inner.read(key).map_err(Error::Repo)
+    }
+
+    fn read_with<T, F>(&self, key: &[u8], f: F) -> Result<T, Self::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> T,
+    {
+        self.check(Op::Read, key)?;
+        self.inner.read_with(key, f).map_err(Error::Repo)
+    }
+}
+
+impl<R: HasKey> HasKey for FaultyRepo<R> {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        self.check(Op::HasKey, key)?;
+        self.inner.has_key(key).map_err(Error::Repo)
+    }
+}
+
+impl<R: Remove + HasKey> Remove for FaultyRepo<R> {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive(trace::Op::Remove, 0);
+
+        self.check(Op::Remove, key)?;
+        span.issue(trace::Op::Remove, 0);
+        self.inner.remove(key).map_err(Error::Repo)
+    }
+
+    // a single removal, however the inner repo learns whether the key existed
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        let span = trace::enter(self);
+        span.receive(trace::Op::Remove, 0);
+
+        self.check(Op::Remove, key)?;
+        span.issue(trace::Op::Remove, 0);
+        self.inner.remove_returning(key).map_err(Error::Repo)
+    }
+}
+
+impl<R: Iterate> Iterate for FaultyRepo<R> {
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error> {
+        self.inner.range(min, max, order).map_err(Error::Repo)
+    }
+
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error> {
+        self.inner.range_keys(min, max, order).map_err(Error::Repo)
+    }
+
+    fn scan(&self, prefix: &[u8]) -> Result<RawEntries<'_>, Self::Error> {
+        self.inner.scan(prefix).map_err(Error::Repo)
+    }
+}
@@ -0,0 +1,150 @@
+use kv_storage::{Bound, Fallible, HasKey, Iterate, Order, RawEntries, RawKeys, Read};
+
+#[derive(Debug, thiserror::Error)]
+#[error("infallible")]
+pub struct Infallible;
+
+pub type Entry = (Box<[u8]>, Box<[u8]>);
+
+/// A readonly repo over a fixed, sorted set of entries, served by binary search.
+///
+/// Ranges binary-search their start and walk the sorted entries from there. It deliberately
+/// doesn't implement `Write` or `Remove`.
+#[derive(Default)]
+pub struct FrozenRepo {
+    entries: Vec<Entry>,
+}
+
+impl FrozenRepo {
+    /// Freeze the given entries, sorting them by key. If a key appears more than once the last
+    /// entry wins.
+    pub fn new(mut entries: Vec<Entry>) -> Self {
+        // stable, so duplicates keep their relative order and the last one can be kept
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        entries.reverse();
+        entries.dedup_by(|(a, _), (b, _)| a == b);
+        entries.reverse();
+
+        entries.shrink_to_fit();
+
+        Self { entries }
+    }
+
+    /// Freeze every entry of a repo that can be scanned.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the repo fails to start the scan.
+    pub fn freeze<R: Iterate>(repo: &R) -> Result<Self, R::Error> {
+        let entries = repo.range(Bound::Unbounded, Bound::Unbounded, Order::Ascending)?;
+
+        Ok(entries.collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        self.entries
+            .binary_search_by(|(k, _)| k.as_ref().cmp(key))
+            .ok()
+            .map(|idx| self.entries[idx].1.as_ref())
+    }
+
+    /// The entries whose keys lie between the bounds, in ascending order.
+    fn between(&self, min: Bound<&[u8]>, max: Bound<&[u8]>) -> &[Entry] {
+        // how many entries sort before `key`, and how many up to and including it
+        let below = |key: &[u8]| self.entries.partition_point(|(k, _)| k.as_ref() < key);
+        let through = |key: &[u8]| self.entries.partition_point(|(k, _)| k.as_ref() <= key);
+
+        let start = match min {
+            Bound::Inclusive(key) => below(key),
+            Bound::Exclusive(key) => through(key),
+            Bound::Unbounded => 0,
+        };
+
+        let end = match max {
+            Bound::Inclusive(key) => through(key),
+            Bound::Exclusive(key) => below(key),
+            Bound::Unbounded => self.entries.len(),
+        };
+
+        &self.entries[start..end.max(start)]
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for FrozenRepo
+where
+    K: Into<Box<[u8]>>,
+    V: Into<Box<[u8]>>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self::new(
+            iter.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
+impl Fallible for FrozenRepo {
+    type Error = Infallible;
+}
+
+impl Read for FrozenRepo {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(self.get(key).map(<[u8]>::to_vec))
+    }
+
+    fn read_with<R, F>(&self, key: &[u8], f: F) -> Result<R, Self::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> R,
+    {
+        Ok(f(self.get(key)))
+    }
+}
+
+impl HasKey for FrozenRepo {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        Ok(self.get(key).is_some())
+    }
+}
+
+impl Iterate for FrozenRepo {
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error> {
+        let entries = self
+            .between(min, max)
+            .iter()
+            .map(|(key, value)| (key.to_vec(), value.to_vec()));
+
+        Ok(match order {
+            Order::Ascending => Box::new(entries),
+            Order::Descending => Box::new(entries.rev()),
+        })
+    }
+
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error> {
+        let keys = self.between(min, max).iter().map(|(key, _)| key.to_vec());
+
+        Ok(match order {
+            Order::Ascending => Box::new(keys),
+            Order::Descending => Box::new(keys.rev()),
+        })
+    }
+}
@@ -0,0 +1,432 @@
+//! A repo keeping each value in its own file under a directory.
+
+use std::{
+    collections::BTreeSet,
+    convert::Infallible,
+    fs::{self, File},
+    io::{self, BufWriter, Write as _},
+    ops::{Bound as StdBound, RangeBounds},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use kv_storage::{
+    Bound, Durability, Fallible, HasKey, Iterate, Order, RawEntries, RawKeys, Read, Remove,
+    Removed, StreamFill, Write,
+};
+
+/// Starts every entry's file name, so temporary files, which start with a dot, never look like
+/// entries, and the empty key still has a name.
+const ENTRY_PREFIX: char = 'k';
+
+/// Starts the directories holding the leading part of a key too long for a single file name.
+const CHUNK_PREFIX: char = 'd';
+
+/// The most hex digits in one path component, keeping names well under the usual 255-byte
+/// `NAME_MAX` however long the key is.
+const CHUNK_LEN: usize = 200;
+
+/// An I/O failure, with the path it happened at.
+#[derive(Debug, thiserror::Error)]
+#[error("{}: {source}", path.display())]
+pub struct Error {
+    pub path: PathBuf,
+    #[source]
+    pub source: io::Error,
+}
+
+/// Attach a path to the I/O errors of a result.
+trait AtPath<T> {
+    fn at(self, path: &Path) -> Result<T, Error>;
+}
+
+impl<T> AtPath<T> for io::Result<T> {
+    fn at(self, path: &Path) -> Result<T, Error> {
+        self.map_err(|source| Error {
+            path: path.to_owned(),
+            source,
+        })
+    }
+}
+
+/// Stores each value in its own file under a root directory, named after the hex-encoded key.
+///
+/// Keys too long for one file name are split into nested directories of 200 hex digits each,
+/// the file holding the rest.
+///
+/// Writes go to a temporary file renamed over the entry, so readers see either the old value
+/// or the new one, never part of it.
+#[derive(Debug, Clone)]
+pub struct FsRepo {
+    root: PathBuf,
+    sharded: bool,
+}
+
+impl FsRepo {
+    /// Use the directory at `root`, created on the first write if needed.
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            sharded: false,
+        }
+    }
+
+    /// Spread the entries over subdirectories named after the first byte of their key, to keep
+    /// directories small.
+    ///
+    /// The layout is part of the stored format: open a directory the way it was written.
+    #[must_use]
+    pub fn sharded(mut self) -> Self {
+        self.sharded = true;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The path of the file holding the entry at `key`.
+    pub fn path(&self, key: &[u8]) -> PathBuf {
+        let mut path = match (self.sharded, key.first()) {
+            (true, Some(first)) => self.root.join(format!("{first:02x}")),
+            _ => self.root.clone(),
+        };
+
+        let hex = encode(key);
+
+        // every chunk but the last names a directory, the last always holds at least a digit
+        let dirs = hex.len().saturating_sub(1) / CHUNK_LEN;
+
+        for chunk in 0..dirs {
+            path.push(format!(
+                "{CHUNK_PREFIX}{}",
+                &hex[chunk * CHUNK_LEN..(chunk + 1) * CHUNK_LEN]
+            ));
+        }
+
+        path.push(format!("{ENTRY_PREFIX}{}", &hex[dirs * CHUNK_LEN..]));
+        path
+    }
+
+    /// Write to a temporary file next to the entry's path with `fill`, then rename it over the
+    /// entry. The file is dropped instead when `fill` fails on its own.
+    fn write_atomic<E>(
+        &self,
+        key: &[u8],
+        sync: bool,
+        fill: impl FnOnce(&mut File) -> io::Result<Result<(), E>>,
+    ) -> Result<Result<(), E>, Error> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+
+        let path = self.path(key);
+        let dir = path.parent().unwrap_or(&self.root);
+        fs::create_dir_all(dir).at(dir)?;
+
+        let temp = dir.join(format!(
+            ".tmp-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let written = File::create(&temp).and_then(|mut file| {
+            let filled = fill(&mut file)?;
+
+            if filled.is_ok() {
+                if sync {
+                    file.sync_all()?;
+                }
+
+                fs::rename(&temp, &path)?;
+            }
+
+            Ok(filled)
+        });
+
+        match written {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => {
+                let _ = fs::remove_file(&temp);
+                return Ok(Err(error));
+            }
+            Err(source) => {
+                let _ = fs::remove_file(&temp);
+                return Err(Error { path, source });
+            }
+        }
+
+        if sync {
+            // the rename is only durable once the directory is
+            File::open(dir).and_then(|dir| dir.sync_all()).at(dir)?;
+        }
+
+        Ok(Ok(()))
+    }
+
+    /// Write `bytes` to a temporary file, then rename it over the entry.
+    fn write_bytes(&self, key: &[u8], bytes: &[u8], sync: bool) -> Result<(), Error> {
+        let Ok(()) =
+            self.write_atomic::<Infallible>(key, sync, |file| file.write_all(bytes).map(Ok))?;
+        Ok(())
+    }
+
+    /// Every key stored, sorted.
+    fn keys(&self) -> Result<BTreeSet<Vec<u8>>, Error> {
+        let mut keys = BTreeSet::new();
+
+        for entry in read_dir(&self.root)? {
+            let entry = entry.at(&self.root)?;
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else { continue };
+
+            if self.sharded && is_shard(name) {
+                collect_keys(&entry.path(), &mut Vec::new(), &mut keys)?;
+            }
+        }
+
+        collect_keys(&self.root, &mut Vec::new(), &mut keys)?;
+
+        Ok(keys)
+    }
+
+    /// The keys between the bounds, in order.
+    fn range_keys_vec(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let range: (StdBound<&[u8]>, StdBound<&[u8]>) = (min.into(), max.into());
+        let keys = self
+            .keys()?
+            .into_iter()
+            .filter(|key| range.contains(key.as_slice()));
+
+        Ok(match order {
+            Order::Ascending => keys.collect(),
+            Order::Descending => keys.rev().collect(),
+        })
+    }
+}
+
+/// The entries of the directory at `path`, none if it doesn't exist yet.
+fn read_dir(path: &Path) -> Result<impl Iterator<Item = io::Result<fs::DirEntry>>, Error> {
+    match fs::read_dir(path) {
+        Ok(entries) => Ok(Some(entries).into_iter().flatten()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None.into_iter().flatten()),
+        Err(source) => Err(Error {
+            path: path.to_owned(),
+            source,
+        }),
+    }
+}
+
+/// Add the keys of the entries under `dir` to `keys`, following the directories of long keys.
+/// `prefix` holds the part of the key the directories above `dir` encode.
+fn collect_keys(
+    dir: &Path,
+    prefix: &mut Vec<u8>,
+    keys: &mut BTreeSet<Vec<u8>>,
+) -> Result<(), Error> {
+    for entry in read_dir(dir)? {
+        let entry = entry.at(dir)?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else { continue };
+
+        if let Some(hex) = name.strip_prefix(ENTRY_PREFIX) {
+            // only the file of a long key sits in a chunk directory, and never names it empty
+            if prefix.is_empty() || !hex.is_empty() {
+                keys.extend(decode(hex).map(|rest| [prefix.as_slice(), &rest].concat()));
+            }
+        } else if let Some(chunk) = name
+            .strip_prefix(CHUNK_PREFIX)
+            .filter(|hex| hex.len() == CHUNK_LEN)
+            .and_then(decode)
+        {
+            let len = prefix.len();
+            prefix.extend(chunk);
+            collect_keys(&entry.path(), prefix, keys)?;
+            prefix.truncate(len);
+        }
+    }
+
+    Ok(())
+}
+
+fn encode(key: &[u8]) -> String {
+    let mut hex = String::with_capacity(key.len() * 2);
+
+    for byte in key {
+        hex.push_str(&format!("{byte:02x}"));
+    }
+
+    hex
+}
+
+/// The bytes hex-encoded in `hex`, `None` if it isn't valid.
+fn decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn is_shard(name: &str) -> bool {
+    name.len() == 2 && name.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// Treat a missing file as a missing entry.
+fn not_found_as_none<T>(result: io::Result<T>) -> io::Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// Keeps the first failure of the writer it wraps, so it can be reported whatever the code
+/// writing through it does with the error.
+struct TrackedWriter<W> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W> TrackedWriter<W> {
+    fn track(&mut self, error: io::Error) -> io::Error {
+        let kind = error.kind();
+
+        // interrupted writes are retried, they aren't failures
+        if kind != io::ErrorKind::Interrupted {
+            self.error.get_or_insert(error);
+        }
+
+        kind.into()
+    }
+}
+
+impl<W: io::Write> io::Write for TrackedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf).map_err(|error| self.track(error))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush().map_err(|error| self.track(error))
+    }
+}
+
+impl Fallible for FsRepo {
+    type Error = Error;
+}
+
+impl Write for FsRepo {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        self.write_bytes(key, bytes, false)
+    }
+
+    /// Any level above [`Durability::Relaxed`] syncs the file and its directory.
+    fn write_durable(
+        &mut self,
+        key: &[u8],
+        bytes: &[u8],
+        durability: Durability,
+    ) -> Result<(), Self::Error> {
+        self.write_bytes(key, bytes, durability > Durability::Relaxed)
+    }
+
+    fn supports_durability(&self) -> bool {
+        true
+    }
+
+    /// Streams into the temporary file, which is then renamed over the entry like any write.
+    fn write_stream<E>(
+        &mut self,
+        key: &[u8],
+        fill: &mut StreamFill<'_, E>,
+    ) -> Result<Result<(), E>, Self::Error> {
+        self.write_atomic(key, false, |file| {
+            let mut writer = TrackedWriter {
+                inner: BufWriter::new(file),
+                error: None,
+            };
+
+            let filled = fill(&mut writer);
+
+            // the file's failures are ours, whatever `fill` made of them
+            if let Some(error) = writer.error {
+                return Err(error);
+            }
+
+            if filled.is_ok() {
+                writer.inner.flush()?;
+            }
+
+            Ok(filled)
+        })
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+}
+
+impl Read for FsRepo {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        let path = self.path(key);
+        not_found_as_none(fs::read(&path)).at(&path)
+    }
+}
+
+impl HasKey for FsRepo {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        let path = self.path(key);
+        path.try_exists().at(&path)
+    }
+}
+
+impl Remove for FsRepo {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.remove_returning(key).map(drop)
+    }
+
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        let path = self.path(key);
+
+        Ok(match not_found_as_none(fs::remove_file(&path)).at(&path)? {
+            Some(()) => Removed::Existed,
+            None => Removed::DidNotExist,
+        })
+    }
+}
+
+/// Lists the directory on every call, entries removed meanwhile are skipped.
+impl Iterate for FsRepo {
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error> {
+        let mut entries = Vec::new();
+
+        for key in self.range_keys_vec(min, max, order)? {
+            if let Some(bytes) = self.read(&key)? {
+                entries.push((key, bytes));
+            }
+        }
+
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error> {
+        let keys = self.range_keys_vec(min, max, order)?;
+        Ok(Box::new(keys.into_iter()))
+    }
+}
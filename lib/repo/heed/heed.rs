@@ -0,0 +1,360 @@
+//! A repo over the unnamed database of an LMDB environment, through `heed`.
+//!
+//! [`HeedRepo`] runs each call in its own transaction. LMDB only writes in explicit
+//! transactions, so [`HeedRepo::begin_write`] keeps one open for a whole mutable session instead.
+
+use std::{ops::Bound as StdBound, path::Path};
+
+use heed::{types::Bytes, Database, Env, EnvOpenOptions, MdbError, RoTxn, RwTxn};
+use kv_storage::{
+    BatchOp, Bound, Fallible, HasKey, Iterate, Order, RawEntries, Read, Remove, Removed, Write,
+};
+
+pub use heed;
+
+type Db = Database<Bytes, Bytes>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The environment's map size was reached, grow it with [`HeedRepo::grow`].
+    #[error("the LMDB map is full")]
+    MapFull,
+    /// A write failed earlier in the session, which aborted its transaction.
+    #[error("the write session was aborted by an earlier error")]
+    Aborted,
+    #[error(transparent)]
+    Heed(heed::Error),
+}
+
+impl From<heed::Error> for Error {
+    fn from(error: heed::Error) -> Self {
+        match error {
+            heed::Error::Mdb(MdbError::MapFull) => Self::MapFull,
+            error => Self::Heed(error),
+        }
+    }
+}
+
+/// Stores entries in the unnamed database of an LMDB environment.
+///
+/// Writes that fill the map grow it, doubling its size, and are retried.
+pub struct HeedRepo {
+    env: Env,
+    db: Db,
+}
+
+impl HeedRepo {
+    /// Open the environment at `path`, an existing directory, with heed's default options.
+    ///
+    /// # Safety
+    ///
+    /// The same as [`EnvOpenOptions::open`], and the environment must not have transactions
+    /// open outside of this repo, since a full map is grown in place.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the environment can't be opened.
+    pub unsafe fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let env = unsafe { EnvOpenOptions::new().open(path)? };
+        unsafe { Self::from_env(env) }
+    }
+
+    /// Use an open environment, creating its unnamed database if needed.
+    ///
+    /// # Safety
+    ///
+    /// The environment must not have transactions open outside of this repo, since a full map
+    /// is grown in place.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the database can't be created.
+    pub unsafe fn from_env(env: Env) -> Result<Self, Error> {
+        let mut txn = env.write_txn()?;
+        let db = env.create_database(&mut txn, None)?;
+        txn.commit()?;
+
+        Ok(Self { env, db })
+    }
+
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    /// The size of the environment's map, in bytes.
+    pub fn map_size(&self) -> usize {
+        self.env.info().map_size
+    }
+
+    /// Double the size of the environment's map.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if LMDB fails to resize the map.
+    pub fn grow(&mut self) -> Result<(), Error> {
+        let size = self.map_size() * 2;
+
+        // SAFETY: the repo is borrowed mutably, so it has no transaction open, and the
+        // constructors' contract rules out others
+        unsafe { self.env.resize(size)? };
+
+        Ok(())
+    }
+
+    /// Open a write transaction for a session, committed by [`HeedSession::commit`].
+    ///
+    /// LMDB allows one write transaction at a time, other writers block until it's done.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the transaction can't be started.
+    pub fn begin_write(&mut self) -> Result<HeedSession<'_>, Error> {
+        Ok(HeedSession {
+            txn: Some(self.env.write_txn()?),
+            db: self.db,
+        })
+    }
+
+    /// Run `f` in a read transaction.
+    fn read_txn<R>(&self, f: impl FnOnce(&RoTxn, Db) -> heed::Result<R>) -> Result<R, Error> {
+        Ok(f(&*self.env.read_txn()?, self.db)?)
+    }
+
+    /// Run `f` in a write transaction and commit it, growing the map and retrying if it's full.
+    fn write_txn<R>(&mut self, f: impl Fn(&mut RwTxn, Db) -> heed::Result<R>) -> Result<R, Error> {
+        loop {
+            let result = {
+                let mut txn = self.env.write_txn()?;
+                f(&mut txn, self.db).and_then(|result| txn.commit().map(|()| result))
+            };
+
+            match result.map_err(Error::from) {
+                Err(Error::MapFull) => self.grow()?,
+                result => return result,
+            }
+        }
+    }
+}
+
+/// A write transaction held for a mutable session, see [`HeedRepo::begin_write`].
+///
+/// Reads see the session's own writes. Dropping the session commits it too, but discards any
+/// error: use [`HeedSession::commit`] to see them.
+///
+/// LMDB can't carry on a transaction after a failed write, so one aborts the whole session and
+/// every later call returns [`Error::Aborted`]. After an [`Error::MapFull`], grow the map and
+/// redo the session.
+pub struct HeedSession<'a> {
+    /// `None` once aborted.
+    txn: Option<RwTxn<'a>>,
+    db: Db,
+}
+
+impl HeedSession<'_> {
+    /// Commit the writes made in the session.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the session was aborted or LMDB fails to commit,
+    /// nothing is applied then.
+    pub fn commit(mut self) -> Result<(), Error> {
+        Ok(self.txn.take().ok_or(Error::Aborted)?.commit()?)
+    }
+
+    /// Drop the writes made in the session.
+    pub fn abort(mut self) {
+        if let Some(txn) = self.txn.take() {
+            txn.abort();
+        }
+    }
+
+    fn read_txn<R>(&self, f: impl FnOnce(&RoTxn, Db) -> heed::Result<R>) -> Result<R, Error> {
+        let txn = self.txn.as_ref().ok_or(Error::Aborted)?;
+        Ok(f(txn, self.db)?)
+    }
+
+    /// Run `f` in the session's transaction, aborting it if `f` fails.
+    fn write_txn<R>(
+        &mut self,
+        f: impl FnOnce(&mut RwTxn, Db) -> heed::Result<R>,
+    ) -> Result<R, Error> {
+        let txn = self.txn.as_mut().ok_or(Error::Aborted)?;
+        let result = f(txn, self.db);
+
+        if result.is_err() {
+            self.txn = None;
+        }
+
+        Ok(result?)
+    }
+}
+
+impl Drop for HeedSession<'_> {
+    fn drop(&mut self) {
+        if let Some(txn) = self.txn.take() {
+            let _ = txn.commit();
+        }
+    }
+}
+
+fn read_with<R, F>(txn: &RoTxn, db: Db, key: &[u8], f: F) -> heed::Result<R>
+where
+    F: FnOnce(Option<&[u8]>) -> R,
+{
+    Ok(f(db.get(txn, key)?))
+}
+
+fn remove_returning(txn: &mut RwTxn, db: Db, key: &[u8]) -> heed::Result<Removed> {
+    Ok(if db.delete(txn, key)? {
+        Removed::Existed
+    } else {
+        Removed::DidNotExist
+    })
+}
+
+fn write_batch(txn: &mut RwTxn, db: Db, ops: &[BatchOp<'_>]) -> heed::Result<()> {
+    for (key, bytes) in ops {
+        match bytes {
+            Some(bytes) => db.put(txn, key, bytes)?,
+            None => {
+                db.delete(txn, key)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Collect the entries between the bounds, the transaction doesn't outlive the call.
+fn range(
+    txn: &RoTxn,
+    db: Db,
+    min: Bound<&[u8]>,
+    max: Bound<&[u8]>,
+    order: Order,
+) -> heed::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    if Bound::is_empty_range(&min, &max) {
+        return Ok(Vec::new());
+    }
+
+    let range = (StdBound::from(min), StdBound::from(max));
+    let owned = |entry: heed::Result<(&[u8], &[u8])>| {
+        entry.map(|(key, bytes)| (key.to_vec(), bytes.to_vec()))
+    };
+
+    match order {
+        Order::Ascending => db.range(txn, &range)?.map(owned).collect(),
+        Order::Descending => db.rev_range(txn, &range)?.map(owned).collect(),
+    }
+}
+
+impl Fallible for HeedRepo {
+    type Error = Error;
+}
+
+impl Write for HeedRepo {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        self.write_txn(|txn, db| db.put(txn, key, bytes))
+    }
+
+    // every write is committed, which LMDB syncs by default
+    fn supports_durability(&self) -> bool {
+        true
+    }
+}
+
+impl Read for HeedRepo {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.read_with(key, |bytes| bytes.map(<[u8]>::to_vec))
+    }
+
+    fn read_with<R, F>(&self, key: &[u8], f: F) -> Result<R, Self::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> R,
+    {
+        self.read_txn(|txn, db| read_with(txn, db, key, f))
+    }
+}
+
+impl HasKey for HeedRepo {}
+
+impl Remove for HeedRepo {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.remove_returning(key).map(drop)
+    }
+
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        self.write_txn(|txn, db| remove_returning(txn, db, key))
+    }
+
+    /// Applies the ops atomically, in one transaction.
+    fn write_batch(&mut self, ops: &[BatchOp<'_>]) -> Result<(), Self::Error> {
+        self.write_txn(|txn, db| write_batch(txn, db, ops))
+    }
+}
+
+/// Entries are read eagerly, the read transaction ends with the call.
+impl Iterate for HeedRepo {
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error> {
+        let entries = self.read_txn(|txn, db| range(txn, db, min, max, order))?;
+        Ok(Box::new(entries.into_iter()))
+    }
+}
+
+impl Fallible for HeedSession<'_> {
+    type Error = Error;
+}
+
+impl Write for HeedSession<'_> {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        self.write_txn(|txn, db| db.put(txn, key, bytes))
+    }
+}
+
+impl Read for HeedSession<'_> {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.read_with(key, |bytes| bytes.map(<[u8]>::to_vec))
+    }
+
+    fn read_with<R, F>(&self, key: &[u8], f: F) -> Result<R, Self::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> R,
+    {
+        self.read_txn(|txn, db| read_with(txn, db, key, f))
+    }
+}
+
+impl HasKey for HeedSession<'_> {}
+
+impl Remove for HeedSession<'_> {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.remove_returning(key).map(drop)
+    }
+
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        self.write_txn(|txn, db| remove_returning(txn, db, key))
+    }
+
+    /// A failing op aborts the whole session, so the batch is all or nothing.
+    fn write_batch(&mut self, ops: &[BatchOp<'_>]) -> Result<(), Self::Error> {
+        self.write_txn(|txn, db| write_batch(txn, db, ops))
+    }
+}
+
+/// Entries are read eagerly, like [`HeedRepo`]'s.
+impl Iterate for HeedSession<'_> {
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error> {
+        let entries = self.read_txn(|txn, db| range(txn, db, min, max, order))?;
+        Ok(Box::new(entries.into_iter()))
+    }
+}
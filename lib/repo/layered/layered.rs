@@ -0,0 +1,195 @@
+use std::cell::{Ref, RefCell};
+
+use kv_storage::{
+    system,
+    trace::{self, Op, Traceable},
+    Fallible, HasKey, Read, Remove, Removed, Write,
+};
+
+/// What the primary's tombstone keys are prefixed with unless configured otherwise.
+///
+/// It is [`system::TOMBSTONE`], in the namespace [`kv_storage::Item`], [`kv_storage::Map`] and
+/// the other containers refuse, so no declared key can collide with a tombstone. Raw keys
+/// written straight to the repo can still start with it.
+pub const DEFAULT_TOMBSTONE_PREFIX: &[u8] = system::TOMBSTONE;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error<P, F> {
+    #[error("primary: {0}")]
+    Primary(P),
+    #[error("fallback: {0}")]
+    Fallback(F),
+}
+
+/// Reads from a primary repo, falling back to a second one for keys the primary doesn't have,
+/// e.g. while migrating from a legacy store.
+///
+/// Writes and removals only ever reach the primary. Removing a key the fallback still has
+/// leaves a tombstone in the primary, an empty value under the tombstone prefix followed by the
+/// key, so the key reads as missing from then on. Writing the key again makes it visible, the
+/// stale tombstone is left in place.
+pub struct LayeredRepo<P, F> {
+    primary: RefCell<P>,
+    fallback: F,
+    tombstone_prefix: Vec<u8>,
+    promote_on_read: bool,
+}
+
+impl<P, F> LayeredRepo<P, F> {
+    pub fn new(primary: P, fallback: F) -> Self {
+        Self {
+            primary: RefCell::new(primary),
+            fallback,
+            tombstone_prefix: DEFAULT_TOMBSTONE_PREFIX.to_vec(),
+            promote_on_read: false,
+        }
+    }
+
+    /// Prefix tombstone keys with `prefix` instead of [`DEFAULT_TOMBSTONE_PREFIX`], it must not
+    /// start any key stored through the repo.
+    pub fn with_tombstone_prefix(mut self, prefix: impl Into<Vec<u8>>) -> Self {
+        self.tombstone_prefix = prefix.into();
+        self
+    }
+
+    /// Copy values read from the fallback into the primary, so each key is only looked up in
+    /// the fallback once.
+    pub fn promote_on_read(mut self, promote: bool) -> Self {
+        self.promote_on_read = promote;
+        self
+    }
+
+    pub fn primary(&self) -> Ref<'_, P> {
+        self.primary.borrow()
+    }
+
+    pub fn fallback(&self) -> &F {
+        &self.fallback
+    }
+
+    pub fn into_parts(self) -> (P, F) {
+        (self.primary.into_inner(), self.fallback)
+    }
+
+    fn tombstone(&self, key: &[u8]) -> Vec<u8> {
+        [&self.tombstone_prefix, key].concat()
+    }
+}
+
+impl<P: HasKey, F: Fallible> LayeredRepo<P, F> {
+    fn is_tombstoned(&self, key: &[u8]) -> Result<bool, Error<P::Error, F::Error>> {
+        self.primary
+            .borrow()
+            .has_key(&self.tombstone(key))
+            .map_err(Error::Primary)
+    }
+}
+
+impl<P, F> Traceable for LayeredRepo<P, F> {
+    const LAYER: &'static str = "layered";
+}
+
+impl<P: Fallible, F: Fallible> Fallible for LayeredRepo<P, F> {
+    type Error = Error<P::Error, F::Error>;
+}
+
+impl<P: Write, F: Fallible> Write for LayeredRepo<P, F> {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive(Op::Write, bytes.len());
+        span.issue(Op::Write, bytes.len());
+
+        self.primary
+            .get_mut()
+            .write(key, bytes)
+            .map_err(Error::Primary)
+    }
+
+    fn write_owned(&mut self, key: &[u8], bytes: Vec<u8>) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive(Op::Write, bytes.len());
+        span.issue(Op::Write, bytes.len());
+
+        self.primary
+            .get_mut()
+            .write_owned(key, bytes)
+            .map_err(Error::Primary)
+    }
+
+    fn takes_ownership(&self) -> bool {
+        self.primary.borrow().takes_ownership()
+    }
+}
+
+impl<P: Write + HasKey, F: Read> Read for LayeredRepo<P, F> {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        if let Some(bytes) = self.primary.borrow().read(key).map_err(Error::Primary)? {
+            return Ok(Some(bytes));
+        }
+
+        if self.is_tombstoned(key)? {
+            return Ok(None);
+        }
+
+        let bytes = self.fallback.read(key).map_err(Error::Fallback)?;
+
+        if let (Some(bytes), true) = (&bytes, self.promote_on_read) {
+            self.primary
+                .borrow_mut()
+                .write(key, bytes)
+                .map_err(Error::Primary)?;
+        }
+
+        Ok(bytes)
+    }
+}
+
+impl<P: Write + HasKey, F: HasKey> HasKey for LayeredRepo<P, F> {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        if self.primary.borrow().has_key(key).map_err(Error::Primary)? {
+            return Ok(true);
+        }
+
+        if self.is_tombstoned(key)? {
+            return Ok(false);
+        }
+
+        self.fallback.has_key(key).map_err(Error::Fallback)
+    }
+}
+
+impl<P: Write + Remove + HasKey, F: HasKey> Remove for LayeredRepo<P, F> {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.remove_returning(key).map(drop)
+    }
+
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        let span = trace::enter(self);
+        span.receive(Op::Remove, 0);
+
+        span.issue(Op::Read, 0);
+        let tombstoned = self.is_tombstoned(key)?;
+
+        if !tombstoned {
+            span.issue(Op::Read, 0);
+        }
+
+        let in_fallback = !tombstoned && self.fallback.has_key(key).map_err(Error::Fallback)?;
+
+        let tombstone = self.tombstone(key);
+        let primary = self.primary.get_mut();
+
+        span.issue(Op::Remove, 0);
+        let removed = primary.remove_returning(key).map_err(Error::Primary)?;
+
+        if in_fallback {
+            span.issue(Op::Write, 0);
+            primary.write(&tombstone, &[]).map_err(Error::Primary)?;
+        }
+
+        Ok(match (removed, in_fallback) {
+            (Removed::DidNotExist, false) => Removed::DidNotExist,
+            _ => Removed::Existed,
+        })
+    }
+}
@@ -0,0 +1,473 @@
+//! A bitcask-style repo: one append-only log file, indexed in memory.
+//!
+//! Each record is laid out as `key_len: u32`, `value_len: u32`, `header_crc: u32`, `key`,
+//! `value`, `crc: u32`, all little-endian. A `value_len` of `u32::MAX` marks a tombstone, without
+//! a value. The header CRC covers the two lengths, so a damaged length is caught before it is
+//! trusted, the final CRC covers everything before it in the record.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, Read as _, Seek, SeekFrom, Write as _},
+    ops::Bound as StdBound,
+    path::{Path, PathBuf},
+};
+
+use kv_storage::{
+    Bound, Compactable, CompactionReport, CompactionStats, Durability, Fallible, HasKey, Iterate,
+    Order, RawEntries, RawKeys, Read, Remove, Removed, Write,
+};
+
+const TOMBSTONE: u32 = u32::MAX;
+
+/// The bytes of a record's lengths and their CRC.
+const HEADER_LEN: usize = 12;
+
+/// The bytes a record takes on top of its key and value.
+const OVERHEAD: u64 = HEADER_LEN as u64 + 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// A record failed its CRC check, or didn't parse.
+    #[error("corrupt record at offset {offset}")]
+    Corrupt { offset: u64 },
+}
+
+/// Where the live record of a key sits in the log.
+#[derive(Debug, Copy, Clone)]
+struct Location {
+    offset: u64,
+    len: u64,
+}
+
+impl Location {
+    /// Where the value starts, for a key of `key_len` bytes.
+    fn value_start(key_len: usize) -> usize {
+        HEADER_LEN + key_len
+    }
+}
+
+/// Stores entries in a single append-only log file.
+///
+/// Every key's latest record is indexed in memory, reads seek to it. Overwritten and removed
+/// values stay in the file until [`Compactable::compact`] rewrites it with only the live ones.
+pub struct LogRepo {
+    path: PathBuf,
+    /// Seeking needs the file mutably, reads included.
+    file: RefCell<File>,
+    index: BTreeMap<Vec<u8>, Location>,
+    /// The end of the last whole record.
+    len: u64,
+    dead_bytes: u64,
+}
+
+impl LogRepo {
+    /// Open the log at `path`, creating it if needed, and rebuild the index by replaying it.
+    ///
+    /// A torn final record, as left by a crash mid-write, is cut off the file. A damaged record
+    /// with others after it, or a damaged header anywhere, is corruption, the file is then left as
+    /// it is.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file can't be opened or read, or holds a corrupt
+    /// record before its end.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        let mut repo = Self {
+            path,
+            file: RefCell::new(file),
+            index: BTreeMap::new(),
+            len: 0,
+            dead_bytes: 0,
+        };
+
+        repo.replay()?;
+
+        Ok(repo)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn replay(&mut self) -> Result<(), Error> {
+        let file = self.file.get_mut().try_clone()?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(&file);
+
+        while self.len < file_len {
+            let offset = self.len;
+
+            let torn = match read_record(&mut reader) {
+                Ok(record) => {
+                    let location = Location {
+                        offset,
+                        len: record.len,
+                    };
+
+                    self.len += record.len;
+                    self.index_record(record.key, record.value.is_some(), location);
+
+                    continue;
+                }
+                // the header's CRC held, so the lengths are right and the file really ends early
+                Err(RecordError::Torn) => true,
+                // a bad final record is as torn as a short one
+                Err(RecordError::Crc { len }) => offset + len >= file_len,
+                Err(RecordError::Header) => false,
+                Err(RecordError::Io(error)) => return Err(error.into()),
+            };
+
+            if !torn {
+                return Err(Error::Corrupt { offset });
+            }
+
+            break;
+        }
+
+        if self.len < file_len {
+            file.set_len(self.len)?;
+        }
+
+        Ok(())
+    }
+
+    /// Point `key` at a record just appended, counting what it supersedes as dead.
+    fn index_record(&mut self, key: Vec<u8>, live: bool, location: Location) {
+        let superseded = if live {
+            self.index.insert(key, location)
+        } else {
+            self.dead_bytes += location.len;
+            self.index.remove(&key)
+        };
+
+        if let Some(superseded) = superseded {
+            self.dead_bytes += superseded.len;
+        }
+    }
+
+    /// Append a record, a `None` value being a tombstone.
+    fn append(&mut self, key: &[u8], value: Option<&[u8]>, sync: bool) -> Result<(), Error> {
+        let record = encode_record(key, value)?;
+        let location = Location {
+            offset: self.len,
+            len: record.len() as u64,
+        };
+
+        let file = self.file.get_mut();
+        file.seek(SeekFrom::Start(location.offset))?;
+        file.write_all(&record)?;
+
+        if sync {
+            file.sync_data()?;
+        }
+
+        self.len += location.len;
+        self.index_record(key.to_vec(), value.is_some(), location);
+
+        Ok(())
+    }
+
+    /// Read the value of the record at `location`, checking its CRC.
+    fn read_at(&self, key: &[u8], location: Location) -> Result<Vec<u8>, Error> {
+        let corrupt = || Error::Corrupt {
+            offset: location.offset,
+        };
+
+        let len = usize::try_from(location.len).map_err(|_| corrupt())?;
+        let mut record = vec![0; len];
+
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(location.offset))?;
+        file.read_exact(&mut record)?;
+
+        let (body, crc) = record.split_at(len - 4);
+
+        if crc32fast::hash(body).to_le_bytes() != crc {
+            return Err(corrupt());
+        }
+
+        record.truncate(len - 4);
+        Ok(record.split_off(Location::value_start(key.len())))
+    }
+
+    /// The keys between the bounds, with where their records are, in order.
+    fn locate(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Vec<(Vec<u8>, Location)> {
+        if Bound::is_empty_range(&min, &max) {
+            return Vec::new();
+        }
+
+        let range = self
+            .index
+            .range::<[u8], _>((StdBound::from(min), StdBound::from(max)))
+            .map(|(key, location)| (key.clone(), *location));
+
+        match order {
+            Order::Ascending => range.collect(),
+            Order::Descending => range.rev().collect(),
+        }
+    }
+}
+
+struct Record {
+    key: Vec<u8>,
+    value: Option<Vec<u8>>,
+    len: u64,
+}
+
+enum RecordError {
+    /// The file ended mid-record.
+    Torn,
+    /// The lengths failed their CRC check, so where the record ends is unknown.
+    Header,
+    /// The record, `len` bytes long, failed its CRC check.
+    Crc {
+        len: u64,
+    },
+    Io(io::Error),
+}
+
+impl From<io::Error> for RecordError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => Self::Torn,
+            _ => Self::Io(error),
+        }
+    }
+}
+
+fn encode_record(key: &[u8], value: Option<&[u8]>) -> io::Result<Vec<u8>> {
+    let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "record too large for the log");
+
+    let key_len = u32::try_from(key.len()).map_err(|_| too_large())?;
+    let value_len = match value {
+        Some(value) => u32::try_from(value.len())
+            .ok()
+            .filter(|len| *len != TOMBSTONE)
+            .ok_or_else(too_large)?,
+        None => TOMBSTONE,
+    };
+
+    let value = value.unwrap_or_default();
+    let mut record = Vec::with_capacity(key.len() + value.len() + OVERHEAD as usize);
+
+    record.extend_from_slice(&key_len.to_le_bytes());
+    record.extend_from_slice(&value_len.to_le_bytes());
+
+    let header_crc = crc32fast::hash(&record);
+    record.extend_from_slice(&header_crc.to_le_bytes());
+    record.extend_from_slice(key);
+    record.extend_from_slice(value);
+
+    let crc = crc32fast::hash(&record);
+    record.extend_from_slice(&crc.to_le_bytes());
+
+    Ok(record)
+}
+
+/// Read `len` bytes without trusting `len` with an allocation, it may be garbage.
+fn read_bytes(reader: &mut impl io::Read, len: u32) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(len.into()).read_to_end(&mut bytes)?;
+
+    if bytes.len() < len as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(bytes)
+}
+
+fn read_record(reader: &mut impl io::Read) -> Result<Record, RecordError> {
+    let mut header = [0; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+
+    let (lengths, header_crc) = header.split_at(8);
+
+    if crc32fast::hash(lengths).to_le_bytes() != header_crc {
+        return Err(RecordError::Header);
+    }
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&header);
+
+    let key_len = u32::from_le_bytes([lengths[0], lengths[1], lengths[2], lengths[3]]);
+    let value_len = u32::from_le_bytes([lengths[4], lengths[5], lengths[6], lengths[7]]);
+
+    let key = read_bytes(reader, key_len)?;
+    hasher.update(&key);
+
+    let value = if value_len == TOMBSTONE {
+        None
+    } else {
+        let value = read_bytes(reader, value_len)?;
+        hasher.update(&value);
+        Some(value)
+    };
+
+    let mut crc = [0; 4];
+    reader.read_exact(&mut crc)?;
+
+    let len = OVERHEAD + u64::from(key_len) + value.as_ref().map_or(0, |value| value.len() as u64);
+
+    if hasher.finalize().to_le_bytes() != crc {
+        return Err(RecordError::Crc { len });
+    }
+
+    Ok(Record { key, value, len })
+}
+
+impl Fallible for LogRepo {
+    type Error = Error;
+}
+
+impl Write for LogRepo {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        self.append(key, Some(bytes), false)
+    }
+
+    /// Records are handed to the OS as they are appended, [`Durability::Sync`] syncs the file
+    /// too.
+    fn write_durable(
+        &mut self,
+        key: &[u8],
+        bytes: &[u8],
+        durability: Durability,
+    ) -> Result<(), Self::Error> {
+        self.append(key, Some(bytes), durability == Durability::Sync)
+    }
+
+    fn supports_durability(&self) -> bool {
+        true
+    }
+}
+
+impl Read for LogRepo {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.index
+            .get(key)
+            .map(|location| self.read_at(key, *location))
+            .transpose()
+    }
+}
+
+impl HasKey for LogRepo {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        Ok(self.index.contains_key(key))
+    }
+}
+
+impl Remove for LogRepo {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.remove_returning(key).map(drop)
+    }
+
+    /// Only appends a tombstone for a key that exists.
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        if !self.index.contains_key(key) {
+            return Ok(Removed::DidNotExist);
+        }
+
+        self.append(key, None, false)?;
+        Ok(Removed::Existed)
+    }
+}
+
+/// Values are read eagerly, a corrupt one fails the whole range.
+impl Iterate for LogRepo {
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error> {
+        let entries = self
+            .locate(min, max, order)
+            .into_iter()
+            .map(|(key, location)| {
+                let bytes = self.read_at(&key, location)?;
+                Ok((key, bytes))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error> {
+        let keys = self.locate(min, max, order).into_iter().map(|(key, _)| key);
+        Ok(Box::new(keys))
+    }
+}
+
+impl Compactable for LogRepo {
+    fn compaction_stats(&self) -> CompactionStats {
+        CompactionStats {
+            live_keys: self.index.len() as u64,
+            dead_bytes: self.dead_bytes,
+            total_bytes: self.len,
+        }
+    }
+
+    /// Writes the live records to a new file, then renames it over the log, so a crash leaves
+    /// either the old log or the compacted one.
+    fn compact(&mut self) -> Result<CompactionReport, Self::Error> {
+        let before = self.compaction_stats();
+
+        let mut compacted_path = self.path.clone().into_os_string();
+        compacted_path.push(".compact");
+        let compacted_path = PathBuf::from(compacted_path);
+
+        let mut compacted = File::create(&compacted_path)?;
+        let mut index = BTreeMap::new();
+        let mut len = 0;
+
+        for (key, location) in &self.index {
+            let value = self.read_at(key, *location)?;
+            let record = encode_record(key, Some(&value))?;
+
+            compacted.write_all(&record)?;
+            index.insert(
+                key.clone(),
+                Location {
+                    offset: len,
+                    len: record.len() as u64,
+                },
+            );
+            len += record.len() as u64;
+        }
+
+        compacted.sync_all()?;
+        drop(compacted);
+        fs::rename(&compacted_path, &self.path)?;
+
+        *self.file.get_mut() = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        self.index = index;
+        self.len = len;
+        self.dead_bytes = 0;
+
+        Ok(CompactionReport {
+            before,
+            after: self.compaction_stats(),
+        })
+    }
+}
@@ -1,14 +1,212 @@
-use std::collections::HashMap;
+//! An in-memory repo, and with the default `bincode` feature a ready-made store:
+//!
+//! ```
+//! use kv_storage_memory::prelude::*;
+//!
+//! const TOTAL: Item<u64> = item!("total");
+//!
+//! let mut store = MemStore::new_in_memory();
+//! TOTAL.save(&mut store, 42).unwrap();
+//! assert_eq!(TOTAL.may_load(&store).unwrap(), Some(42));
+//! ```
 
-use kv_storage::{Fallible, HasKey, Read, Remove, Write};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read as _},
+    path::Path,
+};
+
+use kv_storage::{
+    BatchOp, Bound, Fallible, HasKey, Iterate, KvStore, Order, RawEntries, RawKeys, Read, Remove,
+    Removed, Write,
+};
+
+pub mod prelude {
+    pub use kv_storage::prelude::*;
+
+    #[cfg(feature = "bincode")]
+    pub use crate::MemStore;
+    pub use crate::{MemoryRepo, NewInMemory};
+
+    /// Content assertions and repos for testing error handling and access patterns.
+    #[cfg(feature = "testing")]
+    pub mod testing {
+        pub use kv_storage::testing::*;
+        pub use kv_storage_faulty::{FaultyRepo, InjectedFault, Op as FaultOp, OpCounts};
+        pub use kv_storage_replay::{OpLog, ReadCounts, RecordingRepo, Replay, ReplayError};
+    }
+}
 
 #[derive(Debug, thiserror::Error)]
 #[error("infallible")]
 pub struct Infallible;
 
+/// Keeps entries in a `BTreeMap`, so ranges and scans are ordered by key bytes exactly like on
+/// disk-backed repos. That makes it a deterministic test double for code that iterates.
 #[derive(Default)]
 pub struct MemoryRepo {
-    map: HashMap<Vec<u8>, Vec<u8>>,
+    map: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl MemoryRepo {
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Capture the current contents, to be brought back with [`MemoryRepo::restore`].
+    ///
+    /// Checkpoints are full copies, independent of each other and of the repo, so any of them
+    /// can be restored any number of times, in any order.
+    #[must_use]
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            map: self.map.clone(),
+        }
+    }
+
+    /// Rewind to exactly the checkpointed contents, dropping keys created since and bringing
+    /// back keys removed since.
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.map = checkpoint.map;
+    }
+
+    /// Write the contents to `writer`, to be read back with [`MemoryRepo::load_from`].
+    ///
+    /// The dump starts with the magic bytes `KVMR` and a version byte, then holds the entry
+    /// count and each key and value, all length-prefixed with little-endian `u64`s.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing fails.
+    pub fn dump_to<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(DUMP_MAGIC)?;
+        writer.write_all(&[DUMP_VERSION])?;
+        writer.write_all(&(self.map.len() as u64).to_le_bytes())?;
+
+        for (key, value) in &self.map {
+            for bytes in [key, value] {
+                writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+                writer.write_all(bytes)?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// Read back a repo written by [`MemoryRepo::dump_to`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if reading fails, or with
+    /// [`io::ErrorKind::InvalidData`] if the data isn't a dump of a known version.
+    pub fn load_from<R: io::Read>(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; DUMP_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+
+        if magic != *DUMP_MAGIC {
+            return Err(invalid_dump("not a memory repo dump"));
+        }
+
+        let mut version = [0];
+        reader.read_exact(&mut version)?;
+
+        if version[0] != DUMP_VERSION {
+            return Err(invalid_dump(format!(
+                "unsupported memory repo dump version {}, expected {DUMP_VERSION}",
+                version[0]
+            )));
+        }
+
+        let mut map = BTreeMap::new();
+
+        for _ in 0..read_u64(&mut reader)? {
+            let key = read_length_prefixed(&mut reader)?;
+            let value = read_length_prefixed(&mut reader)?;
+            map.insert(key, value);
+        }
+
+        Ok(Self { map })
+    }
+
+    /// [`MemoryRepo::dump_to`] a file at `path`, replacing it if it exists.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file can't be created or written.
+    pub fn dump_to_path(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.dump_to(BufWriter::new(File::create(path)?))
+    }
+
+    /// [`MemoryRepo::load_from`] the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file can't be read or isn't a valid dump.
+    pub fn load_from_path(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::load_from(BufReader::new(File::open(path)?))
+    }
+}
+
+const DUMP_MAGIC: &[u8; 4] = b"KVMR";
+const DUMP_VERSION: u8 = 1;
+
+fn invalid_dump(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn read_u64(reader: &mut impl io::Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Read a length-prefixed byte string, without trusting the length with an allocation.
+fn read_length_prefixed(reader: &mut impl io::Read) -> io::Result<Vec<u8>> {
+    let len = read_u64(reader)?;
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+
+    if (bytes.len() as u64) < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(bytes)
+}
+
+/// The contents of a [`MemoryRepo`] at some point, see [`MemoryRepo::checkpoint`].
+#[derive(Clone)]
+pub struct Checkpoint {
+    map: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+#[cfg(feature = "bincode")]
+pub type MemStore = KvStore<kv_storage_bincode::Bincode, MemoryRepo>;
+
+pub trait NewInMemory {
+    /// Create an empty store backed by a `MemoryRepo`.
+    fn new_in_memory() -> Self;
+}
+
+impl<Serde: Default> NewInMemory for KvStore<Serde, MemoryRepo> {
+    fn new_in_memory() -> Self {
+        KvStore::from_repo(MemoryRepo::default())
+    }
+}
+
+impl IntoIterator for MemoryRepo {
+    type Item = (Vec<u8>, Vec<u8>);
+    type IntoIter = std::collections::btree_map::IntoIter<Vec<u8>, Vec<u8>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.into_iter()
+    }
 }
 
 impl Fallible for MemoryRepo {
@@ -20,11 +218,27 @@ impl Write for MemoryRepo {
         self.map.insert(key.to_owned(), bytes.to_owned());
         Ok(())
     }
+
+    fn write_owned(&mut self, key: &[u8], bytes: Vec<u8>) -> Result<(), Self::Error> {
+        self.map.insert(key.to_owned(), bytes);
+        Ok(())
+    }
+
+    fn takes_ownership(&self) -> bool {
+        true
+    }
 }
 
 impl Read for MemoryRepo {
     fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
-        Ok(self.map.get(key).map(Clone::clone))
+        Ok(self.map.get(key).cloned())
+    }
+
+    fn read_with<R, F>(&self, key: &[u8], f: F) -> Result<R, Self::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> R,
+    {
+        Ok(f(self.map.get(key).map(Vec::as_slice)))
     }
 }
 
@@ -34,9 +248,72 @@ impl HasKey for MemoryRepo {
     }
 }
 
+impl Iterate for MemoryRepo {
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error> {
+        // `BTreeMap::range` panics on inverted bounds
+        if Bound::is_empty_range(&min, &max) {
+            return Ok(Box::new(std::iter::empty()));
+        }
+
+        let entries = self
+            .map
+            .range::<[u8], _>((min.into(), max.into()))
+            .map(|(key, bytes)| (key.clone(), bytes.clone()));
+
+        Ok(match order {
+            Order::Ascending => Box::new(entries),
+            Order::Descending => Box::new(entries.rev()),
+        })
+    }
+
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error> {
+        if Bound::is_empty_range(&min, &max) {
+            return Ok(Box::new(std::iter::empty()));
+        }
+
+        let keys = self
+            .map
+            .range::<[u8], _>((min.into(), max.into()))
+            .map(|(key, _)| key.clone());
+
+        Ok(match order {
+            Order::Ascending => Box::new(keys),
+            Order::Descending => Box::new(keys.rev()),
+        })
+    }
+}
+
 impl Remove for MemoryRepo {
     fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
         self.map.remove(key);
         Ok(())
     }
+
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        Ok(match self.map.remove(key) {
+            Some(_) => Removed::Existed,
+            None => Removed::DidNotExist,
+        })
+    }
+
+    fn write_batch(&mut self, ops: &[BatchOp<'_>]) -> Result<(), Self::Error> {
+        for (key, bytes) in ops {
+            match bytes {
+                Some(bytes) => self.map.insert(key.to_vec(), bytes.to_vec()),
+                None => self.map.remove(key.as_ref()),
+            };
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,223 @@
+use std::cell::Cell;
+
+use kv_storage::{
+    trace::{self, Op, Traceable},
+    BatchOp, Bound, Durability, Fallible, HasKey, Iterate, Order, RawEntries, RawKeys, Read,
+    Remove, Write,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error<A, B> {
+    #[error("primary: {0}")]
+    Primary(A),
+    #[error("secondary: {0}")]
+    Secondary(B),
+}
+
+/// A read the secondary repo disagreed with.
+#[derive(Debug)]
+pub enum Mismatch<'a, E> {
+    /// The repos hold different bytes at the key.
+    Differs {
+        key: &'a [u8],
+        primary: Option<&'a [u8]>,
+        secondary: Option<&'a [u8]>,
+    },
+    /// Reading the key from the secondary failed.
+    Failed { key: &'a [u8], error: E },
+}
+
+type OnMismatch<E> = Box<dyn Fn(Mismatch<'_, E>)>;
+
+/// Applies every change to two repos, e.g. the old and new stores during a migration, and
+/// serves reads from the first.
+///
+/// The primary is written first, and the secondary only once it succeeded. A failure on the
+/// secondary leaves the repos diverged, which the returned [`Error::Secondary`] reports.
+///
+/// With [`MirrorRepo::verify_reads`] values read are also read from the secondary and
+/// compared, differences are reported without failing the read.
+pub struct MirrorRepo<A, B: Fallible> {
+    primary: A,
+    secondary: B,
+    on_mismatch: Option<OnMismatch<B::Error>>,
+    mismatches: Cell<u64>,
+}
+
+impl<A, B: Fallible> MirrorRepo<A, B> {
+    pub fn new(primary: A, secondary: B) -> Self {
+        Self {
+            primary,
+            secondary,
+            on_mismatch: None,
+            mismatches: Cell::new(0),
+        }
+    }
+
+    /// Check each value read against the secondary, calling `on_mismatch` when they differ.
+    ///
+    /// Key checks aren't verified, they only tell whether the primary holds the key.
+    pub fn verify_reads(mut self, on_mismatch: impl Fn(Mismatch<'_, B::Error>) + 'static) -> Self {
+        self.on_mismatch = Some(Box::new(on_mismatch));
+        self
+    }
+
+    pub fn primary(&self) -> &A {
+        &self.primary
+    }
+
+    pub fn secondary(&self) -> &B {
+        &self.secondary
+    }
+
+    pub fn into_parts(self) -> (A, B) {
+        (self.primary, self.secondary)
+    }
+
+    /// How many verified reads the secondary disagreed with.
+    pub fn mismatches(&self) -> u64 {
+        self.mismatches.get()
+    }
+}
+
+impl<A, B: Fallible> Traceable for MirrorRepo<A, B> {
+    const LAYER: &'static str = "mirror";
+}
+
+impl<A: Fallible, B: Fallible> Fallible for MirrorRepo<A, B> {
+    type Error = Error<A::Error, B::Error>;
+}
+
+impl<A: Write, B: Write> Write for MirrorRepo<A, B> {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive(Op::Write, bytes.len());
+
+        span.issue(Op::Write, bytes.len());
+        self.primary.write(key, bytes).map_err(Error::Primary)?;
+        span.issue(Op::Write, bytes.len());
+        self.secondary.write(key, bytes).map_err(Error::Secondary)
+    }
+
+    fn write_durable(
+        &mut self,
+        key: &[u8],
+        bytes: &[u8],
+        durability: Durability,
+    ) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive(Op::Write, bytes.len());
+
+        span.issue(Op::Write, bytes.len());
+        self.primary
+            .write_durable(key, bytes, durability)
+            .map_err(Error::Primary)?;
+        span.issue(Op::Write, bytes.len());
+        self.secondary
+            .write_durable(key, bytes, durability)
+            .map_err(Error::Secondary)
+    }
+
+    fn supports_durability(&self) -> bool {
+        self.primary.supports_durability() && self.secondary.supports_durability()
+    }
+}
+
+impl<A: Read, B: Read> Read for MirrorRepo<A, B> {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        let bytes = self.primary.read(key).map_err(Error::Primary)?;
+
+        if let Some(on_mismatch) = &self.on_mismatch {
+            let mismatch = |mismatch| {
+                self.mismatches.set(self.mismatches.get() + 1);
+                on_mismatch(mismatch);
+            };
+
+            match self.secondary.read(key) {
+                Ok(secondary) if secondary == bytes => {}
+                Ok(secondary) => mismatch(Mismatch::Differs {
+                    key,
+                    primary: bytes.as_deref(),
+                    secondary: secondary.as_deref(),
+                }),
+                Err(error) => mismatch(Mismatch::Failed { key, error }),
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    fn read_with<T, F>(&self, key: &[u8], f: F) -> Result<T, Self::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> T,
+    {
+        // verifying needs a copy of the primary's bytes to compare
+        if self.on_mismatch.is_some() {
+            return Ok(f(self.read(key)?.as_deref()));
+        }
+
+        self.primary.read_with(key, f).map_err(Error::Primary)
+    }
+
+    fn read_many(&self, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>, Self::Error> {
+        if self.on_mismatch.is_some() {
+            return keys.iter().map(|key| self.read(key)).collect();
+        }
+
+        self.primary.read_many(keys).map_err(Error::Primary)
+    }
+}
+
+impl<A: HasKey, B: Read> HasKey for MirrorRepo<A, B> {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        self.primary.has_key(key).map_err(Error::Primary)
+    }
+}
+
+impl<A: Write + Remove, B: Write + Remove> Remove for MirrorRepo<A, B> {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive(Op::Remove, 0);
+
+        span.issue(Op::Remove, 0);
+        self.primary.remove(key).map_err(Error::Primary)?;
+        span.issue(Op::Remove, 0);
+        self.secondary.remove(key).map_err(Error::Secondary)
+    }
+
+    fn write_batch(&mut self, ops: &[BatchOp<'_>]) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive_batch(ops);
+
+        span.issue_batch(ops);
+        self.primary.write_batch(ops).map_err(Error::Primary)?;
+        span.issue_batch(ops);
+        self.secondary.write_batch(ops).map_err(Error::Secondary)
+    }
+}
+
+impl<A: Iterate, B: Fallible> Iterate for MirrorRepo<A, B> {
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error> {
+        self.primary.range(min, max, order).map_err(Error::Primary)
+    }
+
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error> {
+        self.primary
+            .range_keys(min, max, order)
+            .map_err(Error::Primary)
+    }
+
+    fn scan(&self, prefix: &[u8]) -> Result<RawEntries<'_>, Self::Error> {
+        self.primary.scan(prefix).map_err(Error::Primary)
+    }
+}
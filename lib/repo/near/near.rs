@@ -0,0 +1,132 @@
+//! NEAR contract storage as a repo, with the default `bincode` feature a ready-made store:
+//!
+//! ```
+//! use kv_storage::prelude::*;
+//! use kv_storage_near::{NearRepo, NearStore};
+//!
+//! const TOTAL: Item<u64> = item!("total");
+//!
+//! let mut store: NearStore = KvStore::from_repo(NearRepo::new());
+//! TOTAL.save(&mut store, 42).unwrap();
+//! assert_eq!(TOTAL.may_load(&store).unwrap(), Some(42));
+//! ```
+//!
+//! Native builds can't reach the NEAR host, so they get an in-memory storage per thread instead,
+//! enough for tests and examples. It is separate from near-sdk's `unit-testing` mock blockchain.
+
+use std::borrow::Cow;
+
+#[cfg(feature = "bincode")]
+use kv_storage::KvStore;
+use kv_storage::{Fallible, HasKey, Read, Remove, Removed, Write};
+#[cfg(target_arch = "wasm32")]
+use near_sdk::env;
+
+/// Stands in for the contract storage functions of `near_sdk::env` off-chain.
+#[cfg(not(target_arch = "wasm32"))]
+mod env {
+    use std::{cell::RefCell, collections::BTreeMap};
+
+    thread_local! {
+        static STORAGE: RefCell<BTreeMap<Vec<u8>, Vec<u8>>> = RefCell::default();
+    }
+
+    pub fn storage_write(key: &[u8], value: &[u8]) -> bool {
+        STORAGE.with_borrow_mut(|storage| storage.insert(key.to_vec(), value.to_vec()).is_some())
+    }
+
+    pub fn storage_read(key: &[u8]) -> Option<Vec<u8>> {
+        STORAGE.with_borrow(|storage| storage.get(key).cloned())
+    }
+
+    pub fn storage_has_key(key: &[u8]) -> bool {
+        STORAGE.with_borrow(|storage| storage.contains_key(key))
+    }
+
+    pub fn storage_remove(key: &[u8]) -> bool {
+        STORAGE.with_borrow_mut(|storage| storage.remove(key).is_some())
+    }
+}
+
+/// The storage of the running contract.
+///
+/// NEAR storage is ambient, so the repo holds nothing but an optional key prefix, letting
+/// several logical stores share the contract's storage without colliding.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct NearRepo<P = ()> {
+    prefix: P,
+}
+
+impl NearRepo {
+    pub const fn new() -> Self {
+        Self { prefix: () }
+    }
+
+    /// Prefix every key with `prefix` in contract storage.
+    pub const fn prefixed(prefix: &'static [u8]) -> NearRepo<&'static [u8]> {
+        NearRepo { prefix }
+    }
+}
+
+/// How a [`NearRepo`] turns its keys into contract storage keys.
+pub trait KeyPrefix {
+    fn storage_key<'k>(&self, key: &'k [u8]) -> Cow<'k, [u8]>;
+}
+
+impl KeyPrefix for () {
+    fn storage_key<'k>(&self, key: &'k [u8]) -> Cow<'k, [u8]> {
+        Cow::Borrowed(key)
+    }
+}
+
+impl KeyPrefix for &'static [u8] {
+    fn storage_key<'k>(&self, key: &'k [u8]) -> Cow<'k, [u8]> {
+        Cow::Owned([*self, key].concat())
+    }
+}
+
+#[cfg(feature = "bincode")]
+pub type NearStore<P = ()> = KvStore<kv_storage_bincode::Bincode, NearRepo<P>>;
+
+#[derive(Debug, thiserror::Error)]
+#[error("infallible")]
+pub struct Infallible;
+
+impl<P> Fallible for NearRepo<P> {
+    type Error = Infallible;
+}
+
+impl<P: KeyPrefix> Write for NearRepo<P> {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        env::storage_write(&self.prefix.storage_key(key), bytes);
+        Ok(())
+    }
+}
+
+// `storage_read` copies the value out of the host, so there is nothing to lend and the default
+// `read_with` is as good as it gets
+impl<P: KeyPrefix> Read for NearRepo<P> {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(env::storage_read(&self.prefix.storage_key(key)))
+    }
+}
+
+impl<P: KeyPrefix> HasKey for NearRepo<P> {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        Ok(env::storage_has_key(&self.prefix.storage_key(key)))
+    }
+}
+
+impl<P: KeyPrefix> Remove for NearRepo<P> {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.remove_returning(key).map(drop)
+    }
+
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        Ok(if env::storage_remove(&self.prefix.storage_key(key)) {
+            Removed::Existed
+        } else {
+            Removed::DidNotExist
+        })
+    }
+}
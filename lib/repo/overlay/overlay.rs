@@ -0,0 +1,129 @@
+use std::{borrow::Cow, collections::BTreeMap};
+
+use kv_storage::{
+    trace::{self, Op, Traceable},
+    BatchOp, Fallible, HasKey, Read, Remove, Write,
+};
+
+/// Buffers writes and removals in memory on top of a base repo, until they are committed to it
+/// as a single batch or discarded.
+///
+/// Reads see the buffered changes first, so a key removed in the overlay reads as missing even
+/// if the base still has it.
+pub struct OverlayRepo<R> {
+    base: R,
+    /// `None` marks a removal.
+    changes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl<R> OverlayRepo<R> {
+    pub fn new(base: R) -> Self {
+        Self {
+            base,
+            changes: BTreeMap::new(),
+        }
+    }
+
+    pub fn base(&self) -> &R {
+        &self.base
+    }
+
+    /// How many keys have buffered changes.
+    pub fn pending(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Drop the buffered changes, returning the untouched base.
+    pub fn discard(self) -> R {
+        self.base
+    }
+}
+
+impl<R: Write + Remove> OverlayRepo<R> {
+    /// Apply the buffered changes to the base in one batch, returning it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the base fails to apply the batch, how much of it
+    /// was applied then depends on the base's [`Remove::write_batch`] implementation.
+    pub fn commit(mut self) -> Result<R, R::Error> {
+        let ops: Vec<BatchOp<'_>> = self
+            .changes
+            .iter()
+            .map(|(key, bytes)| (Cow::from(key.as_slice()), bytes.as_deref().map(Cow::from)))
+            .collect();
+
+        let span = trace::enter(&self);
+        span.issue_batch(&ops);
+
+        self.base.write_batch(&ops)?;
+
+        Ok(self.base)
+    }
+}
+
+impl<R> Traceable for OverlayRepo<R> {
+    const LAYER: &'static str = "overlay";
+}
+
+impl<R: Fallible> Fallible for OverlayRepo<R> {
+    type Error = R::Error;
+}
+
+impl<R: Fallible> Write for OverlayRepo<R> {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        trace::enter(self).receive(Op::Write, bytes.len());
+
+        self.changes.insert(key.to_vec(), Some(bytes.to_vec()));
+        Ok(())
+    }
+
+    fn write_owned(&mut self, key: &[u8], bytes: Vec<u8>) -> Result<(), Self::Error> {
+        trace::enter(self).receive(Op::Write, bytes.len());
+
+        self.changes.insert(key.to_vec(), Some(bytes));
+        Ok(())
+    }
+
+    fn takes_ownership(&self) -> bool {
+        true
+    }
+}
+
+impl<R: Read> Read for OverlayRepo<R> {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        match self.changes.get(key) {
+            Some(change) => Ok(change.clone()),
+            None => self.base.read(key),
+        }
+    }
+
+    fn read_with<T, F>(&self, key: &[u8], f: F) -> Result<T, Self::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> T,
+    {
+        match self.changes.get(key) {
+            Some(change) => Ok(f(change.as_deref())),
+            None => self.base.read_with(key, f),
+        }
+    }
+}
+
+impl<R: HasKey> HasKey for OverlayRepo<R> {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        match self.changes.get(key) {
+            Some(change) => Ok(change.is_some()),
+            None => self.base.has_key(key),
+        }
+    }
+}
+
+// buffering a change can't fail, so the default `write_batch` is already all or nothing
+impl<R: Fallible> Remove for OverlayRepo<R> {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        trace::enter(self).receive(Op::Remove, 0);
+
+        self.changes.insert(key.to_vec(), None);
+        Ok(())
+    }
+}
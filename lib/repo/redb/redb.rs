@@ -0,0 +1,296 @@
+//! A repo over a `redb` database, storing every entry in one fixed table.
+//!
+//! [`RedbRepo`] runs each call in its own transaction, committed durably before returning.
+//! [`RedbRepo::begin_write`] keeps a single write transaction open instead, for batch workloads.
+
+use std::{ops::Bound as StdBound, path::Path};
+
+use kv_storage::{
+    BatchOp, Bound, Fallible, HasKey, Iterate, Order, RawEntries, Read, Remove, Removed, Write,
+};
+use redb::{
+    AccessGuard, Database, ReadableTable, StorageError, Table, TableDefinition, WriteTransaction,
+};
+
+pub use redb;
+
+/// The table every entry is stored in.
+pub const TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("kv-storage");
+
+/// The errors of the redb operations the repos perform.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Database(#[from] redb::DatabaseError),
+    /// Boxed, redb's is large enough to bloat every result.
+    #[error(transparent)]
+    Transaction(Box<redb::TransactionError>),
+    #[error(transparent)]
+    Table(#[from] redb::TableError),
+    #[error(transparent)]
+    Storage(#[from] redb::StorageError),
+    #[error(transparent)]
+    Commit(#[from] redb::CommitError),
+}
+
+impl From<redb::TransactionError> for Error {
+    fn from(error: redb::TransactionError) -> Self {
+        Self::Transaction(Box::new(error))
+    }
+}
+
+pub struct RedbRepo {
+    db: Database,
+}
+
+impl RedbRepo {
+    /// Open the database at `path`, creating it if needed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the database can't be opened or its table created.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_database(Database::create(path)?)
+    }
+
+    /// Use an open database, creating the table if needed so reads always find it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the table can't be created.
+    pub fn from_database(db: Database) -> Result<Self, Error> {
+        let txn = db.begin_write()?;
+        txn.open_table(TABLE)?;
+        txn.commit()?;
+
+        Ok(Self { db })
+    }
+
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    /// Open a write transaction, applied by [`RedbTransaction::commit`] and aborted if dropped.
+    ///
+    /// Reads through it see its own writes. Other transactions on the database, including the
+    /// per-call ones of this repo, block until it's committed or dropped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the transaction can't be started.
+    pub fn begin_write(&self) -> Result<RedbTransaction, Error> {
+        Ok(RedbTransaction {
+            txn: self.db.begin_write()?,
+        })
+    }
+
+    /// Run `f` against the table in a write transaction, committing if it succeeds.
+    fn write_with<R>(
+        &self,
+        f: impl FnOnce(&mut Table<&[u8], &[u8]>) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        let txn = self.db.begin_write()?;
+        let result = f(&mut txn.open_table(TABLE)?)?;
+        txn.commit()?;
+
+        Ok(result)
+    }
+}
+
+/// Applies its writes and removals to the database in one go when committed.
+pub struct RedbTransaction {
+    txn: WriteTransaction,
+}
+
+impl RedbTransaction {
+    /// Apply the changes made through the transaction.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if redb fails to commit, nothing is applied then.
+    pub fn commit(self) -> Result<(), Error> {
+        Ok(self.txn.commit()?)
+    }
+
+    /// Drop the changes made through the transaction.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if redb fails to roll back.
+    pub fn abort(self) -> Result<(), Error> {
+        Ok(self.txn.abort()?)
+    }
+}
+
+/// Call `f` with the bytes at `key` in `table`.
+fn read_with<T, R, F>(table: &T, key: &[u8], f: F) -> Result<R, Error>
+where
+    T: ReadableTable<&'static [u8], &'static [u8]>,
+    F: FnOnce(Option<&[u8]>) -> R,
+{
+    let value = table.get(key)?;
+    Ok(f(value.as_ref().map(|value| value.value())))
+}
+
+fn remove_returning(table: &mut Table<&[u8], &[u8]>, key: &[u8]) -> Result<Removed, Error> {
+    Ok(match table.remove(key)? {
+        Some(_) => Removed::Existed,
+        None => Removed::DidNotExist,
+    })
+}
+
+fn write_batch(table: &mut Table<&[u8], &[u8]>, ops: &[BatchOp<'_>]) -> Result<(), Error> {
+    for (key, bytes) in ops {
+        match bytes {
+            Some(bytes) => table.insert(key.as_ref(), bytes.as_ref())?,
+            None => table.remove(key.as_ref())?,
+        };
+    }
+
+    Ok(())
+}
+
+type Guard<'a> = AccessGuard<'a, &'static [u8]>;
+
+/// Collect the entries between the bounds, so iteration errors surface before yielding.
+fn range<T>(
+    table: &T,
+    min: Bound<&[u8]>,
+    max: Bound<&[u8]>,
+    order: Order,
+) -> Result<RawEntries<'static>, Error>
+where
+    T: ReadableTable<&'static [u8], &'static [u8]>,
+{
+    // `ReadableTable::range` panics on inverted bounds
+    if Bound::is_empty_range(&min, &max) {
+        return Ok(Box::new(std::iter::empty()));
+    }
+
+    let entries = table.range::<&[u8]>((StdBound::from(min), StdBound::from(max)))?;
+    let owned = |entry: Result<(Guard, Guard), StorageError>| {
+        entry.map(|(key, bytes)| (key.value().to_vec(), bytes.value().to_vec()))
+    };
+
+    let entries = match order {
+        Order::Ascending => entries.map(owned).collect::<Result<Vec<_>, _>>()?,
+        Order::Descending => entries.rev().map(owned).collect::<Result<Vec<_>, _>>()?,
+    };
+
+    Ok(Box::new(entries.into_iter()))
+}
+
+impl Fallible for RedbRepo {
+    type Error = Error;
+}
+
+impl Write for RedbRepo {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        self.write_with(|table| {
+            table.insert(key, bytes)?;
+            Ok(())
+        })
+    }
+
+    // every write is committed with redb's default, immediate durability
+    fn supports_durability(&self) -> bool {
+        true
+    }
+}
+
+impl Read for RedbRepo {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.read_with(key, |bytes| bytes.map(<[u8]>::to_vec))
+    }
+
+    fn read_with<R, F>(&self, key: &[u8], f: F) -> Result<R, Self::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> R,
+    {
+        read_with(&self.db.begin_read()?.open_table(TABLE)?, key, f)
+    }
+}
+
+impl HasKey for RedbRepo {}
+
+impl Remove for RedbRepo {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.remove_returning(key).map(drop)
+    }
+
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        self.write_with(|table| remove_returning(table, key))
+    }
+
+    /// Applies the ops atomically, in one transaction.
+    fn write_batch(&mut self, ops: &[BatchOp<'_>]) -> Result<(), Self::Error> {
+        self.write_with(|table| write_batch(table, ops))
+    }
+}
+
+/// Entries are read eagerly: redb can fail mid-iteration, and the yielded items can't carry
+/// errors.
+impl Iterate for RedbRepo {
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error> {
+        range(&self.db.begin_read()?.open_table(TABLE)?, min, max, order)
+    }
+}
+
+impl Fallible for RedbTransaction {
+    type Error = Error;
+}
+
+impl Write for RedbTransaction {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        self.txn.open_table(TABLE)?.insert(key, bytes)?;
+        Ok(())
+    }
+}
+
+impl Read for RedbTransaction {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.read_with(key, |bytes| bytes.map(<[u8]>::to_vec))
+    }
+
+    fn read_with<R, F>(&self, key: &[u8], f: F) -> Result<R, Self::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> R,
+    {
+        read_with(&self.txn.open_table(TABLE)?, key, f)
+    }
+}
+
+impl HasKey for RedbTransaction {}
+
+impl Remove for RedbTransaction {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.remove_returning(key).map(drop)
+    }
+
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        remove_returning(&mut self.txn.open_table(TABLE)?, key)
+    }
+
+    /// Applies the ops in the open transaction, a failure leaves the ones before it applied
+    /// there.
+    fn write_batch(&mut self, ops: &[BatchOp<'_>]) -> Result<(), Self::Error> {
+        write_batch(&mut self.txn.open_table(TABLE)?, ops)
+    }
+}
+
+/// Entries are read eagerly, like [`RedbRepo`]'s.
+impl Iterate for RedbTransaction {
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error> {
+        range(&self.txn.open_table(TABLE)?, min, max, order)
+    }
+}
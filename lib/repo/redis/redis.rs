@@ -0,0 +1,127 @@
+//! A repo over a Redis database, through the synchronous `redis` client.
+
+use std::cell::RefCell;
+
+use kv_storage::{BatchOp, Fallible, HasKey, Read, Remove, Removed, Write};
+use redis::{Client, Connection, IntoConnectionInfo, RedisError};
+
+pub use redis;
+
+/// Stores entries as Redis strings, their keys namespaced by an optional prefix so several
+/// stores can share a database.
+///
+/// Keys and values are sent as binary-safe bulk strings, any bytes survive.
+pub struct RedisRepo {
+    /// Redis commands need the connection mutably, reads included.
+    conn: RefCell<Connection>,
+    prefix: Vec<u8>,
+}
+
+impl RedisRepo {
+    /// Connect to the server at `url`, e.g. `redis://127.0.0.1/`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the URL is invalid or the connection fails.
+    pub fn open(url: impl IntoConnectionInfo) -> Result<Self, RedisError> {
+        let conn = Client::open(url)?.get_connection()?;
+        Ok(Self::from_connection(conn))
+    }
+
+    pub fn from_connection(conn: Connection) -> Self {
+        Self {
+            conn: RefCell::new(conn),
+            prefix: Vec::new(),
+        }
+    }
+
+    /// Prefix every key with `prefix` in Redis.
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: impl Into<Vec<u8>>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    pub fn prefix(&self) -> &[u8] {
+        &self.prefix
+    }
+
+    /// The Redis key for `key`.
+    fn key(&self, key: &[u8]) -> Vec<u8> {
+        [self.prefix.as_slice(), key].concat()
+    }
+
+    fn query<T: redis::FromRedisValue>(&self, cmd: &redis::Cmd) -> Result<T, RedisError> {
+        cmd.query(&mut self.conn.borrow_mut())
+    }
+}
+
+impl From<Connection> for RedisRepo {
+    fn from(conn: Connection) -> Self {
+        Self::from_connection(conn)
+    }
+}
+
+impl Fallible for RedisRepo {
+    type Error = RedisError;
+}
+
+impl Write for RedisRepo {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        self.query(redis::cmd("SET").arg(self.key(key)).arg(bytes))
+    }
+}
+
+impl Read for RedisRepo {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.query(redis::cmd("GET").arg(self.key(key)))
+    }
+
+    /// Reads every key in one `MGET`.
+    fn read_many(&self, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>, Self::Error> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let keys: Vec<_> = keys.iter().map(|key| self.key(key)).collect();
+        self.query(redis::cmd("MGET").arg(keys))
+    }
+}
+
+impl HasKey for RedisRepo {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        self.query(redis::cmd("EXISTS").arg(self.key(key)))
+    }
+}
+
+impl Remove for RedisRepo {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.remove_returning(key).map(drop)
+    }
+
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        let removed: usize = self.query(redis::cmd("DEL").arg(self.key(key)))?;
+
+        Ok(if removed > 0 {
+            Removed::Existed
+        } else {
+            Removed::DidNotExist
+        })
+    }
+
+    /// Applies the ops atomically, in one `MULTI`/`EXEC` pipeline.
+    fn write_batch(&mut self, ops: &[BatchOp<'_>]) -> Result<(), Self::Error> {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+
+        for (key, bytes) in ops {
+            match bytes {
+                Some(bytes) => pipe.cmd("SET").arg(self.key(key)).arg(bytes.as_ref()),
+                None => pipe.cmd("DEL").arg(self.key(key)),
+            }
+            .ignore();
+        }
+
+        pipe.query(&mut self.conn.borrow_mut())
+    }
+}
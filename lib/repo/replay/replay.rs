@@ -0,0 +1,404 @@
+//! Record the operations reaching a repo with [`RecordingRepo`], then step through them against a
+//! local repo, usually a `MemoryRepo`, with [`Replay`].
+//!
+//! Every recorded op carries the bytes its key held beforehand, so replaying onto a store that
+//! doesn't match the recorded history stops at the first op that disagrees.
+
+use std::cell::Cell;
+
+use kv_storage::{
+    trace::{self, Traceable},
+    Bound, Durability, Fallible, HasKey, Iterate, KvStore, Order, RawEntries, RawKeys, Read,
+    Remove, Write,
+};
+use serde::{Deserialize, Serialize};
+
+/// A recorded storage operation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Op {
+    Write {
+        key: Vec<u8>,
+        bytes: Vec<u8>,
+        /// What the key held before the write.
+        previous: Option<Vec<u8>>,
+    },
+    Remove {
+        key: Vec<u8>,
+        /// What the key held before the remove.
+        previous: Option<Vec<u8>>,
+    },
+}
+
+impl Op {
+    pub fn key(&self) -> &[u8] {
+        match self {
+            Op::Write { key, .. } | Op::Remove { key, .. } => key,
+        }
+    }
+
+    pub fn previous(&self) -> Option<&[u8]> {
+        match self {
+            Op::Write { previous, .. } | Op::Remove { previous, .. } => previous.as_deref(),
+        }
+    }
+}
+
+/// A sequence of recorded ops, serializable with any serde format.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpLog {
+    pub version: u32,
+    pub ops: Vec<Op>,
+}
+
+impl OpLog {
+    /// The format version written by this crate, the only one [`Replay`] accepts.
+    pub const VERSION: u32 = 1;
+
+    pub fn new() -> Self {
+        Self {
+            version: Self::VERSION,
+            ops: Vec::new(),
+        }
+    }
+}
+
+impl Default for OpLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How many reads reached a [`RecordingRepo`], they aren't part of its log since replaying
+/// doesn't need them.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct ReadCounts {
+    /// Single key reads, through `read` or `read_with`.
+    pub reads: u64,
+    /// Multi-gets, however many keys each one asked for.
+    pub read_manys: u64,
+    /// Keys asked for through multi-gets.
+    pub keys_read_many: u64,
+    pub has_keys: u64,
+}
+
+/// Records every write and remove reaching the inner repo, along with the previous bytes, and
+/// counts the reads.
+///
+/// Reading the previous value costs an extra read per operation, made on the inner repo so it
+/// isn't counted.
+pub struct RecordingRepo<R> {
+    inner: R,
+    log: OpLog,
+    reads: Cell<ReadCounts>,
+}
+
+impl<R> RecordingRepo<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            log: OpLog::new(),
+            reads: Cell::default(),
+        }
+    }
+
+    pub fn log(&self) -> &OpLog {
+        &self.log
+    }
+
+    pub fn read_counts(&self) -> ReadCounts {
+        self.reads.get()
+    }
+
+    pub fn reset_read_counts(&self) {
+        self.reads.take();
+    }
+
+    fn count_read(&self, f: impl FnOnce(&mut ReadCounts)) {
+        let mut counts = self.reads.get();
+        f(&mut counts);
+        self.reads.set(counts);
+    }
+
+    /// Hand over the ops recorded so far, recording continues into an empty log.
+    pub fn take_log(&mut self) -> OpLog {
+        std::mem::take(&mut self.log)
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_parts(self) -> (R, OpLog) {
+        (self.inner, self.log)
+    }
+}
+
+impl<R> Traceable for RecordingRepo<R> {
+    const LAYER: &'static str = "recording";
+}
+
+impl<R: Fallible> Fallible for RecordingRepo<R> {
+    type Error = R::Error;
+}
+
+impl<R> Write for RecordingRepo<R>
+where
+    R: Write + Read,
+{
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        self.write_durable(key, bytes, Durability::Relaxed)
+    }
+
+    fn write_durable(
+        &mut self,
+        key: &[u8],
+        bytes: &[u8],
+        durability: Durability,
+    ) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive(trace::Op::Write, bytes.len());
+
+        let previous = self.inner.read(key)?;
+        span.issue(trace::Op::Read, previous.as_ref().map_or(0, Vec::len));
+
+        span.issue(trace::Op::Write, bytes.len());
+        self.inner.write_durable(key, bytes, durability)?;
+
+        self.log.ops.push(Op::Write {
+            key: key.to_owned(),
+            bytes: bytes.to_owned(),
+            previous,
+        });
+
+        Ok(())
+    }
+
+    fn supports_durability(&self) -> bool {
+        self.inner.supports_durability()
+    }
+}
+
+impl<R: Read> Read for RecordingRepo<R> {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.count_read(|counts| counts.reads += 1);
+        self.inner.read(key)
+    }
+
+    fn read_with<T, F>(&self, key: &[u8], f: F) -> Result<T, Self::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> T,
+    {
+        self.count_read(|counts| counts.reads += 1);
+        self.inner.read_with(key, f)
+    }
+
+    fn read_many(&self, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>, Self::Error> {
+        self.count_read(|counts| {
+            counts.read_manys += 1;
+            counts.keys_read_many += keys.len() as u64;
+        });
+        self.inner.read_many(keys)
+    }
+}
+
+impl<R: HasKey> HasKey for RecordingRepo<R> {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        self.count_read(|counts| counts.has_keys += 1);
+        self.inner.has_key(key)
+    }
+}
+
+impl<R> Remove for RecordingRepo<R>
+where
+    R: Remove + Read,
+{
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive(trace::Op::Remove, 0);
+
+        let previous = self.inner.read(key)?;
+        span.issue(trace::Op::Read, previous.as_ref().map_or(0, Vec::len));
+
+        span.issue(trace::Op::Remove, 0);
+        self.inner.remove(key)?;
+
+        self.log.ops.push(Op::Remove {
+            key: key.to_owned(),
+            previous,
+        });
+
+        Ok(())
+    }
+}
+
+// scans aren't counted, they can't be batched any further
+impl<R: Iterate> Iterate for RecordingRepo<R> {
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error> {
+        self.inner.range(min, max, order)
+    }
+
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error> {
+        self.inner.range_keys(min, max, order)
+    }
+
+    fn scan(&self, prefix: &[u8]) -> Result<RawEntries<'_>, Self::Error> {
+        self.inner.scan(prefix)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReplayError<E> {
+    #[error("unsupported op log version {found}, expected {}", OpLog::VERSION)]
+    UnsupportedVersion { found: u32 },
+    /// The store didn't hold the bytes the op was recorded against.
+    #[error("replay diverged at op {position}")]
+    Divergence {
+        position: usize,
+        key: Vec<u8>,
+        expected: Option<Vec<u8>>,
+        found: Option<Vec<u8>>,
+    },
+    #[error("replay store error")]
+    Repo(#[source] E),
+}
+
+/// Applies an [`OpLog`] to a store one op at a time.
+pub struct Replay<Serde, Repo> {
+    log: OpLog,
+    position: usize,
+    store: KvStore<Serde, Repo>,
+}
+
+impl<Serde: Default, Repo: Fallible + Default> Replay<Serde, Repo> {
+    /// Replay the log from the start onto an empty store.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the log's version isn't supported.
+    pub fn from_log(log: OpLog) -> Result<Self, ReplayError<Repo::Error>> {
+        Self::onto(log, KvStore::from_repo(Repo::default()))
+    }
+}
+
+impl<Serde, Repo: Fallible> Replay<Serde, Repo> {
+    /// Replay the log from the start onto a store that may already hold data, each op is checked
+    /// against it before being applied.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the log's version isn't supported.
+    pub fn onto(log: OpLog, store: KvStore<Serde, Repo>) -> Result<Self, ReplayError<Repo::Error>> {
+        if log.version != OpLog::VERSION {
+            return Err(ReplayError::UnsupportedVersion { found: log.version });
+        }
+
+        Ok(Self {
+            log,
+            position: 0,
+            store,
+        })
+    }
+
+    /// How many ops have been applied.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position == self.log.ops.len()
+    }
+
+    pub fn log(&self) -> &OpLog {
+        &self.log
+    }
+
+    /// The store as of the current position.
+    pub fn state(&self) -> &KvStore<Serde, Repo> {
+        &self.store
+    }
+
+    pub fn into_state(self) -> KvStore<Serde, Repo> {
+        self.store
+    }
+
+    /// Apply the next op, returning it, or `None` once the log is exhausted.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store doesn't hold the op's previous bytes, the
+    /// op is then left unapplied, or if the repo encounters an error.
+    pub fn step(&mut self) -> Result<Option<&Op>, ReplayError<Repo::Error>>
+    where
+        Repo: Read + Write + Remove,
+    {
+        let Some(op) = self.log.ops.get(self.position) else {
+            return Ok(None);
+        };
+
+        let repo = self.store.mut_repo();
+
+        let found = repo.read(op.key()).map_err(ReplayError::Repo)?;
+
+        if found.as_deref() != op.previous() {
+            return Err(ReplayError::Divergence {
+                position: self.position,
+                key: op.key().to_owned(),
+                expected: op.previous().map(ToOwned::to_owned),
+                found,
+            });
+        }
+
+        match op {
+            Op::Write { key, bytes, .. } => repo.write(key, bytes),
+            Op::Remove { key, .. } => repo.remove(key),
+        }
+        .map_err(ReplayError::Repo)?;
+
+        self.position += 1;
+
+        Ok(Some(op))
+    }
+
+    /// Step until `pred` holds for the store after an applied op, returning whether it did before
+    /// the log ran out.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if replay diverges, see [`Replay::step`].
+    pub fn run_until<P>(&mut self, mut pred: P) -> Result<bool, ReplayError<Repo::Error>>
+    where
+        Repo: Read + Write + Remove,
+        P: FnMut(&KvStore<Serde, Repo>) -> bool,
+    {
+        while self.step()?.is_some() {
+            if pred(&self.store) {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Apply all remaining ops.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if replay diverges, see [`Replay::step`].
+    pub fn run(&mut self) -> Result<(), ReplayError<Repo::Error>>
+    where
+        Repo: Read + Write + Remove,
+    {
+        while self.step()?.is_some() {}
+        Ok(())
+    }
+}
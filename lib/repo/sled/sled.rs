@@ -0,0 +1,208 @@
+//! A repo over a `sled` tree, for embedded persistence.
+
+use std::path::Path;
+
+use kv_storage::{
+    BatchOp, Bound, Durability, Fallible, HasKey, Iterate, Order, RawEntries, RawKeys, Read,
+    Remove, Removed, Write,
+};
+use sled::{Db, IVec, Tree};
+
+pub use sled;
+
+/// Stores entries in a `sled` tree, either a database's default tree or a named one.
+///
+/// Writes are buffered by sled and persisted in the background, use [`SledRepo::flush`] or
+/// durable writes where that matters.
+#[derive(Clone)]
+pub struct SledRepo {
+    tree: Tree,
+}
+
+impl SledRepo {
+    /// Open the database at `path`, creating it if needed, and use its default tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the database can't be opened.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, sled::Error> {
+        let db = sled::open(path)?;
+        Ok(Self::from_db(&db))
+    }
+
+    /// Use the default tree of an open database.
+    pub fn from_db(db: &Db) -> Self {
+        Self::from_tree((**db).clone())
+    }
+
+    pub fn from_tree(tree: Tree) -> Self {
+        Self { tree }
+    }
+
+    pub fn tree(&self) -> &Tree {
+        &self.tree
+    }
+
+    /// Persist every buffered write to disk, returning how many bytes were flushed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if sled fails to write to disk.
+    pub fn flush(&self) -> Result<usize, sled::Error> {
+        self.tree.flush()
+    }
+}
+
+impl From<Tree> for SledRepo {
+    fn from(tree: Tree) -> Self {
+        Self::from_tree(tree)
+    }
+}
+
+impl Fallible for SledRepo {
+    type Error = sled::Error;
+}
+
+impl Write for SledRepo {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        self.tree.insert(key, bytes)?;
+        Ok(())
+    }
+
+    fn write_durable(
+        &mut self,
+        key: &[u8],
+        bytes: &[u8],
+        durability: Durability,
+    ) -> Result<(), Self::Error> {
+        self.write(key, bytes)?;
+
+        // sled only knows one way to persist, a synced flush
+        if durability > Durability::Relaxed {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn supports_durability(&self) -> bool {
+        true
+    }
+}
+
+impl Read for SledRepo {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(self.tree.get(key)?.map(|bytes| bytes.to_vec()))
+    }
+
+    fn read_with<R, F>(&self, key: &[u8], f: F) -> Result<R, Self::Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> R,
+    {
+        Ok(f(self.tree.get(key)?.as_deref()))
+    }
+}
+
+impl HasKey for SledRepo {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        self.tree.contains_key(key)
+    }
+}
+
+impl Remove for SledRepo {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.tree.remove(key)?;
+        Ok(())
+    }
+
+    fn remove_returning(&mut self, key: &[u8]) -> Result<Removed, Self::Error> {
+        Ok(match self.tree.remove(key)? {
+            Some(_) => Removed::Existed,
+            None => Removed::DidNotExist,
+        })
+    }
+
+    /// Applies the ops atomically.
+    fn write_batch(&mut self, ops: &[BatchOp<'_>]) -> Result<(), Self::Error> {
+        let mut batch = sled::Batch::default();
+
+        for (key, bytes) in ops {
+            match bytes {
+                Some(bytes) => batch.insert(key.as_ref(), bytes.as_ref()),
+                None => batch.remove(key.as_ref()),
+            }
+        }
+
+        self.tree.apply_batch(batch)
+    }
+}
+
+/// The owned sled bound matching a kv-storage one.
+fn sled_bound(bound: Bound<&[u8]>) -> std::ops::Bound<IVec> {
+    bound.map(IVec::from).into()
+}
+
+impl SledRepo {
+    /// Collect the entries between the bounds, so iteration errors surface before yielding.
+    fn collect_range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<Vec<(IVec, IVec)>, sled::Error> {
+        // `Tree::range` panics on inverted bounds
+        if Bound::is_empty_range(&min, &max) {
+            return Ok(Vec::new());
+        }
+
+        let entries = self.tree.range((sled_bound(min), sled_bound(max)));
+
+        match order {
+            Order::Ascending => entries.collect(),
+            Order::Descending => entries.rev().collect(),
+        }
+    }
+}
+
+/// Entries are read eagerly: sled can fail mid-iteration, and the yielded items can't carry
+/// errors.
+impl Iterate for SledRepo {
+    fn range(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawEntries<'_>, Self::Error> {
+        let entries = self.collect_range(min, max, order)?;
+
+        Ok(Box::new(
+            entries
+                .into_iter()
+                .map(|(key, bytes)| (key.to_vec(), bytes.to_vec())),
+        ))
+    }
+
+    fn range_keys(
+        &self,
+        min: Bound<&[u8]>,
+        max: Bound<&[u8]>,
+        order: Order,
+    ) -> Result<RawKeys<'_>, Self::Error> {
+        let entries = self.collect_range(min, max, order)?;
+
+        Ok(Box::new(entries.into_iter().map(|(key, _)| key.to_vec())))
+    }
+
+    fn scan(&self, prefix: &[u8]) -> Result<RawEntries<'_>, Self::Error> {
+        let entries = self
+            .tree
+            .scan_prefix(prefix)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Box::new(
+            entries
+                .into_iter()
+                .map(|(key, bytes)| (key.to_vec(), bytes.to_vec())),
+        ))
+    }
+}
@@ -0,0 +1,281 @@
+use kv_storage::{
+    trace::{self, Op, Traceable},
+    Durability, Fallible, HasKey, Read, Remove, Write,
+};
+
+/// What usage is measured against.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Limit {
+    /// Total bytes of keys and values.
+    Bytes(u64),
+    /// Number of keys.
+    Keys(u64),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+/// A threshold was crossed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WatermarkEvent {
+    /// The threshold crossed, as a percentage of the limit.
+    pub percent: u8,
+    pub direction: Direction,
+    /// Usage after the operation that crossed the threshold.
+    pub usage: u64,
+    pub limit: Limit,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Usage {
+    pub bytes: u64,
+    pub keys: u64,
+}
+
+struct Threshold {
+    percent: u8,
+    above: bool,
+}
+
+/// Tracks how much of a bounded repo is used and calls back when configured thresholds are
+/// crossed.
+///
+/// Each threshold fires once when usage rises to or above it, and is re-armed (firing a `Down`
+/// event) once usage drops back below it. Overwrites and removes read the previous value from
+/// the inner repo to keep the accounting exact.
+pub struct WatermarkRepo<R, F> {
+    inner: R,
+    limit: Limit,
+    thresholds: Vec<Threshold>,
+    usage: Usage,
+    on_event: F,
+}
+
+impl<R, F> WatermarkRepo<R, F>
+where
+    F: FnMut(WatermarkEvent),
+{
+    /// Wrap an empty repo, `thresholds` are percentages of `limit`.
+    pub fn new(inner: R, limit: Limit, thresholds: &[u8], on_event: F) -> Self {
+        Self::with_usage(inner, Usage::default(), limit, thresholds, on_event)
+    }
+
+    /// Wrap a repo that already holds `usage`.
+    ///
+    /// Thresholds already exceeded start out crossed, without firing.
+    pub fn with_usage(
+        inner: R,
+        usage: Usage,
+        limit: Limit,
+        thresholds: &[u8],
+        on_event: F,
+    ) -> Self {
+        let mut repo = Self {
+            inner,
+            limit,
+            thresholds: Vec::with_capacity(thresholds.len()),
+            usage,
+            on_event,
+        };
+
+        let mut thresholds = thresholds.to_vec();
+        thresholds.sort_unstable();
+        thresholds.dedup();
+
+        for percent in thresholds {
+            let above = repo.is_above(percent);
+            repo.thresholds.push(Threshold { percent, above });
+        }
+
+        repo
+    }
+
+    pub fn usage(&self) -> Usage {
+        self.usage
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn current(&self) -> (u64, u64) {
+        match self.limit {
+            Limit::Bytes(max) => (self.usage.bytes, max),
+            Limit::Keys(max) => (self.usage.keys, max),
+        }
+    }
+
+    fn is_above(&self, percent: u8) -> bool {
+        let (current, max) = self.current();
+        u128::from(current) * 100 >= u128::from(max) * u128::from(percent)
+    }
+
+    fn notify(&mut self) {
+        let (usage, _) = self.current();
+
+        // crossing up is reported from the lowest threshold, crossing down from the highest
+        let mut events = Vec::new();
+
+        for idx in 0..self.thresholds.len() {
+            let above = self.is_above(self.thresholds[idx].percent);
+            let threshold = &mut self.thresholds[idx];
+
+            if above == threshold.above {
+                continue;
+            }
+
+            threshold.above = above;
+
+            events.push(WatermarkEvent {
+                percent: threshold.percent,
+                direction: if above {
+                    Direction::Up
+                } else {
+                    Direction::Down
+                },
+                usage,
+                limit: self.limit,
+            });
+        }
+
+        if events
+            .first()
+            .is_some_and(|e| e.direction == Direction::Down)
+        {
+            events.reverse();
+        }
+
+        for event in events {
+            (self.on_event)(event);
+        }
+    }
+}
+
+impl<R, F> Traceable for WatermarkRepo<R, F> {
+    const LAYER: &'static str = "watermark";
+}
+
+impl<R: Fallible, F> Fallible for WatermarkRepo<R, F> {
+    type Error = R::Error;
+}
+
+impl<R, F> Write for WatermarkRepo<R, F>
+where
+    R: Read + Write,
+    F: FnMut(WatermarkEvent),
+{
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        self.tracked_write(key, bytes, |inner| inner.write(key, bytes))
+    }
+
+    fn write_durable(
+        &mut self,
+        key: &[u8],
+        bytes: &[u8],
+        durability: Durability,
+    ) -> Result<(), Self::Error> {
+        self.tracked_write(key, bytes, |inner| {
+            inner.write_durable(key, bytes, durability)
+        })
+    }
+
+    fn supports_durability(&self) -> bool {
+        self.inner.supports_durability()
+    }
+}
+
+impl<R, F> WatermarkRepo<R, F>
+where
+    R: Read + Write,
+    F: FnMut(WatermarkEvent),
+{
+    fn tracked_write(
+        &mut self,
+        key: &[u8],
+        bytes: &[u8],
+        write: impl FnOnce(&mut R) -> Result<(), R::Error>,
+    ) -> Result<(), R::Error> {
+        let span = trace::enter(self);
+        span.receive(Op::Write, bytes.len());
+
+        let previous = self.inner.read(key)?;
+        span.issue(Op::Read, previous.as_ref().map_or(0, Vec::len));
+
+        span.issue(Op::Write, bytes.len());
+        write(&mut self.inner)?;
+
+        match previous {
+            Some(previous) => {
+                self.usage.bytes -= previous.len() as u64;
+            }
+            None => {
+                self.usage.keys += 1;
+                self.usage.bytes += key.len() as u64;
+            }
+        }
+
+        self.usage.bytes += bytes.len() as u64;
+
+        self.notify();
+
+        Ok(())
+    }
+}
+
+impl<R, F> Remove for WatermarkRepo<R, F>
+where
+    R: Read + Remove,
+    F: FnMut(WatermarkEvent),
+{
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        let span = trace::enter(self);
+        span.receive(Op::Remove, 0);
+
+        let previous = self.inner.read(key)?;
+        span.issue(Op::Read, previous.as_ref().map_or(0, Vec::len));
+
+        let Some(previous) = previous else {
+            return Ok(());
+        };
+
+        span.issue(Op::Remove, 0);
+        self.inner.remove(key)?;
+
+        self.usage.keys -= 1;
+        self.usage.bytes -= (key.len() + previous.len()) as u64;
+
+        self.notify();
+
+        Ok(())
+    }
+}
+
+impl<R: Read, F> Read for WatermarkRepo<R, F> {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.inner.read(key)
+    }
+
+    fn read_with<T, G>(&self, key: &[u8], f: G) -> Result<T, Self::Error>
+    where
+        G: FnOnce(Option<&[u8]>) -> T,
+    {
+        self.inner.read_with(key, f)
+    }
+
+    fn read_many(&self, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>, Self::Error> {
+        self.inner.read_many(keys)
+    }
+}
+
+impl<R: HasKey, F> HasKey for WatermarkRepo<R, F> {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        self.inner.has_key(key)
+    }
+}
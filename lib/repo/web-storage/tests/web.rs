@@ -0,0 +1,62 @@
+//! Needs a browser: `wasm-pack test --headless --firefox lib/repo/web-storage`.
+#![cfg(target_arch = "wasm32")]
+
+use kv_storage::{HasKey, Read, Remove, Write};
+use kv_storage_web_storage::{Error, StorageKind, WebStorageRepo};
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn repo(kind: StorageKind, namespace: &str) -> WebStorageRepo {
+    let repo = WebStorageRepo::new(kind, namespace).unwrap();
+    repo.storage().clear().unwrap();
+    repo
+}
+
+#[wasm_bindgen_test]
+fn round_trips_arbitrary_bytes() {
+    for kind in [StorageKind::Local, StorageKind::Session] {
+        let mut repo = repo(kind, "test:");
+        let key = [0, 255, b'/', 0];
+        let bytes = [0, 0, 159, 146, 150];
+
+        assert_eq!(repo.read(&key).unwrap(), None);
+
+        repo.write(&key, &bytes).unwrap();
+        assert!(repo.has_key(&key).unwrap());
+        assert_eq!(repo.read(&key).unwrap().as_deref(), Some(&bytes[..]));
+
+        repo.write(&key, &[]).unwrap();
+        assert_eq!(repo.read(&key).unwrap(), Some(Vec::new()));
+
+        repo.remove(&key).unwrap();
+        assert!(!repo.has_key(&key).unwrap());
+    }
+}
+
+#[wasm_bindgen_test]
+fn namespaces_coexist() {
+    let mut first = repo(StorageKind::Local, "first:");
+    let mut second = WebStorageRepo::new(StorageKind::Local, "second:").unwrap();
+
+    first.write(b"key", b"1").unwrap();
+    assert!(!second.has_key(b"key").unwrap());
+
+    second.write(b"key", b"2").unwrap();
+    assert_eq!(first.read(b"key").unwrap().as_deref(), Some(&b"1"[..]));
+    assert_eq!(second.read(b"key").unwrap().as_deref(), Some(&b"2"[..]));
+}
+
+#[wasm_bindgen_test]
+fn surfaces_a_full_quota() {
+    let mut repo = repo(StorageKind::Local, "quota:");
+
+    // past every browser's quota, a few MiB per origin
+    let huge = vec![0; 16 << 20];
+
+    assert!(matches!(
+        repo.write(b"huge", &huge),
+        Err(Error::QuotaExceeded)
+    ));
+    assert!(!repo.has_key(b"huge").unwrap());
+}
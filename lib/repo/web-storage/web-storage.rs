@@ -0,0 +1,145 @@
+//! A repo over the browser's `localStorage` or `sessionStorage`, for `wasm32` targets.
+//!
+//! Web storage only holds strings, so keys and values are base64-encoded.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use kv_storage::{Fallible, HasKey, Read, Remove, Write};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{DomException, Storage};
+
+pub use web_sys;
+
+/// Which of the window's storage areas to use.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StorageKind {
+    /// `localStorage`, kept across sessions.
+    Local,
+    /// `sessionStorage`, cleared when the page session ends.
+    Session,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// There's no window, or the browser denies access to its storage.
+    #[error("web storage is unavailable")]
+    Unavailable,
+    /// The storage area is full, a write didn't fit.
+    #[error("web storage quota exceeded")]
+    QuotaExceeded,
+    /// A stored value isn't valid base64, it wasn't written through a repo.
+    #[error(transparent)]
+    Decode(#[from] base64::DecodeError),
+    /// Any other exception thrown by the browser.
+    #[error("{name}: {message}")]
+    Js { name: String, message: String },
+}
+
+impl From<JsValue> for Error {
+    fn from(value: JsValue) -> Self {
+        let Some(exception) = value.dyn_ref::<DomException>() else {
+            return Self::Js {
+                name: "Error".to_owned(),
+                message: format!("{value:?}"),
+            };
+        };
+
+        let name = exception.name();
+
+        // Firefox used to report quota errors under its own name
+        if name == "QuotaExceededError"
+            || name == "NS_ERROR_DOM_QUOTA_REACHED"
+            || exception.code() == DomException::QUOTA_EXCEEDED_ERR
+        {
+            return Self::QuotaExceeded;
+        }
+
+        Self::Js {
+            name,
+            message: exception.message(),
+        }
+    }
+}
+
+/// Stores entries in a web storage area, their keys namespaced by a prefix so several stores
+/// can share it.
+pub struct WebStorageRepo {
+    storage: Storage,
+    namespace: String,
+}
+
+impl WebStorageRepo {
+    /// Use the window's storage area of the given kind.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`Error::Unavailable`] outside of a window, or if the browser
+    /// denies access to storage, e.g. for third-party frames.
+    pub fn new(kind: StorageKind, namespace: impl Into<String>) -> Result<Self, Error> {
+        let window = web_sys::window().ok_or(Error::Unavailable)?;
+
+        let storage = match kind {
+            StorageKind::Local => window.local_storage(),
+            StorageKind::Session => window.session_storage(),
+        };
+
+        // access denied throws in some browsers and returns nothing in others
+        let storage = storage.ok().flatten().ok_or(Error::Unavailable)?;
+
+        Ok(Self::from_storage(storage, namespace))
+    }
+
+    pub fn from_storage(storage: Storage, namespace: impl Into<String>) -> Self {
+        Self {
+            storage,
+            namespace: namespace.into(),
+        }
+    }
+
+    pub fn storage(&self) -> &Storage {
+        &self.storage
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// The storage key for `key`.
+    fn key(&self, key: &[u8]) -> String {
+        let mut encoded = self.namespace.clone();
+        STANDARD.encode_string(key, &mut encoded);
+        encoded
+    }
+}
+
+impl Fallible for WebStorageRepo {
+    type Error = Error;
+}
+
+impl Write for WebStorageRepo {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        Ok(self
+            .storage
+            .set_item(&self.key(key), &STANDARD.encode(bytes))?)
+    }
+}
+
+impl Read for WebStorageRepo {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        match self.storage.get_item(&self.key(key))? {
+            Some(encoded) => Ok(Some(STANDARD.decode(encoded)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl HasKey for WebStorageRepo {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        Ok(self.storage.get_item(&self.key(key))?.is_some())
+    }
+}
+
+impl Remove for WebStorageRepo {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        Ok(self.storage.remove_item(&self.key(key))?)
+    }
+}
@@ -1,15 +1,95 @@
-use kv_storage::{Deserializer, Fallible, Serializer};
+//! The bincode serializer, with a reusable buffer:
+//!
+//! ```
+//! use kv_storage::prelude::*;
+//! use kv_storage_bincode::Bincode;
+//! use kv_storage_memory::MemoryRepo;
+//!
+//! const TOTAL: Item<u64> = item!("total");
+//!
+//! let mut store = KvStore::new(Bincode::new_with_capacity(64), MemoryRepo::default());
+//! TOTAL.save(&mut store, 42).unwrap();
+//! assert_eq!(TOTAL.may_load(&store).unwrap(), Some(42));
+//! ```
+
+use std::{io, marker::PhantomData};
+
+use kv_storage::{Deserializer, Fallible, Serializer, StreamError};
 use serde::{de::DeserializeOwned, Serialize};
 
 #[cfg(feature = "bincode-no-custom")]
 use bincode_no_custom as bincode;
 
-#[derive(Default)]
-pub struct Bincode {
+/// Run `$body` with `$opts` bound to the `bincode::Options` matching a [`BincodeConfig`] and an
+/// optional size limit.
+macro_rules! with_options {
+    ($config:expr, |$opts:ident| $body:expr) => {
+        with_options!($config, None::<u64>, |$opts| $body)
+    };
+    ($config:expr, $limit:expr, |$opts:ident| $body:expr) => {{
+        use bincode::Options as _;
+
+        let base = bincode::DefaultOptions::new().allow_trailing_bytes();
+
+        match ($config.int_encoding, $config.endian) {
+            (IntEncoding::Fixint, Endian::Little) => {
+                with_limit!(
+                    base.with_fixint_encoding().with_little_endian(),
+                    $limit,
+                    |$opts| $body
+                )
+            }
+            (IntEncoding::Fixint, Endian::Big) => {
+                with_limit!(
+                    base.with_fixint_encoding().with_big_endian(),
+                    $limit,
+                    |$opts| $body
+                )
+            }
+            (IntEncoding::Varint, Endian::Little) => {
+                with_limit!(
+                    base.with_varint_encoding().with_little_endian(),
+                    $limit,
+                    |$opts| $body
+                )
+            }
+            (IntEncoding::Varint, Endian::Big) => {
+                with_limit!(
+                    base.with_varint_encoding().with_big_endian(),
+                    $limit,
+                    |$opts| $body
+                )
+            }
+        }
+    }};
+}
+
+macro_rules! with_limit {
+    ($options:expr, $limit:expr, |$opts:ident| $body:expr) => {
+        match $limit {
+            Some(limit) => {
+                let $opts = $options.with_limit(limit);
+                $body
+            }
+            None => {
+                let $opts = $options;
+                $body
+            }
+        }
+    };
+}
+
+/// The bincode serializer, configured by a [`BincodeOptions`] type.
+pub struct BincodeWith<O> {
     buffer: Vec<u8>,
+    _o: PhantomData<O>,
 }
 
+/// The bincode serializer with the options of the `bincode::serialize` family of functions.
+pub type Bincode = BincodeWith<LegacyOptions>;
+
 pub type Error = bincode::Error;
+pub use bincode::ErrorKind;
 
 impl Bincode {
     pub fn new() -> Self {
@@ -17,30 +97,226 @@ impl Bincode {
     }
 
     pub fn new_with_buffer(buffer: Vec<u8>) -> Self {
-        Self { buffer }
+        Self {
+            buffer,
+            _o: PhantomData,
+        }
     }
 
     pub fn new_with_capacity(capacity: usize) -> Self {
+        Self::new_with_buffer(Vec::with_capacity(capacity))
+    }
+
+    /// A serializer using other options, e.g. `Bincode::with_options(VarintOptions)`.
+    pub fn with_options<O: BincodeOptions>(_options: O) -> BincodeWith<O> {
+        BincodeWith::default()
+    }
+}
+
+impl<O> BincodeWith<O> {
+    /// Reuse a buffer, keeping the options.
+    #[must_use]
+    pub fn with_buffer(self, buffer: Vec<u8>) -> Self {
+        Self { buffer, ..self }
+    }
+}
+
+impl<O> Default for BincodeWith<O> {
+    fn default() -> Self {
         Self {
-            buffer: Vec::with_capacity(capacity),
+            buffer: Vec::new(),
+            _o: PhantomData,
         }
     }
 }
 
-impl Fallible for Bincode {
+impl<O> Fallible for BincodeWith<O> {
     type Error = Error;
 }
 
-impl Serializer for Bincode {
+impl<O: BincodeOptions> Serializer for BincodeWith<O> {
+    fn serialize<T: Serialize>(&mut self, item: &T) -> Result<&[u8], Self::Error> {
+        self.buffer.clear();
+        with_options!(O::CONFIG, O::LIMIT, |opts| opts
+            .serialize_into(&mut self.buffer, item))?;
+        Ok(&self.buffer)
+    }
+
+    fn serialize_owned<T: Serialize>(&mut self, item: &T) -> Result<Vec<u8>, Self::Error> {
+        self.serialize(item)?;
+        Ok(std::mem::take(&mut self.buffer))
+    }
+
+    fn serialize_into<T, W>(&mut self, item: &T, writer: W) -> Result<(), StreamError<Error>>
+    where
+        T: Serialize,
+        W: io::Write,
+    {
+        with_options!(O::CONFIG, O::LIMIT, |opts| opts
+            .serialize_into(writer, item))
+        .map_err(|error| match *error {
+            ErrorKind::Io(error) => StreamError::Io(error),
+            _ => StreamError::Serde(error),
+        })
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+}
+
+impl<O: BincodeOptions> Deserializer for BincodeWith<O> {
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        // bincode only enforces limits when reading from a reader, not a slice
+        if O::LIMIT.is_some_and(|limit| bytes.len() as u64 > limit) {
+            return Err(Box::new(ErrorKind::SizeLimit));
+        }
+
+        with_options!(O::CONFIG, |opts| opts.deserialize(bytes))
+    }
+}
+
+/// The options of a [`Bincode`] serializer, as a type because deserializing has no `self` to
+/// read them from.
+pub trait BincodeOptions {
+    const CONFIG: BincodeConfig;
+
+    /// The most bytes a value may take, larger ones fail with [`ErrorKind::SizeLimit`] both when
+    /// serializing and deserializing, before anything is decoded.
+    const LIMIT: Option<u64> = None;
+}
+
+/// [`BincodeConfig::LEGACY`] without a size limit, what [`Bincode`] uses.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct LegacyOptions;
+
+impl BincodeOptions for LegacyOptions {
+    const CONFIG: BincodeConfig = BincodeConfig::LEGACY;
+}
+
+/// [`BincodeConfig::VARINT`] without a size limit.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct VarintOptions;
+
+impl BincodeOptions for VarintOptions {
+    const CONFIG: BincodeConfig = BincodeConfig::VARINT;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IntEncoding {
+    Fixint,
+    Varint,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+/// A bincode options preset, as a value so presets can be listed in a [`ConfigTable`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BincodeConfig {
+    pub int_encoding: IntEncoding,
+    pub endian: Endian,
+}
+
+impl BincodeConfig {
+    /// What the `bincode::serialize` family of functions (and [`Bincode`]) use.
+    pub const LEGACY: Self = Self {
+        int_encoding: IntEncoding::Fixint,
+        endian: Endian::Little,
+    };
+
+    /// What `bincode::DefaultOptions` uses.
+    pub const VARINT: Self = Self {
+        int_encoding: IntEncoding::Varint,
+        endian: Endian::Little,
+    };
+}
+
+/// The configurations a [`NegotiatedBincode`] knows, indexed by config id.
+///
+/// Append new configurations, never reorder or remove them: ids are stored with every value.
+pub trait ConfigTable {
+    const CONFIGS: &'static [BincodeConfig];
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum NegotiationError {
+    #[error(transparent)]
+    Bincode(#[from] Error),
+    #[error("value has no config id")]
+    MissingConfigId,
+    #[error("unknown bincode config id {0}, the value was written by a newer configuration")]
+    UnknownConfig(u8),
+}
+
+/// Bincode prefixed with a 1-byte config id, so values written by older configurations stay
+/// readable. Always writes with the newest (last) configuration in the table.
+pub struct NegotiatedBincode<C> {
+    buffer: Vec<u8>,
+    _c: PhantomData<C>,
+}
+
+impl<C: ConfigTable> NegotiatedBincode<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The id values are written with.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the table is empty or has more than 256 configurations.
+    pub fn current_id() -> u8 {
+        assert!(!C::CONFIGS.is_empty(), "empty bincode config table");
+        u8::try_from(C::CONFIGS.len() - 1).expect("too many bincode configs")
+    }
+}
+
+impl<C> Default for NegotiatedBincode<C> {
+    fn default() -> Self {
+        Self {
+            buffer: Vec::new(),
+            _c: PhantomData,
+        }
+    }
+}
+
+impl<C> Fallible for NegotiatedBincode<C> {
+    type Error = NegotiationError;
+}
+
+impl<C: ConfigTable> Serializer for NegotiatedBincode<C> {
     fn serialize<T: Serialize>(&mut self, item: &T) -> Result<&[u8], Self::Error> {
+        let id = Self::current_id();
+
         self.buffer.clear();
-        bincode::serialize_into(&mut self.buffer, item)?;
+        self.buffer.push(id);
+
+        with_options!(C::CONFIGS[usize::from(id)], |opts| opts
+            .serialize_into(&mut self.buffer, item))?;
+
         Ok(&self.buffer)
     }
+
+    fn serialize_owned<T: Serialize>(&mut self, item: &T) -> Result<Vec<u8>, Self::Error> {
+        self.serialize(item)?;
+        Ok(std::mem::take(&mut self.buffer))
+    }
 }
 
-impl Deserializer for Bincode {
-    fn deserialize<T: DeserializeOwned>(bytes: Vec<u8>) -> Result<T, Self::Error> {
-        bincode::deserialize(&bytes)
+impl<C: ConfigTable> Deserializer for NegotiatedBincode<C> {
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        let Some((&id, payload)) = bytes.split_first() else {
+            return Err(NegotiationError::MissingConfigId);
+        };
+
+        let config = C::CONFIGS
+            .get(usize::from(id))
+            .ok_or(NegotiationError::UnknownConfig(id))?;
+
+        with_options!(config, |opts| opts.deserialize(payload)).map_err(NegotiationError::from)
     }
 }
@@ -3,6 +3,7 @@
 //! instance of the `DefaultOptions` struct:
 //!
 //! ```rust
+//! # extern crate bincode_no_custom as bincode;
 //! use bincode::Options;
 //! let my_options = bincode::DefaultOptions::new();
 //! ```
@@ -21,6 +22,7 @@
 //! settings as the functions, you should adjust the `DefaultOptions` struct like so:
 //!
 //! ```rust
+//! # extern crate bincode_no_custom as bincode;
 //! use bincode::Options;
 //! let my_options = bincode::DefaultOptions::new()
 //!     .with_fixint_encoding()
@@ -64,6 +66,7 @@ mod trailing;
 /// For example, if you wanted to limit the bincode deserializer to 1 kilobyte of user input:
 ///
 /// ```rust
+/// # extern crate bincode_no_custom as bincode;
 /// use bincode::Options;
 /// let my_options = bincode::DefaultOptions::new().with_limit(1024);
 /// ```
@@ -1,5 +1,16 @@
 #![deny(missing_docs)]
 #![allow(unknown_lints, bare_trait_objects, deprecated)]
+#![allow(dead_code, non_local_definitions)]
+#![allow(
+    clippy::doc_lazy_continuation,
+    clippy::extra_unused_lifetimes,
+    clippy::legacy_numeric_constants,
+    clippy::manual_is_multiple_of,
+    clippy::mem_replace_with_default,
+    clippy::multiple_bound_locations,
+    clippy::needless_lifetimes,
+    clippy::unnecessary_cast
+)]
 
 //! Bincode is a crate for encoding and decoding using a tiny binary
 //! serialization strategy.  Using it, you can easily go from having
@@ -9,6 +20,7 @@
 //! ### Using Basic Functions
 //!
 //! ```edition2018
+//! # extern crate bincode_no_custom as bincode;
 //! fn main() {
 //!     // The object that we will serialize.
 //!     let target: Option<String>  = Some("hello world".to_string());
@@ -1,7 +1,9 @@
+#![allow(warnings, clippy::all)]
+
 #[macro_use]
 extern crate serde_derive;
 
-extern crate bincode;
+extern crate bincode_no_custom as bincode;
 #[macro_use]
 extern crate serde;
 extern crate serde_bytes;
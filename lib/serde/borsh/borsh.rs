@@ -0,0 +1,366 @@
+//! Borsh values, for data shared with Borsh-based ecosystems.
+//!
+//! The core `Serializer`/`Deserializer` traits are bound to serde, so this crate mirrors
+//! `KvStore`, `Item` and `Map` with Borsh bounds instead, like `kv-storage-prost` does for
+//! protobuf. Keys are composed exactly like the core containers, and [`borsh_item!`] and
+//! [`borsh_map!`] take the same arguments as `item!` and `map!`:
+//!
+//! ```
+//! use borsh::{BorshDeserialize, BorshSerialize};
+//! use kv_storage_borsh::{borsh_item, borsh_map, BorshItem, BorshMap, BorshStore};
+//! use kv_storage_memory::MemoryRepo;
+//!
+//! #[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+//! struct Account {
+//!     balance: u128,
+//! }
+//!
+//! const TOTAL: BorshItem<u64> = borsh_item!("total");
+//! const ACCOUNTS: BorshMap<64, &str, Account> = borsh_map!("accounts");
+//!
+//! let mut store = BorshStore::<MemoryRepo>::default();
+//!
+//! TOTAL.save(&mut store, 42).unwrap();
+//! ACCOUNTS.save(&mut store, "alice", Account { balance: 10 }).unwrap();
+//!
+//! assert_eq!(TOTAL.may_load(&store).unwrap(), Some(42));
+//! assert_eq!(ACCOUNTS.may_load(&store, "alice").unwrap(), Some(Account { balance: 10 }));
+//! ```
+
+use std::{borrow::Borrow, marker::PhantomData};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use kv_storage::{EncodeLike, Fallible, HasKey, Map, Read, Remove, Write, WriteCompositeKey};
+
+pub use borsh;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use kv_storage;
+}
+
+/// Borsh reports every failure as an IO error.
+pub type Error = borsh::io::Error;
+
+/// The Borsh codec, reusing its buffer across encodes.
+#[derive(Default)]
+pub struct Borsh {
+    buffer: Vec<u8>,
+}
+
+impl Borsh {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_with_capacity(capacity: usize) -> Self {
+        Self {
+            buffer: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Encode a value, returning the buffer.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the value fails to encode.
+    pub fn encode<T: BorshSerialize>(&mut self, value: &T) -> Result<&[u8], Error> {
+        self.buffer.clear();
+        value.serialize(&mut self.buffer)?;
+        Ok(&self.buffer)
+    }
+
+    /// Decode a value, which must span all of the bytes.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the bytes aren't exactly one encoded value.
+    pub fn decode<T: BorshDeserialize>(bytes: &[u8]) -> Result<T, Error> {
+        borsh::from_slice(bytes)
+    }
+}
+
+impl Fallible for Borsh {
+    type Error = Error;
+}
+
+/// `KvStore` for Borsh values.
+#[derive(Default)]
+pub struct BorshStore<Repo> {
+    codec: Borsh,
+    repo: Repo,
+}
+
+impl<Repo> BorshStore<Repo> {
+    pub const fn new(codec: Borsh, repo: Repo) -> Self {
+        Self { codec, repo }
+    }
+
+    pub fn from_repo(repo: Repo) -> Self {
+        Self::new(Borsh::new(), repo)
+    }
+
+    pub fn repo(&self) -> &Repo {
+        &self.repo
+    }
+
+    pub fn mut_repo(&mut self) -> &mut Repo {
+        &mut self.repo
+    }
+}
+
+impl<Repo: Fallible> Fallible for BorshStore<Repo> {
+    type Error = kv_storage::Error<Error, Repo::Error>;
+}
+
+impl<Repo> BorshStore<Repo>
+where
+    Repo: Read + HasKey,
+{
+    /// Load a value for a given key if it exists.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Read encounters an error.
+    /// - Decoding encounters an error.
+    pub fn may_load<T: BorshDeserialize>(
+        &self,
+        key: &[u8],
+    ) -> Result<Option<T>, <Self as Fallible>::Error> {
+        let Some(bytes) = self.repo.read(key).map_err(kv_storage::Error::Repo)? else {
+            return Ok(None);
+        };
+
+        Borsh::decode(&bytes)
+            .map(Some)
+            .map_err(kv_storage::Error::Serde)
+    }
+
+    /// Check if a key exists in storage.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the repo encounters an error.
+    pub fn has_key(&self, key: &[u8]) -> Result<bool, <Self as Fallible>::Error> {
+        self.repo.has_key(key).map_err(kv_storage::Error::Repo)
+    }
+}
+
+impl<Repo> BorshStore<Repo>
+where
+    Repo: Read + HasKey + Write + Remove,
+{
+    /// Save a value against the given key.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Encoding encounters an error.
+    /// - Write encounters an error.
+    pub fn save<T: BorshSerialize>(
+        &mut self,
+        key: &[u8],
+        value: &T,
+    ) -> Result<(), <Self as Fallible>::Error> {
+        let buffer = self.codec.encode(value).map_err(kv_storage::Error::Serde)?;
+        self.repo
+            .write(key, buffer)
+            .map_err(kv_storage::Error::Repo)
+    }
+
+    /// Remove a key and any associated data from storage.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the repo encounters an error.
+    pub fn remove(&mut self, key: &[u8]) -> Result<(), <Self as Fallible>::Error> {
+        self.repo.remove(key).map_err(kv_storage::Error::Repo)
+    }
+}
+
+/// `Item` for Borsh values.
+pub struct BorshItem<T> {
+    key: &'static [u8],
+    _t: PhantomData<T>,
+}
+
+impl<T> BorshItem<T> {
+    #[must_use]
+    pub const fn new(key: &'static [u8]) -> Self {
+        Self {
+            key,
+            _t: PhantomData,
+        }
+    }
+
+    /// Reuse the key of a core `Item` declaration, e.g. one built with `item!`.
+    #[must_use]
+    pub const fn from_item<U>(item: &kv_storage::Item<U>) -> Self {
+        Self::new(item.key())
+    }
+
+    #[must_use]
+    pub const fn key(&self) -> &'static [u8] {
+        self.key
+    }
+
+    /// Save the item to storage.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn save<Repo>(
+        &self,
+        store: &mut BorshStore<Repo>,
+        value: impl Borrow<T>,
+    ) -> Result<(), <BorshStore<Repo> as Fallible>::Error>
+    where
+        T: BorshSerialize,
+        Repo: Read + HasKey + Write + Remove,
+    {
+        store.save(self.key, value.borrow())
+    }
+
+    /// Load the item from storage if it exists, otherwise `None`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load<Repo>(
+        &self,
+        store: &BorshStore<Repo>,
+    ) -> Result<Option<T>, <BorshStore<Repo> as Fallible>::Error>
+    where
+        T: BorshDeserialize,
+        Repo: Read + HasKey,
+    {
+        store.may_load(self.key)
+    }
+
+    /// Clear the item from storage.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn clear<Repo>(
+        &self,
+        store: &mut BorshStore<Repo>,
+    ) -> Result<(), <BorshStore<Repo> as Fallible>::Error>
+    where
+        Repo: Read + HasKey + Write + Remove,
+    {
+        store.remove(self.key)
+    }
+}
+
+/// `Map` for Borsh values, composing keys exactly like the core `Map`.
+pub struct BorshMap<const N: usize, K, V> {
+    map: Map<N, K, V>,
+}
+
+impl<const N: usize, K, V> BorshMap<N, K, V>
+where
+    K: WriteCompositeKey,
+{
+    #[must_use]
+    pub const fn new(prefix: &'static [u8]) -> Self {
+        Self {
+            map: Map::new(prefix),
+        }
+    }
+
+    /// Reuse the prefix of a core `Map` declaration, e.g. one built with `map!`.
+    #[must_use]
+    pub const fn from_map<U>(map: &Map<N, K, U>) -> Self {
+        Self::new(map.prefix())
+    }
+
+    #[must_use]
+    pub const fn prefix(&self) -> &'static [u8] {
+        self.map.prefix()
+    }
+
+    /// Save the value for the given key.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn save<Repo>(
+        &self,
+        store: &mut BorshStore<Repo>,
+        key: impl EncodeLike<K>,
+        value: impl Borrow<V>,
+    ) -> Result<(), <BorshStore<Repo> as Fallible>::Error>
+    where
+        V: BorshSerialize,
+        Repo: Read + HasKey + Write + Remove,
+    {
+        store.save(self.map.key(key).as_ref(), value.borrow())
+    }
+
+    /// Load the value for the given key if it exists, otherwise `None`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load<Repo>(
+        &self,
+        store: &BorshStore<Repo>,
+        key: impl EncodeLike<K>,
+    ) -> Result<Option<V>, <BorshStore<Repo> as Fallible>::Error>
+    where
+        V: BorshDeserialize,
+        Repo: Read + HasKey,
+    {
+        store.may_load(self.map.key(key).as_ref())
+    }
+
+    /// Check if a key exists.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn has_key<Repo>(
+        &self,
+        store: &BorshStore<Repo>,
+        key: impl EncodeLike<K>,
+    ) -> Result<bool, <BorshStore<Repo> as Fallible>::Error>
+    where
+        Repo: Read + HasKey,
+    {
+        store.has_key(self.map.key(key).as_ref())
+    }
+
+    /// Remove any value stored at the given key.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn remove<Repo>(
+        &self,
+        store: &mut BorshStore<Repo>,
+        key: impl EncodeLike<K>,
+    ) -> Result<(), <BorshStore<Repo> as Fallible>::Error>
+    where
+        Repo: Read + HasKey + Write + Remove,
+    {
+        store.remove(self.map.key(key).as_ref())
+    }
+}
+
+/// Declare a [`BorshItem`], taking the same arguments as `item!`.
+#[macro_export]
+macro_rules! borsh_item {
+    ($($args:tt)+) => {
+        $crate::BorshItem::from_item::<()>(&$crate::__private::kv_storage::item!($($args)+))
+    };
+}
+
+/// Declare a [`BorshMap`], taking the same arguments as `map!`.
+#[macro_export]
+macro_rules! borsh_map {
+    ($($args:tt)+) => {
+        $crate::BorshMap::from_map::<()>(&$crate::__private::kv_storage::map!($($args)+))
+    };
+}
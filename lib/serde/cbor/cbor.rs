@@ -0,0 +1,71 @@
+//! The CBOR serializer, with a reusable buffer, for self-describing values:
+//!
+//! ```
+//! use kv_storage::prelude::*;
+//! use kv_storage_cbor::Cbor;
+//! use kv_storage_memory::MemoryRepo;
+//!
+//! const TOTAL: Item<u64> = item!("total");
+//!
+//! let mut store = KvStore::new(Cbor::new_with_capacity(64), MemoryRepo::default());
+//! TOTAL.save(&mut store, 42).unwrap();
+//! assert_eq!(TOTAL.may_load(&store).unwrap(), Some(42));
+//! ```
+
+use kv_storage::{Deserializer, Fallible, Serializer};
+use serde::{de::DeserializeOwned, Serialize};
+
+pub use ciborium;
+
+#[derive(Default)]
+pub struct Cbor {
+    buffer: Vec<u8>,
+}
+
+/// Serialization and deserialization errors, `ciborium` has a type for each.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Serialize(#[from] ciborium::ser::Error<std::io::Error>),
+    #[error(transparent)]
+    Deserialize(#[from] ciborium::de::Error<std::io::Error>),
+}
+
+impl Cbor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_with_buffer(buffer: Vec<u8>) -> Self {
+        Self { buffer }
+    }
+
+    pub fn new_with_capacity(capacity: usize) -> Self {
+        Self {
+            buffer: Vec::with_capacity(capacity),
+        }
+    }
+}
+
+impl Fallible for Cbor {
+    type Error = Error;
+}
+
+impl Serializer for Cbor {
+    fn serialize<T: Serialize>(&mut self, item: &T) -> Result<&[u8], Self::Error> {
+        self.buffer.clear();
+        ciborium::into_writer(item, &mut self.buffer)?;
+        Ok(&self.buffer)
+    }
+
+    fn serialize_owned<T: Serialize>(&mut self, item: &T) -> Result<Vec<u8>, Self::Error> {
+        self.serialize(item)?;
+        Ok(std::mem::take(&mut self.buffer))
+    }
+}
+
+impl Deserializer for Cbor {
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        Ok(ciborium::from_reader(bytes)?)
+    }
+}
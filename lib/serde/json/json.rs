@@ -0,0 +1,78 @@
+//! The JSON serializer, with a reusable buffer, for values that should stay readable as stored:
+//!
+//! ```
+//! use kv_storage::prelude::*;
+//! use kv_storage_json::Json;
+//! use kv_storage_memory::MemoryRepo;
+//!
+//! const TOTAL: Item<u64> = item!("total");
+//!
+//! let mut store: KvStore<Json, MemoryRepo> = KvStore::default();
+//! TOTAL.save(&mut store, 42).unwrap();
+//! assert_eq!(TOTAL.may_load(&store).unwrap(), Some(42));
+//! ```
+
+use kv_storage::{Deserializer, Fallible, Serializer};
+use serde::{de::DeserializeOwned, Serialize};
+
+#[derive(Default)]
+pub struct Json {
+    buffer: Vec<u8>,
+    pretty: bool,
+}
+
+pub type Error = serde_json::Error;
+
+impl Json {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write values indented over several lines when `pretty` is set, reading doesn't care.
+    pub fn with_pretty(pretty: bool) -> Self {
+        Self {
+            buffer: Vec::new(),
+            pretty,
+        }
+    }
+
+    pub fn new_with_buffer(buffer: Vec<u8>) -> Self {
+        Self {
+            buffer,
+            pretty: false,
+        }
+    }
+
+    pub fn new_with_capacity(capacity: usize) -> Self {
+        Self::new_with_buffer(Vec::with_capacity(capacity))
+    }
+}
+
+impl Fallible for Json {
+    type Error = Error;
+}
+
+impl Serializer for Json {
+    fn serialize<T: Serialize>(&mut self, item: &T) -> Result<&[u8], Self::Error> {
+        self.buffer.clear();
+
+        if self.pretty {
+            serde_json::to_writer_pretty(&mut self.buffer, item)?;
+        } else {
+            serde_json::to_writer(&mut self.buffer, item)?;
+        }
+
+        Ok(&self.buffer)
+    }
+
+    fn serialize_owned<T: Serialize>(&mut self, item: &T) -> Result<Vec<u8>, Self::Error> {
+        self.serialize(item)?;
+        Ok(std::mem::take(&mut self.buffer))
+    }
+}
+
+impl Deserializer for Json {
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        serde_json::from_slice(bytes)
+    }
+}
@@ -0,0 +1,99 @@
+//! The MessagePack serializer, with a reusable buffer, for values shared with other languages:
+//!
+//! ```
+//! use kv_storage::prelude::*;
+//! use kv_storage_memory::MemoryRepo;
+//! use kv_storage_msgpack::{MessagePack, StructEncoding};
+//!
+//! const TOTAL: Item<u64> = item!("total");
+//!
+//! let serde = MessagePack::with_struct_encoding(StructEncoding::Tuple);
+//! let mut store = KvStore::new(serde, MemoryRepo::default());
+//! TOTAL.save(&mut store, 42).unwrap();
+//! assert_eq!(TOTAL.may_load(&store).unwrap(), Some(42));
+//! ```
+
+use kv_storage::{Deserializer, Fallible, Serializer};
+use serde::{de::DeserializeOwned, Serialize};
+
+pub use rmp_serde;
+
+/// How structs are written, both are read back.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum StructEncoding {
+    /// A map keyed by field name, so other decoders can match fields by name.
+    #[default]
+    Map,
+    /// An array of the fields in declaration order, more compact but tied to the field order.
+    Tuple,
+}
+
+#[derive(Default)]
+pub struct MessagePack {
+    buffer: Vec<u8>,
+    structs: StructEncoding,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Encode(#[from] rmp_serde::encode::Error),
+    #[error(transparent)]
+    Decode(#[from] rmp_serde::decode::Error),
+}
+
+impl MessagePack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_struct_encoding(structs: StructEncoding) -> Self {
+        Self {
+            buffer: Vec::new(),
+            structs,
+        }
+    }
+
+    pub fn new_with_buffer(buffer: Vec<u8>) -> Self {
+        Self {
+            buffer,
+            structs: StructEncoding::default(),
+        }
+    }
+
+    pub fn new_with_capacity(capacity: usize) -> Self {
+        Self::new_with_buffer(Vec::with_capacity(capacity))
+    }
+
+    pub fn struct_encoding(&self) -> StructEncoding {
+        self.structs
+    }
+}
+
+impl Fallible for MessagePack {
+    type Error = Error;
+}
+
+impl Serializer for MessagePack {
+    fn serialize<T: Serialize>(&mut self, item: &T) -> Result<&[u8], Self::Error> {
+        self.buffer.clear();
+
+        match self.structs {
+            StructEncoding::Map => rmp_serde::encode::write_named(&mut self.buffer, item)?,
+            StructEncoding::Tuple => rmp_serde::encode::write(&mut self.buffer, item)?,
+        }
+
+        Ok(&self.buffer)
+    }
+
+    fn serialize_owned<T: Serialize>(&mut self, item: &T) -> Result<Vec<u8>, Self::Error> {
+        self.serialize(item)?;
+        Ok(std::mem::take(&mut self.buffer))
+    }
+}
+
+impl Deserializer for MessagePack {
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        Ok(rmp_serde::from_slice(bytes)?)
+    }
+}
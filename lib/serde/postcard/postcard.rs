@@ -0,0 +1,84 @@
+//! The postcard serializer, with a reusable buffer, for the smallest code and values:
+//!
+//! ```
+//! use kv_storage::prelude::*;
+//! use kv_storage_memory::MemoryRepo;
+//! use kv_storage_postcard::Postcard;
+//!
+//! const TOTAL: Item<u64> = item!("total");
+//!
+//! let mut store = KvStore::new(Postcard::new_with_capacity(64), MemoryRepo::default());
+//! TOTAL.save(&mut store, 42).unwrap();
+//! assert_eq!(TOTAL.may_load(&store).unwrap(), Some(42));
+//! ```
+//!
+//! Postcard values don't describe their schema. Reading a value with a different struct than it
+//! was written with, e.g. after adding or removing a field, fails when the bytes run out or some
+//! are left over, but changes keeping the same encoded shape (reordering fields of one type) go
+//! unnoticed. Migrate values when their type changes.
+
+use kv_storage::{Deserializer, Fallible, Serializer};
+use serde::{de::DeserializeOwned, Serialize};
+
+#[derive(Default)]
+pub struct Postcard {
+    buffer: Vec<u8>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Postcard(#[from] postcard::Error),
+    /// The value was decoded before the end of the bytes, it was likely written with another type.
+    #[error("{0} bytes left over after the value")]
+    TrailingBytes(usize),
+}
+
+impl Postcard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_with_buffer(buffer: Vec<u8>) -> Self {
+        Self { buffer }
+    }
+
+    pub fn new_with_capacity(capacity: usize) -> Self {
+        Self {
+            buffer: Vec::with_capacity(capacity),
+        }
+    }
+}
+
+impl Fallible for Postcard {
+    type Error = Error;
+}
+
+impl Serializer for Postcard {
+    fn serialize<T: Serialize>(&mut self, item: &T) -> Result<&[u8], Self::Error> {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.clear();
+
+        // the buffer is lost on error, the next value starts a new one
+        self.buffer = postcard::to_extend(item, buffer)?;
+
+        Ok(&self.buffer)
+    }
+
+    fn serialize_owned<T: Serialize>(&mut self, item: &T) -> Result<Vec<u8>, Self::Error> {
+        self.serialize(item)?;
+        Ok(std::mem::take(&mut self.buffer))
+    }
+}
+
+impl Deserializer for Postcard {
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        let (item, rest) = postcard::take_from_bytes(bytes)?;
+
+        if !rest.is_empty() {
+            return Err(Error::TrailingBytes(rest.len()));
+        }
+
+        Ok(item)
+    }
+}
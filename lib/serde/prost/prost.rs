@@ -0,0 +1,461 @@
+//! Protobuf values via `prost`, for data that non-Rust consumers decode directly.
+//!
+//! The core `Serializer`/`Deserializer` traits are bound to serde, so this crate mirrors
+//! `KvStore`, `Item` and `Map` with `prost::Message` bounds instead. Keys are composed exactly
+//! like the core containers, so `item!`/`map!` declarations can be shared.
+//!
+//! `prost` drops the fields a message type doesn't know when decoding, so loading a value written
+//! by a newer schema and saving it back loses them, unless the codec is built with
+//! [`Prost::preserving_unknown_fields`].
+
+use std::{borrow::Borrow, marker::PhantomData};
+
+use kv_storage::{EncodeLike, Fallible, HasKey, Map, Read, Remove, Write, WriteCompositeKey};
+use prost::{encoding::DecodeContext, Message};
+
+pub use prost;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Encode(#[from] prost::EncodeError),
+    #[error(transparent)]
+    Decode(#[from] prost::DecodeError),
+    #[error("value is too short to contain a schema id header")]
+    MissingHeader,
+    #[error("unknown schema id {0}")]
+    UnknownSchema(u32),
+}
+
+/// Schema-registry style framing: a 4-byte big-endian schema id before the protobuf payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaHeader {
+    id: u32,
+    accepted: Vec<u32>,
+}
+
+impl SchemaHeader {
+    /// Write `id`, and only accept values written with it.
+    pub fn new(id: u32) -> Self {
+        Self {
+            id,
+            accepted: vec![id],
+        }
+    }
+
+    /// Also accept values written with the given ids, e.g. older compatible schema versions.
+    #[must_use]
+    pub fn accepting(mut self, ids: impl IntoIterator<Item = u32>) -> Self {
+        self.accepted.extend(ids);
+        self
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+/// The protobuf codec, reusing its buffer across encodes.
+#[derive(Default)]
+pub struct Prost {
+    buffer: Vec<u8>,
+    header: Option<SchemaHeader>,
+    preserve_unknown: bool,
+}
+
+impl Prost {
+    /// Raw protobuf bytes, no header.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prefix every value with a schema id header, and require one when decoding.
+    pub fn with_header(header: SchemaHeader) -> Self {
+        Self {
+            buffer: Vec::new(),
+            header: Some(header),
+            preserve_unknown: false,
+        }
+    }
+
+    /// Keep the fields of a stored value that the saved message type doesn't know, e.g. ones
+    /// added by a newer schema, by writing them back ahead of the new encoding.
+    ///
+    /// Every save then reads the value it replaces, and fails if that value can't be decoded.
+    #[must_use]
+    pub fn preserving_unknown_fields(mut self) -> Self {
+        self.preserve_unknown = true;
+        self
+    }
+
+    pub fn header(&self) -> Option<&SchemaHeader> {
+        self.header.as_ref()
+    }
+
+    pub fn preserves_unknown_fields(&self) -> bool {
+        self.preserve_unknown
+    }
+
+    /// Encode a message, returning the buffer.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the message fails to encode.
+    pub fn encode<M: Message>(&mut self, message: &M) -> Result<&[u8], Error> {
+        self.encode_after(&[], message)
+    }
+
+    /// Encode a message after some already encoded fields, which it overrides where they overlap.
+    fn encode_after<M: Message>(&mut self, fields: &[u8], message: &M) -> Result<&[u8], Error> {
+        self.buffer.clear();
+
+        if let Some(header) = &self.header {
+            self.buffer.extend_from_slice(&header.id.to_be_bytes());
+        }
+
+        self.buffer.extend_from_slice(fields);
+        message.encode(&mut self.buffer)?;
+
+        Ok(&self.buffer)
+    }
+
+    /// Decode a message, validating the header if one is configured.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The header is missing or names a schema id that isn't accepted.
+    /// - The payload fails to decode.
+    pub fn decode<M: Message + Default>(&self, bytes: &[u8]) -> Result<M, Error> {
+        M::decode(self.payload(bytes)?).map_err(Error::from)
+    }
+
+    /// The protobuf payload of a stored value, after validating its header.
+    fn payload<'a>(&self, bytes: &'a [u8]) -> Result<&'a [u8], Error> {
+        let Some(header) = &self.header else {
+            return Ok(bytes);
+        };
+
+        let Some((id, payload)) = bytes.split_first_chunk::<4>() else {
+            return Err(Error::MissingHeader);
+        };
+
+        let id = u32::from_be_bytes(*id);
+
+        if !header.accepted.contains(&id) {
+            return Err(Error::UnknownSchema(id));
+        }
+
+        Ok(payload)
+    }
+}
+
+/// The fields of an encoded message that `M` doesn't know, still encoded.
+fn unknown_fields<M: Message + Default>(mut payload: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut unknown = Vec::new();
+
+    while !payload.is_empty() {
+        let field = payload;
+
+        let (tag, wire_type) = prost::encoding::decode_key(&mut payload)?;
+        prost::encoding::skip_field(wire_type, tag, &mut payload, DecodeContext::default())?;
+
+        let field = &field[..field.len() - payload.len()];
+
+        // `M` skips the fields it doesn't know, leaving nothing to encode. Known fields holding
+        // their default can't be told apart, but those are overridden by the new encoding.
+        if M::decode(field).map_or(true, |known| known.encoded_len() == 0) {
+            unknown.extend_from_slice(field);
+        }
+    }
+
+    Ok(unknown)
+}
+
+impl Fallible for Prost {
+    type Error = Error;
+}
+
+/// `KvStore` for protobuf values.
+#[derive(Default)]
+pub struct ProstStore<Repo> {
+    codec: Prost,
+    repo: Repo,
+}
+
+impl<Repo> ProstStore<Repo> {
+    pub const fn new(codec: Prost, repo: Repo) -> Self {
+        Self { codec, repo }
+    }
+
+    pub fn repo(&self) -> &Repo {
+        &self.repo
+    }
+
+    pub fn mut_repo(&mut self) -> &mut Repo {
+        &mut self.repo
+    }
+
+    pub fn codec(&self) -> &Prost {
+        &self.codec
+    }
+}
+
+impl<Repo: Fallible> Fallible for ProstStore<Repo> {
+    type Error = kv_storage::Error<Error, Repo::Error>;
+}
+
+impl<Repo> ProstStore<Repo>
+where
+    Repo: Read + HasKey,
+{
+    /// Load a message for a given key if it exists.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Read encounters an error.
+    /// - Decoding encounters an error.
+    pub fn may_load<M: Message + Default>(
+        &self,
+        key: &[u8],
+    ) -> Result<Option<M>, <Self as Fallible>::Error> {
+        let Some(bytes) = self.repo.read(key).map_err(kv_storage::Error::Repo)? else {
+            return Ok(None);
+        };
+
+        self.codec
+            .decode(&bytes)
+            .map(Some)
+            .map_err(kv_storage::Error::Serde)
+    }
+
+    /// Check if a key exists in storage.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the repo encounters an error.
+    pub fn has_key(&self, key: &[u8]) -> Result<bool, <Self as Fallible>::Error> {
+        self.repo.has_key(key).map_err(kv_storage::Error::Repo)
+    }
+}
+
+impl<Repo> ProstStore<Repo>
+where
+    Repo: Read + HasKey + Write + Remove,
+{
+    /// Save a message against the given key.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Encoding encounters an error.
+    /// - Write encounters an error.
+    pub fn save<M: Message + Default>(
+        &mut self,
+        key: &[u8],
+        message: &M,
+    ) -> Result<(), <Self as Fallible>::Error> {
+        let unknown = match self.codec.preserve_unknown {
+            true => self.unknown_fields::<M>(key)?,
+            false => Vec::new(),
+        };
+
+        let buffer = self
+            .codec
+            .encode_after(&unknown, message)
+            .map_err(kv_storage::Error::Serde)?;
+        self.repo
+            .write(key, buffer)
+            .map_err(kv_storage::Error::Repo)
+    }
+
+    /// The fields of the value stored at `key` that `M` doesn't know.
+    fn unknown_fields<M: Message + Default>(
+        &self,
+        key: &[u8],
+    ) -> Result<Vec<u8>, <Self as Fallible>::Error> {
+        let Some(bytes) = self.repo.read(key).map_err(kv_storage::Error::Repo)? else {
+            return Ok(Vec::new());
+        };
+
+        self.codec
+            .payload(&bytes)
+            .and_then(unknown_fields::<M>)
+            .map_err(kv_storage::Error::Serde)
+    }
+
+    /// Remove a key and any associated data from storage.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the repo encounters an error.
+    pub fn remove(&mut self, key: &[u8]) -> Result<(), <Self as Fallible>::Error> {
+        self.repo.remove(key).map_err(kv_storage::Error::Repo)
+    }
+}
+
+/// `Item` for protobuf values.
+pub struct ProstItem<M> {
+    key: &'static [u8],
+    _m: PhantomData<M>,
+}
+
+impl<M> ProstItem<M> {
+    #[must_use]
+    pub const fn new(key: &'static [u8]) -> Self {
+        Self {
+            key,
+            _m: PhantomData,
+        }
+    }
+
+    /// Reuse the key of a core `Item` declaration, e.g. one built with `item!`.
+    #[must_use]
+    pub const fn from_item<T>(item: &kv_storage::Item<T>) -> Self {
+        Self::new(item.key())
+    }
+
+    #[must_use]
+    pub const fn key(&self) -> &'static [u8] {
+        self.key
+    }
+
+    /// Save the item to storage.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn save<Repo>(
+        &self,
+        store: &mut ProstStore<Repo>,
+        message: impl Borrow<M>,
+    ) -> Result<(), <ProstStore<Repo> as Fallible>::Error>
+    where
+        M: Message + Default,
+        Repo: Read + HasKey + Write + Remove,
+    {
+        store.save(self.key, message.borrow())
+    }
+
+    /// Load the item from storage if it exists, otherwise `None`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load<Repo>(
+        &self,
+        store: &ProstStore<Repo>,
+    ) -> Result<Option<M>, <ProstStore<Repo> as Fallible>::Error>
+    where
+        M: Message + Default,
+        Repo: Read + HasKey,
+    {
+        store.may_load(self.key)
+    }
+
+    /// Clear the item from storage.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn clear<Repo>(
+        &self,
+        store: &mut ProstStore<Repo>,
+    ) -> Result<(), <ProstStore<Repo> as Fallible>::Error>
+    where
+        Repo: Read + HasKey + Write + Remove,
+    {
+        store.remove(self.key)
+    }
+}
+
+/// `Map` for protobuf values, composing keys exactly like the core `Map`.
+pub struct ProstMap<const N: usize, K, M> {
+    map: Map<N, K, M>,
+}
+
+impl<const N: usize, K, M> ProstMap<N, K, M>
+where
+    K: WriteCompositeKey,
+{
+    #[must_use]
+    pub const fn new(prefix: &'static [u8]) -> Self {
+        Self {
+            map: Map::new(prefix),
+        }
+    }
+
+    /// Reuse the prefix of a core `Map` declaration, e.g. one built with `map!`.
+    #[must_use]
+    pub const fn from_map<V>(map: &Map<N, K, V>) -> Self {
+        Self::new(map.prefix())
+    }
+
+    /// Save the message for the given key.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn save<Repo>(
+        &self,
+        store: &mut ProstStore<Repo>,
+        key: impl EncodeLike<K>,
+        message: impl Borrow<M>,
+    ) -> Result<(), <ProstStore<Repo> as Fallible>::Error>
+    where
+        M: Message + Default,
+        Repo: Read + HasKey + Write + Remove,
+    {
+        store.save(self.map.key(key).as_ref(), message.borrow())
+    }
+
+    /// Load the message for the given key if it exists, otherwise `None`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn may_load<Repo>(
+        &self,
+        store: &ProstStore<Repo>,
+        key: impl EncodeLike<K>,
+    ) -> Result<Option<M>, <ProstStore<Repo> as Fallible>::Error>
+    where
+        M: Message + Default,
+        Repo: Read + HasKey,
+    {
+        store.may_load(self.map.key(key).as_ref())
+    }
+
+    /// Check if a key exists.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn has_key<Repo>(
+        &self,
+        store: &ProstStore<Repo>,
+        key: impl EncodeLike<K>,
+    ) -> Result<bool, <ProstStore<Repo> as Fallible>::Error>
+    where
+        Repo: Read + HasKey,
+    {
+        store.has_key(self.map.key(key).as_ref())
+    }
+
+    /// Remove any message stored at the given key.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store encounters an error.
+    pub fn remove<Repo>(
+        &self,
+        store: &mut ProstStore<Repo>,
+        key: impl EncodeLike<K>,
+    ) -> Result<(), <ProstStore<Repo> as Fallible>::Error>
+    where
+        Repo: Read + HasKey + Write + Remove,
+    {
+        store.remove(self.map.key(key).as_ref())
+    }
+}
@@ -0,0 +1,98 @@
+//! The RON serializer, with a reusable buffer, for values meant to be edited by hand:
+//!
+//! ```
+//! use kv_storage::prelude::*;
+//! use kv_storage_memory::MemoryRepo;
+//! use kv_storage_ron::Ron;
+//!
+//! const LIMITS: Item<(u32, String)> = item!("limits");
+//!
+//! let mut store = KvStore::new(Ron::with_pretty(true), MemoryRepo::default());
+//! LIMITS.save(&mut store, (10, "daily".to_owned())).unwrap();
+//! assert_eq!(LIMITS.may_load(&store).unwrap(), Some((10, "daily".to_owned())));
+//! ```
+
+use kv_storage::{Deserializer, Fallible, Serializer};
+use ron::{
+    error::{Position, SpannedError},
+    ser::PrettyConfig,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+pub use ron;
+
+#[derive(Default)]
+pub struct Ron {
+    buffer: String,
+    pretty: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Serialize(ron::Error),
+    /// The stored text isn't a valid value, with where ron stopped reading it.
+    #[error(transparent)]
+    Deserialize(#[from] SpannedError),
+}
+
+impl Error {
+    /// Where in the stored text deserialization failed.
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            Error::Serialize(_) => None,
+            Error::Deserialize(err) => Some(err.span.start),
+        }
+    }
+}
+
+impl Ron {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write values over several indented lines when `pretty` is set, reading doesn't care.
+    pub fn with_pretty(pretty: bool) -> Self {
+        Self {
+            buffer: String::new(),
+            pretty,
+        }
+    }
+
+    pub fn new_with_capacity(capacity: usize) -> Self {
+        Self {
+            buffer: String::with_capacity(capacity),
+            pretty: false,
+        }
+    }
+}
+
+impl Fallible for Ron {
+    type Error = Error;
+}
+
+impl Serializer for Ron {
+    fn serialize<T: Serialize>(&mut self, item: &T) -> Result<&[u8], Self::Error> {
+        self.buffer.clear();
+
+        if self.pretty {
+            ron::ser::to_writer_pretty(&mut self.buffer, item, PrettyConfig::default())
+        } else {
+            ron::ser::to_writer(&mut self.buffer, item)
+        }
+        .map_err(Error::Serialize)?;
+
+        Ok(self.buffer.as_bytes())
+    }
+
+    fn serialize_owned<T: Serialize>(&mut self, item: &T) -> Result<Vec<u8>, Self::Error> {
+        self.serialize(item)?;
+        Ok(std::mem::take(&mut self.buffer).into_bytes())
+    }
+}
+
+impl Deserializer for Ron {
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        Ok(ron::de::from_bytes(bytes)?)
+    }
+}
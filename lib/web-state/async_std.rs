@@ -0,0 +1,75 @@
+//! An async [`SharedKvStore`] over an async-std `RwLock`.
+//!
+//! Only acquiring the lock is async, the closures themselves are synchronous, so the store is
+//! never held across an `.await` in handler code.
+
+use std::sync::Arc;
+
+use async_std::sync::RwLock;
+use kv_storage::{Fallible, Item, KvStore, MutStorage};
+use serde::{de::DeserializeOwned, Serialize};
+
+pub struct SharedKvStore<Serde, Repo> {
+    store: Arc<RwLock<KvStore<Serde, Repo>>>,
+}
+
+impl<Serde, Repo> SharedKvStore<Serde, Repo> {
+    pub fn new(store: KvStore<Serde, Repo>) -> Self {
+        Self {
+            store: Arc::new(RwLock::new(store)),
+        }
+    }
+
+    /// Run `f` with shared access to the store, concurrently with other readers.
+    pub async fn read<R>(&self, f: impl FnOnce(&KvStore<Serde, Repo>) -> R) -> R {
+        f(&*self.store.read().await)
+    }
+
+    /// Run `f` with exclusive access to the store.
+    pub async fn write<R>(&self, f: impl FnOnce(&mut KvStore<Serde, Repo>) -> R) -> R {
+        f(&mut *self.store.write().await)
+    }
+
+    /// Load `item`, initializing it with `init` if it doesn't exist yet.
+    ///
+    /// Like [`crate::SharedKvStore::get_or_try_init`], only one `init` runs however many
+    /// handles race.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store or `init` encounters an error, nothing
+    /// is saved if `init` fails.
+    pub async fn get_or_try_init<T, F, E>(&self, item: &Item<T>, init: F) -> Result<T, E>
+    where
+        KvStore<Serde, Repo>: MutStorage,
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Result<T, E>,
+        E: From<<KvStore<Serde, Repo> as Fallible>::Error>,
+    {
+        if let Some(existing) = self.read(|store| item.may_load(store)).await? {
+            return Ok(existing);
+        }
+
+        self.write(|store| item.get_or_try_init(store, init)).await
+    }
+}
+
+impl<Serde, Repo> Clone for SharedKvStore<Serde, Repo> {
+    fn clone(&self) -> Self {
+        Self {
+            store: Arc::clone(&self.store),
+        }
+    }
+}
+
+impl<Serde: Default, Repo: Default> Default for SharedKvStore<Serde, Repo> {
+    fn default() -> Self {
+        Self::new(KvStore::default())
+    }
+}
+
+impl<Serde, Repo> From<KvStore<Serde, Repo>> for SharedKvStore<Serde, Repo> {
+    fn from(store: KvStore<Serde, Repo>) -> Self {
+        Self::new(store)
+    }
+}
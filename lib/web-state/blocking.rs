@@ -0,0 +1,131 @@
+//! Executor-agnostic async access to a [`SharedKvStore`].
+//!
+//! The closures are sent over a channel to a single worker `std::thread`, started on first use,
+//! and the returned [`Blocking`] future is woken once its closure is done. Any executor can then
+//! await store access without blocking its own threads, and without this crate depending on a
+//! runtime.
+//!
+//! The worker runs closures one at a time in the order they were spawned, across every store, so
+//! a slow closure delays the others. Reads through it don't run concurrently, use
+//! [`SharedKvStore::read`] from a thread of your own for that.
+
+use std::{
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex, OnceLock, PoisonError,
+    },
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+use kv_storage::KvStore;
+
+use crate::SharedKvStore;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The channel to the worker thread, started by the first job.
+fn worker() -> &'static Sender<Job> {
+    static WORKER: OnceLock<Sender<Job>> = OnceLock::new();
+
+    WORKER.get_or_init(|| {
+        let (sender, jobs) = mpsc::channel::<Job>();
+
+        thread::Builder::new()
+            .name("kv-storage-blocking".to_owned())
+            .spawn(move || jobs.into_iter().for_each(|job| job()))
+            .expect("the worker thread starts");
+
+        sender
+    })
+}
+
+/// A closure running on the worker thread, resolving to its result.
+///
+/// A panic in the closure is resumed when the future is polled.
+#[must_use = "futures do nothing unless polled"]
+pub struct Blocking<R> {
+    slot: Arc<Mutex<Slot<R>>>,
+}
+
+struct Slot<R> {
+    output: Option<thread::Result<R>>,
+    waker: Option<Waker>,
+}
+
+impl<R: Send + 'static> Blocking<R> {
+    fn spawn(f: impl FnOnce() -> R + Send + 'static) -> Self {
+        let slot = Arc::new(Mutex::new(Slot {
+            output: None,
+            waker: None,
+        }));
+        let done = Arc::clone(&slot);
+
+        let job = move || {
+            // a panicking closure mustn't take the worker down with it
+            let output = panic::catch_unwind(AssertUnwindSafe(f));
+
+            let waker = {
+                let mut done = done.lock().unwrap_or_else(PoisonError::into_inner);
+                done.output = Some(output);
+                done.waker.take()
+            };
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        };
+
+        worker()
+            .send(Box::new(job))
+            .expect("the worker thread runs as long as the process");
+
+        Self { slot }
+    }
+}
+
+impl<R> Future for Blocking<R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
+        let mut slot = self.slot.lock().unwrap_or_else(PoisonError::into_inner);
+
+        match slot.output.take() {
+            Some(Ok(output)) => Poll::Ready(output),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<Serde, Repo> SharedKvStore<Serde, Repo>
+where
+    Serde: Send + Sync + 'static,
+    Repo: Send + Sync + 'static,
+{
+    /// Run `f` with shared access to the store on the worker thread.
+    pub fn spawn_read<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&KvStore<Serde, Repo>) -> R + Send + 'static,
+    ) -> Blocking<R> {
+        let shared = self.clone();
+
+        Blocking::spawn(move || shared.read(f))
+    }
+
+    /// Run `f` with exclusive access to the store on the worker thread.
+    pub fn spawn_write<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut KvStore<Serde, Repo>) -> R + Send + 'static,
+    ) -> Blocking<R> {
+        let shared = self.clone();
+
+        Blocking::spawn(move || shared.write(f))
+    }
+}
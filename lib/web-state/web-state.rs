@@ -0,0 +1,85 @@
+//! A `KvStore` shareable across web handlers, e.g. as axum state or actix `Data`.
+//!
+//! Access goes through closures so a lock guard can never be held across an `.await`.
+//! [`SharedKvStore::spawn_read`] and [`SharedKvStore::spawn_write`] run them on a `std::thread`
+//! and can be awaited on any executor. The optional `tokio` and `async-std` features add
+//! [`tokio::SharedKvStore`] and [`async_std::SharedKvStore`], which wait on their runtime's lock
+//! instead of a thread.
+
+use std::sync::Arc;
+
+use kv_storage::{Fallible, Item, KvStore, MutStorage};
+use parking_lot::RwLock;
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "async-std")]
+pub mod async_std;
+pub mod blocking;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+pub struct SharedKvStore<Serde, Repo> {
+    store: Arc<RwLock<KvStore<Serde, Repo>>>,
+}
+
+impl<Serde, Repo> SharedKvStore<Serde, Repo> {
+    pub fn new(store: KvStore<Serde, Repo>) -> Self {
+        Self {
+            store: Arc::new(RwLock::new(store)),
+        }
+    }
+
+    /// Run `f` with shared access to the store, concurrently with other readers.
+    pub fn read<R>(&self, f: impl FnOnce(&KvStore<Serde, Repo>) -> R) -> R {
+        f(&self.store.read())
+    }
+
+    /// Run `f` with exclusive access to the store.
+    pub fn write<R>(&self, f: impl FnOnce(&mut KvStore<Serde, Repo>) -> R) -> R {
+        f(&mut self.store.write())
+    }
+
+    /// Load `item`, initializing it with `init` if it doesn't exist yet.
+    ///
+    /// Initializers are serialized under the write lock, and the item is checked again once it
+    /// is held, so however many handles race only one `init` runs, the others return the value
+    /// it saved.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store or `init` encounters an error, nothing
+    /// is saved if `init` fails.
+    pub fn get_or_try_init<T, F, E>(&self, item: &Item<T>, init: F) -> Result<T, E>
+    where
+        KvStore<Serde, Repo>: MutStorage,
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Result<T, E>,
+        E: From<<KvStore<Serde, Repo> as Fallible>::Error>,
+    {
+        if let Some(existing) = self.read(|store| item.may_load(store))? {
+            return Ok(existing);
+        }
+
+        self.write(|store| item.get_or_try_init(store, init))
+    }
+}
+
+impl<Serde, Repo> Clone for SharedKvStore<Serde, Repo> {
+    fn clone(&self) -> Self {
+        Self {
+            store: Arc::clone(&self.store),
+        }
+    }
+}
+
+impl<Serde: Default, Repo: Default> Default for SharedKvStore<Serde, Repo> {
+    fn default() -> Self {
+        Self::new(KvStore::default())
+    }
+}
+
+impl<Serde, Repo> From<KvStore<Serde, Repo>> for SharedKvStore<Serde, Repo> {
+    fn from(store: KvStore<Serde, Repo>) -> Self {
+        Self::new(store)
+    }
+}
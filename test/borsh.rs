@@ -0,0 +1,98 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use kv_storage::{prelude::*, Error as StoreError, Read, Write};
+use kv_storage_borsh::{borsh_item, borsh_map, BorshItem, BorshMap, BorshStore};
+use kv_storage_memory::MemoryRepo;
+
+type Store = BorshStore<MemoryRepo>;
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+struct Account {
+    owner: String,
+    balance: u128,
+    frozen: Option<Reason>,
+}
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+enum Reason {
+    Audit { since: u64 },
+    Closed,
+}
+
+fn account() -> Account {
+    Account {
+        owner: "alice".to_owned(),
+        balance: 100,
+        frozen: Some(Reason::Audit { since: 7 }),
+    }
+}
+
+#[test]
+fn borsh_item_round_trip() {
+    const ACCOUNT: BorshItem<Account> = borsh_item!("account");
+
+    let mut store = Store::default();
+
+    assert_eq!(ACCOUNT.may_load(&store).unwrap(), None);
+
+    ACCOUNT.save(&mut store, account()).unwrap();
+
+    assert_eq!(ACCOUNT.may_load(&store).unwrap(), Some(account()));
+
+    ACCOUNT.clear(&mut store).unwrap();
+
+    assert_eq!(ACCOUNT.may_load(&store).unwrap(), None);
+}
+
+#[test]
+fn borsh_map_shares_core_keys() {
+    const CORE: Map<32, (&str, u32), ()> = map!("accounts", ns = "bank");
+    const ACCOUNTS: BorshMap<32, (&str, u32), Account> = borsh_map!("accounts", ns = "bank");
+
+    assert_eq!(ACCOUNTS.prefix(), CORE.prefix());
+    assert_eq!(
+        BorshMap::<32, (&str, u32), Account>::from_map(&CORE).prefix(),
+        CORE.prefix()
+    );
+
+    let mut store = Store::default();
+
+    ACCOUNTS.save(&mut store, ("alice", 1), account()).unwrap();
+
+    assert!(ACCOUNTS.has_key(&store, ("alice", 1)).unwrap());
+    assert!(!ACCOUNTS.has_key(&store, ("alice", 2)).unwrap());
+
+    // other consumers decode the stored bytes with plain borsh
+    let raw = store
+        .repo()
+        .read(CORE.key(("alice", 1)).as_ref())
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(borsh::from_slice::<Account>(&raw).unwrap(), account());
+
+    ACCOUNTS.remove(&mut store, ("alice", 1)).unwrap();
+
+    assert_eq!(ACCOUNTS.may_load(&store, ("alice", 1)).unwrap(), None);
+}
+
+#[test]
+fn borsh_rejects_truncated_and_trailing_bytes() {
+    const TOTAL: BorshItem<u64> = borsh_item!("total");
+
+    let mut store = Store::default();
+
+    store.mut_repo().write(TOTAL.key(), &[1, 2, 3]).unwrap();
+
+    assert!(matches!(TOTAL.may_load(&store), Err(StoreError::Serde(_))));
+
+    store.mut_repo().write(TOTAL.key(), &[0; 9]).unwrap();
+
+    assert!(matches!(TOTAL.may_load(&store), Err(StoreError::Serde(_))));
+
+    store
+        .mut_repo()
+        .write(TOTAL.key(), &7u64.to_le_bytes())
+        .unwrap();
+
+    assert_eq!(TOTAL.may_load(&store).unwrap(), Some(7));
+}
@@ -0,0 +1,169 @@
+use std::cell::Cell;
+
+use kv_storage::{BatchOp, Fallible, HasKey, KvStore, MutStorage, Read, Remove, Write};
+use kv_storage_bincode::Bincode;
+use kv_storage_buffered::BufferedRepo;
+use kv_storage_memory::prelude::*;
+
+/// Counts the writes and removals that reach the repo, and the batches they arrive in.
+#[derive(Default)]
+struct CountingRepo {
+    inner: MemoryRepo,
+    changes: Cell<usize>,
+    batches: Cell<usize>,
+}
+
+impl CountingRepo {
+    fn tick(&self) {
+        self.changes.set(self.changes.get() + 1);
+    }
+}
+
+impl Fallible for CountingRepo {
+    type Error = <MemoryRepo as Fallible>::Error;
+}
+
+impl Write for CountingRepo {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        self.tick();
+        self.inner.write(key, bytes)
+    }
+}
+
+impl Read for CountingRepo {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.inner.read(key)
+    }
+}
+
+impl HasKey for CountingRepo {}
+
+impl Remove for CountingRepo {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.tick();
+        self.inner.remove(key)
+    }
+
+    fn write_batch(&mut self, ops: &[BatchOp<'_>]) -> Result<(), Self::Error> {
+        self.batches.set(self.batches.get() + 1);
+        self.changes.set(self.changes.get() + ops.len());
+        self.inner.write_batch(ops)
+    }
+}
+
+const HEIGHT: Item<u64> = item!("height");
+const SCORES: Map<16, u32, u64> = map!("scores");
+
+type BufferedStore = KvStore<Bincode, BufferedRepo<CountingRepo>>;
+
+#[test]
+fn buffered_coalesces_writes_to_a_key() {
+    let mut store: BufferedStore = KvStore::from_repo(BufferedRepo::new(CountingRepo::default()));
+
+    for height in 1..=10 {
+        HEIGHT.save(&mut store, height).unwrap();
+        assert_eq!(HEIGHT.load(&store).unwrap(), height);
+    }
+
+    assert_eq!(store.repo().pending(), 1);
+    assert_eq!(store.repo().inner().changes.get(), 0);
+    assert_eq!(store.repo().inner().inner.read(HEIGHT.key()).unwrap(), None);
+
+    store.mut_repo().flush().unwrap();
+
+    assert_eq!(store.repo().pending(), 0);
+    assert_eq!(store.repo().inner().changes.get(), 1);
+    assert_eq!(HEIGHT.load(&store).unwrap(), 10);
+
+    // nothing left to flush
+    store.mut_repo().flush().unwrap();
+    assert_eq!(store.repo().inner().changes.get(), 1);
+}
+
+#[test]
+fn buffered_reads_removals_before_flushing() {
+    let mut inner = CountingRepo::default();
+    inner.write(b"key", b"value").unwrap();
+
+    let mut repo = BufferedRepo::new(inner);
+    repo.remove(b"key").unwrap();
+
+    assert_eq!(repo.read(b"key").unwrap(), None);
+    assert!(!repo.has_key(b"key").unwrap());
+    assert!(repo.inner().has_key(b"key").unwrap());
+
+    let inner = repo.into_inner().unwrap();
+    assert!(!inner.has_key(b"key").unwrap());
+}
+
+#[test]
+fn buffered_flushes_at_thresholds() {
+    let mut store: BufferedStore =
+        KvStore::from_repo(BufferedRepo::new(CountingRepo::default()).flush_at_keys(3));
+
+    SCORES.save(&mut store, 0, 1).unwrap();
+    SCORES.save(&mut store, 0, 2).unwrap();
+    SCORES.save(&mut store, 1, 1).unwrap();
+    assert_eq!(store.repo().pending(), 2);
+
+    SCORES.save(&mut store, 2, 1).unwrap();
+    assert_eq!(store.repo().pending(), 0);
+    assert_eq!(store.repo().inner().changes.get(), 3);
+    assert!(store
+        .repo()
+        .inner()
+        .inner
+        .has_key(SCORES.key(2).as_ref())
+        .unwrap());
+    assert_eq!(SCORES.load(&store, 0).unwrap(), 2);
+
+    let mut repo = BufferedRepo::new(CountingRepo::default()).flush_at_bytes(8);
+
+    repo.write(b"a", b"123").unwrap();
+    repo.write(b"a", b"12").unwrap();
+    assert_eq!(repo.pending_bytes(), 3);
+
+    repo.write(b"b", b"1234").unwrap();
+    assert_eq!(repo.pending(), 0);
+    assert_eq!(repo.inner().changes.get(), 2);
+
+    repo.discard();
+}
+
+#[test]
+fn buffered_flushes_a_batch_whole() {
+    let mut store: BufferedStore =
+        KvStore::from_repo(BufferedRepo::new(CountingRepo::default()).flush_at_keys(2));
+
+    // the threshold is reached on the second of three saves
+    store
+        .save_batch(&[
+            (SCORES.key(0).as_ref(), &1u64),
+            (SCORES.key(1).as_ref(), &1u64),
+            (SCORES.key(2).as_ref(), &1u64),
+        ])
+        .unwrap();
+
+    assert_eq!(store.repo().pending(), 0);
+    assert_eq!(store.repo().inner().batches.get(), 1);
+    assert_eq!(store.repo().inner().changes.get(), 3);
+
+    // a batch below the threshold stays pending, and is flushed with the next change
+    store
+        .save_batch(&[(SCORES.key(3).as_ref(), &1u64)])
+        .unwrap();
+    assert_eq!(store.repo().pending(), 1);
+
+    SCORES.save(&mut store, 4, 1).unwrap();
+    assert_eq!(store.repo().pending(), 0);
+    assert_eq!(store.repo().inner().batches.get(), 2);
+    assert_eq!(SCORES.load(&store, 3).unwrap(), 1);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "never flushed")]
+fn buffered_asserts_changes_are_flushed() {
+    let mut repo = BufferedRepo::new(CountingRepo::default());
+    repo.write(b"key", b"value").unwrap();
+}
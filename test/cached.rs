@@ -0,0 +1,225 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use kv_storage::{HasKey, KvStore, Read, Write};
+use kv_storage_bincode::Bincode;
+use kv_storage_cached::{CacheStats, CachedRepo, Freshness, StalePolicy};
+use kv_storage_faulty::{FaultyRepo, Op};
+use kv_storage_memory::prelude::*;
+
+const CONFIG: Item<String> = item!("config");
+const HOT: Map<16, u32, u64> = map!("hot");
+
+type CachedStore = KvStore<Bincode, CachedRepo<MemoryRepo>>;
+
+fn capacity(entries: usize) -> NonZeroUsize {
+    NonZeroUsize::new(entries).unwrap()
+}
+
+#[test]
+fn cached_reads_see_their_own_writes() {
+    let mut store: CachedStore =
+        KvStore::from_repo(CachedRepo::new(MemoryRepo::default(), capacity(16)));
+
+    CONFIG.save(&mut store, "old".to_owned()).unwrap();
+    assert_eq!(CONFIG.load(&store).unwrap(), "old");
+
+    CONFIG.save(&mut store, "new".to_owned()).unwrap();
+    assert_eq!(CONFIG.load(&store).unwrap(), "new");
+
+    CONFIG.clear(&mut store).unwrap();
+    assert_eq!(CONFIG.may_load(&store).unwrap(), None);
+
+    // every load was served from the entry the writes left behind
+    assert_eq!(store.repo().stats(), CacheStats { hits: 3, misses: 0 });
+}
+
+#[test]
+fn cached_counts_hits_and_misses() {
+    let mut inner = MemoryRepo::default();
+    inner.write(b"hot", b"value").unwrap();
+
+    let repo = CachedRepo::new(inner, capacity(16));
+
+    assert_eq!(repo.read(b"hot").unwrap().as_deref(), Some(&b"value"[..]));
+    assert_eq!(repo.read(b"hot").unwrap().as_deref(), Some(&b"value"[..]));
+    assert!(repo.has_key(b"hot").unwrap());
+    assert_eq!(repo.stats(), CacheStats { hits: 2, misses: 1 });
+
+    // missing keys are cached too
+    assert!(!repo.has_key(b"cold").unwrap());
+    assert_eq!(repo.read(b"cold").unwrap(), None);
+    assert_eq!(repo.stats(), CacheStats { hits: 3, misses: 2 });
+    assert_eq!(repo.cached(), 2);
+
+    repo.reset_stats();
+    repo.clear_cache();
+    assert_eq!(repo.read(b"hot").unwrap().as_deref(), Some(&b"value"[..]));
+    assert_eq!(repo.stats(), CacheStats { hits: 0, misses: 1 });
+}
+
+#[test]
+fn cached_evicts_least_recently_used() {
+    let mut store: CachedStore =
+        KvStore::from_repo(CachedRepo::new(MemoryRepo::default(), capacity(2)));
+
+    for id in 0..3 {
+        HOT.save(&mut store, id, u64::from(id)).unwrap();
+    }
+
+    assert_eq!(store.repo().cached(), 2);
+
+    // the first entry was evicted, but its value still comes from the inner repo
+    store.repo().reset_stats();
+    assert_eq!(HOT.load(&store, 0).unwrap(), 0);
+    assert_eq!(store.repo().stats(), CacheStats { hits: 0, misses: 1 });
+
+    // which evicted the second, now least recently used
+    assert_eq!(HOT.load(&store, 2).unwrap(), 2);
+    assert_eq!(HOT.load(&store, 1).unwrap(), 1);
+    assert_eq!(store.repo().stats(), CacheStats { hits: 1, misses: 2 });
+}
+
+#[test]
+fn cached_caps_total_bytes() {
+    let mut repo = CachedRepo::new(MemoryRepo::default(), capacity(16)).with_max_bytes(10);
+
+    repo.write(b"a", b"1234").unwrap();
+    repo.write(b"b", b"1234").unwrap();
+    assert_eq!(repo.cached_bytes(), 10);
+
+    repo.write(b"c", b"12").unwrap();
+    assert_eq!(repo.cached(), 2);
+    assert_eq!(repo.cached_bytes(), 8);
+
+    // too large to cache at all
+    repo.write(b"large", b"123456").unwrap();
+    assert_eq!(repo.cached_bytes(), 8);
+
+    repo.reset_stats();
+    assert_eq!(
+        repo.read(b"large").unwrap().as_deref(),
+        Some(&b"123456"[..])
+    );
+    assert_eq!(repo.read(b"a").unwrap().as_deref(), Some(&b"1234"[..]));
+    assert_eq!(repo.stats(), CacheStats { hits: 0, misses: 2 });
+}
+
+/// A clock advanced by hand, in whole seconds.
+fn manual_clock() -> (Arc<AtomicU64>, impl Fn() -> Instant + Send + 'static) {
+    let start = Instant::now();
+    let elapsed = Arc::new(AtomicU64::new(0));
+    let seconds = Arc::clone(&elapsed);
+
+    let clock = move || start + Duration::from_secs(seconds.load(Ordering::Relaxed));
+
+    (elapsed, clock)
+}
+
+#[test]
+fn cached_refreshes_expired_entries() {
+    let (elapsed, clock) = manual_clock();
+
+    let mut repo = CachedRepo::new(MemoryRepo::default(), capacity(16))
+        .with_ttl(Duration::from_secs(10))
+        .with_clock(clock);
+
+    repo.write(b"price", b"1").unwrap();
+
+    // changed behind the cache's back
+    repo.mut_inner().write(b"price", b"2").unwrap();
+
+    elapsed.store(10, Ordering::Relaxed);
+    assert_eq!(repo.read(b"price").unwrap().as_deref(), Some(&b"1"[..]));
+
+    elapsed.store(11, Ordering::Relaxed);
+    assert_eq!(
+        repo.read_with_freshness(b"price").unwrap(),
+        (Some(b"2".to_vec()), Freshness::Fresh)
+    );
+    assert_eq!(repo.stats(), CacheStats { hits: 1, misses: 1 });
+}
+
+#[test]
+fn cached_serves_stale_entries_while_the_backend_is_down() {
+    let (elapsed, clock) = manual_clock();
+
+    let mut inner = FaultyRepo::new(MemoryRepo::default());
+    inner.write(b"price", b"1").unwrap();
+    inner.write(b"cold", b"2").unwrap();
+
+    let mut repo = CachedRepo::new(inner, capacity(16))
+        .with_ttl(Duration::from_secs(10))
+        .with_stale_policy(StalePolicy::ServeStale {
+            max_age: Duration::from_secs(60),
+        })
+        .with_clock(clock);
+
+    assert_eq!(repo.read(b"price").unwrap().as_deref(), Some(&b"1"[..]));
+    assert!(!repo.has_key(b"missing").unwrap());
+
+    repo.mut_inner().fail_randomly(None, 1.0, 0);
+
+    // still fresh, the backend isn't asked
+    elapsed.store(5, Ordering::Relaxed);
+    assert_eq!(
+        repo.read_with_freshness(b"price").unwrap(),
+        (Some(b"1".to_vec()), Freshness::Fresh)
+    );
+
+    elapsed.store(30, Ordering::Relaxed);
+    assert_eq!(
+        repo.read_with_freshness(b"price").unwrap(),
+        (
+            Some(b"1".to_vec()),
+            Freshness::Stale {
+                age: Duration::from_secs(30)
+            }
+        )
+    );
+
+    // plain reads serve stale bytes silently, missing keys included
+    assert_eq!(repo.read(b"price").unwrap().as_deref(), Some(&b"1"[..]));
+    assert!(!repo.has_key(b"missing").unwrap());
+
+    // never cached, so there is nothing to fall back on
+    assert!(repo.read(b"cold").is_err());
+
+    elapsed.store(61, Ordering::Relaxed);
+    assert!(repo.read_with_freshness(b"price").is_err());
+    assert!(repo.read(b"price").is_err());
+
+    // back up, the entry is refreshed and fresh again
+    repo.mut_inner().clear_faults();
+    assert_eq!(
+        repo.read_with_freshness(b"price").unwrap(),
+        (Some(b"1".to_vec()), Freshness::Fresh)
+    );
+}
+
+#[test]
+fn cached_propagates_backend_errors_by_default() {
+    let (elapsed, clock) = manual_clock();
+
+    let mut inner = FaultyRepo::new(MemoryRepo::default());
+    inner.write(b"price", b"1").unwrap();
+
+    let mut repo = CachedRepo::new(inner, capacity(16))
+        .with_ttl(Duration::from_secs(10))
+        .with_clock(clock);
+
+    assert_eq!(repo.read(b"price").unwrap().as_deref(), Some(&b"1"[..]));
+
+    repo.mut_inner().fail_key_on(Op::Read, b"price".to_vec());
+
+    elapsed.store(11, Ordering::Relaxed);
+
+    let err = repo.read_with_freshness(b"price").unwrap_err();
+    assert_eq!(err.fault().map(|fault| fault.op), Some(Op::Read));
+}
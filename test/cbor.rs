@@ -0,0 +1,71 @@
+use kv_storage::{prelude::*, Error as StoreError, Read, Write};
+use kv_storage_cbor::{ciborium, Cbor, Error};
+use kv_storage_memory::MemoryRepo;
+use serde::{Deserialize, Serialize};
+
+type CborStore = KvStore<Cbor, MemoryRepo>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Balance {
+    account: String,
+    total: u128,
+    balance: u128,
+    history: Vec<i64>,
+}
+
+fn balance() -> Balance {
+    Balance {
+        account: "alice".to_owned(),
+        total: u128::MAX,
+        balance: 100,
+        history: vec![-5, 0, 105],
+    }
+}
+
+#[test]
+fn cbor_round_trip() {
+    const BALANCE: Item<Balance> = item!("balance");
+    const BALANCES: Map<64, (&str, u8), Option<Balance>> = map!("balances");
+
+    let mut store = CborStore::default();
+
+    BALANCE.save(&mut store, balance()).unwrap();
+    BALANCES
+        .save(&mut store, ("alice", 1), Some(balance()))
+        .unwrap();
+    BALANCES.save(&mut store, ("alice", 2), None).unwrap();
+
+    assert_eq!(BALANCE.may_load(&store).unwrap(), Some(balance()));
+    assert_eq!(
+        BALANCES.may_load(&store, ("alice", 1)).unwrap(),
+        Some(Some(balance()))
+    );
+    assert_eq!(BALANCES.may_load(&store, ("alice", 2)).unwrap(), Some(None));
+    assert_eq!(BALANCES.may_load(&store, ("alice", 3)).unwrap(), None);
+
+    // other CBOR tooling reads the stored bytes without the schema
+    let raw = store.repo().read(BALANCE.key()).unwrap().unwrap();
+    let value: ciborium::Value = ciborium::from_reader(raw.as_slice()).unwrap();
+
+    assert!(value.is_map());
+}
+
+#[test]
+fn cbor_truncated_values_fail_to_deserialize() {
+    const BALANCE: Item<Balance> = item!("balance");
+
+    let mut store = CborStore::default();
+
+    BALANCE.save(&mut store, balance()).unwrap();
+
+    let raw = store.repo().read(BALANCE.key()).unwrap().unwrap();
+
+    for len in [0, 1, raw.len() / 2, raw.len() - 1] {
+        store.mut_repo().write(BALANCE.key(), &raw[..len]).unwrap();
+
+        assert!(matches!(
+            BALANCE.may_load(&store),
+            Err(StoreError::Serde(Error::Deserialize(_)))
+        ));
+    }
+}
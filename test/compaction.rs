@@ -0,0 +1,169 @@
+use kv_storage::{
+    item, map, Compactable, CompactionPolicy, CompactionReport, CompactionStats, Fallible, HasKey,
+    Item, KvStore, MaintenanceScheduler, Map, Read, Remove, Write,
+};
+use kv_storage_bincode::Bincode;
+use kv_storage_memory::Infallible;
+
+/// An append-only log, the latest record for a key wins and `None` is a tombstone.
+#[derive(Default)]
+struct LogRepo {
+    records: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+}
+
+impl LogRepo {
+    fn latest(&self, key: &[u8]) -> Option<&Option<Vec<u8>>> {
+        self.records
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    fn record_len((key, value): &(Vec<u8>, Option<Vec<u8>>)) -> u64 {
+        (key.len() + value.as_ref().map_or(0, Vec::len)) as u64
+    }
+}
+
+impl Fallible for LogRepo {
+    type Error = Infallible;
+}
+
+impl Write for LogRepo {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        self.records.push((key.to_vec(), Some(bytes.to_vec())));
+        Ok(())
+    }
+}
+
+impl Read for LogRepo {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(self.latest(key).cloned().flatten())
+    }
+}
+
+impl HasKey for LogRepo {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        Ok(matches!(self.latest(key), Some(Some(_))))
+    }
+}
+
+impl Remove for LogRepo {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.records.push((key.to_vec(), None));
+        Ok(())
+    }
+}
+
+impl Compactable for LogRepo {
+    fn compaction_stats(&self) -> CompactionStats {
+        let mut stats = CompactionStats::default();
+
+        for (i, record) in self.records.iter().enumerate() {
+            let len = Self::record_len(record);
+            let superseded = self.records[i + 1..].iter().any(|(k, _)| *k == record.0);
+
+            stats.total_bytes += len;
+
+            if superseded || record.1.is_none() {
+                stats.dead_bytes += len;
+            } else {
+                stats.live_keys += 1;
+            }
+        }
+
+        stats
+    }
+
+    fn compact(&mut self) -> Result<CompactionReport, Self::Error> {
+        let before = self.compaction_stats();
+
+        let mut live: Vec<(Vec<u8>, Option<Vec<u8>>)> = Vec::new();
+
+        for (key, value) in self.records.drain(..).rev() {
+            if live.iter().any(|(k, _)| *k == key) {
+                continue;
+            }
+            live.push((key, value));
+        }
+
+        live.retain(|(_, value)| value.is_some());
+        live.reverse();
+        self.records = live;
+
+        Ok(CompactionReport {
+            before,
+            after: self.compaction_stats(),
+        })
+    }
+}
+
+const COUNTER: Item<u64> = item!("counter");
+const NAMES: Map<16, u32, String> = map!("names");
+
+#[test]
+fn dead_percent_policy_compacts_overwrites() {
+    let mut store: KvStore<Bincode, LogRepo> = KvStore::default();
+    let mut scheduler = MaintenanceScheduler::new(CompactionPolicy::DeadPercent(50));
+
+    COUNTER.save(&mut store, 0).unwrap();
+    NAMES.save(&mut store, 1, "alice".to_owned()).unwrap();
+    NAMES.save(&mut store, 2, "bob".to_owned()).unwrap();
+
+    assert_eq!(scheduler.maybe_compact(store.mut_repo(), 0).unwrap(), None);
+
+    for i in 1..=20 {
+        COUNTER.save(&mut store, i).unwrap();
+    }
+    NAMES.remove(&mut store, 2).unwrap();
+
+    let stats = store.repo().compaction_stats();
+
+    assert_eq!(stats.live_keys, 2);
+    assert!(stats.dead_percent() >= 50);
+
+    let report = scheduler
+        .maybe_compact(store.mut_repo(), 10)
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(report.before, stats);
+    assert_eq!(report.after.dead_bytes, 0);
+    assert_eq!(report.after.live_keys, 2);
+    assert!(report.reclaimed_bytes() > 0);
+    assert_eq!(store.repo().records.len(), 2);
+    assert_eq!(scheduler.last_compaction(), Some(10));
+
+    assert_eq!(COUNTER.may_load(&store).unwrap(), Some(20));
+    assert_eq!(NAMES.may_load(&store, 1).unwrap().as_deref(), Some("alice"));
+    assert_eq!(NAMES.may_load(&store, 2).unwrap(), None);
+
+    // nothing left to reclaim
+    assert_eq!(scheduler.maybe_compact(store.mut_repo(), 20).unwrap(), None);
+}
+
+#[test]
+fn interval_policy_compacts_on_schedule() {
+    let mut store: KvStore<Bincode, LogRepo> = KvStore::default();
+    let mut scheduler = MaintenanceScheduler::new(CompactionPolicy::Interval(100));
+
+    COUNTER.save(&mut store, 1).unwrap();
+
+    assert!(scheduler
+        .maybe_compact(store.mut_repo(), 0)
+        .unwrap()
+        .is_some());
+
+    COUNTER.save(&mut store, 2).unwrap();
+
+    assert_eq!(scheduler.maybe_compact(store.mut_repo(), 50).unwrap(), None);
+    assert_eq!(store.repo().records.len(), 2);
+
+    let report = scheduler
+        .maybe_compact(store.mut_repo(), 100)
+        .unwrap()
+        .unwrap();
+
+    assert!(report.reclaimed_bytes() > 0);
+    assert_eq!(COUNTER.may_load(&store).unwrap(), Some(2));
+}
@@ -0,0 +1,159 @@
+//! A minimal contract built on `Balance`, driven through `cosmwasm_std::testing`.
+
+use cosmwasm_std::{
+    testing::{mock_dependencies, mock_env, mock_info, MockQuerier},
+    to_json_binary, Binary, ContractResult, Deps, DepsMut, Env, MessageInfo, QuerierWrapper,
+    Response, StdError, StdResult, Storage as _, SystemError, SystemResult, WasmQuery,
+};
+use kv_storage::{item, map, Bound, Item, KvStore, Map, Order, Storage};
+use kv_storage_bincode::Bincode;
+use kv_storage_cosmwasm::{ContractRepo, CwStore, CwStoreMut, FromCosmwasm, FromCosmwasmReadonly};
+
+use mock_consumer::Balance;
+
+const OWNER: Item<String> = item!("owner");
+
+fn std_err(err: impl std::fmt::Debug) -> StdError {
+    StdError::generic_err(format!("{err:?}"))
+}
+
+fn instantiate(deps: DepsMut, _env: Env, info: MessageInfo) -> StdResult<Response> {
+    let mut store: CwStoreMut = KvStore::<Bincode, _>::cosmwasm(deps.storage);
+
+    OWNER
+        .save(&mut store, info.sender.into_string())
+        .map_err(std_err)?;
+
+    Ok(Response::new())
+}
+
+fn execute_deposit(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    amount: u128,
+) -> StdResult<Response> {
+    let mut store: CwStoreMut = KvStore::<Bincode, _>::cosmwasm(deps.storage);
+
+    let mut balance = Balance::load_account(&store, info.sender.as_str()).map_err(std_err)?;
+
+    balance
+        .deposit(amount)
+        .map_err(std_err)?
+        .save(&mut store)
+        .map_err(std_err)?;
+
+    Ok(Response::new())
+}
+
+fn load_balance<Store: Storage>(store: &Store, account: &str) -> StdResult<u128> {
+    Balance::load_account(store, account)
+        .map(|balance| balance.balance())
+        .map_err(std_err)
+}
+
+fn query_balance(deps: Deps, _env: Env, account: &str) -> StdResult<Binary> {
+    let store: CwStore = KvStore::<Bincode, _>::cosmwasm_ro(deps.storage);
+
+    to_json_binary(&load_balance(&store, account)?)
+}
+
+#[test]
+fn contract_entry_points_work() {
+    let mut deps = mock_dependencies();
+
+    instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[])).unwrap();
+
+    execute_deposit(deps.as_mut(), mock_env(), mock_info("alice", &[]), 100).unwrap();
+    execute_deposit(deps.as_mut(), mock_env(), mock_info("alice", &[]), 50).unwrap();
+
+    let balance: u128 =
+        cosmwasm_std::from_json(query_balance(deps.as_ref(), mock_env(), "alice").unwrap())
+            .unwrap();
+
+    assert_eq!(balance, 150);
+
+    let store: CwStore = KvStore::<Bincode, _>::cosmwasm_ro(&deps.storage);
+
+    assert_eq!(OWNER.may_load(&store).unwrap().unwrap(), "admin");
+    assert_eq!(load_balance(&store, "bob").unwrap(), 0);
+}
+
+#[test]
+fn contract_repo_reads_deployed_contract_state() {
+    const CONTRACT: &str = "contract0";
+
+    let mut deps = mock_dependencies();
+
+    instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[])).unwrap();
+    execute_deposit(deps.as_mut(), mock_env(), mock_info("alice", &[]), 100).unwrap();
+
+    let contract_storage = deps.storage;
+
+    let mut querier = MockQuerier::<cosmwasm_std::Empty>::new(&[]);
+
+    querier.update_wasm(move |query| match query {
+        WasmQuery::Raw { contract_addr, key } if contract_addr == CONTRACT => {
+            let value = contract_storage.get(key).unwrap_or_default();
+            SystemResult::Ok(ContractResult::Ok(value.into()))
+        }
+        _ => SystemResult::Err(SystemError::NoSuchContract {
+            addr: CONTRACT.to_owned(),
+        }),
+    });
+
+    let querier = QuerierWrapper::<cosmwasm_std::Empty>::new(&querier);
+
+    let store: KvStore<Bincode, _> =
+        KvStore::new(Bincode::new(), ContractRepo::new(querier, CONTRACT));
+
+    assert_eq!(OWNER.may_load(&store).unwrap().unwrap(), "admin");
+    assert_eq!(load_balance(&store, "alice").unwrap(), 100);
+    assert!(!Balance::account_exists(&store, "bob").unwrap());
+
+    let other: KvStore<Bincode, _> =
+        KvStore::new(Bincode::new(), ContractRepo::new(querier, "contract1"));
+
+    assert!(OWNER.may_load(&other).is_err());
+}
+
+#[test]
+fn cosmwasm_repo_iterates_map_entries() {
+    const SCORES: Map<16, u64, u64> = map!("scores");
+
+    let mut deps = mock_dependencies();
+    let mut store: CwStoreMut = KvStore::<Bincode, _>::cosmwasm(deps.as_mut().storage);
+
+    for n in 1..=4 {
+        SCORES.save(&mut store, n, n * 10).unwrap();
+    }
+    OWNER.save(&mut store, "alice".to_owned()).unwrap();
+
+    let store: CwStore = KvStore::<Bincode, _>::cosmwasm_ro(deps.as_ref().storage);
+
+    let page: Vec<_> = SCORES
+        .range(
+            &store,
+            Bound::Exclusive(1),
+            Bound::Inclusive(3),
+            Order::Descending,
+        )
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(page, [(3, 30), (2, 20)]);
+
+    let keys: Vec<_> = SCORES.keys(&store).unwrap().map(Result::unwrap).collect();
+    assert_eq!(keys, [1, 2, 3, 4]);
+
+    // an empty range yields nothing
+    let empty = SCORES
+        .range(
+            &store,
+            Bound::Inclusive(3),
+            Bound::Exclusive(2),
+            Order::Ascending,
+        )
+        .unwrap();
+    assert_eq!(empty.count(), 0);
+}
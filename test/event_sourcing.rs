@@ -0,0 +1,57 @@
+use event_sourcing::{Account, AccountError, Event};
+use kv_storage_memory::prelude::*;
+
+#[test]
+fn rejects_overdraft() {
+    let account = Account::new(3);
+    let mut store = MemStore::new_in_memory();
+
+    account.append(&mut store, Event::Deposited(10)).unwrap();
+
+    assert!(matches!(
+        account.append(&mut store, Event::Withdrew(11)),
+        Err(AccountError::InsufficientFunds {
+            balance: 10,
+            requested: 11
+        })
+    ));
+
+    assert_eq!(Account::rehydrate(&store).unwrap().version, 1);
+}
+
+#[test]
+fn snapshot_plus_tail_matches_full_replay() {
+    let mut seed: u64 = 0x2545_F491_4F6C_DD1D;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+
+    for run in 0..50 {
+        let account = Account::new(next() % 7 + 1);
+        let mut store = MemStore::new_in_memory();
+
+        for _ in 0..next() % 60 {
+            let amount = next() % 100;
+
+            let event = if next() % 3 == 0 {
+                Event::Withdrew(amount)
+            } else {
+                Event::Deposited(amount)
+            };
+
+            match account.append(&mut store, event) {
+                Ok(_) | Err(AccountError::InsufficientFunds { .. }) => {}
+                Err(err) => panic!("run {run}: {err:?}"),
+            }
+
+            assert_eq!(
+                Account::rehydrate(&store).unwrap(),
+                Account::replay_all(&store).unwrap(),
+                "run {run}"
+            );
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use kv_storage::{
+    Bound, Error as StoreError, IndexedMap, Iterate, KvStore, MultiIndex, Order, Read, Remove,
+    UniqueIndex, Write,
+};
+use kv_storage_bincode::Bincode;
+use kv_storage_faulty::{check_crash_points, Error as FaultyError, FaultyRepo, InjectedFault, Op};
+use kv_storage_memory::prelude::*;
+
+use mock_consumer::{Balance, Error};
+
+type FaultyStore = KvStore<Bincode, FaultyRepo<MemoryRepo>>;
+
+fn store() -> FaultyStore {
+    KvStore::from_repo(FaultyRepo::new(MemoryRepo::default()))
+}
+
+/// The fault behind a consumer error, if any.
+fn injected<S, T>(err: &Error<StoreError<S, FaultyError<T>>>) -> Option<&InjectedFault> {
+    match err {
+        Error::Storage(StoreError::Repo(err)) => err.fault(),
+        _ => None,
+    }
+}
+
+#[test]
+fn faulty_counts_the_writes_of_a_save() {
+    let mut store = store();
+
+    let mut alice = Balance::load_account(&store, "alice").unwrap();
+    assert_eq!(store.repo().counts().reads, 2);
+
+    store.repo().reset_counts();
+    alice.deposit(100).unwrap().save(&mut store).unwrap();
+
+    // the balance and the total
+    assert_eq!(store.repo().counts().writes, 2);
+    assert_eq!(store.repo().counts().reads, 0);
+}
+
+#[test]
+fn faulty_fails_a_save_halfway() {
+    let mut store = store();
+    store.mut_repo().fail_nth(Op::Write, 2);
+
+    let mut alice = Balance::load_account(&store, "alice").unwrap();
+    let err = alice.deposit(100).unwrap().save(&mut store).err().unwrap();
+
+    let fault = injected(&err).unwrap();
+    assert_eq!(fault.op, Op::Write);
+
+    // the first write of the batch went through
+    assert_eq!(store.repo().counts().writes, 2);
+    assert_eq!(Balance::load_total(&store).unwrap(), 100);
+    assert_eq!(Balance::load_account(&store, "alice").unwrap().balance(), 0);
+
+    // only the second write was programmed to fail
+    let mut alice = Balance::load_account(&store, "alice").unwrap();
+    alice.deposit(100).unwrap().save(&mut store).unwrap();
+}
+
+#[test]
+fn faulty_fails_reads_of_a_key() {
+    let mut store = store();
+
+    let mut alice = Balance::load_account(&store, "alice").unwrap();
+    alice.deposit(100).unwrap().save(&mut store).unwrap();
+
+    let mut bob = Balance::load_account(&store, "bob").unwrap();
+    bob.deposit(20).unwrap().save(&mut store).unwrap();
+
+    let alice_key = store
+        .repo()
+        .inner()
+        .range_keys(Bound::Unbounded, Bound::Unbounded, Order::Ascending)
+        .unwrap()
+        .find(|key| key.ends_with(b"alice"))
+        .unwrap();
+
+    store.mut_repo().fail_key_on(Op::Read, alice_key.clone());
+
+    let err = Balance::load_account(&store, "alice").err().unwrap();
+    assert_eq!(injected(&err).unwrap().key, alice_key);
+
+    // other accounts, and other operations on the key, are unaffected
+    assert_eq!(Balance::load_account(&store, "bob").unwrap().balance(), 20);
+    assert!(Balance::account_exists(&store, "alice").unwrap());
+
+    store.mut_repo().fail_nth(Op::HasKey, 2);
+    let err = Balance::account_exists(&store, "bob").unwrap_err();
+    assert_eq!(injected(&err).unwrap().op, Op::HasKey);
+
+    store.mut_repo().clear_faults();
+    assert_eq!(
+        Balance::load_account(&store, "alice").unwrap().balance(),
+        100
+    );
+}
+
+#[test]
+fn faulty_random_faults_follow_the_seed() {
+    let failures = |seed| {
+        let mut repo = FaultyRepo::new(MemoryRepo::default());
+        repo.fail_randomly(Some(Op::Write), 0.25, seed);
+
+        (0..200u32)
+            .filter(|n| repo.write(&n.to_be_bytes(), b"value").is_err())
+            .collect::<Vec<_>>()
+    };
+
+    let first = failures(7);
+
+    assert_eq!(first, failures(7));
+    assert_ne!(first, failures(8));
+    assert!((20..80).contains(&first.len()), "{} failures", first.len());
+}
+
+#[test]
+fn faulty_fails_everything_after_n_changes() {
+    let mut repo = FaultyRepo::new(MemoryRepo::default());
+    repo.fail_after(2);
+
+    repo.write(b"a", b"1").unwrap();
+    repo.remove(b"a").unwrap();
+
+    assert!(repo.write(b"b", b"2").is_err());
+    assert!(repo.remove(b"a").is_err());
+    assert!(repo.write(b"c", b"3").is_err());
+
+    // reads aren't affected
+    assert_eq!(repo.read(b"b").unwrap(), None);
+}
+
+type Purchase = (String, u64);
+
+const PURCHASES: Map<64, u64, Purchase> = map!("purchases");
+
+const BY_BUYER: MultiIndex<64, String, u64, Purchase> =
+    MultiIndex::new(|(buyer, _)| buyer.clone(), PURCHASES.prefix(), b"by_buyer");
+
+const BY_RECEIPT: UniqueIndex<64, u64, Purchase> =
+    UniqueIndex::new(|(_, receipt)| *receipt, PURCHASES.prefix(), b"by_receipt");
+
+type Indexes = (
+    MultiIndex<64, String, u64, Purchase>,
+    UniqueIndex<64, u64, Purchase>,
+);
+
+const INDEXED: IndexedMap<64, u64, Purchase, Indexes> =
+    IndexedMap::new(PURCHASES, (BY_BUYER, BY_RECEIPT));
+
+fn with_purchase(purchase: Option<Purchase>) -> MemoryRepo {
+    let mut store = MemStore::new_in_memory();
+
+    if let Some(purchase) = purchase {
+        INDEXED.save(&mut store, 1, purchase).unwrap();
+    }
+
+    store.into_repo()
+}
+
+/// Every index entry points at a purchase holding the value it indexes.
+fn assert_indexes_consistent(repo: MemoryRepo, cut: u64) {
+    let store = MemStore::from_repo(repo);
+
+    for buyer in ["alice", "bob"] {
+        for pk in BY_BUYER.keys(&store, &buyer.to_owned()).unwrap() {
+            let purchase = INDEXED.may_load(&store, pk.unwrap()).unwrap();
+
+            assert_eq!(
+                purchase.map(|(buyer, _)| buyer).as_deref(),
+                Some(buyer),
+                "after {cut} changes"
+            );
+        }
+    }
+
+    for receipt in [10, 20] {
+        let Some(pk) = BY_RECEIPT.load_pk(&store, &receipt).unwrap() else {
+            continue;
+        };
+
+        let key = [PURCHASES.prefix(), &pk].concat();
+        let purchase = store.may_load::<Purchase>(&key).unwrap();
+
+        assert_eq!(
+            purchase.map(|(_, receipt)| receipt),
+            Some(receipt),
+            "after {cut} changes"
+        );
+    }
+}
+
+fn run(repo: FaultyRepo<MemoryRepo>, op: impl FnOnce(&mut FaultyStore)) -> FaultyRepo<MemoryRepo> {
+    let mut store = KvStore::from_repo(repo);
+    op(&mut store);
+    store.into_repo()
+}
+
+#[test]
+fn indexed_save_is_consistent_at_every_crash_point() {
+    let changes = check_crash_points(
+        || with_purchase(None),
+        |repo| {
+            run(repo, |store| {
+                let _ = INDEXED.save(store, 1, ("alice".to_owned(), 10));
+            })
+        },
+        assert_indexes_consistent,
+    );
+
+    // the purchase and an entry per index
+    assert_eq!(changes, 3);
+}
+
+#[test]
+fn indexed_overwrite_is_consistent_at_every_crash_point() {
+    let mut completed = false;
+
+    let changes = check_crash_points(
+        || with_purchase(Some(("alice".to_owned(), 10))),
+        |repo| {
+            run(repo, |store| {
+                let _ = INDEXED.save(store, 1, ("bob".to_owned(), 20));
+            })
+        },
+        |repo, cut| {
+            let store = MemStore::from_repo(repo);
+            completed |= BY_RECEIPT.load_pk(&store, &20).unwrap().is_some();
+
+            assert_indexes_consistent(store.into_repo(), cut);
+        },
+    );
+
+    // the old index entries removed, the purchase and the new index entries saved
+    assert_eq!(changes, 5);
+    assert!(completed);
+}
+
+#[test]
+fn indexed_remove_is_consistent_at_every_crash_point() {
+    let changes = check_crash_points(
+        || with_purchase(Some(("alice".to_owned(), 10))),
+        |repo| {
+            run(repo, |store| {
+                let _ = INDEXED.remove(store, 1);
+            })
+        },
+        assert_indexes_consistent,
+    );
+
+    assert_eq!(changes, 3);
+}
@@ -0,0 +1,234 @@
+use std::{fs, thread};
+
+use kv_storage::{Error, HasKey, Item, Iterate, KvStore, Read, Remove, Write};
+use kv_storage_bincode::Bincode;
+use kv_storage_fs::FsRepo;
+use kv_storage_memory::prelude::*;
+
+use mock_consumer::Balance;
+use serde::{ser::Error as _, Serialize, Serializer};
+
+#[test]
+fn fs_balance_scenario() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store: KvStore<Bincode, FsRepo> =
+        KvStore::from_repo(FsRepo::new(dir.path().to_owned()).sharded());
+
+    let mut alice = Balance::load_account(&store, "alice").unwrap();
+    alice.deposit(1000).unwrap().save(&mut store).unwrap();
+
+    let mut bob = Balance::load_account(&store, "bob").unwrap();
+    bob.deposit(20).unwrap().save(&mut store).unwrap();
+
+    // a fresh repo over the same directory sees everything
+    let store: KvStore<Bincode, FsRepo> =
+        KvStore::from_repo(FsRepo::new(dir.path().to_owned()).sharded());
+
+    assert_eq!(Balance::load_total(&store).unwrap(), 1020);
+    assert_eq!(
+        Balance::load_all(&store).unwrap(),
+        [("alice".to_owned(), 1000), ("bob".to_owned(), 20)]
+    );
+}
+
+#[test]
+fn fs_keys_with_path_hostile_bytes() {
+    let keys: [&[u8]; 6] = [b"", b"/", b"..", b"../escape", b"a\0b", b"\\\xff"];
+
+    for sharded in [false, true] {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("store");
+
+        let mut repo = FsRepo::new(root.clone());
+        if sharded {
+            repo = repo.sharded();
+        }
+
+        for (i, key) in keys.iter().enumerate() {
+            repo.write(key, &[u8::try_from(i).unwrap()]).unwrap();
+            assert!(repo.path(key).starts_with(&root));
+        }
+
+        // nothing escaped the root
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        for (i, key) in keys.iter().enumerate() {
+            assert!(repo.has_key(key).unwrap());
+            assert_eq!(
+                repo.read(key).unwrap(),
+                Some(vec![u8::try_from(i).unwrap()])
+            );
+        }
+
+        let mut sorted = keys.map(<[u8]>::to_vec);
+        sorted.sort();
+
+        let listed: Vec<_> = repo
+            .range_keys(Bound::Unbounded, Bound::Unbounded, Order::Ascending)
+            .unwrap()
+            .collect();
+
+        assert_eq!(listed, sorted);
+
+        repo.remove(b"..").unwrap();
+        assert!(!repo.has_key(b"..").unwrap());
+        assert!(repo.has_key(b"../escape").unwrap());
+    }
+}
+
+#[test]
+fn fs_keys_longer_than_a_file_name() {
+    let long: Vec<u8> = (0..300u16).map(|i| i.to_le_bytes()[0]).collect();
+    // shares the long key's first chunk directory, and ends exactly on a chunk boundary
+    let boundary = &long[..200];
+
+    for sharded in [false, true] {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut repo = FsRepo::new(dir.path().to_owned());
+        if sharded {
+            repo = repo.sharded();
+        }
+
+        repo.write(&long, b"long").unwrap();
+        repo.write(boundary, b"boundary").unwrap();
+        repo.write(b"short", b"short").unwrap();
+
+        for component in repo.path(&long).strip_prefix(dir.path()).unwrap() {
+            assert!(component.len() <= 255);
+        }
+
+        assert_eq!(repo.read(&long).unwrap(), Some(b"long".to_vec()));
+        assert_eq!(repo.read(boundary).unwrap(), Some(b"boundary".to_vec()));
+
+        let listed: Vec<_> = repo
+            .range_keys(Bound::Unbounded, Bound::Unbounded, Order::Ascending)
+            .unwrap()
+            .collect();
+
+        assert_eq!(listed, [boundary.to_vec(), long.clone(), b"short".to_vec()]);
+
+        let listed: Vec<_> = repo
+            .range_keys(
+                Bound::Exclusive(boundary),
+                Bound::Unbounded,
+                Order::Descending,
+            )
+            .unwrap()
+            .collect();
+
+        assert_eq!(listed, [b"short".to_vec(), long.clone()]);
+
+        repo.remove(&long).unwrap();
+        assert!(!repo.has_key(&long).unwrap());
+        assert!(repo.has_key(boundary).unwrap());
+    }
+}
+
+#[test]
+fn fs_concurrent_writes_leave_no_partial_files() {
+    const LEN: usize = 256 << 10;
+
+    let dir = tempfile::tempdir().unwrap();
+    let repo = FsRepo::new(dir.path().to_owned());
+
+    let writers: Vec<_> = (0..4u8)
+        .map(|id| {
+            let mut repo = repo.clone();
+            thread::spawn(move || {
+                for _ in 0..20 {
+                    repo.write(b"shared", &vec![id; LEN]).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    let reader = {
+        let repo = repo.clone();
+        thread::spawn(move || {
+            for _ in 0..200 {
+                // every read sees one writer's value in full
+                if let Some(bytes) = repo.read(b"shared").unwrap() {
+                    assert_eq!(bytes.len(), LEN);
+                    assert!(bytes.iter().all(|byte| *byte == bytes[0]));
+                }
+            }
+        })
+    };
+
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    reader.join().unwrap();
+
+    let names: Vec<_> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+
+    assert_eq!(names, [repo.path(b"shared").file_name().unwrap()]);
+}
+
+const SNAPSHOT: Item<(u64, Vec<u8>)> = item!("snapshot");
+
+/// The names of the files directly under `dir`, sorted.
+fn file_names(dir: &std::path::Path) -> Vec<std::ffi::OsString> {
+    let mut names: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+
+    names.sort();
+    names
+}
+
+#[test]
+fn fs_streaming_save_goes_through_the_temp_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store: KvStore<Bincode, FsRepo> =
+        KvStore::from_repo(FsRepo::new(dir.path().to_owned()));
+
+    assert!(store.repo().supports_streaming());
+
+    let snapshot: Vec<u8> = (0..1 << 20).map(|i: u32| i.to_le_bytes()[0]).collect();
+    SNAPSHOT.save(&mut store, (7, snapshot.clone())).unwrap();
+
+    assert_eq!(SNAPSHOT.may_load(&store).unwrap(), Some((7, snapshot)));
+    assert_eq!(
+        file_names(dir.path()),
+        [store.repo().path(SNAPSHOT.key()).file_name().unwrap()]
+    );
+}
+
+#[test]
+fn fs_failed_streaming_save_keeps_the_old_value() {
+    struct Failing;
+
+    impl Serialize for Failing {
+        fn serialize<S: Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(S::Error::custom("unserializable"))
+        }
+    }
+
+    // the same key as `SNAPSHOT`, failing once the first field is streamed
+    const PARTIAL: Item<(u64, Failing)> = item!("snapshot");
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut store: KvStore<Bincode, FsRepo> =
+        KvStore::from_repo(FsRepo::new(dir.path().to_owned()));
+
+    SNAPSHOT.save(&mut store, (1, vec![1, 2, 3])).unwrap();
+
+    assert!(matches!(
+        PARTIAL.save(&mut store, (7, Failing)),
+        Err(Error::Serde(_))
+    ));
+
+    // the temporary file is dropped rather than renamed over the entry
+    assert_eq!(SNAPSHOT.may_load(&store).unwrap(), Some((1, vec![1, 2, 3])));
+    assert_eq!(
+        file_names(dir.path()),
+        [store.repo().path(SNAPSHOT.key()).file_name().unwrap()]
+    );
+}
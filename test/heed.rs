@@ -0,0 +1,108 @@
+use kv_storage::{Error, KvStore};
+use kv_storage_bincode::Bincode;
+use kv_storage_heed::{Error as HeedError, HeedRepo, HeedSession};
+use kv_storage_memory::prelude::*;
+
+const BLOBS: Map<32, u32, Vec<u8>> = map!("blobs");
+
+/// Past LMDB's default map size of 1 MiB when saved in full.
+const BLOB_COUNT: u32 = 16;
+
+fn blob(id: u32) -> Vec<u8> {
+    vec![id.to_le_bytes()[0]; 128 << 10]
+}
+
+fn open(dir: &tempfile::TempDir) -> HeedRepo {
+    // SAFETY: the environment is private to the test and only used through the repo
+    unsafe { HeedRepo::open(dir.path()).unwrap() }
+}
+
+#[test]
+fn heed_per_call_writes_grow_the_map() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store: KvStore<Bincode, HeedRepo> = KvStore::from_repo(open(&dir));
+
+    let initial = store.repo().map_size();
+
+    for id in 0..BLOB_COUNT {
+        BLOBS.save(&mut store, id, blob(id)).unwrap();
+    }
+
+    assert!(store.repo().map_size() > initial);
+    assert_eq!(BLOBS.may_load(&store, 7).unwrap(), Some(blob(7)));
+
+    BLOBS.remove(&mut store, 7).unwrap();
+    assert_eq!(BLOBS.may_load(&store, 7).unwrap(), None);
+}
+
+#[test]
+fn heed_session_commits_explicitly_or_on_drop() {
+    const COUNTER: Item<u64> = item!("counter");
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut repo = open(&dir);
+
+    let mut session: KvStore<Bincode, HeedSession<'_>> =
+        KvStore::from_repo(repo.begin_write().unwrap());
+    COUNTER.save(&mut session, 1).unwrap();
+
+    // the session reads its own writes
+    assert_eq!(COUNTER.may_load(&session).unwrap(), Some(1));
+    session.into_repo().commit().unwrap();
+
+    {
+        let mut session: KvStore<Bincode, HeedSession<'_>> =
+            KvStore::from_repo(repo.begin_write().unwrap());
+        COUNTER.save(&mut session, 2).unwrap();
+    }
+
+    let mut aborted: KvStore<Bincode, HeedSession<'_>> =
+        KvStore::from_repo(repo.begin_write().unwrap());
+    COUNTER.save(&mut aborted, 3).unwrap();
+    aborted.into_repo().abort();
+
+    let store: KvStore<Bincode, HeedRepo> = KvStore::from_repo(repo);
+    assert_eq!(COUNTER.may_load(&store).unwrap(), Some(2));
+}
+
+#[test]
+fn heed_session_surfaces_a_full_map() {
+    fn save_blobs(repo: &mut HeedRepo) -> Result<(), HeedError> {
+        let mut session: KvStore<Bincode, HeedSession<'_>> =
+            KvStore::from_repo(repo.begin_write()?);
+
+        for id in 0..BLOB_COUNT {
+            match BLOBS.save(&mut session, id, blob(id)) {
+                Ok(()) => {}
+                Err(Error::Repo(error)) => return Err(error),
+                Err(error) => panic!("{error}"),
+            }
+        }
+
+        session.into_repo().commit()
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut repo = open(&dir);
+
+    assert!(matches!(save_blobs(&mut repo), Err(HeedError::MapFull)));
+
+    // the full session was aborted, not committed when dropped
+    let store: KvStore<Bincode, HeedRepo> = KvStore::from_repo(repo);
+    assert_eq!(BLOBS.may_load(&store, 0).unwrap(), None);
+
+    let mut repo = store.into_repo();
+
+    loop {
+        match save_blobs(&mut repo) {
+            Err(HeedError::MapFull) => repo.grow().unwrap(),
+            result => break result.unwrap(),
+        }
+    }
+
+    let store: KvStore<Bincode, HeedRepo> = KvStore::from_repo(repo);
+    assert_eq!(
+        BLOBS.may_load(&store, BLOB_COUNT - 1).unwrap(),
+        Some(blob(BLOB_COUNT - 1))
+    );
+}
@@ -1,14 +1,91 @@
+#[cfg(test)]
+mod cosmwasm;
+
+#[cfg(test)]
+mod watermark;
+
+#[cfg(test)]
+mod prost;
+
+#[cfg(test)]
+mod web_state;
+
+#[cfg(test)]
+mod compaction;
+
+#[cfg(test)]
+mod scoped;
+
+#[cfg(test)]
+mod event_sourcing;
+
+#[cfg(test)]
+mod replay;
+
+#[cfg(test)]
+mod overlay;
+
+#[cfg(test)]
+mod json;
+
+#[cfg(test)]
+mod msgpack;
+
+#[cfg(test)]
+mod cbor;
+
+#[cfg(test)]
+mod postcard;
+
+#[cfg(test)]
+mod ron;
+
+#[cfg(test)]
+mod borsh;
+
+#[cfg(test)]
+mod streaming;
+
+#[cfg(test)]
+mod sled;
+
+#[cfg(test)]
+mod redb;
+
+#[cfg(test)]
+mod heed;
+
+#[cfg(all(test, feature = "redis-tests"))]
+mod redis;
+
+#[cfg(test)]
+mod fs;
+
+#[cfg(test)]
+mod log;
+
 #[cfg(test)]
 mod test {
-    use kv_storage::{map, KvStore, Map};
-    use kv_storage_bincode::Bincode;
-    use kv_storage_memory::MemoryRepo;
+    use kv_storage::{
+        system::{self, ReservedKey, SystemKeyError},
+        BoundedError, BoundedMap, CounterError, Durability, EmbedOriginalKey, EncodeLike,
+        EntryState, Fallible, HasKey, HeaderedMap, IndexError, IndexedMap, InjectedError,
+        KeyDecodeError, KeyDeserialize, KeyDisplay, KeyObfuscation, MapState, MultiIndex,
+        ObfuscatedMap, OrderedF32, OrderedF64, RangeError, Read, Remove, Removed, SnapshotMap,
+        TimestampedMap, UniqueIndex, Write, WriteCompositeKey, WriteKeyPart,
+    };
+    use kv_storage_bincode::{
+        Bincode, BincodeConfig, BincodeOptions, BincodeWith, ConfigTable, ErrorKind,
+        NegotiatedBincode, NegotiationError, VarintOptions,
+    };
+    use kv_storage_frozen::FrozenRepo;
+    use kv_storage_memory::prelude::{testing::*, *};
 
     use mock_consumer::Balance;
 
     #[test]
     fn it_works() {
-        let mut storage: KvStore<Bincode, MemoryRepo> = KvStore::default();
+        let mut storage = MemStore::new_in_memory();
 
         assert!(!Balance::account_exists(&storage, "alice").unwrap());
 
@@ -38,7 +115,7 @@ mod test {
         const DOUBLE: Map<1024, (&str, &str), String> = map!("double_key");
         const TRIPLE: Map<1024, (&str, &str, &str), String> = map!("triple_key");
 
-        let mut storage: KvStore<Bincode, MemoryRepo> = KvStore::default();
+        let mut storage = MemStore::new_in_memory();
 
         DOUBLE
             .save(&mut storage, ("alice", "bob"), "hello".to_owned())
@@ -64,4 +141,3153 @@ mod test {
             "hello"
         );
     }
-}
+
+    #[test]
+    fn composite_keys_update() {
+        const ALLOWANCES: Map<1024, (&str, &str), u64> = map!("allowances");
+
+        type Error = Box<dyn std::error::Error>;
+
+        let spend = |amount: u64| {
+            move |allowance: Option<u64>| -> Result<Option<u64>, Error> {
+                let left = allowance
+                    .unwrap_or_default()
+                    .checked_sub(amount)
+                    .ok_or("allowance exceeded")?;
+
+                Ok(Some(left).filter(|left| *left > 0))
+            }
+        };
+
+        let mut storage = MemStore::new_in_memory();
+
+        let granted = ALLOWANCES
+            .update(&mut storage, ("alice", "bob"), |allowance| {
+                assert_eq!(allowance, None);
+                Ok::<_, Error>(10)
+            })
+            .unwrap();
+
+        assert_eq!(granted, 10);
+
+        let doubled = ALLOWANCES.update(&mut storage, ("alice", "bob"), |allowance| {
+            Ok::<_, Error>(allowance.unwrap() * 2)
+        });
+
+        assert_eq!(doubled.unwrap(), 20);
+
+        let failed = ALLOWANCES.update(&mut storage, ("alice", "bob"), |_| {
+            Err::<u64, Error>("no".into())
+        });
+
+        assert!(failed.is_err());
+        assert_eq!(
+            ALLOWANCES.may_load(&storage, ("alice", "bob")).unwrap(),
+            Some(20)
+        );
+
+        assert_eq!(
+            ALLOWANCES
+                .update_or_remove(&mut storage, ("alice", "bob"), spend(5))
+                .unwrap(),
+            Some(15)
+        );
+
+        // overspending fails and leaves the allowance alone
+        assert!(ALLOWANCES
+            .update_or_remove(&mut storage, ("alice", "bob"), spend(16))
+            .is_err());
+        assert_eq!(
+            ALLOWANCES.may_load(&storage, ("alice", "bob")).unwrap(),
+            Some(15)
+        );
+
+        // spending it all removes the entry
+        assert_eq!(
+            ALLOWANCES
+                .update_or_remove(&mut storage, ("alice", "bob"), spend(15))
+                .unwrap(),
+            None
+        );
+        assert!(!ALLOWANCES.has_key(&storage, ("alice", "bob")).unwrap());
+    }
+
+    storage_keys! {
+        const CONFIG: Item<String> = item!("config");
+        const NAMES: Map<1024, u64, String> = map!("names");
+    }
+
+    #[test]
+    fn storage_keys_declares_items_and_maps() {
+        let mut storage = MemStore::new_in_memory();
+
+        CONFIG.save(&mut storage, "config".to_owned()).unwrap();
+        NAMES.save(&mut storage, 1, "alice".to_owned()).unwrap();
+
+        assert_eq!(CONFIG.may_load(&storage).unwrap().unwrap(), "config");
+        assert_eq!(NAMES.may_load(&storage, 1).unwrap().unwrap(), "alice");
+    }
+
+    #[test]
+    fn storage_keys_rejects_duplicates() {
+        trybuild::TestCases::new().compile_fail("ui/duplicate_storage_keys.rs");
+    }
+
+    #[test]
+    fn remove_returning_reports_presence() {
+        const ITEM: Item<u64> = item!("remove_returning_item");
+        const MAP: Map<1024, &str, u64> = map!("remove_returning_map");
+
+        let mut storage = MemStore::new_in_memory();
+
+        assert_eq!(
+            ITEM.clear_returning(&mut storage).unwrap(),
+            Removed::DidNotExist
+        );
+        ITEM.save(&mut storage, 1).unwrap();
+        assert_eq!(
+            ITEM.clear_returning(&mut storage).unwrap(),
+            Removed::Existed
+        );
+        assert!(ITEM.is_empty(&storage).unwrap());
+
+        assert_eq!(
+            MAP.remove_returning(&mut storage, "alice").unwrap(),
+            Removed::DidNotExist
+        );
+        MAP.save(&mut storage, "alice", 1).unwrap();
+        assert_eq!(
+            MAP.remove_returning(&mut storage, "alice").unwrap(),
+            Removed::Existed
+        );
+        assert!(!MAP.has_key(&storage, "alice").unwrap());
+    }
+
+    #[test]
+    fn remove_returning_is_a_single_memory_op() {
+        const MAP: Map<1024, &str, u64> = map!("remove_returning_counted");
+
+        let mut storage: KvStore<Bincode, FaultyRepo<MemoryRepo>> =
+            KvStore::from_repo(FaultyRepo::new(MemoryRepo::default()));
+
+        MAP.save(&mut storage, "alice", 1).unwrap();
+        storage.repo().reset_counts();
+
+        assert_eq!(
+            MAP.remove_returning(&mut storage, "alice").unwrap(),
+            Removed::Existed
+        );
+        assert_eq!(
+            MAP.remove_returning(&mut storage, "alice").unwrap(),
+            Removed::DidNotExist
+        );
+
+        // `MemoryRepo` learns whether the key existed from the removal itself
+        let counts = storage.repo().counts();
+        assert_eq!(counts.removes, 2);
+        assert_eq!(counts.has_keys, 0);
+        assert_eq!(counts.reads, 0);
+    }
+
+    struct KeyBytes(Vec<u8>);
+
+    impl WriteKeyPart for KeyBytes {
+        fn write_key_part(&mut self, part: &[u8]) {
+            self.0.extend_from_slice(part);
+        }
+    }
+
+    fn key_bytes(key: &impl WriteCompositeKey) -> Vec<u8> {
+        let mut bytes = KeyBytes(Vec::with_capacity(key.total_len()));
+        key.write_into(&mut bytes);
+        bytes.0
+    }
+
+    fn sampled_f64s() -> Vec<f64> {
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+
+        let mut samples: Vec<f64> = (0..10_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                f64::from_bits(state)
+            })
+            .filter(|f| !f.is_nan())
+            .collect();
+
+        samples.extend([
+            f64::NEG_INFINITY,
+            f64::MIN,
+            -1.0,
+            -f64::MIN_POSITIVE,
+            -0.0,
+            0.0,
+            f64::MIN_POSITIVE,
+            1.0,
+            f64::MAX,
+            f64::INFINITY,
+        ]);
+
+        samples
+    }
+
+    #[test]
+    fn ordered_float_keys_sort_like_floats() {
+        let samples = sampled_f64s();
+
+        for pair in samples.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+
+            let key_a = key_bytes(&OrderedF64::new(a).unwrap());
+            let key_b = key_bytes(&OrderedF64::new(b).unwrap());
+
+            assert_eq!(key_a.cmp(&key_b), a.partial_cmp(&b).unwrap(), "{a} vs {b}");
+
+            #[allow(clippy::cast_possible_truncation)]
+            let (a, b) = (a as f32, b as f32);
+
+            let key_a = key_bytes(&OrderedF32::new(a).unwrap());
+            let key_b = key_bytes(&OrderedF32::new(b).unwrap());
+
+            assert_eq!(key_a.cmp(&key_b), a.partial_cmp(&b).unwrap(), "{a} vs {b}");
+        }
+    }
+
+    #[test]
+    fn ordered_float_keys_reject_nan_and_merge_zeroes() {
+        assert!(OrderedF64::new(f64::NAN).is_err());
+        assert!(OrderedF32::try_from(f32::NAN).is_err());
+
+        let negative_zero = OrderedF64::new(-0.0).unwrap();
+
+        assert!(negative_zero.get().is_sign_positive());
+        assert_eq!(
+            key_bytes(&negative_zero),
+            key_bytes(&OrderedF64::new(0.0).unwrap())
+        );
+
+        const PRICES: Map<16, OrderedF64, u64> = map!("prices");
+
+        let mut storage = MemStore::new_in_memory();
+
+        PRICES
+            .save(&mut storage, OrderedF64::new(-0.0).unwrap(), 1)
+            .unwrap();
+
+        assert_eq!(
+            PRICES
+                .may_load(&storage, OrderedF64::new(0.0).unwrap())
+                .unwrap(),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn macro_prefixes_never_overlap() {
+        const BALANCE: Map<16, &str, u64> = map!("balance");
+        const BALANCES: Map<16, &str, u64> = map!("balances");
+        const NESTED: Map<16, &str, u64> = map!("balance::s");
+        const ITEM: Item<u64> = item!("balance::x");
+
+        let prefixes = [
+            BALANCE.prefix(),
+            BALANCES.prefix(),
+            NESTED.prefix(),
+            ITEM.key(),
+        ];
+
+        for (i, a) in prefixes.iter().enumerate() {
+            for (j, b) in prefixes.iter().enumerate() {
+                if i != j {
+                    assert!(!b.starts_with(a), "{a:?} is a prefix of {b:?}");
+                }
+            }
+        }
+
+        let mut storage = MemStore::new_in_memory();
+
+        BALANCE.save(&mut storage, "s", 1).unwrap();
+        BALANCES.save(&mut storage, "", 2).unwrap();
+        NESTED.save(&mut storage, "", 3).unwrap();
+
+        assert_eq!(BALANCE.may_load(&storage, "s").unwrap(), Some(1));
+        assert_eq!(BALANCES.may_load(&storage, "").unwrap(), Some(2));
+        assert_eq!(NESTED.may_load(&storage, "").unwrap(), Some(3));
+        assert!(ITEM.is_empty(&storage).unwrap());
+    }
+
+    #[test]
+    fn may_load_if_consults_length_before_deserializing() {
+        const NAMES: Map<16, u64, String> = map!("may_load_if_names");
+        const BLOB: Item<Vec<u8>> = item!("may_load_if_blob");
+
+        let mut storage = MemStore::new_in_memory();
+
+        NAMES.save(&mut storage, 1, "alice".to_owned()).unwrap();
+        BLOB.save(&mut storage, vec![0; 1024]).unwrap();
+
+        assert_eq!(
+            NAMES.may_load_if(&storage, 1, |len| len > 0).unwrap(),
+            Some("alice".to_owned())
+        );
+        assert_eq!(NAMES.may_load_if(&storage, 2, |_| true).unwrap(), None);
+        assert_eq!(BLOB.may_load_if(&storage, |len| len <= 64).unwrap(), None);
+
+        let mut seen = None;
+        assert!(BLOB
+            .may_load_if(&storage, |len| {
+                seen = Some(len);
+                true
+            })
+            .unwrap()
+            .is_some());
+        assert!(seen.unwrap() > 1024);
+    }
+
+    #[test]
+    fn read_modify_write_helpers_read_once() {
+        const COUNTER: Item<u64> = item!("single_read_counter");
+        const STOCK: Map<16, &str, u32> = map!("single_read_stock");
+
+        let mut storage = KvStore::new(Bincode::new(), RecordingRepo::new(MemoryRepo::default()));
+
+        COUNTER.save(&mut storage, 1).unwrap();
+        STOCK.save(&mut storage, "apples", 2).unwrap();
+
+        let single_read = |storage: &KvStore<Bincode, RecordingRepo<MemoryRepo>>| {
+            let counts = storage.repo().read_counts();
+            storage.repo().reset_read_counts();
+            assert_eq!(
+                (counts.reads, counts.has_keys, counts.read_manys),
+                (1, 0, 0)
+            );
+        };
+
+        storage.repo().reset_read_counts();
+
+        COUNTER
+            .update(&mut storage, |n| Ok::<_, Error<_, _>>(n.unwrap() + 1))
+            .unwrap();
+        single_read(&storage);
+
+        STOCK
+            .update(&mut storage, "apples", |n| {
+                Ok::<_, Error<_, _>>(n.unwrap() + 1)
+            })
+            .unwrap();
+        single_read(&storage);
+
+        STOCK
+            .update_or_remove(&mut storage, "apples", |_| Ok::<_, Error<_, _>>(None))
+            .unwrap();
+        single_read(&storage);
+
+        STOCK
+            .entry(&mut storage, "pears")
+            .unwrap()
+            .or_insert(1)
+            .save()
+            .unwrap();
+        single_read(&storage);
+    }
+
+    #[test]
+    fn keys_are_assembled_at_compile_time() {
+        const NAMESPACE: &str = "bank";
+        const KEY: &[u8] = kv_storage::concat_keys!(NAMESPACE.as_bytes(), b"::", b"total");
+        const TOTAL: Item<u64> = Item::new(KEY);
+
+        assert_eq!(KEY, b"bank::total");
+        assert_eq!(TOTAL.key(), [NAMESPACE.as_bytes(), b"::total"].concat());
+
+        fn declared() -> Item<u64> {
+            item!("compile_time")
+        }
+
+        assert!(std::ptr::eq(declared().key(), declared().key()));
+        assert_eq!(
+            declared().key().len(),
+            kv_storage::namespaced_len(module_path!(), "compile_time")
+        );
+    }
+
+    #[test]
+    fn key_display_renders_text_and_hex() {
+        assert_eq!(KeyDisplay::new(b"bank::total").to_string(), "bank::total");
+        assert_eq!(KeyDisplay::new(b"ok").to_string(), "ok");
+
+        let mut with_suffix = b"balances::".to_vec();
+        with_suffix.extend_from_slice(&42u64.to_be_bytes());
+
+        assert_eq!(
+            KeyDisplay::new(&with_suffix).to_string(),
+            "balances::<000000000000002a>"
+        );
+
+        assert_eq!(
+            KeyDisplay::new(&[0xde, 0xad, 0xbe, 0xef]).to_string(),
+            "<deadbeef>"
+        );
+
+        const TOTAL: Item<u64> = Item::new(kv_storage::namespaced_key!("bank", "total"));
+
+        assert_eq!(
+            KeyDisplay::new(TOTAL.key()).to_string(),
+            "<0004>bank<0005>total"
+        );
+
+        let oversized = [b'a'; 100];
+
+        assert_eq!(
+            KeyDisplay::new(&oversized).with_max_len(8).to_string(),
+            "aaaaaaaa…(100 bytes)"
+        );
+        assert_eq!(format!("{:?}", KeyDisplay::new(b"key")), "\"key\"");
+    }
+
+    #[test]
+    fn frozen_repo_serves_a_snapshot() {
+        let mut storage = MemStore::new_in_memory();
+
+        for (account, amount) in [("alice", 100), ("bob", 200), ("carol", 300)] {
+            Balance::load_account(&storage, account)
+                .unwrap()
+                .deposit(amount)
+                .unwrap()
+                .save(&mut storage)
+                .unwrap();
+        }
+
+        let repo = std::mem::take(storage.mut_repo());
+        let frozen: KvStore<Bincode, FrozenRepo> =
+            KvStore::from_repo(repo.into_iter().collect::<FrozenRepo>());
+
+        assert_eq!(frozen.repo().len(), 4);
+
+        for (account, amount) in [("alice", 100), ("bob", 200), ("carol", 300)] {
+            assert!(Balance::account_exists(&frozen, account).unwrap());
+            assert_eq!(
+                Balance::load_account(&frozen, account).unwrap().balance(),
+                amount
+            );
+        }
+
+        assert!(!Balance::account_exists(&frozen, "dave").unwrap());
+        assert_eq!(Balance::load_total(&frozen).unwrap(), 600);
+
+        let last_wins: FrozenRepo = [
+            (b"k".to_vec(), b"1".to_vec()),
+            (b"k".to_vec(), b"2".to_vec()),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(last_wins.len(), 1);
+        assert_eq!(last_wins.read(b"k").unwrap(), Some(b"2".to_vec()));
+    }
+
+    #[test]
+    fn frozen_repo_ranges_like_the_repo_it_froze() {
+        const SCORES: Map<16, u64, u64> = map!("scores");
+        const OWNER: Item<String> = item!("owner");
+
+        fn range<Store: IterStorage>(
+            store: &Store,
+            min: Bound<u64>,
+            max: Bound<u64>,
+            order: Order,
+        ) -> Vec<(u64, u64)>
+        where
+            Store::Error: std::fmt::Debug,
+        {
+            SCORES
+                .range(store, min, max, order)
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        }
+
+        let mut storage = MemStore::new_in_memory();
+
+        for n in (0..20).step_by(2) {
+            SCORES.save(&mut storage, n, n * 10).unwrap();
+        }
+        OWNER.save(&mut storage, "alice".to_owned()).unwrap();
+
+        let frozen: KvStore<Bincode, FrozenRepo> =
+            KvStore::from_repo(FrozenRepo::freeze(storage.repo()).unwrap());
+
+        assert_eq!(frozen.repo().len(), 11);
+        assert_eq!(OWNER.may_load(&frozen).unwrap().as_deref(), Some("alice"));
+
+        // bounds on, between and past the stored keys
+        let bounds = || {
+            [3, 4, 18, 25]
+                .into_iter()
+                .flat_map(|n| [Bound::Inclusive(n), Bound::Exclusive(n)])
+                .chain([Bound::Unbounded])
+        };
+
+        for min in bounds() {
+            for max in bounds() {
+                for order in [Order::Ascending, Order::Descending] {
+                    assert_eq!(
+                        range(&frozen, min, max, order),
+                        range(&storage, min, max, order),
+                        "{min:?}..{max:?} {order:?}"
+                    );
+                }
+            }
+        }
+
+        let keys: Vec<_> = SCORES.keys(&frozen).unwrap().map(Result::unwrap).collect();
+        assert_eq!(keys, (0..20).step_by(2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn timestamped_map_maintains_envelope() {
+        const ORDERS: TimestampedMap<16, u64, String> = TimestampedMap::new(map!("orders"));
+
+        let now = std::cell::Cell::new(100);
+        let clock = || now.get();
+
+        let mut storage = MemStore::new_in_memory();
+
+        ORDERS
+            .save(&mut storage, 1, "pending".to_owned(), &clock)
+            .unwrap();
+
+        let created = ORDERS.may_load(&storage, 1).unwrap().unwrap();
+        assert_eq!(created.created_at(), 100);
+        assert_eq!(created.updated_at(), 100);
+
+        now.set(150);
+        ORDERS
+            .save(&mut storage, 1, "filled".to_owned(), &clock)
+            .unwrap();
+
+        let updated = ORDERS.may_load(&storage, 1).unwrap().unwrap();
+        assert_eq!(updated.created_at(), 100);
+        assert_eq!(updated.updated_at(), 150);
+        assert_eq!(updated.value(), "filled");
+
+        // a clock going backwards doesn't move updated_at back
+        now.set(120);
+        ORDERS
+            .save(&mut storage, 1, "settled".to_owned(), &clock)
+            .unwrap();
+
+        let settled = ORDERS.may_load(&storage, 1).unwrap().unwrap();
+        assert_eq!(settled.created_at(), 100);
+        assert_eq!(settled.updated_at(), 150);
+
+        ORDERS
+            .save_with_created(&mut storage, 2, "imported".to_owned(), 10, &clock)
+            .unwrap();
+
+        let imported = ORDERS.may_load(&storage, 2).unwrap().unwrap();
+        assert_eq!(imported.created_at(), 10);
+        assert_eq!(imported.updated_at(), 120);
+
+        assert_eq!(
+            ORDERS.may_load_value(&storage, 1).unwrap(),
+            Some("settled".to_owned())
+        );
+        assert_eq!(ORDERS.may_load_value(&storage, 3).unwrap(), None);
+    }
+
+    #[test]
+    fn get_or_try_init_initializes_once() {
+        const CONFIG: Item<String> = item!("get_or_try_init");
+
+        #[derive(Debug)]
+        enum InitError {
+            Storage,
+            Failed,
+        }
+
+        impl<S> From<kv_storage::Error<S, kv_storage_memory::Infallible>> for InitError {
+            fn from(_: kv_storage::Error<S, kv_storage_memory::Infallible>) -> Self {
+                InitError::Storage
+            }
+        }
+
+        let mut storage = MemStore::new_in_memory();
+
+        assert!(matches!(
+            CONFIG.get_or_try_init(&mut storage, || Err(InitError::Failed)),
+            Err(InitError::Failed)
+        ));
+        assert!(CONFIG.is_empty(&storage).unwrap());
+
+        let mut calls = 0;
+
+        for _ in 0..3 {
+            let config = CONFIG
+                .get_or_try_init(&mut storage, || {
+                    calls += 1;
+                    Ok::<_, InitError>("initial".to_owned())
+                })
+                .unwrap();
+
+            assert_eq!(config, "initial");
+        }
+
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn bounded_map_tracks_count() {
+        const ALLOWANCES: BoundedMap<16, u32, u128> = BoundedMap::new(map!("allowances"), 2);
+
+        let mut storage = MemStore::new_in_memory();
+
+        assert_eq!(ALLOWANCES.count(&storage).unwrap(), 0);
+
+        ALLOWANCES.save(&mut storage, 1, 10).unwrap();
+        ALLOWANCES.save(&mut storage, 2, 20).unwrap();
+        assert_eq!(ALLOWANCES.count(&storage).unwrap(), 2);
+
+        // overwriting doesn't count, and is allowed at capacity
+        ALLOWANCES.save(&mut storage, 1, 15).unwrap();
+        assert_eq!(ALLOWANCES.count(&storage).unwrap(), 2);
+        assert_eq!(ALLOWANCES.may_load(&storage, 1).unwrap(), Some(15));
+
+        assert!(matches!(
+            ALLOWANCES.save(&mut storage, 3, 30),
+            Err(BoundedError::CapacityExceeded { max: 2 })
+        ));
+        assert!(!ALLOWANCES.has_key(&storage, 3).unwrap());
+
+        // removing an absent key doesn't free a slot
+        assert_eq!(
+            ALLOWANCES.remove(&mut storage, 3).unwrap(),
+            Removed::DidNotExist
+        );
+        assert_eq!(ALLOWANCES.count(&storage).unwrap(), 2);
+
+        assert_eq!(
+            ALLOWANCES.remove(&mut storage, 2).unwrap(),
+            Removed::Existed
+        );
+        assert_eq!(ALLOWANCES.count(&storage).unwrap(), 1);
+
+        ALLOWANCES.save(&mut storage, 3, 30).unwrap();
+        assert_eq!(ALLOWANCES.count(&storage).unwrap(), 2);
+
+        // clearing frees every slot
+        assert_eq!(ALLOWANCES.clear(&mut storage).unwrap(), 2);
+        assert_eq!(ALLOWANCES.count(&storage).unwrap(), 0);
+        assert!(!ALLOWANCES.has_key(&storage, 1).unwrap());
+
+        ALLOWANCES.save(&mut storage, 4, 40).unwrap();
+        ALLOWANCES.save(&mut storage, 5, 50).unwrap();
+        assert_eq!(ALLOWANCES.count(&storage).unwrap(), 2);
+
+        // clearing an empty map leaves the counter at zero
+        ALLOWANCES.clear(&mut storage).unwrap();
+        assert_eq!(ALLOWANCES.clear(&mut storage).unwrap(), 0);
+        assert_eq!(ALLOWANCES.count(&storage).unwrap(), 0);
+    }
+
+    #[test]
+    fn bounded_map_recount_repairs_the_counter() {
+        const MEMBERS: Map<16, u32, String> = map!("members");
+        const BOUNDED: BoundedMap<16, u32, String> = BoundedMap::new(MEMBERS, 2);
+
+        let mut storage = MemStore::new_in_memory();
+
+        BOUNDED.save(&mut storage, 1, "alice".to_owned()).unwrap();
+
+        // writes through the inner map and to the counter bypass the bookkeeping
+        MEMBERS.save(&mut storage, 2, "bob".to_owned()).unwrap();
+        MEMBERS.save(&mut storage, 3, "carol".to_owned()).unwrap();
+        storage.save(BOUNDED.count_key().as_ref(), &7u64).unwrap();
+
+        assert_eq!(BOUNDED.recount(&mut storage).unwrap(), 3);
+        assert_eq!(BOUNDED.count(&storage).unwrap(), 3);
+
+        // over capacity after the repair, so new keys are still rejected
+        assert!(matches!(
+            BOUNDED.save(&mut storage, 4, "dave".to_owned()),
+            Err(BoundedError::CapacityExceeded { max: 2 })
+        ));
+
+        BOUNDED.clear(&mut storage).unwrap();
+        assert_eq!(BOUNDED.recount(&mut storage).unwrap(), 0);
+    }
+
+    #[test]
+    fn obfuscated_map_hides_keys() {
+        let accounts: ObfuscatedMap<64, &str, u128> =
+            ObfuscatedMap::new(map!("accounts"), KeyObfuscation::new([7; 16]));
+
+        let mut storage = MemStore::new_in_memory();
+
+        accounts.save(&mut storage, "alice", 100).unwrap();
+
+        assert_eq!(accounts.may_load(&storage, "alice").unwrap(), Some(100));
+        assert!(accounts.has_key(&storage, "alice").unwrap());
+        assert!(!accounts.has_key(&storage, "bob").unwrap());
+
+        // a different secret can't find the entry
+        let other: ObfuscatedMap<64, &str, u128> =
+            ObfuscatedMap::new(map!("accounts"), KeyObfuscation::new([8; 16]));
+
+        assert_eq!(other.may_load(&storage, "alice").unwrap(), None);
+
+        accounts.save(&mut storage, "bob", 50).unwrap();
+        accounts.remove(&mut storage, "bob").unwrap();
+
+        assert!(!accounts.has_key(&storage, "bob").unwrap());
+
+        let keys: Vec<_> = std::mem::take(storage.mut_repo())
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0], accounts.key("alice").as_ref());
+        assert!(!keys[0].windows(5).any(|window| window == b"alice"));
+    }
+
+    #[test]
+    fn obfuscated_map_recovers_embedded_keys_by_scanning() {
+        let accounts: ObfuscatedMap<64, (&str, u64), u128> =
+            ObfuscatedMap::new(map!("accounts"), KeyObfuscation::new([7; 16]))
+                .embed_original_key(EmbedOriginalKey::Yes);
+
+        let mut storage = MemStore::new_in_memory();
+
+        accounts.save(&mut storage, ("alice", 1), 100).unwrap();
+        accounts.save(&mut storage, ("bob", 2), 50).unwrap();
+
+        assert_eq!(
+            accounts.may_load(&storage, ("alice", 1)).unwrap(),
+            Some(100)
+        );
+        assert_eq!(accounts.may_load(&storage, ("alice", 2)).unwrap(), None);
+
+        let mut entries: Vec<_> = accounts
+            .entries(&storage)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        entries.sort();
+
+        assert_eq!(
+            entries,
+            [(("alice".to_owned(), 1), 100), (("bob".to_owned(), 2), 50)]
+        );
+
+        // the keys themselves still reveal nothing
+        for key in storage.scan_keys(b"").unwrap() {
+            assert!(!key.windows(5).any(|window| window == b"alice"));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "original keys embedded")]
+    fn obfuscated_map_scans_need_embedded_keys() {
+        let accounts: ObfuscatedMap<64, &str, u128> =
+            ObfuscatedMap::new(map!("accounts"), KeyObfuscation::new([7; 16]));
+
+        let _ = accounts.entries(&MemStore::new_in_memory());
+    }
+
+    #[test]
+    fn save_with_threads_durability_to_repo() {
+        #[derive(Default)]
+        struct Recording {
+            inner: MemoryRepo,
+            levels: Vec<Durability>,
+        }
+
+        impl Fallible for Recording {
+            type Error = <MemoryRepo as Fallible>::Error;
+        }
+
+        impl Write for Recording {
+            fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+                self.inner.write(key, bytes)
+            }
+
+            fn write_durable(
+                &mut self,
+                key: &[u8],
+                bytes: &[u8],
+                durability: Durability,
+            ) -> Result<(), Self::Error> {
+                self.levels.push(durability);
+                self.inner.write(key, bytes)
+            }
+
+            fn supports_durability(&self) -> bool {
+                true
+            }
+        }
+
+        impl Read for Recording {
+            fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+                self.inner.read(key)
+            }
+        }
+
+        impl HasKey for Recording {
+            fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+                self.inner.has_key(key)
+            }
+        }
+
+        impl Remove for Recording {
+            fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+                self.inner.remove(key)
+            }
+        }
+
+        const BALANCE: Item<u128> = item!("balance");
+        const CACHE: Map<16, u32, String> = map!("cache");
+
+        let mut storage: KvStore<Bincode, Recording> = KvStore::default();
+
+        BALANCE
+            .save_with(&mut storage, 100, Durability::Sync)
+            .unwrap();
+        CACHE
+            .save_with(&mut storage, 1, "hot".to_owned(), Durability::Relaxed)
+            .unwrap();
+        BALANCE.save(&mut storage, 200).unwrap();
+
+        assert!(storage.repo().supports_durability());
+        assert_eq!(
+            storage.repo().levels,
+            [Durability::Sync, Durability::Relaxed]
+        );
+        assert_eq!(BALANCE.may_load(&storage).unwrap(), Some(200));
+
+        // repos without durability support fall back to a plain write
+        let mut storage = MemStore::new_in_memory();
+
+        assert!(!storage.repo().supports_durability());
+
+        BALANCE
+            .save_with(&mut storage, 100, Durability::Sync)
+            .unwrap();
+
+        assert_eq!(BALANCE.may_load(&storage).unwrap(), Some(100));
+    }
+
+    #[test]
+    fn failed_save_leaves_no_stale_buffer() {
+        const NOTE: Item<String> = item!("note");
+
+        let mut storage = MemStore::new_in_memory();
+
+        storage.set_post_serialize_hook(|_| Err(InjectedError));
+
+        assert!(matches!(
+            NOTE.save(&mut storage, "a much longer note".to_owned()),
+            Err(Error::Injected(_))
+        ));
+        assert!(NOTE.is_empty(&storage).unwrap());
+
+        storage.clear_hooks();
+        NOTE.save(&mut storage, "short".to_owned()).unwrap();
+
+        let mut expected = KvStore::<Bincode, MemoryRepo>::new_in_memory();
+        NOTE.save(&mut expected, "short".to_owned()).unwrap();
+
+        assert_eq!(
+            storage.repo().read(NOTE.key()).unwrap(),
+            expected.repo().read(NOTE.key()).unwrap()
+        );
+    }
+
+    #[test]
+    fn failed_bounded_save_is_not_partial() {
+        const MEMBERS: BoundedMap<16, u32, String> = BoundedMap::new(map!("members"), 10);
+
+        let mut storage = MemStore::new_in_memory();
+
+        MEMBERS.save(&mut storage, 1, "alice".to_owned()).unwrap();
+
+        storage.set_pre_write_hook(|key, _| {
+            if key == MEMBERS.count_key().as_ref() {
+                Err(InjectedError)
+            } else {
+                Ok(())
+            }
+        });
+
+        assert!(matches!(
+            MEMBERS.save(&mut storage, 2, "bob".to_owned()),
+            Err(BoundedError::Store(Error::Injected(_)))
+        ));
+        assert!(!MEMBERS.has_key(&storage, 2).unwrap());
+        assert_eq!(MEMBERS.count(&storage).unwrap(), 1);
+
+        // overwrites don't touch the counter
+        MEMBERS.save(&mut storage, 1, "carol".to_owned()).unwrap();
+
+        assert!(matches!(
+            MEMBERS.remove(&mut storage, 1),
+            Err(Error::Injected(_))
+        ));
+        assert!(MEMBERS.has_key(&storage, 1).unwrap());
+        assert_eq!(MEMBERS.count(&storage).unwrap(), 1);
+    }
+
+    #[test]
+    fn borrowed_composite_keys_encode_like_owned() {
+        const POSITIONS: Map<64, (String, u64), u32> = map!("positions");
+        const TAGS: Map<64, (&str, Vec<u8>, u8), ()> = map!("tags");
+
+        let owner = "alice".to_owned();
+        let owned = (owner.clone(), 7u64);
+        let expected = POSITIONS.key(owned.clone());
+
+        assert_eq!(POSITIONS.key(&owned).as_ref(), expected.as_ref());
+        assert_eq!(POSITIONS.key((&owner, &7)).as_ref(), expected.as_ref());
+        assert_eq!(POSITIONS.key(("alice", 7)).as_ref(), expected.as_ref());
+        assert_eq!(POSITIONS.key((&owner, 7)).as_ref(), expected.as_ref());
+
+        assert_eq!(
+            TAGS.key((owner.clone(), b"tag".as_slice(), &1)).as_ref(),
+            TAGS.key(("alice", b"tag".to_vec(), 1)).as_ref()
+        );
+
+        let mut storage = MemStore::new_in_memory();
+
+        // no owned tuple needed for any of these
+        POSITIONS.save(&mut storage, ("alice", 7), 100).unwrap();
+        assert_eq!(
+            POSITIONS.may_load(&storage, (&owner, 7)).unwrap(),
+            Some(100)
+        );
+        assert!(POSITIONS.has_key(&storage, &owned).unwrap());
+        POSITIONS
+            .remove(&mut storage, (owner.as_str(), &7))
+            .unwrap();
+        assert!(!POSITIONS.has_key(&storage, owned).unwrap());
+    }
+
+    #[test]
+    fn negotiated_bincode_reads_older_configs() {
+        struct V0;
+
+        impl ConfigTable for V0 {
+            const CONFIGS: &'static [BincodeConfig] = &[BincodeConfig::LEGACY];
+        }
+
+        struct V1;
+
+        impl ConfigTable for V1 {
+            const CONFIGS: &'static [BincodeConfig] =
+                &[BincodeConfig::LEGACY, BincodeConfig::VARINT];
+        }
+
+        const BALANCE: Item<(u64, String)> = item!("balance");
+
+        let balance = (300, "alice".to_owned());
+
+        let mut old: KvStore<NegotiatedBincode<V0>, MemoryRepo> = KvStore::default();
+        BALANCE.save(&mut old, &balance).unwrap();
+
+        // the new node reads the old value, then writes with varints
+        let mut new: KvStore<NegotiatedBincode<V1>, MemoryRepo> =
+            KvStore::from_repo(std::mem::take(old.mut_repo()));
+
+        assert_eq!(BALANCE.may_load(&new).unwrap(), Some(balance.clone()));
+
+        let old_len = new.repo().read(BALANCE.key()).unwrap().unwrap().len();
+        BALANCE.save(&mut new, &balance).unwrap();
+        let new_bytes = new.repo().read(BALANCE.key()).unwrap().unwrap();
+
+        assert_eq!(new_bytes[0], 1);
+        assert!(new_bytes.len() < old_len);
+        assert_eq!(BALANCE.may_load(&new).unwrap(), Some(balance));
+
+        // an old node can't read it, and says why
+        let old: KvStore<NegotiatedBincode<V0>, MemoryRepo> =
+            KvStore::from_repo(std::mem::take(new.mut_repo()));
+
+        assert!(matches!(
+            BALANCE.may_load(&old),
+            Err(Error::Serde(NegotiationError::UnknownConfig(1)))
+        ));
+    }
+
+    #[test]
+    fn bincode_varint_options_shrink_values() {
+        const BALANCE: Item<u128> = item!("balance");
+
+        let mut fixint: KvStore<Bincode, MemoryRepo> = KvStore::default();
+        BALANCE.save(&mut fixint, 1_000).unwrap();
+
+        let mut varint = KvStore::new(
+            Bincode::with_options(VarintOptions),
+            std::mem::take(fixint.mut_repo()),
+        );
+
+        let fixint_len = varint.repo().read(BALANCE.key()).unwrap().unwrap().len();
+        BALANCE.save(&mut varint, 1_000).unwrap();
+        let varint_len = varint.repo().read(BALANCE.key()).unwrap().unwrap().len();
+
+        assert_eq!(fixint_len, 16);
+        assert_eq!(varint_len, 3);
+        assert_eq!(BALANCE.may_load(&varint).unwrap(), Some(1_000));
+    }
+
+    #[test]
+    fn bincode_size_limit_rejects_oversized_values() {
+        struct Limited;
+
+        impl BincodeOptions for Limited {
+            const CONFIG: BincodeConfig = BincodeConfig::LEGACY;
+            const LIMIT: Option<u64> = Some(64);
+        }
+
+        const BLOB: Item<Vec<u8>> = item!("blob");
+
+        let mut unlimited: KvStore<Bincode, MemoryRepo> = KvStore::default();
+        BLOB.save(&mut unlimited, vec![0; 1_000]).unwrap();
+
+        let mut limited: KvStore<BincodeWith<Limited>, MemoryRepo> =
+            KvStore::from_repo(std::mem::take(unlimited.mut_repo()));
+
+        assert!(matches!(
+            BLOB.may_load(&limited),
+            Err(Error::Serde(error)) if matches!(*error, ErrorKind::SizeLimit)
+        ));
+
+        BLOB.save(&mut limited, vec![0; 32]).unwrap();
+
+        assert_eq!(BLOB.may_load(&limited).unwrap(), Some(vec![0; 32]));
+    }
+
+    #[test]
+    fn memory_repo_lends_bytes_to_deserialize() {
+        use std::cell::Cell;
+
+        use kv_storage::{Deserializer, Serializer};
+        use serde::{de::DeserializeOwned, Serialize};
+
+        thread_local! {
+            static SEEN: Cell<*const u8> = const { Cell::new(std::ptr::null()) };
+        }
+
+        /// Bincode, noting where the bytes it's handed live.
+        #[derive(Default)]
+        struct Noting(Bincode);
+
+        impl Fallible for Noting {
+            type Error = kv_storage_bincode::Error;
+        }
+
+        impl Serializer for Noting {
+            fn serialize<T: Serialize>(&mut self, item: &T) -> Result<&[u8], Self::Error> {
+                self.0.serialize(item)
+            }
+        }
+
+        impl Deserializer for Noting {
+            fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+                SEEN.set(bytes.as_ptr());
+                Bincode::deserialize(bytes)
+            }
+        }
+
+        const NAME: Item<String> = item!("name");
+
+        let mut store: KvStore<Noting, MemoryRepo> = KvStore::default();
+        NAME.save(&mut store, "alice".to_owned()).unwrap();
+
+        // a copy would live elsewhere while the stored bytes are lent out
+        store
+            .repo()
+            .read_with(NAME.key(), |stored| {
+                assert_eq!(NAME.may_load(&store).unwrap().as_deref(), Some("alice"));
+                assert_eq!(SEEN.get(), stored.unwrap().as_ptr());
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn loads_read_without_copying() {
+        use std::cell::Cell;
+
+        /// Counts the copies handed out by `read`.
+        struct CopyCounting {
+            inner: MemoryRepo,
+            copies: Cell<usize>,
+        }
+
+        impl Fallible for CopyCounting {
+            type Error = kv_storage_memory::Infallible;
+        }
+
+        impl Read for CopyCounting {
+            fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+                self.copies.set(self.copies.get() + 1);
+                self.inner.read(key)
+            }
+
+            fn read_with<R, F>(&self, key: &[u8], f: F) -> Result<R, Self::Error>
+            where
+                F: FnOnce(Option<&[u8]>) -> R,
+            {
+                self.inner.read_with(key, f)
+            }
+        }
+
+        impl HasKey for CopyCounting {
+            fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+                self.inner.has_key(key)
+            }
+        }
+
+        const NAME: Item<String> = item!("name");
+
+        let mut store = MemStore::new_in_memory();
+        NAME.save(&mut store, "alice".to_owned()).unwrap();
+
+        let store: KvStore<Bincode, CopyCounting> = KvStore::from_repo(CopyCounting {
+            inner: store.into_repo(),
+            copies: Cell::new(0),
+        });
+
+        assert_eq!(NAME.may_load(&store).unwrap().as_deref(), Some("alice"));
+        assert_eq!(NAME.may_load_if(&store, |len| len > 100).unwrap(), None);
+        assert_eq!(
+            store
+                .repo()
+                .read_with(NAME.key(), |bytes| bytes.map(<[u8]>::len))
+                .unwrap(),
+            Some(13)
+        );
+        assert_eq!(store.repo().copies.get(), 0);
+    }
+
+    #[test]
+    fn memory_repo_takes_serialized_bytes() {
+        use kv_storage::Serializer;
+
+        const SNAPSHOT: Item<Vec<u64>> = item!("snapshot");
+        const NAME: Item<String> = item!("name");
+
+        let mut store = MemStore::new_in_memory();
+
+        assert!(store.repo().takes_ownership());
+
+        SNAPSHOT.save(&mut store, vec![7; 1_000]).unwrap();
+        NAME.save(&mut store, "alice".to_owned()).unwrap();
+
+        assert_eq!(SNAPSHOT.may_load(&store).unwrap(), Some(vec![7; 1_000]));
+        assert_eq!(NAME.may_load(&store).unwrap().as_deref(), Some("alice"));
+
+        // the serializer starts over after handing its buffer out
+        let serde = store.mut_serde();
+        let owned = serde.serialize_owned(&2u16).unwrap();
+
+        assert_eq!(serde.serialize(&1u16).unwrap(), [1, 0]);
+        assert_eq!(owned, [2, 0]);
+    }
+
+    #[test]
+    fn read_only_repos_get_has_key() {
+        /// Implements nothing but `read`.
+        struct Fixed(Vec<(Vec<u8>, Vec<u8>)>);
+
+        impl Fallible for Fixed {
+            type Error = kv_storage_memory::Infallible;
+        }
+
+        impl Read for Fixed {
+            fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+                Ok(self
+                    .0
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, bytes)| bytes.clone()))
+            }
+        }
+
+        impl HasKey for Fixed {}
+
+        const FLAG: Item<bool> = item!("flag");
+        const OTHER: Item<bool> = item!("other");
+
+        let store: KvStore<Bincode, Fixed> =
+            KvStore::from_repo(Fixed(vec![(FLAG.key().to_vec(), vec![1])]));
+
+        assert!(Storage::has_key(&store, FLAG.key()).unwrap());
+        assert!(!Storage::has_key(&store, OTHER.key()).unwrap());
+        assert_eq!(FLAG.may_load(&store).unwrap(), Some(true));
+    }
+
+    #[test]
+    fn headered_map_distinguishes_emptied_from_unused() {
+        const CART: HeaderedMap<32, (&str, u32), u32> = map!("cart").with_header();
+
+        let mut storage = MemStore::new_in_memory();
+
+        assert_eq!(CART.state(&storage).unwrap(), MapState::NeverUsed);
+
+        CART.save(&mut storage, ("alice", 1), 2).unwrap();
+        CART.save(&mut storage, ("alice", 2), 1).unwrap();
+        CART.save(&mut storage, ("alice", 1), 3).unwrap();
+
+        assert_eq!(
+            CART.state(&storage).unwrap(),
+            MapState::Populated { count_hint: 2 }
+        );
+        assert_eq!(CART.may_load(&storage, ("alice", 1)).unwrap(), Some(3));
+
+        CART.remove(&mut storage, ("alice", 1)).unwrap();
+        CART.remove(&mut storage, ("alice", 1)).unwrap();
+        CART.remove(&mut storage, ("alice", 2)).unwrap();
+
+        assert_eq!(CART.state(&storage).unwrap(), MapState::Empty);
+
+        CART.purge(&mut storage).unwrap();
+
+        assert_eq!(CART.state(&storage).unwrap(), MapState::NeverUsed);
+
+        // the header is the only entry left, under the map's own prefix
+        CART.save(&mut storage, ("bob", 1), 1).unwrap();
+        CART.remove(&mut storage, ("bob", 1)).unwrap();
+
+        let keys: Vec<_> = std::mem::take(storage.mut_repo())
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+
+        assert_eq!(keys, [CART.header_key().as_ref()]);
+    }
+
+    #[test]
+    fn headered_map_removal_without_a_header_keeps_it_missing() {
+        const PLAIN: Map<32, u32, u32> = map!("legacy_cart");
+        const CART: HeaderedMap<32, u32, u32> = PLAIN.with_header();
+
+        let mut storage = MemStore::new_in_memory();
+
+        // entries written before the header was adopted
+        PLAIN.save(&mut storage, 1, 10).unwrap();
+        PLAIN.save(&mut storage, 2, 20).unwrap();
+
+        assert!(CART.remove(&mut storage, 1).unwrap().existed());
+
+        assert_eq!(CART.state(&storage).unwrap(), MapState::NeverUsed);
+        assert_eq!(CART.count(&storage).unwrap(), 1);
+        assert_eq!(CART.may_load(&storage, 2).unwrap(), Some(20));
+    }
+
+    #[test]
+    fn headered_map_header_is_outside_the_entries() {
+        const IDS: HeaderedMap<32, u128, u32> = map!("ids").with_header();
+        const PLAIN: Map<32, u128, u32> = map!("ids");
+
+        // a key encoding to the bytes the header used to be stored under
+        let suffix_key = u128::from_be_bytes(*b"\xff__map_header__\xff");
+
+        let mut storage = MemStore::new_in_memory();
+
+        IDS.save(&mut storage, 1, 10).unwrap();
+        IDS.save(&mut storage, suffix_key, 20).unwrap();
+
+        assert_eq!(
+            IDS.state(&storage).unwrap(),
+            MapState::Populated { count_hint: 2 }
+        );
+
+        let entries: Vec<_> = IDS
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(entries, [(1, 10), (suffix_key, 20)]);
+
+        let keys: Vec<_> = IDS.keys(&storage).unwrap().map(Result::unwrap).collect();
+
+        assert_eq!(keys, [1, suffix_key]);
+        assert_eq!(IDS.count(&storage).unwrap(), 2);
+
+        // clearing keeps the header, even through the plain map
+        assert_eq!(IDS.clear(&mut storage).unwrap(), 2);
+        assert_eq!(IDS.state(&storage).unwrap(), MapState::Empty);
+        assert_eq!(IDS.count(&storage).unwrap(), 0);
+
+        IDS.save(&mut storage, 1, 10).unwrap();
+        PLAIN.clear(&mut storage).unwrap();
+
+        assert!(storage.repo().has_key(IDS.header_key().as_ref()).unwrap());
+
+        IDS.purge(&mut storage).unwrap();
+        assert_eq!(IDS.state(&storage).unwrap(), MapState::NeverUsed);
+    }
+
+    #[test]
+    fn map_range_yields_every_entry() {
+        let mut storage = MemStore::new_in_memory();
+
+        for (account, amount) in [("alice", 10), ("bob", 20), ("carol", 30)] {
+            let mut balance = Balance::load_account(&storage, account).unwrap();
+            balance.deposit(amount).unwrap().save(&mut storage).unwrap();
+        }
+
+        let all = Balance::load_all(&storage).unwrap();
+
+        assert_eq!(
+            all,
+            [
+                ("alice".to_owned(), 10),
+                ("bob".to_owned(), 20),
+                ("carol".to_owned(), 30)
+            ]
+        );
+    }
+
+    #[test]
+    fn map_range_reports_bad_entries_individually() {
+        const SCORES: Map<16, &str, u64> = map!("scores");
+
+        let mut storage = MemStore::new_in_memory();
+
+        SCORES.save(&mut storage, "alice", 1).unwrap();
+        SCORES.save(&mut storage, "bob", 2).unwrap();
+        storage
+            .mut_repo()
+            .write(SCORES.key("mallory").as_ref(), &[1])
+            .unwrap();
+
+        let (ok, err): (Vec<_>, Vec<_>) = SCORES
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .partition(Result::is_ok);
+
+        let ok: Vec<_> = ok.into_iter().map(Result::unwrap).collect();
+
+        assert_eq!(ok, [("alice".to_owned(), 1), ("bob".to_owned(), 2)]);
+        assert!(matches!(
+            err.as_slice(),
+            [Err(RangeError::Store(kv_storage::Error::Serde(_)))]
+        ));
+    }
+
+    fn round_trip<K>(key: K) -> K::Owned
+    where
+        K: EncodeLike<K> + KeyDeserialize,
+    {
+        let map: Map<64, K, ()> = Map::new(b"rt");
+        let composite = map.key(key);
+
+        K::from_key_bytes(&composite.as_ref()[map.prefix().len()..]).unwrap()
+    }
+
+    #[test]
+    fn keys_round_trip() {
+        assert_eq!(round_trip("alice".to_owned()), "alice");
+        assert_eq!(round_trip("a:b"), "a:b");
+        assert_eq!(round_trip(vec![0u8, b':', 0xff]), [0, b':', 0xff]);
+        assert_eq!(round_trip(b"raw".as_slice()), b"raw");
+        assert_eq!(round_trip(0xabu8), 0xab);
+        assert_eq!(round_trip(0xabcdu16), 0xabcd);
+        assert_eq!(round_trip(u32::MAX - 1), u32::MAX - 1);
+        assert_eq!(round_trip(u64::MAX), u64::MAX);
+        assert_eq!(round_trip(1u128 << 100), 1 << 100);
+        assert_eq!(round_trip(i8::MIN), i8::MIN);
+        assert_eq!(round_trip(-1i32), -1);
+        assert_eq!(round_trip(i64::MAX), i64::MAX);
+        assert_eq!(round_trip(-(1i128 << 100)), -(1 << 100));
+
+        assert_eq!(round_trip(("a:b", "c")), ("a:b".to_owned(), "c".to_owned()));
+        assert_eq!(round_trip(("a", "b:c")), ("a".to_owned(), "b:c".to_owned()));
+        assert_eq!(round_trip(("", 7u64)), (String::new(), 7));
+        assert_eq!(
+            round_trip((1u32, vec![b':'; 300], "tail:")),
+            (1, vec![b':'; 300], "tail:".to_owned())
+        );
+    }
+
+    #[test]
+    fn signed_keys_sort_numerically() {
+        const TICKS: Map<16, i64, ()> = map!("ticks");
+
+        let key = |tick: i64| TICKS.key(tick).as_ref().to_vec();
+
+        assert!(key(i64::MIN) < key(-1));
+        assert!(key(-1) < key(0));
+        assert!(key(0) < key(1));
+        assert!(key(1) < key(i64::MAX));
+    }
+
+    #[test]
+    fn fixed_size_and_scalar_keys() {
+        const BLOCKS: Map<64, [u8; 32], u64> = map!("blocks");
+        const FLAGS: Map<16, bool, String> = map!("flags");
+        const GRADES: Map<16, char, u32> = map!("grades");
+
+        let mut storage = MemStore::new_in_memory();
+
+        let low = [0x11; 32];
+        let high = [0xee; 32];
+
+        // hashes are usually borrowed from elsewhere
+        let borrowed: &[u8; 32] = &low;
+
+        BLOCKS.save(&mut storage, high, 2).unwrap();
+        BLOCKS.save(&mut storage, borrowed, 1).unwrap();
+
+        assert_eq!(BLOCKS.may_load(&storage, high).unwrap(), Some(2));
+        assert_eq!(
+            &BLOCKS.key(low).as_ref()[BLOCKS.prefix().len()..],
+            low.as_slice()
+        );
+
+        let blocks: Vec<([u8; 32], u64)> = BLOCKS
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(blocks, [(low, 1), (high, 2)]);
+
+        let flag = |value: bool| FLAGS.key(value).as_ref()[FLAGS.prefix().len()..].to_vec();
+
+        assert_eq!(flag(false), [0]);
+        assert_eq!(flag(true), [1]);
+
+        FLAGS.save(&mut storage, true, "on".to_owned()).unwrap();
+        FLAGS.save(&mut storage, false, "off".to_owned()).unwrap();
+
+        assert_eq!(
+            FLAGS.may_load(&storage, true).unwrap(),
+            Some("on".to_owned())
+        );
+        assert_eq!(
+            FLAGS.may_load(&storage, false).unwrap(),
+            Some("off".to_owned())
+        );
+
+        GRADES.save(&mut storage, 'é', 2).unwrap();
+        GRADES.save(&mut storage, 'a', 1).unwrap();
+
+        let grades: Vec<char> = GRADES.keys(&storage).unwrap().map(Result::unwrap).collect();
+        assert_eq!(grades, ['a', 'é']);
+
+        assert_eq!(round_trip(('🦀', true)), ('🦀', true));
+
+        assert_eq!(
+            bool::from_key_bytes(&[2]),
+            Err(KeyDecodeError::InvalidValue("bool"))
+        );
+        assert_eq!(
+            char::from_key_bytes(b"ab"),
+            Err(KeyDecodeError::InvalidValue("char"))
+        );
+        assert_eq!(
+            <[u8; 4]>::from_key_bytes(&[0; 3]),
+            Err(KeyDecodeError::InvalidLength {
+                expected: 4,
+                found: 3
+            })
+        );
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn signed_key_order_matches_numeric_order(mut values: Vec<i64>, mut small: Vec<i8>) {
+            const WIDE: Map<16, i64, ()> = Map::new(b"");
+            const NARROW: Map<16, i8, ()> = Map::new(b"");
+
+            values.sort_unstable();
+            small.sort_unstable();
+
+            let encoded: Vec<Vec<u8>> = values
+                .iter()
+                .map(|value| WIDE.key(value).as_ref().to_vec())
+                .collect();
+            let encoded_small: Vec<Vec<u8>> = small
+                .iter()
+                .map(|value| NARROW.key(value).as_ref().to_vec())
+                .collect();
+
+            proptest::prop_assert!(encoded.is_sorted());
+            proptest::prop_assert!(encoded_small.is_sorted());
+
+            for (value, bytes) in values.iter().zip(&encoded) {
+                proptest::prop_assert_eq!(i64::from_key_bytes(bytes), Ok(*value));
+            }
+        }
+    }
+
+    #[test]
+    fn wide_and_nested_tuple_keys() {
+        const FIVE: Map<64, (&str, u8, &str, u64, &str), u32> = map!("five");
+        const NESTED: Map<64, (&str, (u64, u64)), u32> = map!("nested");
+
+        let mut storage = MemStore::new_in_memory();
+
+        FIVE.save(&mut storage, ("a", 1, "b", 2, "c"), 5).unwrap();
+        assert_eq!(
+            FIVE.may_load(&storage, ("a", 1, "b", 2, "c")).unwrap(),
+            Some(5)
+        );
+
+        // every part but the last is length-prefixed
+        assert_eq!(
+            &FIVE.key(("a", 1, "b", 2, "c")).as_ref()[FIVE.prefix().len()..],
+            b"\0\x01a\0\x01\x01\0\x01b\0\x08\0\0\0\0\0\0\0\x02c"
+        );
+        assert_eq!(
+            round_trip(("a", 1u8, "b", 2u64, "c")),
+            ("a".to_owned(), 1, "b".to_owned(), 2, "c".to_owned())
+        );
+        assert_eq!(
+            round_trip((1u8, 2u16, 3u32, 4u64, 5u128, -6i8, '7', "8")),
+            (1, 2, 3, 4, 5, -6, '7', "8".to_owned())
+        );
+
+        // a nested tuple is a single length-prefixed part, or written as is when last
+        NESTED.save(&mut storage, ("pool", (1, 2)), 12).unwrap();
+        NESTED.save(&mut storage, ("pool", (1, 3)), 13).unwrap();
+
+        assert_eq!(
+            &NESTED.key(("pool", (1, 2))).as_ref()[NESTED.prefix().len()..],
+            b"\0\x04pool\0\x08\0\0\0\0\0\0\0\x01\0\0\0\0\0\0\0\x02"
+        );
+        assert_eq!(
+            NESTED.may_load(&storage, ("pool", (1, 3))).unwrap(),
+            Some(13)
+        );
+
+        assert_contents_eq(
+            &storage,
+            &NESTED,
+            [(("pool", (1, 2)), 12), (("pool", (1, 3)), 13)],
+        );
+
+        assert_eq!(
+            round_trip(((1u64, "inner"), "outer")),
+            ((1, "inner".to_owned()), "outer".to_owned())
+        );
+    }
+
+    #[test]
+    fn tuple_keys_with_delimiters_do_not_collide() {
+        const PAIRS: Map<32, (&str, &str), u8> = map!("pairs");
+
+        assert_ne!(
+            PAIRS.key(("a:b", "c")).as_ref(),
+            PAIRS.key(("a", "b:c")).as_ref()
+        );
+
+        let prefix_len = PAIRS.prefix().len();
+
+        assert_eq!(kv_storage::KEY_ENCODING_VERSION, 2);
+        assert_eq!(
+            &PAIRS.key(("a:b", "c")).as_ref()[prefix_len..],
+            b"\0\x03a:bc"
+        );
+        assert_eq!(
+            &PAIRS.key(("a", "b:c")).as_ref()[prefix_len..],
+            b"\0\x01ab:c"
+        );
+
+        let mut storage = MemStore::new_in_memory();
+
+        PAIRS.save(&mut storage, ("a:b", "c"), 1).unwrap();
+        PAIRS.save(&mut storage, ("a", "b:c"), 2).unwrap();
+
+        let all: Vec<_> = PAIRS
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(
+            all,
+            [
+                (("a".to_owned(), "b:c".to_owned()), 2),
+                (("a:b".to_owned(), "c".to_owned()), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_keys_fail_to_decode() {
+        assert_eq!(
+            u64::from_key_bytes(&[1, 2]),
+            Err(KeyDecodeError::InvalidLength {
+                expected: 8,
+                found: 2
+            })
+        );
+        assert_eq!(
+            String::from_key_bytes(&[0xff]),
+            Err(KeyDecodeError::InvalidUtf8)
+        );
+        assert_eq!(
+            <(String, u8)>::from_key_bytes(&[0, 5, b'a']),
+            Err(KeyDecodeError::Truncated)
+        );
+    }
+
+    #[test]
+    fn map_range_pages_with_bounds_and_order() {
+        use Bound::{Exclusive, Inclusive, Unbounded};
+        use Order::{Ascending, Descending};
+
+        const IDS: Map<16, u32, ()> = map!("ids");
+        // shares the `ids` prefix, must not leak into the pages
+        const IDS_EXT: Item<u8> = item!("ids_ext");
+
+        let mut storage = MemStore::new_in_memory();
+
+        for id in [1, 3, 5, 7, 9] {
+            IDS.save(&mut storage, id, ()).unwrap();
+        }
+
+        IDS_EXT.save(&mut storage, 0).unwrap();
+
+        let page = |min, max, order, limit| -> Vec<u32> {
+            IDS.range(&storage, min, max, order)
+                .unwrap()
+                .take(limit)
+                .map(|entry| entry.unwrap().0)
+                .collect()
+        };
+
+        assert_eq!(page(Unbounded, Unbounded, Ascending, 2), [1, 3]);
+        // start_after an existing key
+        assert_eq!(page(Exclusive(3), Unbounded, Ascending, 2), [5, 7]);
+        assert_eq!(page(Inclusive(3), Unbounded, Ascending, 2), [3, 5]);
+        // start_after a missing key
+        assert_eq!(page(Exclusive(4), Unbounded, Ascending, 2), [5, 7]);
+        assert_eq!(page(Exclusive(3), Exclusive(9), Ascending, 10), [5, 7]);
+        assert_eq!(page(Exclusive(3), Inclusive(9), Ascending, 10), [5, 7, 9]);
+
+        assert_eq!(page(Unbounded, Unbounded, Descending, 2), [9, 7]);
+        assert_eq!(page(Unbounded, Exclusive(7), Descending, 2), [5, 3]);
+        assert_eq!(page(Inclusive(3), Inclusive(7), Descending, 10), [7, 5, 3]);
+
+        // paging past the end, and empty or inverted ranges
+        assert!(page(Exclusive(9), Unbounded, Ascending, 2).is_empty());
+        assert!(page(Unbounded, Exclusive(1), Descending, 2).is_empty());
+        assert!(page(Exclusive(5), Exclusive(5), Ascending, 2).is_empty());
+        assert!(page(Inclusive(7), Inclusive(3), Ascending, 2).is_empty());
+        assert_eq!(page(Inclusive(5), Inclusive(5), Ascending, 2), [5]);
+    }
+
+    #[test]
+    fn map_range_orders_composite_keys_by_bytes() {
+        const VISITS: Map<32, (&str, u32), ()> = map!("visits");
+
+        let mut storage = MemStore::new_in_memory();
+
+        for key in [("bob", 2), ("alice", 1), ("bob", 1), ("al", 300), ("al", 9)] {
+            VISITS.save(&mut storage, key, ()).unwrap();
+        }
+
+        let keys: Vec<(String, u32)> = VISITS
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(|entry| entry.unwrap().0)
+            .collect();
+
+        // the leading part is length-prefixed, so shorter names sort first, like on disk
+        assert_eq!(
+            keys,
+            [
+                ("al".to_owned(), 9),
+                ("al".to_owned(), 300),
+                ("bob".to_owned(), 1),
+                ("bob".to_owned(), 2),
+                ("alice".to_owned(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn map_keys_skip_corrupt_values() {
+        // the same map as `Balance`'s
+        const BALANCES: Map<1024, &str, u128> =
+            Map::new(kv_storage::namespaced_key!("mock_consumer", "balances"));
+
+        let mut storage = MemStore::new_in_memory();
+
+        for (account, amount) in [("bob", 20), ("alice", 10)] {
+            let mut balance = Balance::load_account(&storage, account).unwrap();
+            balance.deposit(amount).unwrap().save(&mut storage).unwrap();
+        }
+
+        // a single byte can't be deserialized as a u128
+        storage
+            .mut_repo()
+            .write(BALANCES.key("carol").as_ref(), &[1])
+            .unwrap();
+
+        assert_eq!(
+            Balance::load_accounts(&storage).unwrap(),
+            ["alice", "bob", "carol"]
+        );
+        assert!(Balance::load_all(&storage).is_err());
+
+        let values: Vec<_> = BALANCES.values(&storage).unwrap().collect();
+
+        assert!(matches!(
+            values.as_slice(),
+            [Ok(10), Ok(20), Err(kv_storage::Error::Serde(_))]
+        ));
+    }
+
+    #[test]
+    fn contents_diff_lists_every_difference() {
+        const BALANCES: Map<64, &str, u128> = map!("balances");
+
+        let mut storage = MemStore::new_in_memory();
+
+        BALANCES.save(&mut storage, "alice", 10).unwrap();
+        BALANCES.save(&mut storage, "bob", 20).unwrap();
+        BALANCES.save(&mut storage, "carol", 30).unwrap();
+
+        let diff = contents_diff(
+            &storage,
+            &BALANCES,
+            [("alice", 10), ("bob", 21), ("dave", 40)],
+        );
+
+        assert_eq!(diff.missing, ["dave => 40"]);
+        assert_eq!(diff.unexpected, ["carol => 30"]);
+        assert_eq!(diff.mismatched, ["bob: expected 21, found 20"]);
+
+        // extra entries are fine for a subset check
+        assert_contains(&storage, &BALANCES, [("alice", 10), ("carol", 30)]);
+
+        let failure = std::panic::catch_unwind(|| {
+            assert_contents_eq(&storage, &BALANCES, [("alice", 10), ("bob", 20)]);
+        })
+        .unwrap_err();
+
+        let message = failure.downcast_ref::<String>().unwrap();
+
+        assert!(message.contains("unexpected:\n  carol => 30"), "{message}");
+
+        let failure = std::panic::catch_unwind(|| {
+            assert_contains(&storage, &BALANCES, [("bob", 21)]);
+        })
+        .unwrap_err();
+
+        let message = failure.downcast_ref::<String>().unwrap();
+
+        assert!(
+            message.contains("mismatched:\n  bob: expected 21, found 20"),
+            "{message}"
+        );
+        assert!(!message.contains("unexpected"), "{message}");
+    }
+
+    #[test]
+    fn map_clear_leaves_maps_sharing_a_name_prefix() {
+        const BALANCES: Map<64, &str, u128> = map!("balances");
+        const BALANCES_V2: Map<64, &str, u128> = map!("balances_v2");
+        const BALANCE: Item<u128> = item!("balance");
+
+        let mut storage = MemStore::new_in_memory();
+
+        for account in ["alice", "bob", "carol"] {
+            BALANCES.save(&mut storage, account, 1).unwrap();
+            BALANCES_V2.save(&mut storage, account, 2).unwrap();
+        }
+
+        BALANCE.save(&mut storage, 3).unwrap();
+
+        assert_eq!(BALANCES.clear(&mut storage).unwrap(), 3);
+        assert_eq!(BALANCES.clear(&mut storage).unwrap(), 0);
+
+        assert_contents_eq(&storage, &BALANCES, Vec::<(&str, u128)>::new());
+        assert_contents_eq(
+            &storage,
+            &BALANCES_V2,
+            [("alice", 2), ("bob", 2), ("carol", 2)],
+        );
+        assert_eq!(BALANCE.may_load(&storage).unwrap(), Some(3));
+
+        assert_eq!(BALANCES_V2.clear(&mut storage).unwrap(), 3);
+        assert_eq!(storage.repo().len(), 1);
+    }
+
+    #[test]
+    fn load_reports_missing_keys() {
+        const OWNER: Item<String> = item!("owner");
+        const SCORES: Map<16, &str, u64> = map!("scores");
+
+        fn owner_len<Store: Storage>(store: &Store) -> Result<usize, LoadError<Store::Error>> {
+            // store errors convert with `?`
+            let exists = store.has_key(OWNER.key())?;
+            Ok(if exists { OWNER.load(store)?.len() } else { 0 })
+        }
+
+        let mut storage = MemStore::new_in_memory();
+
+        assert!(matches!(
+            SCORES.load(&storage, "alice"),
+            Err(LoadError::NotFound { key }) if key == SCORES.key("alice").as_ref()
+        ));
+        assert!(OWNER.load(&storage).is_err());
+        assert_eq!(owner_len(&storage).unwrap(), 0);
+
+        OWNER.save(&mut storage, "alice".to_owned()).unwrap();
+        SCORES.save(&mut storage, "alice", 3).unwrap();
+
+        assert_eq!(OWNER.load(&storage).unwrap(), "alice");
+        assert_eq!(SCORES.load(&storage, "alice").unwrap(), 3);
+        assert_eq!(owner_len(&storage).unwrap(), 5);
+
+        storage.mut_repo().write(OWNER.key(), &[1]).unwrap();
+
+        assert!(matches!(
+            OWNER.load(&storage),
+            Err(LoadError::Store(kv_storage::Error::Serde(_)))
+        ));
+    }
+
+    #[test]
+    fn item_update() {
+        const TOTAL: Item<u64> = item!("total");
+
+        #[derive(Debug, thiserror::Error)]
+        enum UpdateError {
+            #[error(transparent)]
+            Store(
+                #[from] kv_storage::Error<kv_storage_bincode::Error, kv_storage_memory::Infallible>,
+            ),
+            #[error("overflow")]
+            Overflow,
+        }
+
+        let add = |amount: u64| {
+            move |total: Option<u64>| {
+                total
+                    .unwrap_or_default()
+                    .checked_add(amount)
+                    .ok_or(UpdateError::Overflow)
+            }
+        };
+
+        let mut storage = MemStore::new_in_memory();
+
+        let mut seen = None;
+        let total = TOTAL
+            .update(&mut storage, |total| {
+                seen = Some(total);
+                add(5)(total)
+            })
+            .unwrap();
+
+        assert_eq!(seen, Some(None));
+        assert_eq!(total, 5);
+        assert_eq!(TOTAL.update(&mut storage, add(2)).unwrap(), 7);
+        assert_eq!(TOTAL.may_load(&storage).unwrap(), Some(7));
+
+        assert!(matches!(
+            TOTAL.update(&mut storage, add(u64::MAX)),
+            Err(UpdateError::Overflow)
+        ));
+        assert_eq!(TOTAL.may_load(&storage).unwrap(), Some(7));
+
+        // a failing update of a missing item doesn't create it
+        const OTHER: Item<u64> = item!("other");
+
+        assert!(OTHER
+            .update(&mut storage, |_| Err(UpdateError::Overflow))
+            .is_err());
+        assert!(OTHER.is_empty(&storage).unwrap());
+    }
+
+    #[test]
+    fn item_counters_use_checked_arithmetic() {
+        const SUPPLY: Item<u64> = item!("supply");
+        const NEXT_ID: Item<u128> = item!("next_id");
+
+        let mut storage = MemStore::new_in_memory();
+
+        // a missing counter counts as zero
+        assert!(matches!(
+            SUPPLY.decrement(&mut storage, 1),
+            Err(CounterError::Underflow)
+        ));
+        assert_eq!(SUPPLY.may_load(&storage).unwrap(), None);
+
+        assert_eq!(SUPPLY.increment(&mut storage, 10).unwrap(), 10);
+        assert_eq!(SUPPLY.decrement(&mut storage, 10).unwrap(), 0);
+        assert!(matches!(
+            SUPPLY.decrement(&mut storage, 1),
+            Err(CounterError::Underflow)
+        ));
+        assert_eq!(SUPPLY.may_load(&storage).unwrap(), Some(0));
+
+        assert_eq!(SUPPLY.increment(&mut storage, u64::MAX).unwrap(), u64::MAX);
+        assert!(matches!(
+            SUPPLY.increment(&mut storage, 1),
+            Err(CounterError::Overflow)
+        ));
+        assert_eq!(SUPPLY.may_load(&storage).unwrap(), Some(u64::MAX));
+
+        assert_eq!(NEXT_ID.next_id(&mut storage).unwrap(), 0);
+        assert_eq!(NEXT_ID.next_id(&mut storage).unwrap(), 1);
+        assert_eq!(NEXT_ID.may_load(&storage).unwrap(), Some(2));
+
+        NEXT_ID.save(&mut storage, u128::MAX).unwrap();
+
+        assert!(matches!(
+            NEXT_ID.next_id(&mut storage),
+            Err(CounterError::Overflow)
+        ));
+        assert_eq!(NEXT_ID.may_load(&storage).unwrap(), Some(u128::MAX));
+    }
+
+    #[test]
+    fn item_fallbacks_and_init() {
+        const LIMIT: Item<u32> = item!("limit");
+
+        let mut storage = MemStore::new_in_memory();
+
+        assert_eq!(LIMIT.load_or_default(&storage).unwrap(), 0);
+        assert_eq!(LIMIT.load_or(&storage, 10).unwrap(), 10);
+        assert_eq!(LIMIT.load_or_else(&storage, || 20).unwrap(), 20);
+
+        // falling back doesn't write anything
+        assert!(LIMIT.is_empty(&storage).unwrap());
+
+        assert!(LIMIT.init(&mut storage, 5).unwrap());
+        assert!(!LIMIT.init(&mut storage, 6).unwrap());
+
+        assert_eq!(LIMIT.load_or_default(&storage).unwrap(), 5);
+        assert_eq!(LIMIT.load_or(&storage, 10).unwrap(), 5);
+        assert_eq!(
+            LIMIT
+                .load_or_else(&storage, || unreachable!("the item exists"))
+                .unwrap(),
+            5
+        );
+    }
+
+    #[test]
+    fn map_entry_writes_only_when_changed() {
+        #[derive(Default)]
+        struct WriteCounting {
+            inner: MemoryRepo,
+            writes: usize,
+        }
+
+        impl Fallible for WriteCounting {
+            type Error = <MemoryRepo as Fallible>::Error;
+        }
+
+        impl Write for WriteCounting {
+            fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+                self.writes += 1;
+                self.inner.write(key, bytes)
+            }
+        }
+
+        impl Read for WriteCounting {
+            fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+                self.inner.read(key)
+            }
+        }
+
+        impl HasKey for WriteCounting {
+            fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+                self.inner.has_key(key)
+            }
+        }
+
+        impl Remove for WriteCounting {
+            fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+                self.inner.remove(key)
+            }
+        }
+
+        const BALANCES: Map<64, &str, u128> = map!("balances");
+
+        let mut storage = KvStore::<Bincode, WriteCounting>::default();
+
+        let entry = BALANCES.entry(&mut storage, "alice").unwrap();
+        assert_eq!(entry.state(), EntryState::Vacant);
+        assert_eq!(entry.and_modify(|b| *b += 1).save().unwrap(), None);
+        assert_eq!(storage.repo().writes, 0);
+
+        let entry = BALANCES.entry(&mut storage, "alice").unwrap().or_insert(10);
+        assert_eq!(entry.state(), EntryState::Inserted);
+        assert_eq!(entry.save().unwrap(), Some(10));
+        assert_eq!(storage.repo().writes, 1);
+
+        let entry = BALANCES
+            .entry(&mut storage, "alice")
+            .unwrap()
+            .or_insert_with(|| unreachable!("alice has a balance"));
+        assert_eq!(entry.state(), EntryState::Loaded);
+        assert_eq!(entry.get(), Some(&10));
+        assert_eq!(entry.save().unwrap(), Some(10));
+        assert_eq!(storage.repo().writes, 1);
+
+        let entry = BALANCES
+            .entry(&mut storage, "alice")
+            .unwrap()
+            .and_modify(|b| *b += 5)
+            .or_insert(0);
+        assert_eq!(entry.state(), EntryState::Modified);
+        assert_eq!(entry.key(), BALANCES.key("alice").as_ref());
+        assert_eq!(entry.save().unwrap(), Some(15));
+        assert_eq!(storage.repo().writes, 2);
+
+        assert_eq!(BALANCES.may_load(&storage, "alice").unwrap(), Some(15));
+    }
+
+    #[test]
+    fn write_batch_applies_in_order() {
+        let mut repo = MemoryRepo::default();
+
+        repo.write(b"a", b"old").unwrap();
+        repo.write(b"b", b"old").unwrap();
+
+        repo.write_batch(&[
+            (b"a".into(), None),
+            (b"c".into(), Some(b"1".into())),
+            (b"b".into(), Some(b"new".into())),
+            (b"c".into(), None),
+            (b"a".into(), Some(b"again".into())),
+        ])
+        .unwrap();
+
+        let entries: Vec<_> = repo.into_iter().collect();
+
+        assert_eq!(
+            entries,
+            [
+                (b"a".to_vec(), b"again".to_vec()),
+                (b"b".to_vec(), b"new".to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn save_batch_mixes_types_and_fails_whole() {
+        const TOTAL: Item<u128> = item!("total");
+        const OWNER: Item<String> = item!("owner");
+        const BALANCES: Map<64, &str, u128> = map!("balances");
+
+        let mut storage = MemStore::new_in_memory();
+
+        let owner = "alice".to_owned();
+
+        storage
+            .save_batch(&[
+                (TOTAL.key(), &100u128),
+                (OWNER.key(), &owner),
+                (BALANCES.key("alice").as_ref(), &100u128),
+            ])
+            .unwrap();
+
+        assert_eq!(TOTAL.may_load(&storage).unwrap(), Some(100));
+        assert_eq!(OWNER.may_load(&storage).unwrap(), Some(owner));
+        assert_eq!(BALANCES.may_load(&storage, "alice").unwrap(), Some(100));
+
+        // fail serializing the second item
+        storage.set_post_serialize_hook(|bytes| {
+            if bytes.len() == 16 {
+                Ok(())
+            } else {
+                Err(InjectedError)
+            }
+        });
+
+        let result = storage.save_batch(&[(TOTAL.key(), &200u128), (OWNER.key(), &"bob")]);
+
+        assert!(matches!(result, Err(kv_storage::Error::Injected(_))));
+        assert_eq!(TOTAL.may_load(&storage).unwrap(), Some(100));
+    }
+
+    #[test]
+    fn memory_checkpoints_restore_exactly() {
+        const TOTAL: Item<u64> = item!("total");
+        const NAMES: Map<16, u32, String> = map!("names");
+
+        let mut storage = MemStore::new_in_memory();
+
+        TOTAL.save(&mut storage, 1).unwrap();
+        NAMES.save(&mut storage, 1, "alice".to_owned()).unwrap();
+
+        let first = storage.repo().checkpoint();
+
+        TOTAL.save(&mut storage, 2).unwrap();
+        NAMES.remove(&mut storage, 1).unwrap();
+        NAMES.save(&mut storage, 2, "bob".to_owned()).unwrap();
+
+        let second = storage.repo().checkpoint();
+
+        TOTAL.clear(&mut storage).unwrap();
+        NAMES.save(&mut storage, 3, "carol".to_owned()).unwrap();
+
+        let alice = || [(1, "alice".to_owned())];
+
+        // restoring the outer checkpoint first, then the inner one, is fine too
+        storage.mut_repo().restore(first.clone());
+
+        assert_eq!(TOTAL.may_load(&storage).unwrap(), Some(1));
+        assert_contents_eq(&storage, &NAMES, alice());
+
+        storage.mut_repo().restore(second);
+
+        assert_eq!(TOTAL.may_load(&storage).unwrap(), Some(2));
+        assert_contents_eq(&storage, &NAMES, [(2, "bob".to_owned())]);
+
+        storage.mut_repo().restore(first);
+
+        assert_eq!(TOTAL.may_load(&storage).unwrap(), Some(1));
+        assert_contents_eq(&storage, &NAMES, alice());
+        assert_eq!(storage.repo().len(), 2);
+    }
+
+    #[test]
+    fn memory_dump_round_trips() {
+        let entries: [(&[u8], Vec<u8>); 4] = [
+            (b"", b"empty key".to_vec()),
+            (b"empty value", Vec::new()),
+            (b"\0/..\xff\0", vec![0; 3]),
+            (
+                b"large",
+                (0..1 << 20).map(|i: u32| i.to_le_bytes()[1]).collect(),
+            ),
+        ];
+
+        let mut repo = MemoryRepo::default();
+
+        for (key, value) in &entries {
+            kv_storage::Write::write(&mut repo, key, value).unwrap();
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("repo.dump");
+
+        repo.dump_to_path(&path).unwrap();
+        let loaded = MemoryRepo::load_from_path(&path).unwrap();
+
+        assert_eq!(loaded.len(), entries.len());
+
+        for (key, value) in &entries {
+            assert_eq!(
+                kv_storage::Read::read(&loaded, key).unwrap().as_ref(),
+                Some(value)
+            );
+        }
+    }
+
+    #[test]
+    fn memory_load_rejects_other_files() {
+        let mut dump = Vec::new();
+        MemoryRepo::default().dump_to(&mut dump).unwrap();
+
+        let mut wrong_magic = dump.clone();
+        wrong_magic[0] = b'X';
+
+        let mut wrong_version = dump.clone();
+        wrong_version[4] += 1;
+
+        for bytes in [wrong_magic, wrong_version] {
+            let error = MemoryRepo::load_from(bytes.as_slice()).err().unwrap();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        }
+
+        assert!(MemoryRepo::load_from(dump.as_slice()).unwrap().is_empty());
+
+        // a dump cut short fails rather than loading what's there
+        let mut repo = MemoryRepo::default();
+        kv_storage::Write::write(&mut repo, b"key", b"value").unwrap();
+
+        let mut dump = Vec::new();
+        repo.dump_to(&mut dump).unwrap();
+        dump.pop();
+
+        let error = MemoryRepo::load_from(dump.as_slice()).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn transaction_commits_or_discards_staged_writes() {
+        let mut storage = MemStore::new_in_memory();
+
+        let mut alice = Balance::load_account(&storage, "alice").unwrap();
+        alice.deposit(100).unwrap().save(&mut storage).unwrap();
+
+        let mut tx = storage.transaction();
+
+        let mut alice = Balance::load_account(&tx, "alice").unwrap();
+        alice.deposit(50).unwrap().save(&mut tx).unwrap();
+
+        let mut bob = Balance::load_account(&tx, "bob").unwrap();
+        bob.deposit(25).unwrap().save(&mut tx).unwrap();
+
+        // reads see the staged writes
+        assert_eq!(Balance::load_account(&tx, "alice").unwrap().balance(), 150);
+        assert_eq!(Balance::load_total(&tx).unwrap(), 175);
+        assert_eq!(tx.pending(), 3);
+
+        // dropping the transaction discards them
+        drop(tx);
+
+        assert_eq!(
+            Balance::load_account(&storage, "alice").unwrap().balance(),
+            100
+        );
+        assert!(!Balance::account_exists(&storage, "bob").unwrap());
+        assert_eq!(Balance::load_total(&storage).unwrap(), 100);
+
+        let mut tx = storage.transaction();
+
+        let mut alice = Balance::load_account(&tx, "alice").unwrap();
+        alice.withdraw(40).unwrap().save(&mut tx).unwrap();
+
+        let mut bob = Balance::load_account(&tx, "bob").unwrap();
+        bob.deposit(40).unwrap().save(&mut tx).unwrap();
+
+        tx.commit().unwrap();
+
+        assert_eq!(
+            Balance::load_account(&storage, "alice").unwrap().balance(),
+            60
+        );
+        assert_eq!(
+            Balance::load_account(&storage, "bob").unwrap().balance(),
+            40
+        );
+        assert_eq!(Balance::load_total(&storage).unwrap(), 100);
+    }
+
+    #[test]
+    fn transaction_stages_durable_saves() {
+        const BALANCE: Item<u128> = item!("durable_balance");
+
+        let mut storage = MemStore::new_in_memory();
+
+        let mut tx = storage.transaction();
+        BALANCE.save_with(&mut tx, 100, Durability::Sync).unwrap();
+        assert_eq!(BALANCE.may_load(&tx).unwrap(), Some(100));
+        drop(tx);
+
+        assert_eq!(BALANCE.may_load(&storage).unwrap(), None);
+
+        let mut tx = storage.transaction();
+        BALANCE.save_with(&mut tx, 100, Durability::Sync).unwrap();
+        tx.commit().unwrap();
+
+        assert_eq!(BALANCE.may_load(&storage).unwrap(), Some(100));
+    }
+
+    #[test]
+    fn transaction_stages_removes() {
+        const NAMES: Map<16, u32, String> = map!("names");
+
+        let mut storage = MemStore::new_in_memory();
+
+        NAMES.save(&mut storage, 1, "alice".to_owned()).unwrap();
+
+        let mut tx = storage.transaction();
+
+        assert_eq!(
+            NAMES.remove_returning(&mut tx, 1).unwrap(),
+            Removed::Existed
+        );
+        assert_eq!(
+            NAMES.remove_returning(&mut tx, 1).unwrap(),
+            Removed::DidNotExist
+        );
+        assert!(!NAMES.has_key(&tx, 1).unwrap());
+
+        NAMES.save(&mut tx, 2, "bob".to_owned()).unwrap();
+        NAMES.remove(&mut tx, 2).unwrap();
+
+        tx.commit().unwrap();
+
+        assert!(storage.repo().is_empty());
+    }
+
+    #[test]
+    fn nested_transactions_do_not_compile() {
+        trybuild::TestCases::new().compile_fail("ui/nested_transaction.rs");
+    }
+
+    #[test]
+    fn map_load_many_keeps_order_and_missing_keys() {
+        const BALANCES: Map<64, &str, u128> = map!("balances");
+        const PAIRS: Map<64, (&str, u32), String> = map!("pairs");
+
+        let mut storage = MemStore::new_in_memory();
+
+        BALANCES.save(&mut storage, "alice", 100).unwrap();
+        BALANCES.save(&mut storage, "carol", 300).unwrap();
+        PAIRS
+            .save(&mut storage, ("alice", 2), "b".to_owned())
+            .unwrap();
+
+        let balances = BALANCES
+            .load_many(&storage, ["dave", "carol", "bob", "alice", "carol"])
+            .unwrap();
+
+        assert_eq!(
+            balances,
+            [
+                ("dave", None),
+                ("carol", Some(300)),
+                ("bob", None),
+                ("alice", Some(100)),
+                ("carol", Some(300)),
+            ]
+        );
+
+        let pairs = PAIRS
+            .load_many(&storage, [("alice", 1), ("alice", 2)])
+            .unwrap();
+
+        assert_eq!(
+            pairs,
+            [(("alice", 1), None), (("alice", 2), Some("b".to_owned()))]
+        );
+
+        assert!(BALANCES
+            .load_many(&storage, Vec::<&str>::new())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn map_load_many_uses_native_multi_get() {
+        use std::cell::Cell;
+
+        struct MultiGet {
+            inner: MemoryRepo,
+            reads: Cell<usize>,
+        }
+
+        impl Fallible for MultiGet {
+            type Error = <MemoryRepo as Fallible>::Error;
+        }
+
+        impl Read for MultiGet {
+            fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+                self.reads.set(self.reads.get() + 1);
+                self.inner.read(key)
+            }
+
+            fn read_many(&self, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>, Self::Error> {
+                self.reads.set(self.reads.get() + 1);
+                self.inner.read_many(keys)
+            }
+        }
+
+        impl HasKey for MultiGet {
+            fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+                self.inner.has_key(key)
+            }
+        }
+
+        const BALANCES: Map<64, &str, u128> = map!("balances");
+
+        let mut seed = MemStore::new_in_memory();
+        BALANCES.save(&mut seed, "alice", 100).unwrap();
+
+        let storage = KvStore::new(
+            Bincode::new(),
+            MultiGet {
+                inner: seed.into_repo(),
+                reads: Cell::new(0),
+            },
+        );
+
+        let balances = BALANCES
+            .load_many(&storage, ["alice", "bob", "carol"])
+            .unwrap();
+
+        assert_eq!(
+            balances,
+            [("alice", Some(100)), ("bob", None), ("carol", None)]
+        );
+        assert_eq!(storage.repo().reads.get(), 1);
+    }
+
+    #[test]
+    fn join_batches_right_side_lookups() {
+        const ORDERS: Map<64, (&str, u32), u32> = map!("orders");
+        const PRODUCTS: Map<16, u32, String> = map!("products");
+
+        let mut storage = KvStore::new(Bincode::new(), RecordingRepo::new(MemoryRepo::default()));
+
+        for id in 0..150 {
+            // every third order references a product that doesn't exist
+            ORDERS.save(&mut storage, ("alice", id), id % 50).unwrap();
+            ORDERS.save(&mut storage, ("bob", id), 0).unwrap();
+        }
+
+        for product in (0..50).filter(|product| product % 3 != 0) {
+            PRODUCTS
+                .save(&mut storage, product, format!("product {product}"))
+                .unwrap();
+        }
+
+        storage.repo().reset_read_counts();
+
+        let orders = ORDERS
+            .sub_prefix(("alice",))
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(Result::unwrap);
+
+        let joined: Vec<_> = kv_storage::join(&storage, orders, |product| *product, &PRODUCTS)
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(joined.len(), 150);
+
+        for (id, (order, product, name)) in joined.into_iter().enumerate() {
+            assert_eq!(order as usize, id);
+            assert_eq!(product, order % 50);
+            assert_eq!(
+                name,
+                (product % 3 != 0).then(|| format!("product {product}"))
+            );
+        }
+
+        // batches of 64, 64 and 22 keys rather than a read per order
+        let counts = storage.repo().read_counts();
+        assert_eq!(counts.read_manys, 3);
+        assert_eq!(counts.keys_read_many, 150);
+        assert_eq!(counts.reads, 0);
+
+        // an empty left side doesn't touch the store
+        storage.repo().reset_read_counts();
+
+        let none = kv_storage::join(
+            &storage,
+            Vec::<(u32, u32)>::new(),
+            |product| *product,
+            &PRODUCTS,
+        );
+
+        assert_eq!(none.count(), 0);
+        assert_eq!(storage.repo().read_counts().read_manys, 0);
+    }
+
+    #[test]
+    fn deque_pushes_and_pops_at_both_ends() {
+        const JOBS: Deque<u32> = deque!("jobs");
+
+        let mut storage = MemStore::new_in_memory();
+
+        assert!(JOBS.is_empty(&storage).unwrap());
+        assert_eq!(JOBS.pop_front(&mut storage).unwrap(), None);
+        assert_eq!(JOBS.pop_back(&mut storage).unwrap(), None);
+        assert_eq!(JOBS.front(&storage).unwrap(), None);
+
+        // the head wraps below zero straight away
+        JOBS.push_front(&mut storage, 2).unwrap();
+        JOBS.push_front(&mut storage, 1).unwrap();
+        JOBS.push_back(&mut storage, 3).unwrap();
+        JOBS.push_back(&mut storage, 4).unwrap();
+
+        assert_eq!(JOBS.len(&storage).unwrap(), 4);
+        assert_eq!(JOBS.front(&storage).unwrap(), Some(1));
+        assert_eq!(JOBS.back(&storage).unwrap(), Some(4));
+
+        // four values plus the head and tail counters
+        assert_eq!(storage.repo().len(), 6);
+
+        assert_eq!(JOBS.pop_back(&mut storage).unwrap(), Some(4));
+        assert_eq!(JOBS.pop_front(&mut storage).unwrap(), Some(1));
+        assert_eq!(JOBS.pop_front(&mut storage).unwrap(), Some(2));
+        assert_eq!(JOBS.pop_front(&mut storage).unwrap(), Some(3));
+        assert_eq!(JOBS.pop_front(&mut storage).unwrap(), None);
+        assert_eq!(JOBS.pop_back(&mut storage).unwrap(), None);
+
+        assert!(JOBS.is_empty(&storage).unwrap());
+        assert_eq!(storage.repo().len(), 2);
+
+        JOBS.push_back(&mut storage, 5).unwrap();
+
+        assert_eq!(JOBS.front(&storage).unwrap(), Some(5));
+        assert_eq!(JOBS.back(&storage).unwrap(), Some(5));
+    }
+
+    #[test]
+    fn deque_persists_across_stores() {
+        const JOBS: Deque<String> = deque!("jobs");
+        const OTHER: Deque<String> = deque!("jobs_other");
+
+        let mut storage = MemStore::new_in_memory();
+
+        for job in ["a", "b", "c"] {
+            JOBS.push_back(&mut storage, job.to_owned()).unwrap();
+        }
+        OTHER.push_back(&mut storage, "x".to_owned()).unwrap();
+
+        let mut storage = MemStore::from_repo(storage.into_repo());
+
+        assert_eq!(JOBS.len(&storage).unwrap(), 3);
+        assert_eq!(OTHER.len(&storage).unwrap(), 1);
+        assert_eq!(JOBS.pop_front(&mut storage).unwrap().as_deref(), Some("a"));
+
+        let mut storage = MemStore::from_repo(storage.into_repo());
+
+        assert_eq!(JOBS.pop_back(&mut storage).unwrap().as_deref(), Some("c"));
+        assert_eq!(JOBS.front(&storage).unwrap().as_deref(), Some("b"));
+        assert_eq!(JOBS.len(&storage).unwrap(), 1);
+        assert_eq!(OTHER.front(&storage).unwrap().as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn reserved_keys_are_rejected() {
+        const HEADER_ITEM: &[u8] = system::MAP_HEADER;
+
+        assert!(matches!(
+            Item::<u64>::try_new(HEADER_ITEM),
+            Err(ReservedKey { key }) if key == system::MAP_HEADER
+        ));
+        assert!(matches!(
+            Map::<16, u32, u64>::try_new(system::NAMESPACE),
+            Err(ReservedKey { .. })
+        ));
+
+        // a user namespace named like the crate's lands in the reserved space
+        let declared = std::panic::catch_unwind(|| {
+            Item::<u64>::new(kv_storage::namespaced_key!("kv_storage", "counter"))
+        });
+        assert!(declared.is_err());
+
+        assert!(Item::<u64>::try_new(b"kv_storage").is_ok());
+        assert!(Map::<16, u32, u64>::try_new(b"config").is_ok());
+    }
+
+    #[test]
+    fn reserved_index_prefixes_are_rejected() {
+        fn owner(value: &(u32, u64)) -> u32 {
+            value.0
+        }
+
+        assert!(matches!(
+            MultiIndex::<16, u32, u64, (u32, u64)>::try_new(owner, b"loans", system::CHANGELOG),
+            Err(ReservedKey { key }) if key == system::CHANGELOG
+        ));
+        assert!(matches!(
+            UniqueIndex::<16, u32, (u32, u64)>::try_new(owner, system::MAP_HEADER, b"by_owner"),
+            Err(ReservedKey { key }) if key == system::MAP_HEADER
+        ));
+
+        let declared = std::panic::catch_unwind(|| {
+            UniqueIndex::<16, u32, (u32, u64)>::new(owner, b"loans", system::NAMESPACE)
+        });
+        assert!(declared.is_err());
+
+        assert!(
+            MultiIndex::<16, u32, u64, (u32, u64)>::try_new(owner, b"loans", b"by_owner").is_ok()
+        );
+        assert!(UniqueIndex::<16, u32, (u32, u64)>::try_new(owner, b"loans", b"by_owner").is_ok());
+    }
+
+    #[test]
+    fn system_records_validate() {
+        const JOBS: Deque<u32> = deque!("jobs");
+        const CART: HeaderedMap<32, u32, u32> = map!("cart").with_header();
+        const MEMBERS: BoundedMap<16, u32, u32> = BoundedMap::new(map!("members"), 4);
+        const BALANCES: SnapshotMap<64, &str, u128> =
+            SnapshotMap::new(map!("balances"), b"balances_changelog");
+
+        let mut storage = MemStore::new_in_memory();
+
+        JOBS.push_back(&mut storage, 1).unwrap();
+        JOBS.push_front(&mut storage, 0).unwrap();
+        JOBS.pop_back(&mut storage).unwrap();
+        CART.save(&mut storage, 1, 2).unwrap();
+        MEMBERS.save(&mut storage, 1, 2).unwrap();
+        BALANCES.save(&mut storage, "alice", 100, 10).unwrap();
+        BALANCES.remove(&mut storage, "alice", 11).unwrap();
+
+        system::validate_system_keys(&storage).unwrap();
+
+        // every bookkeeping record lives in the reserved namespace
+        let reserved: Vec<_> = storage.scan_keys(system::NAMESPACE).unwrap().collect();
+        assert_eq!(reserved.len(), 6);
+        assert!(reserved.iter().all(|key| system::is_reserved(key)));
+
+        let user_keys = storage.repo().len() - reserved.len();
+        assert_eq!(user_keys, 3);
+    }
+
+    #[test]
+    fn corrupt_system_records_are_reported() {
+        const JOBS: Deque<u32> = deque!("jobs");
+        const MEMBERS: BoundedMap<16, u32, u32> = BoundedMap::new(map!("members"), 4);
+
+        let mut storage = MemStore::new_in_memory();
+
+        JOBS.push_back(&mut storage, 1).unwrap();
+        JOBS.push_back(&mut storage, 2).unwrap();
+
+        // the deque's tail claims a value that isn't there
+        let tail = storage
+            .scan_keys(system::DEQUE_BOUNDS)
+            .unwrap()
+            .next()
+            .unwrap();
+        storage.save(&tail, &3u64).unwrap();
+
+        assert!(matches!(
+            system::validate_system_keys(&storage),
+            Err(SystemKeyError::Corrupt { reason, .. }) if reason.contains("deque")
+        ));
+
+        let mut storage = MemStore::new_in_memory();
+
+        MEMBERS.save(&mut storage, 1, 2).unwrap();
+        storage
+            .mut_repo()
+            .write(MEMBERS.count_key().as_ref(), b"x")
+            .unwrap();
+
+        assert!(matches!(
+            system::validate_system_keys(&storage),
+            Err(SystemKeyError::Unreadable { key, .. }) if key == MEMBERS.count_key().as_ref()
+        ));
+
+        let mut storage = MemStore::new_in_memory();
+
+        let unknown = [system::NAMESPACE, b"\0\x07unknown"].concat();
+        storage.save(&unknown, &1u64).unwrap();
+
+        assert!(matches!(
+            system::validate_system_keys(&storage),
+            Err(SystemKeyError::Corrupt { key, .. }) if key == unknown
+        ));
+    }
+
+    #[test]
+    fn set_reports_membership_changes() {
+        const NONCES: Set<16, (&str, u64)> = set!("nonces");
+
+        let mut storage = MemStore::new_in_memory();
+
+        assert!(NONCES.insert(&mut storage, ("alice", 1)).unwrap());
+        assert!(NONCES.insert(&mut storage, ("alice", 2)).unwrap());
+        assert!(!NONCES.insert(&mut storage, ("alice", 1)).unwrap());
+        assert!(NONCES.insert(&mut storage, ("bob", 1)).unwrap());
+
+        assert!(NONCES.contains(&storage, ("alice", 2)).unwrap());
+        assert!(!NONCES.contains(&storage, ("bob", 2)).unwrap());
+
+        assert!(NONCES.remove(&mut storage, ("alice", 2)).unwrap());
+        assert!(!NONCES.remove(&mut storage, ("alice", 2)).unwrap());
+        assert!(!NONCES.remove(&mut storage, ("carol", 1)).unwrap());
+
+        let mut members: Vec<(String, u64)> =
+            NONCES.iter(&storage).unwrap().map(Result::unwrap).collect();
+
+        // tuple keys order by their length-prefixed encoding, not lexically
+        members.sort();
+
+        assert_eq!(members, [("alice".to_owned(), 1), ("bob".to_owned(), 1)]);
+
+        // members are stored with empty values
+        let key = NONCES.key(("bob", 1));
+        assert_eq!(storage.repo().read(key.as_ref()).unwrap(), Some(vec![]));
+    }
+
+    #[test]
+    fn indexed_map_drops_stale_index_entries() {
+        #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Account {
+            owner: String,
+            tier: u8,
+        }
+
+        const ACCOUNTS: Map<64, u64, Account> = map!("accounts");
+
+        type ByOwner = MultiIndex<64, String, u64, Account>;
+        type ByTier = MultiIndex<64, u8, u64, Account>;
+
+        const BY_OWNER: ByOwner = MultiIndex::new(
+            |account| account.owner.clone(),
+            ACCOUNTS.prefix(),
+            b"by_owner",
+        );
+        const BY_TIER: ByTier =
+            MultiIndex::new(|account| account.tier, ACCOUNTS.prefix(), b"by_tier");
+
+        const INDEXED: IndexedMap<64, u64, Account, (ByOwner, ByTier)> =
+            IndexedMap::new(ACCOUNTS, (BY_OWNER, BY_TIER));
+
+        let account = |owner: &str, tier| Account {
+            owner: owner.to_owned(),
+            tier,
+        };
+
+        let owned_by = |storage: &MemStore, owner: &str| -> Vec<u64> {
+            BY_OWNER
+                .keys(storage, &owner.to_owned())
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+
+        let in_tier = |storage: &MemStore, tier: u8| -> Vec<(u64, Account)> {
+            BY_TIER
+                .items(storage, &tier)
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+
+        let mut storage = MemStore::new_in_memory();
+
+        INDEXED.save(&mut storage, 1, account("alice", 1)).unwrap();
+        INDEXED.save(&mut storage, 2, account("bob", 1)).unwrap();
+        INDEXED.save(&mut storage, 3, account("alice", 2)).unwrap();
+
+        assert_eq!(owned_by(&storage, "alice"), [1, 3]);
+        assert_eq!(owned_by(&storage, "bob"), [2]);
+        assert_eq!(
+            in_tier(&storage, 1),
+            [(1, account("alice", 1)), (2, account("bob", 1))]
+        );
+
+        // moving an account to another owner and tier leaves nothing under the old ones
+        INDEXED.save(&mut storage, 1, account("bob", 2)).unwrap();
+
+        assert_eq!(owned_by(&storage, "alice"), [3]);
+        assert_eq!(owned_by(&storage, "bob"), [1, 2]);
+        assert_eq!(in_tier(&storage, 1), [(2, account("bob", 1))]);
+        assert_eq!(
+            in_tier(&storage, 2),
+            [(1, account("bob", 2)), (3, account("alice", 2))]
+        );
+
+        // saving an unchanged value keeps its entries
+        INDEXED.save(&mut storage, 2, account("bob", 1)).unwrap();
+        assert_eq!(owned_by(&storage, "bob"), [1, 2]);
+
+        // three entries, each with an owner and a tier index entry
+        assert_eq!(storage.repo().len(), 9);
+
+        assert_eq!(INDEXED.remove(&mut storage, 3).unwrap(), Removed::Existed);
+        assert_eq!(
+            INDEXED.remove(&mut storage, 3).unwrap(),
+            Removed::DidNotExist
+        );
+
+        assert!(owned_by(&storage, "alice").is_empty());
+        assert_eq!(in_tier(&storage, 2), [(1, account("bob", 2))]);
+        assert_eq!(INDEXED.may_load(&storage, 3).unwrap(), None);
+        assert_eq!(storage.repo().len(), 6);
+    }
+
+    #[test]
+    fn unique_index_rejects_duplicates_and_moves_on_rename() {
+        #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Account {
+            email: String,
+            balance: u64,
+        }
+
+        const ACCOUNTS: Map<16, u64, Account> = map!("accounts");
+
+        type ByEmail = UniqueIndex<64, String, Account>;
+
+        const BY_EMAIL: ByEmail = UniqueIndex::new(
+            |account| account.email.clone(),
+            ACCOUNTS.prefix(),
+            b"by_email",
+        );
+
+        const INDEXED: IndexedMap<16, u64, Account, (ByEmail,)> =
+            IndexedMap::new(ACCOUNTS, (BY_EMAIL,));
+
+        let account = |email: &str, balance| Account {
+            email: email.to_owned(),
+            balance,
+        };
+
+        let by_email = |storage: &MemStore, email: &str| {
+            BY_EMAIL.load_by_unique(storage, &email.to_owned()).unwrap()
+        };
+
+        let mut storage = MemStore::new_in_memory();
+
+        INDEXED.save(&mut storage, 1, account("a@x", 10)).unwrap();
+        INDEXED.save(&mut storage, 2, account("b@x", 20)).unwrap();
+
+        // the same entry may keep its email
+        INDEXED.save(&mut storage, 1, account("a@x", 15)).unwrap();
+        assert_eq!(by_email(&storage, "a@x"), Some(account("a@x", 15)));
+
+        // another entry may not take it, and nothing is written
+        let Err(IndexError::Duplicate {
+            index_key,
+            existing_pk,
+        }) = INDEXED.save(&mut storage, 2, account("a@x", 25))
+        else {
+            panic!("a@x belongs to account 1");
+        };
+
+        assert_eq!(index_key, b"a@x");
+        assert_eq!(existing_pk, 1u64.to_be_bytes());
+        assert_eq!(
+            INDEXED.may_load(&storage, 2).unwrap(),
+            Some(account("b@x", 20))
+        );
+        assert!(matches!(
+            INDEXED.save(&mut storage, 3, account("b@x", 0)),
+            Err(IndexError::Duplicate { .. })
+        ));
+        assert_eq!(INDEXED.may_load(&storage, 3).unwrap(), None);
+
+        // renaming moves the index entry, freeing the old email
+        INDEXED.save(&mut storage, 1, account("c@x", 15)).unwrap();
+
+        assert_eq!(by_email(&storage, "a@x"), None);
+        assert_eq!(by_email(&storage, "c@x"), Some(account("c@x", 15)));
+
+        INDEXED.save(&mut storage, 3, account("a@x", 30)).unwrap();
+        assert_eq!(by_email(&storage, "a@x"), Some(account("a@x", 30)));
+
+        assert_eq!(INDEXED.remove(&mut storage, 2).unwrap(), Removed::Existed);
+        assert_eq!(by_email(&storage, "b@x"), None);
+        assert_eq!(BY_EMAIL.load_pk(&storage, &"b@x".to_owned()).unwrap(), None);
+
+        // three entries left, each with an index entry
+        assert_eq!(storage.repo().len(), 4);
+    }
+
+    #[test]
+    fn snapshot_map_loads_values_at_past_heights() {
+        const BALANCES: SnapshotMap<64, &str, u128> =
+            SnapshotMap::new(map!("balances"), b"balances_changelog");
+
+        let mut storage = MemStore::new_in_memory();
+
+        let at = |storage: &MemStore, account, height| {
+            BALANCES
+                .may_load_at_height(storage, account, height)
+                .unwrap()
+        };
+
+        BALANCES.save(&mut storage, "alice", 100, 10).unwrap();
+        BALANCES.save(&mut storage, "bob", 7, 12).unwrap();
+        BALANCES.save(&mut storage, "alice", 150, 20).unwrap();
+        // the last change at a height wins
+        BALANCES.save(&mut storage, "alice", 200, 20).unwrap();
+        BALANCES.save(&mut storage, "alice", 300, 300).unwrap();
+
+        // before the first write
+        assert_eq!(at(&storage, "alice", 0), None);
+        assert_eq!(at(&storage, "alice", 9), None);
+
+        // at and between writes, heights compare numerically across byte boundaries
+        assert_eq!(at(&storage, "alice", 10), Some(100));
+        assert_eq!(at(&storage, "alice", 19), Some(100));
+        assert_eq!(at(&storage, "alice", 20), Some(200));
+        assert_eq!(at(&storage, "alice", 255), Some(200));
+        assert_eq!(at(&storage, "alice", 256), Some(200));
+        assert_eq!(at(&storage, "alice", 300), Some(300));
+        assert_eq!(at(&storage, "alice", u64::MAX), Some(300));
+
+        // other keys' changelogs don't leak in
+        assert_eq!(at(&storage, "bob", 11), None);
+        assert_eq!(at(&storage, "bob", 400), Some(7));
+        assert_eq!(at(&storage, "carol", 400), None);
+
+        assert_eq!(
+            BALANCES.remove(&mut storage, "alice", 400).unwrap(),
+            Removed::Existed
+        );
+
+        // after a remove
+        assert_eq!(BALANCES.may_load(&storage, "alice").unwrap(), None);
+        assert_eq!(at(&storage, "alice", 399), Some(300));
+        assert_eq!(at(&storage, "alice", 400), None);
+        assert_eq!(at(&storage, "alice", 500), None);
+
+        BALANCES.save(&mut storage, "alice", 1, 500).unwrap();
+
+        assert_eq!(at(&storage, "alice", 450), None);
+        assert_eq!(at(&storage, "alice", 500), Some(1));
+    }
+
+    #[test]
+    fn derive_storage_key() {
+        let cases = trybuild::TestCases::new();
+        cases.pass("ui/derive_storage_key.rs");
+        cases.compile_fail("ui/derive_storage_key_missing.rs");
+    }
+
+    #[test]
+    fn map_sub_prefix_scopes_to_leading_parts() {
+        const ORDERS: Map<64, (&str, u64), u128> = map!("orders");
+        const FILLS: Map<64, (&str, u32, u64), u128> = map!("fills");
+
+        let mut storage = MemStore::new_in_memory();
+
+        // "al" is a byte prefix of "alice", but not a key prefix of her orders
+        for (owner, id, amount) in [
+            ("alice", 1, 10),
+            ("alice", 2, 20),
+            ("al", 1, 5),
+            ("bob", 3, 30),
+        ] {
+            ORDERS.save(&mut storage, (owner, id), amount).unwrap();
+        }
+
+        let alice = ORDERS.sub_prefix(("alice",));
+        let al = ORDERS.sub_prefix(("al",));
+
+        assert_eq!(alice.may_load(&storage, 1).unwrap(), Some(10));
+        assert_eq!(alice.may_load(&storage, 3).unwrap(), None);
+        assert_eq!(al.may_load(&storage, 1).unwrap(), Some(5));
+
+        assert_eq!(alice.count(&storage).unwrap(), 2);
+        assert_eq!(al.count(&storage).unwrap(), 1);
+
+        let orders: Vec<_> = alice
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Descending,
+            )
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(orders, [(2, 20), (1, 10)]);
+
+        let orders: Vec<_> = alice
+            .range(
+                &storage,
+                Bound::Exclusive(1),
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(orders, [(2, 20)]);
+
+        assert_eq!(al.clear(&mut storage).unwrap(), 1);
+        assert_eq!(alice.count(&storage).unwrap(), 2);
+        assert_eq!(ORDERS.may_load(&storage, ("bob", 3)).unwrap(), Some(30));
+
+        // leading parts of a wider key, with the rest as a tuple or a single part
+        FILLS.save(&mut storage, ("alice", 1, 7), 70).unwrap();
+        FILLS.save(&mut storage, ("alice", 2, 8), 80).unwrap();
+
+        let fills: Vec<_> = FILLS
+            .sub_prefix(("alice",))
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(fills, [((1, 7), 70), ((2, 8), 80)]);
+
+        assert_eq!(
+            FILLS
+                .sub_prefix(("alice", 2))
+                .may_load(&storage, 8)
+                .unwrap(),
+            Some(80)
+        );
+    }
+
+    #[test]
+    fn map_sub_prefix_rejects_non_prefixes() {
+        trybuild::TestCases::new().compile_fail("ui/invalid_key_prefix.rs");
+    }
+
+    #[test]
+    fn keys_longer_than_the_stack_buffer_round_trip() {
+        const NOTES: Map<8, (String, u32), String> = map!("notes");
+        const SHORT: DefaultMap<u32, String> = map!("short");
+
+        let mut storage = MemStore::new_in_memory();
+
+        let owner = "a".repeat(300);
+        let key = (owner.clone(), 7);
+
+        assert!(NOTES.key(&key).as_ref().len() > 8);
+
+        NOTES.save(&mut storage, &key, "long".to_owned()).unwrap();
+        SHORT.save(&mut storage, 7, "short".to_owned()).unwrap();
+
+        assert_eq!(
+            NOTES.may_load(&storage, &key).unwrap().as_deref(),
+            Some("long")
+        );
+        assert_eq!(
+            SHORT.may_load(&storage, 7).unwrap().as_deref(),
+            Some("short")
+        );
+
+        let keys: Vec<_> = NOTES.keys(&storage).unwrap().map(Result::unwrap).collect();
+        assert_eq!(keys, [(owner, 7)]);
+    }
+
+    #[test]
+    fn macros_accept_a_fixed_namespace() {
+        mod v1 {
+            use kv_storage::prelude::*;
+
+            pub const TOTAL: Item<u64> = item!("total", ns = "bank");
+            pub const BALANCES: Map<64, &str, u128> = map!("balances", ns = "bank");
+        }
+
+        mod v2 {
+            use kv_storage::prelude::*;
+
+            pub const TOTAL: Item<u64> = item!("total", ns = "bank");
+            pub const BALANCES: Map<64, &str, u128> = map!("balances", ns = "bank");
+        }
+
+        assert_eq!(v1::TOTAL.key(), b"\0\x04bank\0\x05total");
+        assert_eq!(v1::BALANCES.prefix(), b"\0\x04bank\0\x08balances");
+
+        // moving the declarations keeps the data reachable
+        let mut storage = MemStore::new_in_memory();
+
+        v1::TOTAL.save(&mut storage, 10).unwrap();
+        v1::BALANCES.save(&mut storage, "alice", 5).unwrap();
+
+        assert_eq!(v2::TOTAL.may_load(&storage).unwrap(), Some(10));
+        assert_eq!(v2::BALANCES.may_load(&storage, "alice").unwrap(), Some(5));
+
+        const SEEN: Set<64, &str> = set!("seen", ns = "bank");
+        const QUEUE: Deque<u64> = deque!("queue", ns = "bank");
+
+        assert_eq!(SEEN.prefix(), b"\0\x04bank\0\x04seen");
+        assert_eq!(QUEUE.prefix(), b"\0\x04bank\0\x05queue");
+    }
+
+    #[test]
+    fn macros_accept_const_names_and_raw_keys() {
+        trybuild::TestCases::new().pass("ui/macro_key_forms.rs");
+    }
+
+    #[test]
+    fn copy_all_copies_raw_entries_under_the_prefix() {
+        const BALANCES: Map<64, &str, u128> = map!("balances");
+        const OWNER: Item<String> = item!("owner");
+
+        let mut from = MemStore::new_in_memory();
+        let mut to = MemStore::new_in_memory();
+
+        BALANCES.save(&mut from, "alice", 100).unwrap();
+        BALANCES.save(&mut from, "bob", 50).unwrap();
+        OWNER.save(&mut from, "alice".to_owned()).unwrap();
+
+        assert_eq!(from.copy_all(&mut to, BALANCES.prefix()).unwrap(), 2);
+
+        assert_contents_eq(&to, &BALANCES, [("alice", 100), ("bob", 50)]);
+        assert_eq!(OWNER.may_load(&to).unwrap(), None);
+        assert_contents_eq(&from, &BALANCES, [("alice", 100), ("bob", 50)]);
+    }
+
+    #[test]
+    fn move_to_removes_what_it_moved() {
+        const BALANCES: Map<64, u32, u128> = map!("balances");
+
+        let mut from = MemStore::new_in_memory();
+        let mut to = MemStore::new_in_memory();
+
+        // spans several batches
+        let count = u32::try_from(kv_storage::COPY_BATCH * 2 + 1).unwrap();
+        for n in 0..count {
+            BALANCES.save(&mut from, n, u128::from(n)).unwrap();
+        }
+
+        assert_eq!(
+            from.move_to(&mut to, BALANCES.prefix()).unwrap(),
+            count as usize
+        );
+
+        assert!(from.repo().is_empty());
+        assert_eq!(BALANCES.keys(&to).unwrap().count(), count as usize);
+        assert_eq!(
+            BALANCES.may_load(&to, count - 1).unwrap(),
+            Some(u128::from(count - 1))
+        );
+    }
+
+    #[test]
+    fn raw_copies_need_the_same_format() {
+        let cases = trybuild::TestCases::new();
+        cases.pass("ui/copy_all_same_format.rs");
+        cases.compile_fail("ui/copy_all_across_formats.rs");
+    }
+}
+
+#[cfg(test)]
+mod layered;
+
+#[cfg(test)]
+mod cached;
+
+#[cfg(test)]
+mod buffered;
+
+#[cfg(test)]
+mod mirror;
+
+#[cfg(test)]
+mod faulty;
+
+#[cfg(test)]
+mod near;
+
+#[cfg(test)]
+mod samples;
+
+#[cfg(test)]
+mod trace;
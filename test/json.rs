@@ -0,0 +1,141 @@
+use kv_storage::{prelude::*, Read, Write};
+use kv_storage_bincode::Bincode;
+use kv_storage_json::Json;
+use kv_storage_memory::MemoryRepo;
+use serde::{Deserialize, Serialize};
+
+type JsonStore = KvStore<Json, MemoryRepo>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Account {
+    owner: String,
+    balance: u128,
+    tags: Vec<String>,
+    frozen: Option<Reason>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Reason {
+    Audit { since: u64 },
+    Closed,
+}
+
+fn account() -> Account {
+    Account {
+        owner: "alice".to_owned(),
+        balance: 100,
+        tags: vec!["vip".to_owned()],
+        frozen: Some(Reason::Audit { since: 7 }),
+    }
+}
+
+#[test]
+fn json_item_round_trip() {
+    const ACCOUNT: Item<Account> = item!("account");
+    const TOTAL: Item<u64> = item!("total");
+
+    let mut store = JsonStore::default();
+
+    assert_eq!(ACCOUNT.may_load(&store).unwrap(), None);
+
+    ACCOUNT.save(&mut store, account()).unwrap();
+    TOTAL.save(&mut store, u64::MAX).unwrap();
+
+    assert_eq!(ACCOUNT.may_load(&store).unwrap(), Some(account()));
+    assert_eq!(TOTAL.may_load(&store).unwrap(), Some(u64::MAX));
+
+    ACCOUNT.clear(&mut store).unwrap();
+
+    assert_eq!(ACCOUNT.may_load(&store).unwrap(), None);
+}
+
+#[test]
+fn json_map_round_trip() {
+    const ACCOUNTS: Map<64, (&str, u32), Account> = map!("accounts");
+
+    let mut store = JsonStore::new(Json::new_with_capacity(64), MemoryRepo::default());
+
+    let closed = Account {
+        frozen: Some(Reason::Closed),
+        ..account()
+    };
+
+    ACCOUNTS.save(&mut store, ("alice", 1), account()).unwrap();
+    ACCOUNTS.save(&mut store, ("alice", 2), &closed).unwrap();
+
+    let accounts: Vec<_> = ACCOUNTS
+        .range(&store, Bound::Unbounded, Bound::Unbounded, Order::Ascending)
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(
+        accounts,
+        [
+            (("alice".to_owned(), 1), account()),
+            (("alice".to_owned(), 2), closed)
+        ]
+    );
+}
+
+#[test]
+fn json_values_are_stored_as_json() {
+    const ACCOUNT: Item<Account> = item!("account");
+
+    let mut compact = JsonStore::default();
+    let mut pretty = JsonStore::new(Json::with_pretty(true), MemoryRepo::default());
+
+    ACCOUNT.save(&mut compact, account()).unwrap();
+    ACCOUNT.save(&mut pretty, account()).unwrap();
+
+    let compact_bytes = compact.repo().read(ACCOUNT.key()).unwrap().unwrap();
+    let pretty_bytes = pretty.repo().read(ACCOUNT.key()).unwrap().unwrap();
+
+    let text = std::str::from_utf8(&compact_bytes).unwrap();
+
+    assert_eq!(
+        text,
+        r#"{"owner":"alice","balance":100,"tags":["vip"],"frozen":{"Audit":{"since":7}}}"#
+    );
+
+    assert!(std::str::from_utf8(&pretty_bytes)
+        .unwrap()
+        .contains("\n  \"owner\": \"alice\""));
+
+    let compact_value: serde_json::Value = serde_json::from_slice(&compact_bytes).unwrap();
+    let pretty_value: serde_json::Value = serde_json::from_slice(&pretty_bytes).unwrap();
+
+    assert_eq!(compact_value, pretty_value);
+    assert_eq!(ACCOUNT.may_load(&pretty).unwrap(), Some(account()));
+}
+
+#[test]
+fn json_reports_invalid_values() {
+    const TOTAL: Item<u64> = item!("total");
+
+    let mut store = JsonStore::default();
+
+    store.mut_repo().write(TOTAL.key(), b"\"ten\"").unwrap();
+
+    assert!(TOTAL.may_load(&store).is_err());
+}
+
+#[test]
+fn map_copy_to_reserializes_across_formats() {
+    const ACCOUNTS: Map<64, &str, Account> = map!("accounts");
+
+    let mut bincode: KvStore<Bincode, MemoryRepo> = KvStore::default();
+    let mut json = JsonStore::default();
+
+    ACCOUNTS.save(&mut bincode, "alice", account()).unwrap();
+
+    assert_eq!(ACCOUNTS.copy_to(&bincode, &mut json).unwrap(), 1);
+
+    let key = ACCOUNTS.key("alice");
+    let bytes = json.repo().read(key.as_ref()).unwrap().unwrap();
+    assert_eq!(
+        serde_json::from_slice::<Account>(&bytes).unwrap(),
+        account()
+    );
+    assert_eq!(ACCOUNTS.may_load(&json, "alice").unwrap(), Some(account()));
+}
@@ -0,0 +1,112 @@
+use kv_storage::{system, HasKey, Read, Remove, Removed, Write};
+use kv_storage_bincode::Bincode;
+use kv_storage_layered::{LayeredRepo, DEFAULT_TOMBSTONE_PREFIX};
+use kv_storage_memory::prelude::*;
+
+fn legacy() -> MemoryRepo {
+    let mut repo = MemoryRepo::default();
+
+    repo.write(b"legacy", b"old").unwrap();
+    repo.write(b"shadowed", b"old").unwrap();
+
+    repo
+}
+
+fn layered() -> LayeredRepo<MemoryRepo, MemoryRepo> {
+    let mut primary = MemoryRepo::default();
+    primary.write(b"shadowed", b"new").unwrap();
+
+    LayeredRepo::new(primary, legacy())
+}
+
+#[test]
+fn layered_reads_fall_back() {
+    let mut repo = layered();
+
+    assert_eq!(
+        repo.read(b"shadowed").unwrap().as_deref(),
+        Some(&b"new"[..])
+    );
+    assert_eq!(repo.read(b"legacy").unwrap().as_deref(), Some(&b"old"[..]));
+    assert_eq!(repo.read(b"missing").unwrap(), None);
+    assert!(repo.has_key(b"legacy").unwrap());
+    assert!(!repo.has_key(b"missing").unwrap());
+
+    repo.write(b"legacy", b"new").unwrap();
+    assert_eq!(repo.read(b"legacy").unwrap().as_deref(), Some(&b"new"[..]));
+
+    // writes never reach the fallback, and nothing was promoted
+    let (primary, fallback) = repo.into_parts();
+    assert_eq!(
+        fallback.read(b"legacy").unwrap().as_deref(),
+        Some(&b"old"[..])
+    );
+    assert_eq!(primary.read(b"missing").unwrap(), None);
+}
+
+#[test]
+fn layered_tombstones_fallback_keys() {
+    let mut repo = layered();
+
+    assert_eq!(repo.remove_returning(b"legacy").unwrap(), Removed::Existed);
+    assert_eq!(repo.read(b"legacy").unwrap(), None);
+    assert!(!repo.has_key(b"legacy").unwrap());
+    assert_eq!(
+        repo.remove_returning(b"legacy").unwrap(),
+        Removed::DidNotExist
+    );
+
+    // the primary copy goes, and the fallback one stays hidden
+    repo.remove(b"shadowed").unwrap();
+    assert_eq!(repo.read(b"shadowed").unwrap(), None);
+
+    // written again after removal
+    repo.write(b"legacy", b"back").unwrap();
+    assert_eq!(repo.read(b"legacy").unwrap().as_deref(), Some(&b"back"[..]));
+    repo.remove(b"legacy").unwrap();
+    assert_eq!(repo.read(b"legacy").unwrap(), None);
+
+    // keys only in the primary leave no tombstone
+    repo.write(b"fresh", b"new").unwrap();
+    repo.remove(b"fresh").unwrap();
+
+    let tombstone = |key: &[u8]| [DEFAULT_TOMBSTONE_PREFIX, key].concat();
+    let (primary, fallback) = repo.into_parts();
+
+    assert!(primary.has_key(&tombstone(b"legacy")).unwrap());
+    assert!(primary.has_key(&tombstone(b"shadowed")).unwrap());
+    assert!(!primary.has_key(&tombstone(b"fresh")).unwrap());
+    assert!(fallback.has_key(b"legacy").unwrap());
+    assert!(fallback.has_key(b"shadowed").unwrap());
+
+    // tombstones sit in the reserved namespace, as records the validator knows
+    assert!(system::is_reserved(&tombstone(b"legacy")));
+
+    let primary: KvStore<Bincode, MemoryRepo> = KvStore::from_repo(primary);
+    system::validate_system_keys(&primary).unwrap();
+}
+
+#[test]
+fn layered_promotes_fallback_hits() {
+    let repo = LayeredRepo::new(MemoryRepo::default(), legacy())
+        .with_tombstone_prefix(b"~".to_vec())
+        .promote_on_read(true);
+
+    assert!(!repo.primary().has_key(b"legacy").unwrap());
+    assert_eq!(repo.read(b"legacy").unwrap().as_deref(), Some(&b"old"[..]));
+    assert_eq!(
+        repo.primary().read(b"legacy").unwrap().as_deref(),
+        Some(&b"old"[..])
+    );
+
+    // misses aren't promoted
+    assert_eq!(repo.read(b"missing").unwrap(), None);
+    assert!(!repo.primary().has_key(b"missing").unwrap());
+
+    // nor are tombstoned keys
+    let mut repo = repo;
+    repo.remove(b"shadowed").unwrap();
+    assert_eq!(repo.read(b"shadowed").unwrap(), None);
+    assert!(!repo.primary().has_key(b"shadowed").unwrap());
+    assert!(repo.primary().has_key(b"~shadowed").unwrap());
+}
@@ -0,0 +1,236 @@
+use std::{
+    fs::{self, OpenOptions},
+    time::{Duration, Instant},
+};
+
+use kv_storage::{Compactable, KvStore, Read, Write};
+use kv_storage_bincode::Bincode;
+use kv_storage_log::{Error as LogError, LogRepo};
+use kv_storage_memory::prelude::*;
+
+const NAMES: Map<16, u32, String> = map!("names");
+const COUNTER: Item<u64> = item!("counter");
+
+type LogStore = KvStore<Bincode, LogRepo>;
+
+fn file_len(repo: &LogRepo) -> u64 {
+    fs::metadata(repo.path()).unwrap().len()
+}
+
+#[test]
+fn log_reopen_preserves_data() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("kv.log");
+
+    {
+        let mut store: LogStore = KvStore::from_repo(LogRepo::open(&path).unwrap());
+
+        for id in 0..10 {
+            NAMES.save(&mut store, id, format!("name {id}")).unwrap();
+        }
+
+        NAMES.save(&mut store, 3, "renamed".to_owned()).unwrap();
+        NAMES.remove(&mut store, 5).unwrap();
+        COUNTER.save(&mut store, 42).unwrap();
+    }
+
+    let store: LogStore = KvStore::from_repo(LogRepo::open(&path).unwrap());
+
+    assert_eq!(NAMES.load(&store, 3).unwrap(), "renamed");
+    assert_eq!(NAMES.may_load(&store, 5).unwrap(), None);
+    assert_eq!(NAMES.load(&store, 9).unwrap(), "name 9");
+    assert_eq!(COUNTER.load(&store).unwrap(), 42);
+    assert_eq!(store.repo().compaction_stats().live_keys, 10);
+}
+
+#[test]
+fn log_skips_a_torn_tail() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("kv.log");
+
+    let mut repo = LogRepo::open(&path).unwrap();
+    repo.write(b"first", b"1").unwrap();
+    repo.write(b"second", b"2").unwrap();
+    let whole = file_len(&repo);
+    repo.write(b"third", b"3").unwrap();
+    drop(repo);
+
+    // a crash midway through appending the third record
+    let file = OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_len(whole + 7).unwrap();
+    drop(file);
+
+    let mut repo = LogRepo::open(&path).unwrap();
+
+    assert_eq!(repo.read(b"second").unwrap().as_deref(), Some(&b"2"[..]));
+    assert_eq!(repo.read(b"third").unwrap(), None);
+
+    // the torn bytes are gone, so appending carries on from the last whole record
+    assert_eq!(file_len(&repo), whole);
+    repo.write(b"fourth", b"4").unwrap();
+    drop(repo);
+
+    let repo = LogRepo::open(&path).unwrap();
+    assert_eq!(repo.read(b"first").unwrap().as_deref(), Some(&b"1"[..]));
+    assert_eq!(repo.read(b"fourth").unwrap().as_deref(), Some(&b"4"[..]));
+}
+
+#[test]
+fn log_detects_corruption() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("kv.log");
+
+    let mut repo = LogRepo::open(&path).unwrap();
+    repo.write(b"key", b"value").unwrap();
+    repo.write(b"other", b"value").unwrap();
+
+    // flip a byte of the first value
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[12 + 3] ^= 0xff;
+    fs::write(&path, &bytes).unwrap();
+
+    assert!(matches!(
+        repo.read(b"key"),
+        Err(LogError::Corrupt { offset: 0 })
+    ));
+    assert_eq!(repo.read(b"other").unwrap().as_deref(), Some(&b"value"[..]));
+    drop(repo);
+
+    // not the final record, so not a torn write either
+    assert!(matches!(
+        LogRepo::open(&path),
+        Err(LogError::Corrupt { offset: 0 })
+    ));
+}
+
+#[test]
+fn log_refuses_a_bad_length_before_whole_records() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("kv.log");
+
+    let mut repo = LogRepo::open(&path).unwrap();
+    repo.write(b"first", b"1").unwrap();
+    let second = file_len(&repo);
+    repo.write(b"second", b"2").unwrap();
+    repo.write(b"third", b"3").unwrap();
+    drop(repo);
+
+    // the second record's key now runs past the end of the file
+    let mut bytes = fs::read(&path).unwrap();
+    let at = usize::try_from(second).unwrap();
+    bytes[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    fs::write(&path, &bytes).unwrap();
+
+    assert!(matches!(
+        LogRepo::open(&path),
+        Err(LogError::Corrupt { offset }) if offset == second
+    ));
+
+    // the third record is still there to recover
+    assert_eq!(fs::read(&path).unwrap(), bytes);
+}
+
+#[test]
+fn log_refuses_a_bad_length_in_a_large_log_quickly() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("kv.log");
+
+    let mut repo = LogRepo::open(&path).unwrap();
+    let value = vec![7; 4 << 10];
+    let mut middle = 0;
+
+    for id in 0..1024u32 {
+        if id == 512 {
+            middle = file_len(&repo);
+        }
+
+        repo.write(&id.to_be_bytes(), &value).unwrap();
+    }
+
+    drop(repo);
+
+    // the value length of a record in the middle of a 4 MiB log
+    let mut bytes = fs::read(&path).unwrap();
+    let at = usize::try_from(middle).unwrap() + 4;
+    bytes[at] ^= 0x01;
+    fs::write(&path, &bytes).unwrap();
+
+    let start = Instant::now();
+
+    assert!(matches!(
+        LogRepo::open(&path),
+        Err(LogError::Corrupt { offset }) if offset == middle
+    ));
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(fs::read(&path).unwrap(), bytes);
+}
+
+#[test]
+fn log_cuts_a_torn_value_holding_a_whole_record() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("kv.log");
+
+    let mut repo = LogRepo::open(&path).unwrap();
+    repo.write(b"first", b"1").unwrap();
+    drop(repo);
+
+    // a record holding a whole encoded record as its value
+    let inner = fs::read(&path).unwrap();
+    let mut repo = LogRepo::open(&path).unwrap();
+    let whole = file_len(&repo);
+    repo.write(b"outer", &[&inner[..], &[0; 64]].concat())
+        .unwrap();
+    drop(repo);
+
+    // a crash once the embedded record was written, but not the rest of the value
+    let torn = whole + 12 + 5 + inner.len() as u64 + 8;
+    let file = OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_len(torn).unwrap();
+    drop(file);
+
+    let repo = LogRepo::open(&path).unwrap();
+
+    assert_eq!(repo.read(b"first").unwrap().as_deref(), Some(&b"1"[..]));
+    assert_eq!(repo.read(b"outer").unwrap(), None);
+    assert_eq!(file_len(&repo), whole);
+}
+
+#[test]
+fn log_compaction_shrinks_the_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("kv.log");
+
+    let mut store: LogStore = KvStore::from_repo(LogRepo::open(&path).unwrap());
+
+    for count in 0..100 {
+        COUNTER.save(&mut store, count).unwrap();
+    }
+
+    for id in 0..10 {
+        NAMES.save(&mut store, id, format!("name {id}")).unwrap();
+    }
+
+    for id in 0..5 {
+        NAMES.remove(&mut store, id).unwrap();
+    }
+
+    let before = file_len(store.repo());
+    let report = store.mut_repo().compact().unwrap();
+
+    assert_eq!(report.before.total_bytes, before);
+    assert_eq!(report.after.dead_bytes, 0);
+    assert_eq!(report.after.live_keys, 6);
+    assert_eq!(file_len(store.repo()), report.after.total_bytes);
+    assert!(report.reclaimed_bytes() > 0);
+
+    // the compacted log serves reads and appends, and survives reopening
+    NAMES.save(&mut store, 0, "back".to_owned()).unwrap();
+    drop(store);
+
+    let store: LogStore = KvStore::from_repo(LogRepo::open(&path).unwrap());
+
+    assert_eq!(COUNTER.load(&store).unwrap(), 99);
+    assert_eq!(NAMES.load(&store, 0).unwrap(), "back");
+    assert_eq!(NAMES.may_load(&store, 1).unwrap(), None);
+    assert_eq!(NAMES.load(&store, 7).unwrap(), "name 7");
+}
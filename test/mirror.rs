@@ -0,0 +1,136 @@
+use std::{cell::RefCell, rc::Rc};
+
+use kv_storage::{Fallible, HasKey, KvStore, Read, Remove, Write};
+use kv_storage_bincode::Bincode;
+use kv_storage_memory::prelude::*;
+use kv_storage_mirror::{Error as MirrorError, MirrorRepo, Mismatch};
+use kv_storage_sled::SledRepo;
+
+use mock_consumer::Balance;
+
+#[derive(Debug, thiserror::Error)]
+#[error("repo is full")]
+struct Full;
+
+/// Accepts no writes, and holds nothing.
+struct FullRepo;
+
+impl Fallible for FullRepo {
+    type Error = Full;
+}
+
+impl Write for FullRepo {
+    fn write(&mut self, _: &[u8], _: &[u8]) -> Result<(), Self::Error> {
+        Err(Full)
+    }
+}
+
+impl Read for FullRepo {
+    fn read(&self, _: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(None)
+    }
+}
+
+impl HasKey for FullRepo {}
+
+impl Remove for FullRepo {
+    fn remove(&mut self, _: &[u8]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[test]
+fn mirror_writes_to_both_repos() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = MirrorRepo::new(SledRepo::open(dir.path()).unwrap(), MemoryRepo::default());
+    let mut store: KvStore<Bincode, MirrorRepo<SledRepo, MemoryRepo>> = KvStore::from_repo(repo);
+
+    let mut alice = Balance::load_account(&store, "alice").unwrap();
+    alice.deposit(1000).unwrap().save(&mut store).unwrap();
+
+    let mut bob = Balance::load_account(&store, "bob").unwrap();
+    bob.deposit(20).unwrap().save(&mut store).unwrap();
+
+    assert_eq!(Balance::load_total(&store).unwrap(), 1020);
+
+    let (sled, memory) = store.into_repo().into_parts();
+    let sled = KvStore::<Bincode, SledRepo>::from_repo(sled);
+    let memory = KvStore::<Bincode, MemoryRepo>::from_repo(memory);
+
+    assert_eq!(Balance::load_total(&sled).unwrap(), 1020);
+    assert_eq!(Balance::load_total(&memory).unwrap(), 1020);
+}
+
+#[test]
+fn mirror_reports_mismatched_reads() {
+    let mut primary = MemoryRepo::default();
+    primary.write(b"stale", b"new").unwrap();
+
+    let mut secondary = MemoryRepo::default();
+    secondary.write(b"stale", b"old").unwrap();
+    secondary.write(b"orphan", b"old").unwrap();
+
+    let reported = Rc::new(RefCell::new(Vec::new()));
+    let mut repo = MirrorRepo::new(primary, secondary).verify_reads({
+        let reported = reported.clone();
+        move |mismatch| match mismatch {
+            Mismatch::Differs {
+                key,
+                primary,
+                secondary,
+            } => reported.borrow_mut().push((
+                key.to_vec(),
+                primary.map(<[u8]>::to_vec),
+                secondary.map(<[u8]>::to_vec),
+            )),
+            Mismatch::Failed { error, .. } => panic!("{error}"),
+        }
+    });
+
+    repo.write(b"same", b"new").unwrap();
+
+    assert_eq!(repo.read(b"same").unwrap().as_deref(), Some(&b"new"[..]));
+    assert_eq!(repo.mismatches(), 0);
+
+    // the reads themselves still succeed, with the primary's bytes
+    assert_eq!(repo.read(b"stale").unwrap().as_deref(), Some(&b"new"[..]));
+    assert_eq!(repo.read(b"orphan").unwrap(), None);
+    assert_eq!(repo.mismatches(), 2);
+
+    // key checks only ask the primary
+    assert!(!repo.has_key(b"orphan").unwrap());
+    assert_eq!(repo.mismatches(), 2);
+
+    assert_eq!(
+        *reported.borrow(),
+        [
+            (
+                b"stale".to_vec(),
+                Some(b"new".to_vec()),
+                Some(b"old".to_vec())
+            ),
+            (b"orphan".to_vec(), None, Some(b"old".to_vec())),
+        ]
+    );
+}
+
+#[test]
+fn mirror_surfaces_secondary_failures() {
+    let mut repo = MirrorRepo::new(MemoryRepo::default(), FullRepo);
+
+    assert!(matches!(
+        repo.write(b"key", b"value"),
+        Err(MirrorError::Secondary(Full))
+    ));
+
+    // the primary was written before the secondary failed
+    assert!(repo.primary().has_key(b"key").unwrap());
+
+    let mut repo = MirrorRepo::new(FullRepo, MemoryRepo::default());
+
+    assert!(matches!(
+        repo.write(b"key", b"value"),
+        Err(MirrorError::Primary(Full))
+    ));
+    assert!(!repo.secondary().has_key(b"key").unwrap());
+}
@@ -1,4 +1,4 @@
-use kv_storage::{item, map, Item, Map, MutStorage, Storage};
+use kv_storage::{prelude::*, KeyDecodeError, RangeError};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error<S = ()> {
@@ -10,6 +10,17 @@ pub enum Error<S = ()> {
     InsufficientFunds,
     #[error("balance overflow")]
     BalanceOverflow,
+    #[error(transparent)]
+    InvalidKey(KeyDecodeError),
+}
+
+impl<S> From<RangeError<S>> for Error<S> {
+    fn from(err: RangeError<S>) -> Self {
+        match err {
+            RangeError::Key(err) => Error::InvalidKey(err),
+            RangeError::Store(err) => Error::Storage(err),
+        }
+    }
 }
 
 pub struct Balance<'a> {
@@ -78,14 +89,17 @@ impl<'a> std::ops::DerefMut for ModifiedBalance<'a> {
 }
 
 impl<'a> Balance<'a> {
-    const BALANCES: Map<1024, &str, u128> = map!("balances");
+    const BALANCES: Map<1024, &'static str, u128> = map!("balances");
     const TOTAL: Item<u128> = item!("total_balance");
 
     fn save<Store: MutStorage>(&self, store: &mut Store) -> Result<(), Error<Store::Error>> {
-        Self::TOTAL.save(store, self.total)?;
+        let balance_key = Self::BALANCES.key(self.account);
 
-        Self::BALANCES
-            .save(store, self.account, self.balance)
+        store
+            .save_batch(&[
+                (Self::TOTAL.key(), &self.total),
+                (balance_key.as_ref(), &self.balance),
+            ])
             .map_err(Error::from)
     }
 
@@ -97,10 +111,30 @@ impl<'a> Balance<'a> {
     }
 
     pub fn load_total<Store: Storage>(store: &Store) -> Result<u128, Error<Store::Error>> {
-        let total = Self::TOTAL.may_load(store)?.unwrap_or_default();
+        let total = Self::TOTAL.load_or_default(store)?;
         Ok(total)
     }
 
+    /// Every account's balance, ordered by account.
+    pub fn load_all<Store: IterStorage>(
+        store: &Store,
+    ) -> Result<Vec<(String, u128)>, Error<Store::Error>> {
+        Self::BALANCES
+            .range(store, Bound::Unbounded, Bound::Unbounded, Order::Ascending)?
+            .map(|entry| entry.map_err(Error::from))
+            .collect()
+    }
+
+    /// Every account with a balance, in order.
+    pub fn load_accounts<Store: IterStorage>(
+        store: &Store,
+    ) -> Result<Vec<String>, Error<Store::Error>> {
+        Self::BALANCES
+            .keys(store)?
+            .map(|account| account.map_err(Error::InvalidKey))
+            .collect()
+    }
+
     pub fn load_account<Store: Storage>(
         store: &Store,
         account: &'a str,
@@ -0,0 +1,89 @@
+use kv_storage::{prelude::*, Read, Write};
+use kv_storage_memory::MemoryRepo;
+use kv_storage_msgpack::{rmp_serde, MessagePack, StructEncoding};
+use serde::{Deserialize, Serialize};
+
+type MsgPackStore = KvStore<MessagePack, MemoryRepo>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Balance {
+    account: String,
+    total: u128,
+    balance: u128,
+}
+
+fn balance(account: &str, balance: u128) -> Balance {
+    Balance {
+        account: account.to_owned(),
+        total: 1_000,
+        balance,
+    }
+}
+
+#[test]
+fn msgpack_round_trip() {
+    const BALANCE: Item<Balance> = item!("balance");
+    const BALANCES: Map<64, &str, Balance> = map!("balances");
+
+    for structs in [StructEncoding::Map, StructEncoding::Tuple] {
+        let mut store = MsgPackStore::new(
+            MessagePack::with_struct_encoding(structs),
+            MemoryRepo::default(),
+        );
+
+        BALANCE.save(&mut store, balance("alice", 100)).unwrap();
+        BALANCES
+            .save(&mut store, "bob", balance("bob", u128::MAX))
+            .unwrap();
+
+        assert_eq!(
+            BALANCE.may_load(&store).unwrap(),
+            Some(balance("alice", 100))
+        );
+        assert_eq!(
+            BALANCES.may_load(&store, "bob").unwrap(),
+            Some(balance("bob", u128::MAX))
+        );
+        assert_eq!(BALANCES.may_load(&store, "carol").unwrap(), None);
+    }
+}
+
+#[test]
+fn msgpack_bytes_decode_independently() {
+    const BALANCE: Item<Balance> = item!("balance");
+
+    let mut named = MsgPackStore::default();
+    let mut compact = MsgPackStore::new(
+        MessagePack::with_struct_encoding(StructEncoding::Tuple),
+        MemoryRepo::default(),
+    );
+
+    BALANCE.save(&mut named, balance("alice", 100)).unwrap();
+    BALANCE.save(&mut compact, balance("alice", 100)).unwrap();
+
+    let named = named.repo().read(BALANCE.key()).unwrap().unwrap();
+    let compact = compact.repo().read(BALANCE.key()).unwrap().unwrap();
+
+    // a 3 entry map and a 3 element array
+    assert_eq!(named[0], 0x83);
+    assert_eq!(compact[0], 0x93);
+    assert!(named.len() > compact.len());
+
+    for bytes in [&named, &compact] {
+        assert_eq!(
+            rmp_serde::from_slice::<Balance>(bytes).unwrap(),
+            balance("alice", 100)
+        );
+    }
+}
+
+#[test]
+fn msgpack_reports_invalid_values() {
+    const BALANCE: Item<Balance> = item!("balance");
+
+    let mut store = MsgPackStore::default();
+
+    store.mut_repo().write(BALANCE.key(), &[0xc1]).unwrap();
+
+    assert!(BALANCE.may_load(&store).is_err());
+}
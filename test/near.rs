@@ -0,0 +1,93 @@
+use kv_storage::{prelude::*, HasKey, Read};
+use kv_storage_near::{NearRepo, NearStore};
+
+const TOTAL: Item<u64> = item!("total");
+const BALANCES: Map<64, u32, u128> = map!("balances");
+
+#[test]
+fn near_round_trips_values() {
+    let mut store: NearStore = KvStore::from_repo(NearRepo::new());
+
+    assert_eq!(TOTAL.may_load(&store).unwrap(), None);
+
+    TOTAL.save(&mut store, 42).unwrap();
+    assert_eq!(TOTAL.may_load(&store).unwrap(), Some(42));
+
+    TOTAL.save(&mut store, 43).unwrap();
+    assert_eq!(TOTAL.may_load(&store).unwrap(), Some(43));
+
+    // the storage is the contract's, not the store's
+    let store: NearStore = KvStore::from_repo(NearRepo::new());
+    assert_eq!(TOTAL.may_load(&store).unwrap(), Some(43));
+}
+
+#[test]
+fn near_loads_a_range_of_keys() {
+    let mut store: NearStore = KvStore::from_repo(NearRepo::new());
+
+    for account in 0..10 {
+        BALANCES
+            .save(&mut store, account, u128::from(account) * 10)
+            .unwrap();
+    }
+
+    // NEAR storage can't be iterated, so ranges are read key by key
+    let balances = BALANCES.load_many(&store, 5..12).unwrap();
+
+    assert_eq!(
+        balances,
+        [
+            (5, Some(50)),
+            (6, Some(60)),
+            (7, Some(70)),
+            (8, Some(80)),
+            (9, Some(90)),
+            (10, None),
+            (11, None),
+        ]
+    );
+}
+
+#[test]
+fn near_removes_values() {
+    let mut store: NearStore = KvStore::from_repo(NearRepo::new());
+
+    BALANCES.save(&mut store, 1, 100).unwrap();
+
+    assert_eq!(
+        BALANCES.remove_returning(&mut store, 1).unwrap(),
+        Removed::Existed
+    );
+    assert_eq!(
+        BALANCES.remove_returning(&mut store, 1).unwrap(),
+        Removed::DidNotExist
+    );
+    assert!(!BALANCES.has_key(&store, 1).unwrap());
+
+    TOTAL.save(&mut store, 1).unwrap();
+    TOTAL.clear(&mut store).unwrap();
+    assert_eq!(TOTAL.may_load(&store).unwrap(), None);
+}
+
+#[test]
+fn near_prefixes_keep_stores_apart() {
+    let mut first: NearStore<_> = KvStore::from_repo(NearRepo::prefixed(b"first/"));
+    let mut second: NearStore<_> = KvStore::from_repo(NearRepo::prefixed(b"second/"));
+
+    TOTAL.save(&mut first, 1).unwrap();
+    TOTAL.save(&mut second, 2).unwrap();
+
+    assert_eq!(TOTAL.may_load(&first).unwrap(), Some(1));
+    assert_eq!(TOTAL.may_load(&second).unwrap(), Some(2));
+
+    // the prefix is part of the contract storage key
+    let plain = NearRepo::new();
+    let key = [b"first/".as_slice(), TOTAL.key()].concat();
+
+    assert!(plain.has_key(&key).unwrap());
+    assert!(!plain.has_key(TOTAL.key()).unwrap());
+    assert_eq!(
+        plain.read(&key).unwrap(),
+        first.repo().read(TOTAL.key()).unwrap()
+    );
+}
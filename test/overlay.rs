@@ -0,0 +1,118 @@
+use kv_storage::{HasKey, KvStore, Read, Remove, Write};
+use kv_storage_bincode::Bincode;
+use kv_storage_memory::prelude::*;
+use kv_storage_overlay::OverlayRepo;
+
+use mock_consumer::Balance;
+
+fn base() -> MemoryRepo {
+    let mut repo = MemoryRepo::default();
+
+    repo.write(b"kept", b"base").unwrap();
+    repo.write(b"changed", b"base").unwrap();
+    repo.write(b"removed", b"base").unwrap();
+
+    repo
+}
+
+fn apply_changes(overlay: &mut OverlayRepo<MemoryRepo>) {
+    overlay.write(b"changed", b"overlay").unwrap();
+    overlay.write(b"added", b"overlay").unwrap();
+    overlay.remove(b"removed").unwrap();
+    // removed and written again within the overlay
+    overlay.remove(b"readded").unwrap();
+    overlay.write(b"readded", b"overlay").unwrap();
+}
+
+#[test]
+fn overlay_reads_its_own_writes() {
+    let mut overlay = OverlayRepo::new(base());
+
+    apply_changes(&mut overlay);
+
+    assert_eq!(
+        overlay.read(b"kept").unwrap().as_deref(),
+        Some(&b"base"[..])
+    );
+    assert_eq!(
+        overlay.read(b"changed").unwrap().as_deref(),
+        Some(&b"overlay"[..])
+    );
+    assert_eq!(
+        overlay.read(b"added").unwrap().as_deref(),
+        Some(&b"overlay"[..])
+    );
+    assert_eq!(overlay.read(b"removed").unwrap(), None);
+    assert!(!overlay.has_key(b"removed").unwrap());
+    assert!(overlay.has_key(b"readded").unwrap());
+    assert_eq!(overlay.pending(), 4);
+
+    // nothing reached the base yet
+    assert_eq!(
+        overlay.base().read(b"changed").unwrap().as_deref(),
+        Some(&b"base"[..])
+    );
+    assert!(overlay.base().has_key(b"removed").unwrap());
+}
+
+#[test]
+fn overlay_discards() {
+    let mut overlay = OverlayRepo::new(base());
+
+    apply_changes(&mut overlay);
+
+    let base = overlay.discard();
+
+    let entries: Vec<_> = base.into_iter().collect();
+
+    assert_eq!(
+        entries,
+        [
+            (b"changed".to_vec(), b"base".to_vec()),
+            (b"kept".to_vec(), b"base".to_vec()),
+            (b"removed".to_vec(), b"base".to_vec()),
+        ]
+    );
+}
+
+#[test]
+fn overlay_commits_writes_and_removes() {
+    let mut overlay = OverlayRepo::new(base());
+
+    apply_changes(&mut overlay);
+
+    let base = overlay.commit().unwrap();
+
+    let entries: Vec<_> = base.into_iter().collect();
+
+    assert_eq!(
+        entries,
+        [
+            (b"added".to_vec(), b"overlay".to_vec()),
+            (b"changed".to_vec(), b"overlay".to_vec()),
+            (b"kept".to_vec(), b"base".to_vec()),
+            (b"readded".to_vec(), b"overlay".to_vec()),
+        ]
+    );
+}
+
+#[test]
+fn overlay_store_commits_balance_changes() {
+    let mut store: KvStore<Bincode, OverlayRepo<MemoryRepo>> =
+        KvStore::new(Bincode::new(), OverlayRepo::new(MemoryRepo::default()));
+
+    let mut alice = Balance::load_account(&store, "alice").unwrap();
+    alice.deposit(10).unwrap().save(&mut store).unwrap();
+
+    assert_eq!(Balance::load_total(&store).unwrap(), 10);
+    assert!(store.repo().base().is_empty());
+
+    let base = store.into_repo().commit().unwrap();
+    let store = MemStore::from_repo(base);
+
+    assert_eq!(Balance::load_total(&store).unwrap(), 10);
+    assert_eq!(
+        Balance::load_account(&store, "alice").unwrap().balance(),
+        10
+    );
+}
@@ -0,0 +1,123 @@
+use kv_storage::{prelude::*, Deserializer, Error as StoreError, Serializer};
+use kv_storage_bincode::Bincode;
+use kv_storage_memory::MemoryRepo;
+use kv_storage_postcard::{Error, Postcard};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Account {
+    owner: String,
+    balance: u128,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct AccountV2 {
+    owner: String,
+    balance: u128,
+    frozen: bool,
+}
+
+/// The same operations against stores that only differ by serializer.
+fn exercise<Serde>()
+where
+    Serde: Serializer + Deserializer + Default,
+{
+    const CONFIG: Item<Account> = item!("config");
+    const COUNT: Item<u64> = item!("count");
+    const ACCOUNTS: Map<64, (&str, u32), Account> = map!("accounts");
+
+    let mut store: KvStore<Serde, MemoryRepo> = KvStore::default();
+
+    let account = |owner: &str, balance| Account {
+        owner: owner.to_owned(),
+        balance,
+    };
+
+    CONFIG.save(&mut store, account("admin", 0)).unwrap();
+    assert_eq!(CONFIG.may_load(&store).unwrap(), Some(account("admin", 0)));
+
+    assert_eq!(COUNT.next_id(&mut store).unwrap(), 0);
+    assert_eq!(COUNT.next_id(&mut store).unwrap(), 1);
+
+    for (owner, id, balance) in [("alice", 1, 10), ("alice", 2, 20), ("bob", 1, u128::MAX)] {
+        ACCOUNTS
+            .save(&mut store, (owner, id), account(owner, balance))
+            .unwrap();
+    }
+
+    ACCOUNTS
+        .update(&mut store, ("alice", 2), |current| {
+            let mut current = current.unwrap();
+            current.balance += 1;
+            Ok::<_, StoreError<Serde::Error, kv_storage_memory::Infallible>>(current)
+        })
+        .unwrap();
+
+    let alice: Vec<_> = ACCOUNTS
+        .sub_prefix(("alice",))
+        .range(&store, Bound::Unbounded, Bound::Unbounded, Order::Ascending)
+        .unwrap()
+        .map(|entry| entry.unwrap().1.balance)
+        .collect();
+    assert_eq!(alice, [10, 21]);
+
+    ACCOUNTS.remove(&mut store, ("alice", 1)).unwrap();
+    assert_eq!(ACCOUNTS.may_load(&store, ("alice", 1)).unwrap(), None);
+
+    assert_eq!(
+        ACCOUNTS.may_load(&store, ("bob", 1)).unwrap(),
+        Some(account("bob", u128::MAX))
+    );
+    assert_eq!(ACCOUNTS.clear(&mut store).unwrap(), 2);
+}
+
+#[test]
+fn store_operations_with_bincode() {
+    exercise::<Bincode>();
+}
+
+#[test]
+fn store_operations_with_postcard() {
+    exercise::<Postcard>();
+}
+
+#[test]
+fn postcard_schema_changes_fail_loudly() {
+    const ACCOUNT: Item<Account> = item!("account");
+    const ACCOUNT_V2: Item<AccountV2> = item!("account");
+
+    let mut store: KvStore<Postcard, MemoryRepo> = KvStore::default();
+
+    // a field added since the value was written
+    ACCOUNT
+        .save(
+            &mut store,
+            Account {
+                owner: "alice".to_owned(),
+                balance: 10,
+            },
+        )
+        .unwrap();
+
+    assert!(matches!(
+        ACCOUNT_V2.may_load(&store),
+        Err(StoreError::Serde(Error::Postcard(_)))
+    ));
+
+    // a field removed since the value was written
+    ACCOUNT_V2
+        .save(
+            &mut store,
+            AccountV2 {
+                owner: "alice".to_owned(),
+                balance: 10,
+                frozen: true,
+            },
+        )
+        .unwrap();
+
+    assert!(matches!(
+        ACCOUNT.may_load(&store),
+        Err(StoreError::Serde(Error::TrailingBytes(1)))
+    ));
+}
@@ -0,0 +1,148 @@
+use kv_storage::Read;
+use kv_storage_memory::MemoryRepo;
+use kv_storage_prost::{
+    prost::Message, Error, Prost, ProstItem, ProstMap, ProstStore, SchemaHeader,
+};
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Account {
+    #[prost(string, tag = "1")]
+    owner: String,
+    #[prost(uint64, tag = "2")]
+    balance: u64,
+}
+
+fn account() -> Account {
+    Account {
+        owner: "alice".to_owned(),
+        balance: 100,
+    }
+}
+
+#[test]
+fn prost_item_round_trip() {
+    let mut store = ProstStore::new(Prost::new(), MemoryRepo::default());
+    let item = ProstItem::<Account>::new(b"account");
+
+    assert_eq!(item.may_load(&store).unwrap(), None);
+
+    item.save(&mut store, account()).unwrap();
+
+    assert_eq!(item.may_load(&store).unwrap(), Some(account()));
+
+    item.clear(&mut store).unwrap();
+
+    assert_eq!(item.may_load(&store).unwrap(), None);
+}
+
+#[test]
+fn prost_map_shares_core_keys() {
+    let core = kv_storage::Map::<32, u32, ()>::new(b"accounts");
+    let map = ProstMap::<32, u32, Account>::from_map(&core);
+    let mut store = ProstStore::new(
+        Prost::with_header(SchemaHeader::new(7)),
+        MemoryRepo::default(),
+    );
+
+    map.save(&mut store, 1, account()).unwrap();
+
+    assert!(map.has_key(&store, 1).unwrap());
+    assert!(!map.has_key(&store, 2).unwrap());
+
+    // other consumers strip the header and decode with plain protobuf tooling
+    let raw = store.repo().read(core.key(1).as_ref()).unwrap().unwrap();
+
+    assert_eq!(raw[..4], 7u32.to_be_bytes());
+    assert_eq!(Account::decode(&raw[4..]).unwrap(), account());
+
+    map.remove(&mut store, 1).unwrap();
+
+    assert_eq!(map.may_load(&store, 1).unwrap(), None);
+}
+
+#[test]
+fn prost_schema_ids_are_checked() {
+    let item = ProstItem::<Account>::new(b"account");
+
+    let mut old = ProstStore::new(
+        Prost::with_header(SchemaHeader::new(1)),
+        MemoryRepo::default(),
+    );
+
+    item.save(&mut old, account()).unwrap();
+
+    let mut current = ProstStore::new(
+        Prost::with_header(SchemaHeader::new(2).accepting([1])),
+        std::mem::take(old.mut_repo()),
+    );
+
+    assert_eq!(item.may_load(&current).unwrap(), Some(account()));
+
+    let strict = ProstStore::new(
+        Prost::with_header(SchemaHeader::new(2)),
+        std::mem::take(current.mut_repo()),
+    );
+
+    assert!(matches!(
+        item.may_load(&strict),
+        Err(kv_storage::Error::Serde(Error::UnknownSchema(1)))
+    ));
+}
+
+/// `Account` as written by a newer schema.
+#[derive(Clone, PartialEq, prost::Message)]
+struct AccountV2 {
+    #[prost(string, tag = "1")]
+    owner: String,
+    #[prost(uint64, tag = "2")]
+    balance: u64,
+    #[prost(string, tag = "3")]
+    note: String,
+    #[prost(string, repeated, tag = "4")]
+    tags: Vec<String>,
+}
+
+#[test]
+fn prost_unknown_fields_survive_saves() {
+    let newer = ProstItem::<AccountV2>::new(b"account");
+    let older = ProstItem::<Account>::new(b"account");
+
+    let written = AccountV2 {
+        owner: "alice".to_owned(),
+        balance: 100,
+        note: "vip".to_owned(),
+        tags: vec!["a".to_owned(), "b".to_owned()],
+    };
+
+    let mut store = ProstStore::new(
+        Prost::with_header(SchemaHeader::new(1)).preserving_unknown_fields(),
+        MemoryRepo::default(),
+    );
+
+    newer.save(&mut store, written.clone()).unwrap();
+
+    let mut account = older.may_load(&store).unwrap().unwrap();
+    account.balance += 1;
+    older.save(&mut store, account).unwrap();
+
+    let expected = AccountV2 {
+        balance: 101,
+        ..written.clone()
+    };
+
+    assert_eq!(newer.may_load(&store).unwrap(), Some(expected));
+
+    // without preservation the older schema drops what it doesn't know
+    let mut lossy = ProstStore::new(
+        Prost::with_header(SchemaHeader::new(1)),
+        std::mem::take(store.mut_repo()),
+    );
+
+    let account = older.may_load(&lossy).unwrap().unwrap();
+    older.save(&mut lossy, account).unwrap();
+
+    let loaded = newer.may_load(&lossy).unwrap().unwrap();
+
+    assert_eq!(loaded.note, "");
+    assert!(loaded.tags.is_empty());
+}
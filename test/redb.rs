@@ -0,0 +1,114 @@
+use kv_storage::{IterStorage, KvStore};
+use kv_storage_bincode::Bincode;
+use kv_storage_memory::prelude::*;
+use kv_storage_redb::{RedbRepo, RedbTransaction};
+
+use mock_consumer::Balance;
+
+type RedbStore = KvStore<Bincode, RedbRepo>;
+
+const SCORES: Map<32, u32, u64> = map!("scores");
+
+fn scores<Store>(store: &Store) -> Vec<(u32, u64)>
+where
+    Store: IterStorage,
+    Store::Error: std::fmt::Debug,
+{
+    SCORES
+        .range(store, Bound::Unbounded, Bound::Unbounded, Order::Ascending)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn redb_balance_scenario() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store: RedbStore =
+        KvStore::from_repo(RedbRepo::create(dir.path().join("kv.redb")).unwrap());
+
+    assert!(!Balance::account_exists(&store, "alice").unwrap());
+
+    let mut alice = Balance::load_account(&store, "alice").unwrap();
+
+    alice
+        .deposit(1000)
+        .unwrap()
+        .withdraw(500)
+        .unwrap()
+        .save(&mut store)
+        .unwrap();
+
+    let mut bob = Balance::load_account(&store, "bob").unwrap();
+    bob.deposit(20).unwrap().save(&mut store).unwrap();
+
+    assert!(Balance::account_exists(&store, "alice").unwrap());
+    assert_eq!(Balance::load_total(&store).unwrap(), 520);
+    assert_eq!(
+        Balance::load_all(&store).unwrap(),
+        [("alice".to_owned(), 500), ("bob".to_owned(), 20)]
+    );
+}
+
+#[test]
+fn redb_per_call_writes_persist_across_reopening() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("kv.redb");
+
+    {
+        let mut store: RedbStore = KvStore::from_repo(RedbRepo::create(&path).unwrap());
+
+        for id in 0..5 {
+            SCORES.save(&mut store, id, u64::from(id) * 10).unwrap();
+        }
+
+        SCORES.remove(&mut store, 2).unwrap();
+    }
+
+    let store: RedbStore = KvStore::from_repo(RedbRepo::create(&path).unwrap());
+
+    assert_eq!(scores(&store), [(0, 0), (1, 10), (3, 30), (4, 40)]);
+}
+
+#[test]
+fn redb_transaction_applies_on_commit() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = RedbRepo::create(dir.path().join("kv.redb")).unwrap();
+
+    let mut batch: KvStore<Bincode, RedbTransaction> =
+        KvStore::from_repo(repo.begin_write().unwrap());
+
+    for id in 0..100 {
+        SCORES.save(&mut batch, id, u64::from(id)).unwrap();
+    }
+
+    // the transaction reads its own writes
+    assert_eq!(SCORES.may_load(&batch, 42).unwrap(), Some(42));
+    assert_eq!(scores(&batch).len(), 100);
+
+    batch.into_repo().commit().unwrap();
+
+    let store: RedbStore = KvStore::from_repo(repo);
+    assert_eq!(SCORES.may_load(&store, 99).unwrap(), Some(99));
+    assert_eq!(scores(&store).len(), 100);
+}
+
+#[test]
+fn redb_transaction_discards_on_abort_or_drop() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = RedbRepo::create(dir.path().join("kv.redb")).unwrap();
+
+    let mut aborted: KvStore<Bincode, RedbTransaction> =
+        KvStore::from_repo(repo.begin_write().unwrap());
+    SCORES.save(&mut aborted, 1, 10).unwrap();
+    aborted.into_repo().abort().unwrap();
+
+    {
+        let mut dropped: KvStore<Bincode, RedbTransaction> =
+            KvStore::from_repo(repo.begin_write().unwrap());
+        SCORES.save(&mut dropped, 2, 20).unwrap();
+    }
+
+    let store: RedbStore = KvStore::from_repo(repo);
+    assert!(scores(&store).is_empty());
+}
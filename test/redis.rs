@@ -0,0 +1,71 @@
+//! Runs against the Redis server at `REDIS_URL`, or a local one by default.
+
+use kv_storage::{HasKey, KvStore, Read, Remove, Write};
+use kv_storage_bincode::Bincode;
+use kv_storage_memory::prelude::*;
+use kv_storage_redis::RedisRepo;
+
+use mock_consumer::Balance;
+
+/// A repo namespaced to `name` and this process, so runs and tests don't see each other.
+fn repo(name: &str) -> RedisRepo {
+    let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_owned());
+    let prefix = format!("kv-storage-test:{}:{name}:", std::process::id());
+
+    RedisRepo::open(url).unwrap().with_prefix(prefix)
+}
+
+#[test]
+fn redis_balance_scenario() {
+    let mut store: KvStore<Bincode, RedisRepo> = KvStore::from_repo(repo("balance"));
+
+    let mut alice = Balance::load_account(&store, "alice").unwrap();
+    alice.deposit(1000).unwrap().save(&mut store).unwrap();
+
+    assert!(Balance::account_exists(&store, "alice").unwrap());
+    assert!(!Balance::account_exists(&store, "bob").unwrap());
+
+    let mut alice = Balance::load_account(&store, "alice").unwrap();
+    alice.withdraw(400).unwrap().save(&mut store).unwrap();
+
+    assert_eq!(
+        Balance::load_account(&store, "alice").unwrap().balance(),
+        600
+    );
+}
+
+#[test]
+fn redis_keeps_arbitrary_bytes() {
+    let mut repo = repo("bytes");
+    let key = [0, 159, 146, 150, 0, b'\r', b'\n'];
+    let bytes = [0, 0, 255, b' ', 0];
+
+    repo.write(&key, &bytes).unwrap();
+
+    assert!(repo.has_key(&key).unwrap());
+    assert_eq!(repo.read(&key).unwrap().as_deref(), Some(&bytes[..]));
+
+    // keys differing only after a zero byte are distinct
+    assert!(!repo.has_key(&key[..1]).unwrap());
+
+    repo.remove(&key).unwrap();
+    assert_eq!(repo.read(&key).unwrap(), None);
+}
+
+#[test]
+fn redis_prefixes_share_a_database() {
+    const NAME: Item<String> = item!("name");
+
+    let mut first: KvStore<Bincode, RedisRepo> = KvStore::from_repo(repo("first"));
+    let mut second: KvStore<Bincode, RedisRepo> = KvStore::from_repo(repo("second"));
+
+    NAME.save(&mut first, "first".to_owned()).unwrap();
+    assert_eq!(NAME.may_load(&second).unwrap(), None);
+
+    NAME.save(&mut second, "second".to_owned()).unwrap();
+    assert_eq!(NAME.load(&first).unwrap(), "first");
+    assert_eq!(NAME.load(&second).unwrap(), "second");
+
+    NAME.clear(&mut first).unwrap();
+    NAME.clear(&mut second).unwrap();
+}
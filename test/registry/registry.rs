@@ -0,0 +1,48 @@
+//! Containers whose keys collide, for `check_unique_keys` to find.
+
+pub mod bank {
+    use kv_storage::prelude::*;
+
+    pub const TOTAL: Item<u64> = item!("total");
+    pub const BALANCES: Map<64, &str, u128> = map!("balances");
+
+    // copied from above, forgetting to rename it
+    pub const ALLOWANCES: Map<64, (&str, &str), u128> = map!("balances");
+}
+
+pub mod ledger {
+    use kv_storage::prelude::*;
+
+    // pinned to the namespace `bank` happens to be in
+    pub const BALANCES: Map<64, &str, u128> = map!("balances", ns = "key_registry::bank");
+    pub const ENTRIES: Deque<u64> = deque!("entries");
+}
+
+#[cfg(test)]
+mod test {
+    use kv_storage::{check_unique_keys, KeyCollision};
+
+    use crate::bank;
+
+    #[test]
+    fn check_unique_keys_reports_collisions() {
+        let collisions = check_unique_keys();
+
+        let [KeyCollision { key, declarations }] = collisions.as_slice() else {
+            panic!("expected a single collision, found {collisions:?}");
+        };
+
+        assert_eq!(*key, bank::BALANCES.prefix());
+        assert_eq!(declarations.len(), 3);
+
+        assert!(declarations
+            .iter()
+            .all(|declaration| declaration.file == file!()));
+
+        let lines: Vec<_> = declarations
+            .iter()
+            .map(|declaration| declaration.line)
+            .collect();
+        assert_eq!(lines, [7, 10, 17]);
+    }
+}
@@ -0,0 +1,89 @@
+use kv_storage::{prelude::*, Error as StoreError, Read, Write};
+use kv_storage_memory::MemoryRepo;
+use kv_storage_ron::Ron;
+use serde::{Deserialize, Serialize};
+
+type RonStore = KvStore<Ron, MemoryRepo>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Limits {
+    daily: u64,
+    currencies: Vec<String>,
+    mode: Mode,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Mode {
+    Strict,
+    Lenient { grace: u32 },
+}
+
+fn limits() -> Limits {
+    Limits {
+        daily: 100,
+        currencies: vec!["eur".to_owned(), "usd".to_owned()],
+        mode: Mode::Lenient { grace: 3 },
+    }
+}
+
+#[test]
+fn ron_round_trip() {
+    const LIMITS: Item<Limits> = item!("limits");
+    const BY_USER: Map<64, &str, Limits> = map!("by_user");
+
+    for pretty in [false, true] {
+        let mut store = RonStore::new(Ron::with_pretty(pretty), MemoryRepo::default());
+
+        LIMITS.save(&mut store, limits()).unwrap();
+        BY_USER.save(&mut store, "alice", limits()).unwrap();
+
+        assert_eq!(LIMITS.may_load(&store).unwrap(), Some(limits()));
+        assert_eq!(BY_USER.may_load(&store, "alice").unwrap(), Some(limits()));
+    }
+}
+
+#[test]
+fn ron_values_can_be_edited_as_text() {
+    const LIMITS: Item<Limits> = item!("limits");
+
+    let mut store = RonStore::new(Ron::with_pretty(true), MemoryRepo::default());
+
+    LIMITS.save(&mut store, limits()).unwrap();
+
+    let stored = store.repo().read(LIMITS.key()).unwrap().unwrap();
+    let text = String::from_utf8(stored).unwrap();
+
+    assert!(text.contains("daily: 100,\n"));
+
+    let edited = text
+        .replace("daily: 100", "daily: 250")
+        .replace("Lenient(\n        grace: 3,\n    )", "Strict");
+
+    store
+        .mut_repo()
+        .write(LIMITS.key(), edited.as_bytes())
+        .unwrap();
+
+    assert_eq!(
+        LIMITS.may_load(&store).unwrap(),
+        Some(Limits {
+            daily: 250,
+            mode: Mode::Strict,
+            ..limits()
+        })
+    );
+
+    // a bad edit reports where it is
+    let broken = edited.replace("daily: 250", "daily: lots");
+
+    store
+        .mut_repo()
+        .write(LIMITS.key(), broken.as_bytes())
+        .unwrap();
+
+    let Err(StoreError::Serde(err)) = LIMITS.may_load(&store) else {
+        panic!("expected a deserialization error");
+    };
+
+    assert_eq!(err.position().unwrap().line, 2);
+}
@@ -0,0 +1,119 @@
+use std::fs;
+
+use kv_storage::testing::{assert_can_deserialize_samples, record_samples, Sample};
+use kv_storage_bincode::Bincode;
+use kv_storage_memory::prelude::*;
+use serde::{Deserialize, Serialize};
+
+mod v1 {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Account {
+        pub owner: String,
+        pub balance: u64,
+        pub memo: String,
+    }
+
+    pub const ACCOUNTS: Map<16, u32, Account> = map!("accounts");
+}
+
+mod v2 {
+    use super::*;
+
+    /// `balance` removed, so the stored balance is read as the memo's length.
+    #[derive(Serialize, Deserialize)]
+    pub struct Account {
+        pub owner: String,
+        pub memo: String,
+    }
+}
+
+fn seeded() -> MemStore {
+    let mut store = MemStore::new_in_memory();
+
+    for (id, owner) in [(2, "bob"), (1, "alice")] {
+        let account = v1::Account {
+            owner: owner.to_owned(),
+            balance: 1_000_000,
+            memo: "hello".to_owned(),
+        };
+
+        v1::ACCOUNTS.save(&mut store, id, account).unwrap();
+    }
+
+    store
+}
+
+#[test]
+fn recorded_samples_deserialize_as_the_recorded_type() {
+    let dir = tempfile::tempdir().unwrap();
+
+    assert_eq!(
+        record_samples(&seeded(), &v1::ACCOUNTS, dir.path()).unwrap(),
+        2
+    );
+
+    assert_can_deserialize_samples::<v1::Account, Bincode>(dir.path());
+
+    // samples are numbered in key order and carry their key
+    let first = fs::read(dir.path().join("000000.sample")).unwrap();
+    let first = Sample::from_bytes(&first).unwrap();
+
+    assert_eq!(first.key, v1::ACCOUNTS.key(1).as_ref());
+}
+
+#[test]
+fn recording_is_deterministic() {
+    let first = tempfile::tempdir().unwrap();
+    let second = tempfile::tempdir().unwrap();
+
+    // an extra sample left over from an earlier recording is replaced
+    fs::write(second.path().join("000002.sample"), b"stale").unwrap();
+
+    record_samples(&seeded(), &v1::ACCOUNTS, first.path()).unwrap();
+    record_samples(&seeded(), &v1::ACCOUNTS, second.path()).unwrap();
+
+    let files = |dir: &tempfile::TempDir| {
+        let mut files: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                (
+                    path.file_name().unwrap().to_owned(),
+                    fs::read(path).unwrap(),
+                )
+            })
+            .collect();
+
+        files.sort();
+        files
+    };
+
+    assert_eq!(files(&first), files(&second));
+}
+
+#[test]
+fn removed_field_fails_the_check() {
+    let dir = tempfile::tempdir().unwrap();
+
+    record_samples(&seeded(), &v1::ACCOUNTS, dir.path()).unwrap();
+
+    let failure = std::panic::catch_unwind(|| {
+        assert_can_deserialize_samples::<v2::Account, Bincode>(dir.path());
+    })
+    .unwrap_err();
+
+    let message = failure.downcast_ref::<String>().unwrap();
+
+    assert!(message.contains("000000.sample"), "{message}");
+    assert!(message.contains("000001.sample"), "{message}");
+}
+
+#[test]
+#[should_panic(expected = "no samples")]
+fn an_empty_sample_dir_fails_the_check() {
+    let dir = tempfile::tempdir().unwrap();
+
+    assert_can_deserialize_samples::<v1::Account, Bincode>(dir.path());
+}
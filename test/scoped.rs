@@ -0,0 +1,142 @@
+use std::cell::Cell;
+
+use kv_storage::{
+    Fallible, HasKey, Item, KvStore, Permission, Read, Remove, ScopedError, ScopedStore, Write,
+};
+use kv_storage_bincode::Bincode;
+use kv_storage_memory::MemoryRepo;
+
+/// Counts every call that reaches the repo.
+#[derive(Default)]
+struct CountingRepo {
+    inner: MemoryRepo,
+    calls: Cell<usize>,
+}
+
+impl CountingRepo {
+    fn tick(&self) {
+        self.calls.set(self.calls.get() + 1);
+    }
+}
+
+impl Fallible for CountingRepo {
+    type Error = <MemoryRepo as Fallible>::Error;
+}
+
+impl Write for CountingRepo {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        self.tick();
+        self.inner.write(key, bytes)
+    }
+}
+
+impl Read for CountingRepo {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.tick();
+        self.inner.read(key)
+    }
+}
+
+impl HasKey for CountingRepo {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        self.tick();
+        self.inner.has_key(key)
+    }
+}
+
+impl Remove for CountingRepo {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.tick();
+        self.inner.remove(key)
+    }
+}
+
+const SHARED: Item<u64> = Item::new(b"shared/height");
+const OWN: Item<u64> = Item::new(b"plugin_a/counter");
+const CONFIG: Item<u64> = Item::new(b"plugin_a/config/limit");
+const OTHER: Item<u64> = Item::new(b"plugin_b/counter");
+
+fn plugin_store() -> ScopedStore<KvStore<Bincode, CountingRepo>> {
+    let mut store = KvStore::<Bincode, CountingRepo>::default();
+
+    SHARED.save(&mut store, 10).unwrap();
+    CONFIG.save(&mut store, 5).unwrap();
+    OTHER.save(&mut store, 1).unwrap();
+
+    store.repo().calls.set(0);
+
+    ScopedStore::new(store)
+        .readable(b"shared/")
+        .writable(b"plugin_a/")
+        .readable(b"plugin_a/config/")
+}
+
+fn repo_calls(store: &ScopedStore<KvStore<Bincode, CountingRepo>>) -> usize {
+    store.inner().repo().calls.get()
+}
+
+#[test]
+fn scoped_store_allows_own_and_shared_reads() {
+    let mut store = plugin_store();
+
+    OWN.save(&mut store, 1).unwrap();
+
+    assert_eq!(OWN.may_load(&store).unwrap(), Some(1));
+    assert_eq!(SHARED.may_load(&store).unwrap(), Some(10));
+    assert_eq!(CONFIG.may_load(&store).unwrap(), Some(5));
+}
+
+#[test]
+fn scoped_store_denies_before_reaching_repo() {
+    let mut store = plugin_store();
+
+    // shared prefixes are read-only
+    assert!(matches!(
+        SHARED.save(&mut store, 11),
+        Err(ScopedError::AccessDenied {
+            needed: Permission::Write,
+            ..
+        })
+    ));
+
+    // the longer read-only prefix wins over the writable one
+    assert!(matches!(
+        CONFIG.clear(&mut store),
+        Err(ScopedError::AccessDenied {
+            needed: Permission::Write,
+            ..
+        })
+    ));
+
+    // other plugins' data can't even be read
+    let Err(ScopedError::AccessDenied { key, needed }) = OTHER.may_load(&store) else {
+        panic!("expected access denied");
+    };
+
+    assert_eq!(key, b"plugin_b/counter");
+    assert_eq!(needed, Permission::Read);
+
+    assert_eq!(repo_calls(&store), 0);
+
+    let store = store.into_inner();
+
+    assert_eq!(SHARED.may_load(&store).unwrap(), Some(10));
+    assert_eq!(CONFIG.may_load(&store).unwrap(), Some(5));
+}
+
+#[test]
+fn scoped_store_reregistering_replaces_permission() {
+    let store = ScopedStore::new(())
+        .readable(b"a/")
+        .writable(b"a/b")
+        .writable(b"a/");
+
+    assert_eq!(store.permission(b"a/x"), Some(Permission::Write));
+    assert_eq!(store.permission(b"a/bc"), Some(Permission::Write));
+    assert_eq!(store.permission(b"b/"), None);
+
+    let store = store.readable(b"a/b");
+
+    assert_eq!(store.permission(b"a/bc"), Some(Permission::Read));
+    assert_eq!(store.permission(b"a/c"), Some(Permission::Write));
+}
@@ -0,0 +1,78 @@
+use kv_storage::KvStore;
+use kv_storage_bincode::Bincode;
+use kv_storage_memory::prelude::*;
+use kv_storage_sled::SledRepo;
+
+use mock_consumer::Balance;
+
+type SledStore = KvStore<Bincode, SledRepo>;
+
+#[test]
+fn sled_balance_scenario() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store: SledStore = KvStore::from_repo(SledRepo::open(dir.path()).unwrap());
+
+    assert!(!Balance::account_exists(&store, "alice").unwrap());
+
+    let mut alice = Balance::load_account(&store, "alice").unwrap();
+
+    alice
+        .deposit(1000)
+        .unwrap()
+        .withdraw(500)
+        .unwrap()
+        .save(&mut store)
+        .unwrap();
+
+    let mut bob = Balance::load_account(&store, "bob").unwrap();
+    bob.deposit(20).unwrap().save(&mut store).unwrap();
+
+    assert!(Balance::account_exists(&store, "alice").unwrap());
+    assert_eq!(Balance::load_total(&store).unwrap(), 520);
+    assert_eq!(
+        Balance::load_all(&store).unwrap(),
+        [("alice".to_owned(), 500), ("bob".to_owned(), 20)]
+    );
+
+    store.repo().flush().unwrap();
+}
+
+#[test]
+fn sled_store_persists_across_reopening() {
+    const GREETING: Item<String> = item!("greeting");
+    const SCORES: Map<32, u32, u64> = map!("scores");
+
+    let dir = tempfile::tempdir().unwrap();
+
+    {
+        let mut store: SledStore = KvStore::from_repo(SledRepo::open(dir.path()).unwrap());
+
+        GREETING
+            .save_with(&mut store, "hello".to_owned(), Durability::Sync)
+            .unwrap();
+
+        for id in 0..5 {
+            SCORES.save(&mut store, id, u64::from(id) * 10).unwrap();
+        }
+
+        SCORES.remove(&mut store, 2).unwrap();
+        store.repo().flush().unwrap();
+    }
+
+    let store: SledStore = KvStore::from_repo(SledRepo::open(dir.path()).unwrap());
+
+    assert_eq!(GREETING.may_load(&store).unwrap().as_deref(), Some("hello"));
+
+    let scores: Vec<_> = SCORES
+        .range(
+            &store,
+            Bound::Unbounded,
+            Bound::Unbounded,
+            Order::Descending,
+        )
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(scores, [(4, 40), (3, 30), (1, 10), (0, 0)]);
+}
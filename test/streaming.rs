@@ -0,0 +1,142 @@
+use std::io;
+
+use kv_storage::{Error, Fallible, HasKey, Item, KvStore, Read, Remove, StreamFill, Write};
+use kv_storage_bincode::Bincode;
+use kv_storage_json::Json;
+use kv_storage_memory::{prelude::*, Infallible};
+use serde::{ser::Error as _, Serialize, Serializer};
+
+/// Counts what goes through it, standing in for a file or a socket.
+#[derive(Default)]
+struct CountingWriter {
+    bytes: Vec<u8>,
+    written: usize,
+}
+
+impl io::Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written += buf.len();
+        self.bytes.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct StreamingRepo {
+    inner: MemoryRepo,
+    plain_writes: usize,
+    streamed: usize,
+}
+
+impl Fallible for StreamingRepo {
+    type Error = Infallible;
+}
+
+impl Write for StreamingRepo {
+    fn write(&mut self, key: &[u8], bytes: &[u8]) -> Result<(), Self::Error> {
+        self.plain_writes += 1;
+        self.inner.write(key, bytes)
+    }
+
+    fn write_stream<E>(
+        &mut self,
+        key: &[u8],
+        fill: &mut StreamFill<'_, E>,
+    ) -> Result<Result<(), E>, Self::Error> {
+        let mut writer = CountingWriter::default();
+
+        if let Err(error) = fill(&mut writer) {
+            return Ok(Err(error));
+        }
+
+        self.streamed += writer.written;
+        self.inner.write(key, &writer.bytes).map(Ok)
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+}
+
+impl Read for StreamingRepo {
+    fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.inner.read(key)
+    }
+}
+
+impl HasKey for StreamingRepo {
+    fn has_key(&self, key: &[u8]) -> Result<bool, Self::Error> {
+        self.inner.has_key(key)
+    }
+}
+
+impl Remove for StreamingRepo {
+    fn remove(&mut self, key: &[u8]) -> Result<(), Self::Error> {
+        self.inner.remove(key)
+    }
+}
+
+const SNAPSHOT: Item<Vec<u8>> = item!("snapshot");
+
+#[test]
+fn streaming_save_writes_once() {
+    let snapshot: Vec<u8> = (0..4 << 20).map(|i: u32| i.to_le_bytes()[0]).collect();
+
+    let mut store: KvStore<Bincode, StreamingRepo> = KvStore::default();
+    SNAPSHOT.save(&mut store, &snapshot).unwrap();
+
+    // a length prefix, then every byte exactly once
+    assert_eq!(store.repo().streamed, snapshot.len() + 8);
+    assert_eq!(store.repo().plain_writes, 0);
+    assert_eq!(SNAPSHOT.may_load(&store).unwrap(), Some(snapshot));
+}
+
+#[test]
+fn buffering_serializers_write_whole_values() {
+    let mut store: KvStore<Json, StreamingRepo> = KvStore::default();
+    SNAPSHOT.save(&mut store, vec![1, 2, 3]).unwrap();
+
+    assert_eq!(store.repo().streamed, 0);
+    assert_eq!(store.repo().plain_writes, 1);
+    assert_eq!(SNAPSHOT.may_load(&store).unwrap(), Some(vec![1, 2, 3]));
+}
+
+#[test]
+fn failed_streaming_save_writes_nothing() {
+    struct Failing;
+
+    impl Serialize for Failing {
+        fn serialize<S: Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(S::Error::custom("unserializable"))
+        }
+    }
+
+    const PARTIAL: Item<(u64, Failing)> = item!("partial");
+
+    let mut store: KvStore<Bincode, StreamingRepo> = KvStore::default();
+
+    assert!(matches!(
+        PARTIAL.save(&mut store, (7, Failing)),
+        Err(Error::Serde(_))
+    ));
+    assert!(!store.repo().has_key(PARTIAL.key()).unwrap());
+}
+
+#[test]
+fn failed_fill_is_returned_to_the_caller() {
+    let mut repo = MemoryRepo::default();
+
+    let filled = repo
+        .write_stream(b"key", &mut |writer| {
+            writer.write_all(b"partial").map_err(|_| "io")?;
+            Err("fill failed")
+        })
+        .unwrap();
+
+    assert_eq!(filled, Err("fill failed"));
+    assert!(!repo.has_key(b"key").unwrap());
+}
@@ -0,0 +1,258 @@
+use kv_storage::trace::{self, TraceCounts};
+use kv_storage_bincode::Bincode;
+use kv_storage_buffered::BufferedRepo;
+use kv_storage_faulty::FaultyRepo;
+use kv_storage_layered::LayeredRepo;
+use kv_storage_memory::prelude::*;
+use kv_storage_mirror::MirrorRepo;
+use kv_storage_overlay::OverlayRepo;
+use kv_storage_replay::RecordingRepo;
+use kv_storage_watermark::{Limit, WatermarkRepo};
+
+const COUNTER: Item<u64> = item!("counter");
+
+fn store<R>(repo: R) -> KvStore<Bincode, R> {
+    KvStore::from_repo(repo)
+}
+
+fn writes(writes: u64, bytes_written: u64) -> TraceCounts {
+    TraceCounts {
+        writes,
+        bytes_written,
+        ..TraceCounts::default()
+    }
+}
+
+#[test]
+fn mirror_doubles_each_write() {
+    let repo = MirrorRepo::new(MemoryRepo::default(), MemoryRepo::default());
+    let mut store = store(repo);
+
+    let (result, trace) = COUNTER.save_traced(&mut store, 1);
+    result.unwrap();
+
+    let mirror = trace.find("mirror").unwrap();
+    assert_eq!(mirror.received, writes(1, 8));
+    assert_eq!(mirror.issued, writes(2, 16));
+    assert_eq!(trace.issued, mirror.received);
+}
+
+#[test]
+fn buffered_defers_writes_until_flushed() {
+    let mut store = store(BufferedRepo::new(MemoryRepo::default()));
+
+    for n in 0..3 {
+        let (result, trace) = COUNTER.save_traced(&mut store, n);
+        result.unwrap();
+
+        let buffered = trace.find("buffered").unwrap();
+        assert_eq!(buffered.received, writes(1, 8));
+        assert_eq!(buffered.issued, TraceCounts::default());
+    }
+
+    // the three saves coalesced into one
+    let (result, trace) = trace::traced(|| store.mut_repo().flush());
+    result.unwrap();
+
+    let buffered = trace.find("buffered").unwrap();
+    assert_eq!(buffered.received, TraceCounts::default());
+    assert_eq!(buffered.issued, writes(1, 8));
+}
+
+#[test]
+fn nested_layers_report_beneath_the_layer_that_called_them() {
+    let primary = BufferedRepo::new(FaultyRepo::new(MemoryRepo::default())).flush_at_keys(1);
+    let secondary = FaultyRepo::new(MemoryRepo::default());
+    let mut store = store(MirrorRepo::new(primary, secondary));
+
+    let (result, trace) = COUNTER.save_traced(&mut store, 1);
+    result.unwrap();
+
+    assert_eq!(trace.layer, "store");
+    assert_eq!(trace.inner.len(), 1);
+
+    let mirror = &trace.inner[0];
+    assert_eq!(mirror.layer, "mirror");
+    assert_eq!(mirror.issued, writes(2, 16));
+
+    let layers: Vec<_> = mirror.inner.iter().map(|inner| inner.layer).collect();
+    assert_eq!(layers, ["buffered", "faulty"]);
+
+    // a single pending key reaches the threshold, so the buffer flushes right away
+    let buffered = &mirror.inner[0];
+    assert_eq!(buffered.issued, writes(1, 8));
+    assert_eq!(buffered.inner[0].layer, "faulty");
+    assert_eq!(buffered.inner[0].received, writes(1, 8));
+
+    assert_eq!(
+        trace.to_string(),
+        "store: 0 reads (0 B), 1 writes (8 B), 0 removes\n\
+         \x20 mirror: 0 reads (0 B), 2 writes (16 B), 0 removes\n\
+         \x20   buffered: 0 reads (0 B), 1 writes (8 B), 0 removes\n\
+         \x20     faulty: 0 reads (0 B), 1 writes (8 B), 0 removes\n\
+         \x20   faulty: 0 reads (0 B), 1 writes (8 B), 0 removes\n"
+    );
+}
+
+#[test]
+fn repeated_operations_merge_into_one_layer() {
+    let repo = MirrorRepo::new(
+        MemoryRepo::default(),
+        FaultyRepo::new(MemoryRepo::default()),
+    );
+    let mut store = store(repo);
+
+    let (result, trace) = trace::traced(|| {
+        COUNTER.save(&mut store, 1)?;
+        COUNTER.save(&mut store, 2)?;
+        COUNTER.clear(&mut store)
+    });
+    result.unwrap();
+
+    let mirror = trace.find("mirror").unwrap();
+    assert_eq!(mirror.inner.len(), 1);
+    assert_eq!(
+        mirror.issued,
+        TraceCounts {
+            removes: 2,
+            ..writes(4, 32)
+        }
+    );
+    assert_eq!(
+        mirror.inner[0].received,
+        TraceCounts {
+            removes: 1,
+            ..writes(2, 16)
+        }
+    );
+}
+
+#[test]
+fn layered_removals_check_the_fallback_and_leave_a_tombstone() {
+    let mut fallback = MemoryRepo::default();
+    kv_storage::Write::write(&mut fallback, COUNTER.key(), &[0; 8]).unwrap();
+
+    let mut store = store(LayeredRepo::new(MemoryRepo::default(), fallback));
+
+    let (result, trace) = trace::traced(|| COUNTER.clear(&mut store));
+    result.unwrap();
+
+    let layered = trace.find("layered").unwrap();
+    assert_eq!(
+        layered.received,
+        TraceCounts {
+            removes: 1,
+            ..TraceCounts::default()
+        }
+    );
+    assert_eq!(
+        layered.issued,
+        TraceCounts {
+            reads: 2,
+            removes: 1,
+            ..writes(1, 0)
+        }
+    );
+}
+
+#[test]
+fn watermark_reads_the_previous_value() {
+    let repo = WatermarkRepo::new(MemoryRepo::default(), Limit::Keys(10), &[50], |_| {});
+    let mut store = store(repo);
+
+    COUNTER.save(&mut store, 1).unwrap();
+
+    let (result, trace) = COUNTER.save_traced(&mut store, 2);
+    result.unwrap();
+
+    let watermark = trace.find("watermark").unwrap();
+    assert_eq!(watermark.received, writes(1, 8));
+    assert_eq!(
+        watermark.issued,
+        TraceCounts {
+            reads: 1,
+            bytes_read: 8,
+            ..writes(1, 8)
+        }
+    );
+
+    let (result, trace) = trace::traced(|| COUNTER.clear(&mut store));
+    result.unwrap();
+
+    assert_eq!(
+        trace.find("watermark").unwrap().issued,
+        TraceCounts {
+            reads: 1,
+            bytes_read: 8,
+            removes: 1,
+            ..TraceCounts::default()
+        }
+    );
+}
+
+#[test]
+fn recording_reads_the_previous_value() {
+    let mut store = store(RecordingRepo::new(MemoryRepo::default()));
+
+    let (result, trace) = trace::traced(|| {
+        COUNTER.save(&mut store, 1)?;
+        COUNTER.clear(&mut store)
+    });
+    result.unwrap();
+
+    let recording = trace.find("recording").unwrap();
+    assert_eq!(
+        recording.received,
+        TraceCounts {
+            removes: 1,
+            ..writes(1, 8)
+        }
+    );
+    assert_eq!(
+        recording.issued,
+        TraceCounts {
+            reads: 2,
+            bytes_read: 8,
+            removes: 1,
+            ..writes(1, 8)
+        }
+    );
+}
+
+#[test]
+fn overlay_issues_nothing_until_committed() {
+    let base = FaultyRepo::new(MemoryRepo::default());
+    let mut store = store(OverlayRepo::new(base));
+
+    let (result, trace) = trace::traced(|| {
+        COUNTER.save(&mut store, 1)?;
+        COUNTER.save(&mut store, 2)
+    });
+    result.unwrap();
+
+    let overlay = trace.find("overlay").unwrap();
+    assert_eq!(overlay.received, writes(2, 16));
+    assert_eq!(overlay.issued, TraceCounts::default());
+    assert!(overlay.inner.is_empty());
+
+    // the two saves reach the base as one write
+    let (result, trace) = trace::traced(|| store.into_repo().commit());
+    result.unwrap();
+
+    let overlay = trace.find("overlay").unwrap();
+    assert_eq!(overlay.issued, writes(1, 8));
+    assert_eq!(overlay.inner[0].layer, "faulty");
+    assert_eq!(overlay.inner[0].received, writes(1, 8));
+}
+
+#[test]
+fn nothing_is_recorded_outside_a_trace() {
+    let repo = MirrorRepo::new(MemoryRepo::default(), MemoryRepo::default());
+    let mut store = store(repo);
+
+    COUNTER.save(&mut store, 1).unwrap();
+
+    let (result, trace) = trace::traced(|| COUNTER.may_load(&store));
+    assert_eq!(result.unwrap(), Some(1));
+    assert!(trace.inner.is_empty());
+}
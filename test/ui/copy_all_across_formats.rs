@@ -0,0 +1,13 @@
+use kv_storage::prelude::*;
+use kv_storage_bincode::Bincode;
+use kv_storage_json::Json;
+use kv_storage_memory::MemoryRepo;
+
+const BALANCES: Map<64, &str, u128> = map!("balances");
+
+fn main() {
+    let bincode: KvStore<Bincode, MemoryRepo> = KvStore::default();
+    let mut json: KvStore<Json, MemoryRepo> = KvStore::default();
+
+    bincode.copy_all(&mut json, BALANCES.prefix()).unwrap();
+}
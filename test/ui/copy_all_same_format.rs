@@ -0,0 +1,23 @@
+use kv_storage::{prelude::*, SameFormat};
+use kv_storage_bincode::Bincode;
+use kv_storage_memory::MemoryRepo;
+use kv_storage_overlay::OverlayRepo;
+
+const BALANCES: Map<64, &str, u128> = map!("balances");
+
+/// Reads what an older release wrote, which was plain bincode.
+struct LegacyBincode;
+
+impl SameFormat<Bincode> for LegacyBincode {}
+
+fn main() {
+    let mut memory: KvStore<Bincode, MemoryRepo> = KvStore::default();
+    let mut overlay: KvStore<Bincode, OverlayRepo<MemoryRepo>> =
+        KvStore::from_repo(OverlayRepo::new(MemoryRepo::default()));
+
+    memory.copy_all(&mut overlay, BALANCES.prefix()).unwrap();
+    memory.move_to(&mut overlay, BALANCES.prefix()).unwrap();
+
+    let legacy = KvStore::new(LegacyBincode, MemoryRepo::default());
+    legacy.copy_all(&mut memory, BALANCES.prefix()).unwrap();
+}
@@ -0,0 +1,52 @@
+use kv_storage::{prelude::*, KeyDeserialize, StorageKey};
+use kv_storage_bincode::Bincode;
+use kv_storage_memory::MemoryRepo;
+
+#[derive(StorageKey, Debug, PartialEq)]
+struct OrderKey {
+    owner: String,
+    side: Side,
+    id: u64,
+}
+
+#[derive(StorageKey, Debug, PartialEq)]
+struct Pair(u32, String);
+
+#[derive(StorageKey)]
+struct Borrowed<'a> {
+    owner: &'a str,
+    id: u64,
+}
+
+#[derive(StorageKey, Debug, PartialEq)]
+enum Side {
+    Buy = 1,
+    Sell,
+}
+
+const ORDERS: Map<64, OrderKey, u128> = map!("orders");
+const BORROWED: Map<64, Borrowed<'static>, u128> = map!("borrowed");
+
+fn main() {
+    let mut store: KvStore<Bincode, MemoryRepo> = KvStore::default();
+
+    let key = OrderKey {
+        owner: "alice".to_owned(),
+        side: Side::Sell,
+        id: 7,
+    };
+
+    ORDERS.save(&mut store, &key, 100).unwrap();
+    assert_eq!(ORDERS.may_load(&store, &key).unwrap(), Some(100));
+
+    let encoded = ORDERS.key(&key);
+    let encoded = &encoded.as_ref()[ORDERS.prefix().len()..];
+    assert_eq!(OrderKey::from_key_bytes(encoded), Ok(key));
+
+    BORROWED.save(&mut store, Borrowed { owner: "bob", id: 1 }, 5).unwrap();
+
+    // derived keys nest in tuples like any other key part
+    let pair = (Pair(1, "x".to_owned()), Side::Buy);
+    let encoded = kv_storage::Map::<64, (Pair, Side), ()>::new(b"").key(&pair);
+    assert_eq!(<(Pair, Side)>::from_key_bytes(encoded.as_ref()), Ok(pair));
+}
@@ -0,0 +1,15 @@
+use kv_storage::StorageKey;
+
+#[derive(StorageKey)]
+struct PriceKey {
+    market: String,
+    price: f64,
+}
+
+#[derive(StorageKey)]
+enum Status {
+    Open,
+    Filled { amount: u64 },
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use kv_storage::{storage_keys, Item, Map};
+
+storage_keys! {
+    const TOTAL: Item<u128> = item!("total");
+    const BALANCES: Map<1024, &'static str, u128> = map!("balances");
+    const AGAIN: Map<1024, &'static str, u128> = map!("balances");
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use kv_storage::prelude::*;
+
+const ORDERS: Map<64, (&str, u64), u128> = map!("orders");
+
+fn main() {
+    // the whole key leaves nothing to scope to
+    let _ = ORDERS.sub_prefix(("alice", 7u64));
+}
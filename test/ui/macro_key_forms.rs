@@ -0,0 +1,34 @@
+use kv_storage::prelude::*;
+use kv_storage::concat_keys;
+
+const NAMESPACE: &str = "bank";
+const TOTAL_NAME: &str = "total";
+const PREFIX: &[u8] = b"app";
+
+mod names {
+    pub const BALANCES: &str = "balances";
+}
+
+// literal
+const OWNER: Item<String> = item!("owner");
+const SUPPLY: Map<64, &str, u128> = map!("supply", ns = "bank");
+
+// const strs
+const TOTAL: Item<u64> = item!(TOTAL_NAME, ns = NAMESPACE);
+const BALANCES: Map<64, &str, u128> = map!(names::BALANCES, ns = NAMESPACE);
+const SEEN: Set<64, &str> = set!(names::BALANCES);
+
+// byte slices
+const CONFIG: Item<u32> = item!(raw = concat_keys!(PREFIX, b"::config"));
+const QUEUE: Deque<u64> = deque!(raw = b"queue");
+const RAW: Map<64, u32, u32> = map!(raw = PREFIX);
+
+fn main() {
+    assert_eq!(TOTAL.key(), b"\0\x04bank\0\x05total");
+    assert_eq!(BALANCES.prefix(), b"\0\x04bank\0\x08balances");
+    assert_eq!(SUPPLY.prefix(), b"\0\x04bank\0\x06supply");
+    assert_eq!(CONFIG.key(), b"app::config");
+    assert_eq!(QUEUE.prefix(), b"queue");
+    assert_eq!(RAW.prefix(), b"app");
+    assert_ne!(OWNER.key(), SEEN.prefix());
+}
@@ -0,0 +1,15 @@
+use kv_storage::prelude::*;
+use kv_storage_bincode::Bincode;
+use kv_storage_memory::MemoryRepo;
+
+const COUNT: Item<u64> = item!("count");
+
+fn main() {
+    let mut store: KvStore<Bincode, MemoryRepo> = KvStore::default();
+
+    let mut outer = store.transaction();
+    let mut inner = store.transaction();
+
+    COUNT.save(&mut inner, 1).unwrap();
+    COUNT.save(&mut outer, 2).unwrap();
+}
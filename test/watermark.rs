@@ -0,0 +1,73 @@
+use kv_storage::{Remove, Write};
+use kv_storage_memory::MemoryRepo;
+use kv_storage_watermark::{Direction, Limit, Usage, WatermarkRepo};
+
+#[test]
+fn watermark_fires_once_per_crossing() {
+    let mut events = Vec::new();
+
+    {
+        let mut repo =
+            WatermarkRepo::new(MemoryRepo::default(), Limit::Keys(10), &[90, 70], |event| {
+                events.push((event.percent, event.direction))
+            });
+
+        for key in 0u8..9 {
+            repo.write(&[key], b"value").unwrap();
+        }
+
+        // overwriting doesn't add a key
+        repo.write(&[0], b"other").unwrap();
+
+        for key in 3u8..9 {
+            repo.remove(&[key]).unwrap();
+        }
+
+        // removing an absent key doesn't change usage
+        repo.remove(&[100]).unwrap();
+
+        for key in 3u8..7 {
+            repo.write(&[key], b"value").unwrap();
+        }
+
+        assert_eq!(repo.usage(), Usage { keys: 7, bytes: 42 });
+    }
+
+    assert_eq!(
+        events,
+        [
+            (70, Direction::Up),
+            (90, Direction::Up),
+            (90, Direction::Down),
+            (70, Direction::Down),
+            (70, Direction::Up),
+        ]
+    );
+}
+
+#[test]
+fn watermark_tracks_bytes_across_overwrites() {
+    let mut events = Vec::new();
+
+    {
+        let mut repo =
+            WatermarkRepo::new(MemoryRepo::default(), Limit::Bytes(100), &[50], |event| {
+                events.push((event.usage, event.direction))
+            });
+
+        repo.write(b"k", &[0; 60]).unwrap();
+        repo.write(b"k", &[0; 10]).unwrap();
+        repo.write(b"k", &[0; 49]).unwrap();
+
+        assert_eq!(repo.usage(), Usage { keys: 1, bytes: 50 });
+    }
+
+    assert_eq!(
+        events,
+        [
+            (61, Direction::Up),
+            (11, Direction::Down),
+            (50, Direction::Up)
+        ]
+    );
+}
@@ -0,0 +1,438 @@
+use std::{
+    future::Future,
+    path::Path,
+    pin::pin,
+    process::Command,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Barrier,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::Duration,
+};
+
+use axum::{
+    body::Body,
+    extract::{FromRef, State},
+    http::Request,
+    routing::post,
+    Router,
+};
+use kv_storage::{item, Item};
+use kv_storage_bincode::Bincode;
+use kv_storage_memory::{MemStore, MemoryRepo};
+use kv_storage_web_state::SharedKvStore;
+use tower::ServiceExt;
+
+const DEBITS: Item<u64> = item!("debits");
+const CREDITS: Item<u64> = item!("credits");
+
+#[test]
+fn shared_store_reads_are_consistent_during_writes() {
+    let shared = SharedKvStore::from(MemStore::default());
+
+    let writers: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+
+            thread::spawn(move || {
+                for _ in 0..250 {
+                    shared
+                        .write(|store| {
+                            let debits = DEBITS.may_load(store)?.unwrap_or_default();
+                            let credits = CREDITS.may_load(store)?.unwrap_or_default();
+
+                            DEBITS.save(store, debits + 1)?;
+                            CREDITS.save(store, credits + 1)
+                        })
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+
+            thread::spawn(move || {
+                for _ in 0..250 {
+                    let (debits, credits) = shared.read(|store| {
+                        (
+                            DEBITS.may_load(store).unwrap(),
+                            CREDITS.may_load(store).unwrap(),
+                        )
+                    });
+
+                    // both sides of a transfer are always observed together
+                    assert_eq!(debits, credits);
+                }
+            })
+        })
+        .collect();
+
+    for handle in writers.into_iter().chain(readers) {
+        handle.join().unwrap();
+    }
+
+    assert_eq!(
+        shared.read(|store| DEBITS.may_load(store).unwrap()),
+        Some(1000)
+    );
+}
+
+#[test]
+fn shared_store_is_handler_state() {
+    // axum `State`/`FromRef` and actix `Data` need Clone + Send + Sync + 'static
+    fn assert_state<T: Clone + Send + Sync + 'static>(_: &T) {}
+
+    assert_state(&SharedKvStore::from(MemStore::default()));
+}
+
+#[test]
+fn shared_store_initializes_items_once() {
+    const CONFIG: Item<u64> = item!("config");
+
+    let shared = SharedKvStore::from(MemStore::default());
+    let inits = Arc::new(AtomicUsize::new(0));
+    let start = Arc::new(Barrier::new(8));
+
+    let racers: Vec<_> = (0..8)
+        .map(|n| {
+            let shared = shared.clone();
+            let inits = Arc::clone(&inits);
+            let start = Arc::clone(&start);
+
+            thread::spawn(move || {
+                start.wait();
+
+                shared.get_or_try_init(&CONFIG, || {
+                    inits.fetch_add(1, Ordering::SeqCst);
+                    // an expensive default, widening the window for a race
+                    thread::sleep(Duration::from_millis(10));
+                    Ok::<_, kv_storage::Error<_, _>>(n)
+                })
+            })
+        })
+        .collect();
+
+    let values: Vec<_> = racers
+        .into_iter()
+        .map(|racer| racer.join().unwrap().unwrap())
+        .collect();
+
+    assert_eq!(inits.load(Ordering::SeqCst), 1);
+    assert!(values.iter().all(|value| *value == values[0]));
+    assert_eq!(
+        shared.read(|store| CONFIG.may_load(store).unwrap()),
+        Some(values[0])
+    );
+}
+
+/// A minimal executor without a runtime, parking the thread until the future is woken.
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+fn blocking_shared_store_runs_without_a_runtime() {
+    let shared = SharedKvStore::from(MemStore::default());
+
+    block_on(async {
+        for _ in 0..3 {
+            shared
+                .spawn_write(|store| {
+                    let debits = DEBITS.may_load(store)?.unwrap_or_default();
+                    DEBITS.save(store, debits + 1)
+                })
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            shared
+                .spawn_read(|store| DEBITS.may_load(store).unwrap())
+                .await,
+            Some(3)
+        );
+    });
+}
+
+#[test]
+#[should_panic(expected = "closure panicked")]
+fn blocking_shared_store_resumes_panics() {
+    let shared = SharedKvStore::from(MemStore::default());
+
+    block_on(shared.spawn_read(|_| panic!("closure panicked")));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn blocking_shared_store_runs_on_tokio() {
+    let shared = SharedKvStore::from(MemStore::default());
+
+    let writers: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+
+            tokio::spawn(async move {
+                for _ in 0..25 {
+                    shared
+                        .spawn_write(|store| {
+                            let debits = DEBITS.may_load(store)?.unwrap_or_default();
+                            DEBITS.save(store, debits + 1)
+                        })
+                        .await
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for handle in writers {
+        handle.await.unwrap();
+    }
+
+    assert_eq!(
+        shared
+            .spawn_read(|store| DEBITS.may_load(store).unwrap())
+            .await,
+        Some(100)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn async_shared_store_reads_are_consistent_during_writes() {
+    let shared = kv_storage_web_state::tokio::SharedKvStore::from(MemStore::default());
+
+    let writers: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+
+            tokio::spawn(async move {
+                for _ in 0..250 {
+                    shared
+                        .write(|store| {
+                            let debits = DEBITS.may_load(store)?.unwrap_or_default();
+                            let credits = CREDITS.may_load(store)?.unwrap_or_default();
+
+                            DEBITS.save(store, debits + 1)?;
+                            CREDITS.save(store, credits + 1)
+                        })
+                        .await
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+
+            tokio::spawn(async move {
+                for _ in 0..250 {
+                    let (debits, credits) = shared
+                        .read(|store| {
+                            (
+                                DEBITS.may_load(store).unwrap(),
+                                CREDITS.may_load(store).unwrap(),
+                            )
+                        })
+                        .await;
+
+                    assert_eq!(debits, credits);
+                }
+            })
+        })
+        .collect();
+
+    for handle in writers.into_iter().chain(readers) {
+        handle.await.unwrap();
+    }
+
+    assert_eq!(
+        shared.read(|store| DEBITS.may_load(store).unwrap()).await,
+        Some(1000)
+    );
+}
+
+#[async_std::test]
+async fn async_std_shared_store_reads_are_consistent_during_writes() {
+    let shared = kv_storage_web_state::async_std::SharedKvStore::from(MemStore::default());
+
+    let writers: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+
+            async_std::task::spawn(async move {
+                for _ in 0..250 {
+                    shared
+                        .write(|store| {
+                            let debits = DEBITS.may_load(store)?.unwrap_or_default();
+                            let credits = CREDITS.may_load(store)?.unwrap_or_default();
+
+                            DEBITS.save(store, debits + 1)?;
+                            CREDITS.save(store, credits + 1)
+                        })
+                        .await
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+
+            async_std::task::spawn(async move {
+                for _ in 0..250 {
+                    let (debits, credits) = shared
+                        .read(|store| {
+                            (
+                                DEBITS.may_load(store).unwrap(),
+                                CREDITS.may_load(store).unwrap(),
+                            )
+                        })
+                        .await;
+
+                    assert_eq!(debits, credits);
+                }
+            })
+        })
+        .collect();
+
+    for handle in writers.into_iter().chain(readers) {
+        handle.await;
+    }
+
+    assert_eq!(
+        shared.read(|store| DEBITS.may_load(store).unwrap()).await,
+        Some(1000)
+    );
+}
+
+#[async_std::test]
+async fn blocking_shared_store_runs_on_async_std() {
+    let shared = SharedKvStore::from(MemStore::default());
+
+    let writers: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+
+            async_std::task::spawn(async move {
+                for _ in 0..25 {
+                    shared
+                        .spawn_write(|store| {
+                            let debits = DEBITS.may_load(store)?.unwrap_or_default();
+                            DEBITS.save(store, debits + 1)
+                        })
+                        .await
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for handle in writers {
+        handle.await;
+    }
+
+    assert_eq!(
+        shared
+            .spawn_read(|store| DEBITS.may_load(store).unwrap())
+            .await,
+        Some(100)
+    );
+}
+
+/// Each runtime feature on its own, and neither, builds. The test crate enables both, so this
+/// checks the crate in a target directory of its own.
+#[test]
+fn web_state_builds_with_each_runtime_feature() {
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let target = workspace.join("target").join("web-state-features");
+
+    for features in ["", "tokio", "async-std"] {
+        let status = Command::new(env!("CARGO"))
+            .current_dir(&workspace)
+            .args(["check", "--quiet", "--package", "kv-storage-web-state"])
+            .args(["--no-default-features", "--features", features])
+            .arg("--target-dir")
+            .arg(&target)
+            .status()
+            .unwrap();
+
+        assert!(status.success(), "features {features:?} don't build");
+    }
+}
+
+#[tokio::test]
+async fn async_shared_store_is_axum_state() {
+    type Store = kv_storage_web_state::tokio::SharedKvStore<Bincode, MemoryRepo>;
+
+    const HITS: Item<u64> = item!("hits");
+
+    #[derive(Clone)]
+    struct AppState {
+        store: Store,
+    }
+
+    impl FromRef<AppState> for Store {
+        fn from_ref(state: &AppState) -> Self {
+            state.store.clone()
+        }
+    }
+
+    async fn hit(State(store): State<Store>) -> String {
+        let hits = store
+            .write(|store| {
+                let hits = HITS.may_load(store)?.unwrap_or_default() + 1;
+                HITS.save(store, hits).map(|()| hits)
+            })
+            .await
+            .unwrap();
+
+        hits.to_string()
+    }
+
+    let state = AppState {
+        store: Store::default(),
+    };
+    let app = Router::new()
+        .route("/hit", post(hit))
+        .with_state(state.clone());
+
+    for _ in 0..3 {
+        let request = Request::post("/hit").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+
+        assert!(response.status().is_success());
+    }
+
+    assert_eq!(
+        state
+            .store
+            .read(|store| HITS.may_load(store).unwrap())
+            .await,
+        Some(3)
+    );
+}